@@ -0,0 +1,219 @@
+//! 导入成功后的源文件处置：移动到 done / 删除 / gzip 归档到 done / 上传到对象存储后删除。
+//! done 可以在其他文件系统上 (--done-dir)，此时移动改为复制 + fsync 后删除源文件
+
+use crate::{delta, remote, s3};
+use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::Local;
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnSuccess {
+    /// 移动到 done 目录
+    Move,
+    /// 直接删除
+    Delete,
+    /// gzip 压缩为 done/<文件名>.gz 后删除源文件
+    Compress,
+    /// 上传到 --archive-to 指定的 S3 前缀，核对后删除源文件 (由 --archive-to 启用)
+    #[value(skip)]
+    Archive,
+}
+
+/// 校验 --archive-to 的 S3 前缀 (clap 参数解析入口)
+pub fn parse_archive_to(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    match s.strip_prefix("s3://") {
+        Some(rest) if !rest.is_empty() && !rest.starts_with('/') => Ok(path),
+        _ => Err(format!("应为 s3://bucket/prefix/ 形式的路径: {}", s)),
+    }
+}
+
+/// 校验 strftime 模板 (clap 参数解析入口)
+pub fn parse_layout(s: &str) -> Result<String, String> {
+    if StrftimeItems::new(s).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("无效的日期模板: {}", s));
+    }
+    if s.starts_with('/') || s.split('/').any(|part| part == "..") {
+        return Err(format!("日期模板必须是 done 下的相对路径: {}", s));
+    }
+    Ok(s.to_string())
+}
+
+/// 按模板 (本地时间) 计算并创建 done 下的子目录，模板为空时直接使用 done
+pub fn target_dir(done_dir: &Path, layout: Option<&str>) -> Result<PathBuf> {
+    let Some(layout) = layout else {
+        return Ok(done_dir.to_path_buf());
+    };
+    let dir = done_dir.join(Local::now().format(layout).to_string());
+    if remote::is_remote(&dir) {
+        return Ok(dir);
+    }
+    std::fs::create_dir_all(&dir).with_context(|| format!("无法创建目录: {:?}", dir))?;
+    Ok(dir)
+}
+
+/// 启动时创建并检查 done / failed 目录，确认源目录可写、目标目录可写，
+/// move 策略下还要确认能把文件从源目录移动到 done (不在同一文件系统时提示将改为复制后删除)。
+/// 权限或挂载问题在导入开始前暴露，而不是在长时间导入成功后处置文件时才失败
+pub fn prepare_dirs(
+    source: &Path,
+    done_dir: &Path,
+    failed_dir: &Path,
+    layout: Option<&str>,
+    policy: OnSuccess,
+) -> Result<()> {
+    let target = target_dir(done_dir, layout)?;
+    std::fs::create_dir_all(failed_dir)
+        .with_context(|| format!("无法创建目录: {:?}", failed_dir))?;
+    probe_writable(&target)?;
+    probe_writable(failed_dir)?;
+
+    let probe = probe_path(source);
+    File::create(&probe).with_context(|| {
+        format!(
+            "源目录不可写，导入成功后将无法移动或删除源文件: {:?}",
+            source
+        )
+    })?;
+    let moved = target.join(probe.file_name().unwrap_or_default());
+    let renamed = match policy {
+        OnSuccess::Move => std::fs::rename(&probe, &moved),
+        OnSuccess::Delete | OnSuccess::Compress | OnSuccess::Archive => Ok(()),
+    };
+    let _ = std::fs::remove_file(&probe);
+    let _ = std::fs::remove_file(&moved);
+    match renamed {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            println!(
+                "💽 {:?} 与 {:?} 不在同一文件系统，成功的文件将复制到 done 并落盘后再删除源文件",
+                source, target
+            );
+            Ok(())
+        }
+        Err(e) => Err(anyhow::anyhow!(
+            "无法把文件从 {:?} 移动到 {:?}: {}",
+            source,
+            target,
+            e
+        )),
+    }
+}
+
+/// 移动文件；不在同一文件系统 (EXDEV) 时先复制为目标目录下的 .part 临时文件并 fsync，
+/// 改名为目标文件并同步目录后再删除源文件。中途失败时源文件仍在，也不会留下残缺的目标文件
+pub fn move_file(src: &Path, target: &Path) -> Result<()> {
+    match std::fs::rename(src, target) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {}
+        Err(e) => return Err(e.into()),
+    }
+    let part = part_path(target);
+    let result = (|| -> Result<()> {
+        // fs::copy 同时复制权限；修改时间单独保留，verify / --delta 等依赖它
+        std::fs::copy(src, &part)?;
+        let out = File::options().write(true).open(&part)?;
+        if let Ok(mtime) = std::fs::metadata(src).and_then(|m| m.modified()) {
+            out.set_modified(mtime)?;
+        }
+        out.sync_all()?;
+        std::fs::rename(&part, target)?;
+        if let Some(dir) = target.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&part);
+    }
+    result.with_context(|| format!("跨文件系统复制失败: {:?} → {:?}", src, target))?;
+    std::fs::remove_file(src).with_context(|| format!("已复制到 {:?}，但无法删除源文件", target))
+}
+
+/// 写入中的临时文件 `<目标>.part`，写完并 fsync 后再改名为目标文件
+pub fn part_path(target: &Path) -> PathBuf {
+    let mut part = target.as_os_str().to_os_string();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// 在目录中创建并删除一个探测文件，确认当前用户可写
+pub fn probe_writable(dir: &Path) -> Result<()> {
+    let probe = probe_path(dir);
+    File::create(&probe).with_context(|| format!("目录不可写: {:?}", dir))?;
+    std::fs::remove_file(&probe).with_context(|| format!("目录中无法删除文件: {:?}", dir))
+}
+
+fn probe_path(dir: &Path) -> PathBuf {
+    dir.join(format!(".ck-loader-probe-{}", std::process::id()))
+}
+
+/// 文件的 (大小, 修改时间)，用于确认导入期间文件没有被改写
+pub fn fingerprint(path: &Path) -> (u64, Option<SystemTime>) {
+    std::fs::metadata(path)
+        .map(|m| (m.len(), m.modified().ok()))
+        .unwrap_or((0, None))
+}
+
+/// 按策略处置已成功导入的文件，返回处置后的文件位置 (删除时为 None)
+pub fn finish(policy: OnSuccess, path: &Path, done_dir: &Path) -> Result<Option<PathBuf>> {
+    if remote::is_remote(path) {
+        return remote::finish(policy, path, done_dir);
+    }
+    let file_name = path.file_name().context("无效的文件名")?;
+    match policy {
+        OnSuccess::Move => {
+            let target = done_dir.join(file_name);
+            move_file(path, &target)?;
+            Ok(Some(target))
+        }
+        OnSuccess::Delete => {
+            std::fs::remove_file(path)?;
+            Ok(None)
+        }
+        OnSuccess::Compress => {
+            let mut name = file_name.to_os_string();
+            name.push(".gz");
+            let target = done_dir.join(name);
+            gzip(path, &target)?;
+            std::fs::remove_file(path)?;
+            Ok(Some(target))
+        }
+        OnSuccess::Archive => {
+            let target = done_dir.join(file_name);
+            s3::upload(path, &target, &delta::hash_file(path)?)?;
+            std::fs::remove_file(path)?;
+            Ok(Some(target))
+        }
+    }
+}
+
+/// 先写入 .part 临时文件并 fsync，完整写出后再改名，中途失败不会留下残缺的归档
+fn gzip(src: &Path, target: &Path) -> Result<()> {
+    let part = part_path(target);
+
+    let result = (|| -> Result<()> {
+        let mut reader = BufReader::new(File::open(src)?);
+        let out = File::create(&part)?;
+        let mut encoder = GzEncoder::new(BufWriter::new(out), Compression::default());
+        std::io::copy(&mut reader, &mut encoder)?;
+        let mut writer = encoder.finish()?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        std::fs::rename(&part, target)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&part);
+    }
+    result.with_context(|| format!("gzip 归档失败: {:?}", target))
+}
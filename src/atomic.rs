@@ -0,0 +1,144 @@
+//! `--atomic`：整批文件先导入自动创建的暂存表，全部成功且行数核对无误后，再用
+//! `ALTER TABLE <目标表> ATTACH PARTITION ID ... FROM <暂存表>` 把各分区并入目标表，消费方不会看到只导入了一半的批次。
+//!
+//! 暂存表为普通 MergeTree，列、分区键、排序键、主键与存储策略与目标表相同 (ATTACH PARTITION FROM 的要求)，
+//! 因此 Replicated 目标表同样适用。各分区在同一条 ALTER 中依次并入，单个分区的并入是原子的。
+//! 批次未能并入时源文件保持原位，暂存表删除后可以直接重跑。
+
+use crate::cli::{self, Args};
+use crate::clickhouse::{self, locate};
+use crate::orc;
+use crate::report::{FileRecord, FileStatus};
+use anyhow::{bail, Context, Result};
+
+/// 一个目标表本批次的暂存表
+pub struct Staging {
+    pub table: String,
+    target: String,
+}
+
+/// 按目标表的结构与各个键创建空的暂存表 `<目标表>_ckloader_<批次 id>`
+pub async fn create(cfg: &Args, target: &str, batch_id: &str) -> Result<Staging> {
+    let (db, name) = locate(target);
+    let sql = format!(
+        "SELECT engine, partition_key, sorting_key, primary_key, storage_policy FROM system.tables
+         WHERE database = {} AND name = '{}'",
+        db,
+        cli::sql_string(&name)
+    );
+    let tsv = clickhouse::query(cfg, &sql).await?;
+    let rows = clickhouse::rows(&tsv);
+    let Some([engine, partition_key, sorting_key, primary_key, policy]) =
+        rows.first().and_then(|r| r.get(..5))
+    else {
+        bail!("--atomic: 找不到目标表 {}", target);
+    };
+    if !engine.ends_with("MergeTree") {
+        bail!(
+            "--atomic 仅支持 MergeTree 系列的表，{} 的引擎为 {}",
+            target,
+            engine
+        );
+    }
+
+    let staging = format!("{}_ckloader_{}", target, batch_id);
+    let mut sql = format!("CREATE TABLE {} AS {} ENGINE = MergeTree", staging, target);
+    if !partition_key.is_empty() {
+        sql.push_str(&format!(" PARTITION BY {}", partition_key));
+    }
+    match *sorting_key {
+        "" => sql.push_str(" ORDER BY tuple()"),
+        key => sql.push_str(&format!(" ORDER BY ({})", key)),
+    }
+    if !primary_key.is_empty() && primary_key != sorting_key {
+        sql.push_str(&format!(" PRIMARY KEY ({})", primary_key));
+    }
+    if !policy.is_empty() {
+        sql.push_str(&format!(
+            " SETTINGS storage_policy = '{}'",
+            cli::sql_string(policy)
+        ));
+    }
+    clickhouse::query(cfg, &sql)
+        .await
+        .with_context(|| format!("无法创建暂存表 {}", staging))?;
+    println!(
+        "🧪 原子导入: 先导入暂存表 {}，全部成功后再并入 {}",
+        staging, target
+    );
+    Ok(Staging {
+        table: staging,
+        target: target.to_string(),
+    })
+}
+
+impl Staging {
+    /// 核对暂存表的行数与成功文件的 ORC 行数，一致后把全部分区并入目标表并删除暂存表
+    pub async fn commit(&self, cfg: &Args, records: &[FileRecord]) -> Result<()> {
+        let expected = records
+            .iter()
+            .filter(|r| r.status == FileStatus::Success)
+            .map(|r| orc::read_meta(&r.path).ok().map(|m| m.num_rows))
+            .sum::<Option<u64>>();
+        let tsv = clickhouse::query(cfg, &format!("SELECT count() FROM {}", self.table)).await?;
+        let staged: u64 = tsv.trim().parse().context("无法解析暂存表行数")?;
+        match expected {
+            Some(expected) if staged != expected && !(cfg.allows_errors() && staged < expected) => {
+                bail!(
+                    "暂存表 {} 有 {} 行，与成功文件的 ORC 行数 {} 不一致，未并入 {} (暂存表已保留供排查)",
+                    self.table,
+                    staged,
+                    expected,
+                    self.target
+                );
+            }
+            Some(_) => {}
+            None => eprintln!("⚠️ 部分文件无法读取 ORC 行数，并入前不核对暂存表行数"),
+        }
+
+        let (db, name) = locate(&self.table);
+        let sql = format!(
+            "SELECT DISTINCT partition_id FROM system.parts
+             WHERE database = {} AND table = '{}' AND active ORDER BY partition_id",
+            db,
+            cli::sql_string(&name)
+        );
+        let tsv = clickhouse::query(cfg, &sql).await?;
+        let partitions: Vec<&str> = tsv.lines().filter(|l| !l.is_empty()).collect();
+        if !partitions.is_empty() {
+            let attach: Vec<String> = partitions
+                .iter()
+                .map(|id| {
+                    format!(
+                        "ATTACH PARTITION ID '{}' FROM {}",
+                        cli::sql_string(id),
+                        self.table
+                    )
+                })
+                .collect();
+            let sql = format!("ALTER TABLE {} {}", self.target, attach.join(", "));
+            clickhouse::query(cfg, &sql).await.with_context(|| {
+                format!(
+                    "并入 {} 失败，部分分区可能已经并入；暂存表 {} 已保留",
+                    self.target, self.table
+                )
+            })?;
+        }
+        println!(
+            "🔗 原子导入: {} 个分区 / {} 行已并入 {}",
+            partitions.len(),
+            staged,
+            self.target
+        );
+        self.discard(cfg).await;
+        Ok(())
+    }
+
+    /// 删除暂存表，失败时只提示
+    pub async fn discard(&self, cfg: &Args) {
+        let sql = format!("DROP TABLE IF EXISTS {} SYNC", self.table);
+        if let Err(e) = clickhouse::query(cfg, &sql).await {
+            eprintln!("⚠️ 无法删除暂存表 {}: {:#}", self.table, e);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! ClickHouse 审计表：每个文件导入结束后写入一行 (文件、校验和、行数、字节、耗时、状态、query_id)，
+//! 台账与数据放在同一个库里，对账时可以直接和目标表 JOIN。
+//!
+//! 审计行以 async_insert 写入，由服务端攒批，不会因为文件数多而产生大量小 part；
+//! 写入失败只告警，不影响文件本身的导入结果。
+
+use crate::cli::{self, Args};
+use crate::clickhouse;
+use crate::report::{FileRecord, FileStatus, Tags};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// 查询已导入摘要时每条 IN 列表的最大长度
+const CHECKSUMS_PER_QUERY: usize = 1000;
+
+pub struct AuditTable {
+    table: String,
+}
+
+/// 审计表中的一行，字段名即列名
+#[derive(Serialize)]
+struct Row<'a> {
+    path: String,
+    file: &'a str,
+    target_table: &'a str,
+    status: FileStatus,
+    checksum: Option<&'a str>,
+    rows: Option<u64>,
+    bytes: u64,
+    duration_secs: f64,
+    finished_at: u64,
+    error: Option<&'a str>,
+    error_code: Option<u32>,
+    error_name: Option<&'a str>,
+    query_id: Option<&'a str>,
+    pack: Option<&'a str>,
+    tags: &'a Tags,
+}
+
+impl AuditTable {
+    /// 表不存在时按固定结构创建
+    pub async fn open(cfg: &Args, table: &str) -> Result<Self> {
+        let ddl = format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                 path          String,
+                 file          String,
+                 target_table  LowCardinality(String),
+                 status        LowCardinality(String),
+                 checksum      Nullable(String),
+                 rows          Nullable(UInt64),
+                 bytes         UInt64,
+                 duration_secs Float64,
+                 finished_at   DateTime,
+                 error         Nullable(String),
+                 error_code    Nullable(UInt32),
+                 error_name    Nullable(String),
+                 query_id      Nullable(String),
+                 pack          Nullable(String),
+                 tags          Map(String, String)
+             ) ENGINE = MergeTree ORDER BY (target_table, finished_at)",
+            table
+        );
+        clickhouse::query(cfg, &ddl)
+            .await
+            .with_context(|| format!("无法创建审计表: {}", table))?;
+        println!("🧾 审计记录写入: {}", table);
+        Ok(Self {
+            table: table.to_string(),
+        })
+    }
+
+    /// `checksums` 中已成功导入到 `target` 的摘要
+    pub async fn loaded_checksums(
+        &self,
+        cfg: &Args,
+        target: &str,
+        checksums: &[&str],
+    ) -> Result<HashSet<String>> {
+        let mut loaded = HashSet::new();
+        for chunk in checksums.chunks(CHECKSUMS_PER_QUERY) {
+            let list: Vec<String> = chunk.iter().map(|c| format!("'{}'", c)).collect();
+            let sql = format!(
+                "SELECT DISTINCT checksum FROM {} \
+                 WHERE target_table = '{}' AND status = 'success' AND checksum IN ({})",
+                self.table,
+                cli::sql_string(target),
+                list.join(", ")
+            );
+            let tsv = clickhouse::query(cfg, &sql).await?;
+            loaded.extend(tsv.lines().filter(|l| !l.is_empty()).map(str::to_string));
+        }
+        Ok(loaded)
+    }
+
+    /// 写入一个导入单元的全部成员；`rows` 与 `records` 一一对应，为文件的行数
+    pub async fn record(
+        &self,
+        cfg: &Args,
+        records: &[FileRecord],
+        rows: &[Option<u64>],
+    ) -> Result<()> {
+        let mut sql = format!(
+            "INSERT INTO {} SETTINGS async_insert = 1, wait_for_async_insert = 1 FORMAT JSONEachRow\n",
+            self.table
+        );
+        for (r, rows) in records.iter().zip(rows) {
+            let row = Row {
+                path: r.path.to_string_lossy().into_owned(),
+                file: &r.file,
+                target_table: &r.table,
+                status: r.status,
+                checksum: r.hash.as_deref(),
+                rows: *rows,
+                bytes: r.bytes,
+                duration_secs: r.elapsed_secs,
+                finished_at: r.finished_at,
+                error: r.error.as_deref(),
+                error_code: r.error_code,
+                error_name: r.error_name.as_deref(),
+                query_id: r.query_id.as_deref(),
+                pack: r.pack.as_deref(),
+                tags: &r.tags,
+            };
+            sql.push_str(&serde_json::to_string(&row)?);
+            sql.push('\n');
+        }
+        clickhouse::query(cfg, &sql).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+//! `--http-compression auto`：启动时按首个文件选择请求体的压缩方式。
+//!
+//! 读取文件开头一段 (至多 SAMPLE_BYTES)，分别测量磁盘读取速度、各候选压缩方式的单线程速度与压缩比，
+//! 再把原始数据发送到服务端的 null 表函数测量网络吞吐。每种方式的预计吞吐取三者中最慢的一环：
+//! 磁盘读取、压缩速度 × 可用核数 (不超过 --workers)、网络吞吐 × 压缩比。
+//! 候选按 CPU 开销从低到高排列，只有预计吞吐高出 10% 以上才换用更耗 CPU 的方式，
+//! 所以局域网上多半选 none 或 lz4，带宽受限的广域网上选较高级别的 zstd。
+//!
+//! 本地没有可探测的文件 (stdin、远端输入源、copy 等) 时按 zstd 处理；
+//! 网络探测失败时只按磁盘与 CPU 选择。探测结果作用于整个运行期间。
+
+use crate::cli::{Args, Transport};
+use crate::wire::{Compression, Encoder};
+use crate::{http, loader, remote, route};
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// 探测读取的最大字节数
+const SAMPLE_BYTES: u64 = 16 * 1024 * 1024;
+/// 换用更耗 CPU 的方式所需的最小收益
+const MIN_GAIN: f64 = 1.1;
+/// 参与比较的 zstd 级别
+const ZSTD_LEVELS: [i32; 3] = [1, 3, 9];
+
+/// 一种候选压缩方式的测量结果
+struct Candidate {
+    compression: Compression,
+    /// zstd 的级别，其他方式为 0
+    level: i32,
+    /// 单线程压缩速度 (原始数据 MB/s)，不压缩时为无穷大
+    speed: f64,
+    ratio: f64,
+}
+
+impl Candidate {
+    fn label(&self) -> String {
+        match self.compression {
+            Compression::Zstd => format!("zstd (级别 {})", self.level),
+            other => format!("{:?}", other).to_lowercase(),
+        }
+    }
+}
+
+/// 把 `auto` 解析为具体的压缩方式与 zstd 级别；`dir` 为导入目录，从中取首个文件探测
+pub async fn resolve(cfg: &mut Args, dir: Option<&Path>) -> Result<()> {
+    if cfg.http_compression != Compression::Auto {
+        return Ok(());
+    }
+    // 只有 HTTP 传输会压缩请求体
+    if !cfg.transport_chain().contains(&Transport::Http) {
+        cfg.http_compression = Compression::None;
+        return Ok(());
+    }
+    let Some(path) = dir.and_then(|dir| sample_file(cfg, dir).ok().flatten()) else {
+        println!("ℹ️ 没有可供探测的本地文件，--http-compression auto 按 zstd 处理");
+        cfg.http_compression = Compression::Zstd;
+        return Ok(());
+    };
+
+    let (sample, disk) = read_sample(&path).await?;
+    let chunk = cfg.chunk_size() as usize;
+    let candidates = {
+        let sample = sample.clone();
+        tokio::task::spawn_blocking(move || measure(&sample, chunk)).await??
+    };
+    let network = match probe_network(cfg, sample).await {
+        Ok(speed) => Some(speed),
+        Err(e) => {
+            eprintln!("⚠️ 网络吞吐探测失败，只按磁盘与 CPU 选择压缩方式: {:#}", e);
+            None
+        }
+    };
+    let cores = idle_cores().min(cfg.workers.max(1) as f64);
+    println!(
+        "🧪 压缩探测 {:?}: 磁盘 {:.0} MB/s, 网络 {}, 可用核 {:.1}",
+        path,
+        disk,
+        network.map_or("未知".to_string(), |n| format!("{:.0} MB/s", n)),
+        cores
+    );
+
+    // 预计吞吐：磁盘、压缩、网络中最慢的一环
+    let estimate = |c: &Candidate| {
+        let net = network.map_or(f64::INFINITY, |n| n * c.ratio);
+        disk.min(c.speed * cores).min(net)
+    };
+    let mut best = &candidates[0];
+    for c in &candidates {
+        println!(
+            "   压缩 {:>6} MB/s  压缩比 {:>5.2}  预计 {:>6.0} MB/s  {}",
+            if c.speed.is_finite() {
+                format!("{:.0}", c.speed)
+            } else {
+                "-".to_string()
+            },
+            c.ratio,
+            estimate(c),
+            c.label()
+        );
+        if estimate(c) > estimate(best) * MIN_GAIN {
+            best = c;
+        }
+    }
+    println!(
+        "🗜️ --http-compression auto → {} (预计 {:.0} MB/s)",
+        best.label(),
+        estimate(best)
+    );
+    cfg.http_compression = best.compression;
+    if best.compression == Compression::Zstd {
+        cfg.zstd_level = best.level;
+    }
+    Ok(())
+}
+
+/// 目录下的首个非空文件 (按文件名排序，跳过隐藏文件)；--multi-table 时依次查找各表的子目录
+fn sample_file(cfg: &Args, dir: &Path) -> Result<Option<PathBuf>> {
+    if remote::is_remote(dir) || !dir.is_dir() {
+        return Ok(None);
+    }
+    let dirs = if cfg.multi_table {
+        route::table_dirs(dir)?
+            .into_iter()
+            .map(|(d, _)| d)
+            .collect()
+    } else {
+        vec![dir.to_path_buf()]
+    };
+    for dir in dirs {
+        let mut files = loader::discover(&dir)?;
+        files.sort();
+        let sample = files.into_iter().find(|p| {
+            let hidden = p
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            !hidden && std::fs::metadata(p).is_ok_and(|m| m.len() > 0)
+        });
+        if sample.is_some() {
+            return Ok(sample);
+        }
+    }
+    Ok(None)
+}
+
+/// 读取文件开头的样本，返回样本与读取速度 (MB/s)；文件已在页缓存中时测得的是内存速度
+async fn read_sample(path: &Path) -> Result<(Bytes, f64)> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let file = File::open(&path).with_context(|| format!("无法打开 {:?}", path))?;
+        let start = Instant::now();
+        let mut sample = Vec::new();
+        file.take(SAMPLE_BYTES).read_to_end(&mut sample)?;
+        let speed = mb_per_sec(sample.len(), start);
+        Ok((Bytes::from(sample), speed))
+    })
+    .await?
+}
+
+/// 按 CPU 开销从低到高测量各候选方式的单线程压缩速度与压缩比
+fn measure(sample: &[u8], chunk: usize) -> Result<Vec<Candidate>> {
+    let mut candidates = vec![Candidate {
+        compression: Compression::None,
+        level: 0,
+        speed: f64::INFINITY,
+        ratio: 1.0,
+    }];
+    let options = std::iter::once((Compression::Lz4, 0))
+        .chain(ZSTD_LEVELS.iter().map(|&level| (Compression::Zstd, level)));
+    for (compression, level) in options {
+        let mut encoder = Encoder::new(compression, level, 0)?.context("未开启压缩")?;
+        let start = Instant::now();
+        let mut packed = 0;
+        for block in sample.chunks(chunk.max(1)) {
+            packed += encoder.encode(block)?.len();
+        }
+        packed += encoder.finish()?.len();
+        candidates.push(Candidate {
+            compression,
+            level,
+            speed: mb_per_sec(sample.len(), start),
+            ratio: sample.len() as f64 / packed.max(1) as f64,
+        });
+    }
+    Ok(candidates)
+}
+
+/// 把样本原样发送到服务端丢弃，返回网络吞吐 (MB/s)
+async fn probe_network(cfg: &Args, sample: Bytes) -> Result<f64> {
+    let client = http::build_client()?;
+    // 先建立连接，测量不包含 TCP / TLS 握手
+    http::query(&client, cfg, "SELECT 1").await?;
+    let len = sample.len();
+    let start = Instant::now();
+    http::discard(&client, cfg, sample).await?;
+    Ok(mb_per_sec(len, start))
+}
+
+/// 空闲的 CPU 核数：核数减去最近一分钟的平均负载，至少为 1
+fn idle_cores() -> f64 {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get()) as f64;
+    let load = std::fs::read_to_string("/proc/loadavg")
+        .ok()
+        .and_then(|s| s.split_whitespace().next()?.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    (cores - load).max(1.0)
+}
+
+fn mb_per_sec(bytes: usize, start: Instant) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0 / start.elapsed().as_secs_f64().max(1e-9)
+}
@@ -0,0 +1,237 @@
+//! bench 子命令：对同一组文件依次测量各阶段的吞吐，判断瓶颈在磁盘、压缩、网络还是服务端解析。
+//!
+//! 1. 读取：只读文件，不做其他处理 (首次读取时为磁盘速度，之后的阶段大多命中页缓存)
+//! 2. 压缩：读取并按 --http-compression 压缩后丢弃，未开启压缩时跳过
+//! 3. 导入：经 HTTP 写入自动创建的 `ENGINE = Null` 表 (服务端解析后丢弃数据)，结束后删除该表；
+//!    服务端耗时取自 X-ClickHouse-Summary，其余时间视为网络传输与排队
+//!
+//! `--no-insert` 只测前两个阶段，不连接 ClickHouse。源文件不会被移动或删除。
+
+use crate::cli::{Args, BenchArgs, Transport};
+use crate::report::{self, Tags};
+use crate::wire::{Compression, Encoder, Upload};
+use crate::{clickhouse, http, loader, remote, schema};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 一个阶段的结果
+struct Stage {
+    name: String,
+    bytes: u64,
+    elapsed: Duration,
+    note: String,
+}
+
+impl Stage {
+    fn throughput_mb(&self) -> f64 {
+        self.bytes as f64 / 1024.0 / 1024.0 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+pub async fn run(args: BenchArgs) -> Result<()> {
+    if remote::is_remote(&args.dir) {
+        bail!("bench 仅支持本地目录");
+    }
+    let mut opts = args.opts;
+    if !args.no_insert && opts.transport != Transport::Http {
+        println!("ℹ️ 导入阶段固定使用 HTTP 传输，以便取得服务端耗时");
+        opts.transport = Transport::Http;
+    }
+    let cfg = Arc::new(opts);
+    let mut files: Vec<(PathBuf, u64)> = Vec::new();
+    for path in loader::discover(&args.dir)? {
+        let size = std::fs::metadata(&path)?.len();
+        if size > 0 {
+            files.push((path, size));
+        }
+    }
+    files.sort();
+    if let Some(n) = args.files {
+        files.truncate(n);
+    }
+    if files.is_empty() {
+        bail!("{:?} 中没有可用于测试的文件", args.dir);
+    }
+    let total: u64 = files.iter().map(|(_, size)| size).sum();
+    println!(
+        "⏱️ 基准测试: {} 个文件, {:.1} MB, 并行数 {}",
+        files.len(),
+        total as f64 / 1024.0 / 1024.0,
+        cfg.workers
+    );
+
+    let mut stages = vec![read_stage(&cfg, &files).await?];
+    if cfg.http_compression != Compression::None {
+        stages.push(compress_stage(&cfg, &files).await?);
+    }
+    let mut server_share = None;
+    if !args.no_insert {
+        let table = format!("ck_loader_bench_{}", std::process::id());
+        schema::create_table(&cfg, &table, &files[0].0, "Null").await?;
+        let result = insert_stage(&cfg, &table, &files).await;
+        if let Err(e) = clickhouse::query(&cfg, &format!("DROP TABLE IF EXISTS {}", table)).await {
+            eprintln!("⚠️ 无法删除测试表 {}: {:#}", table, e);
+        }
+        let (stage, share) = result?;
+        stages.push(stage);
+        server_share = Some(share);
+    }
+
+    println!("\n🏁 各阶段吞吐:");
+    for stage in &stages {
+        println!(
+            "   {:<16} {:>9.1} MB/s  耗时 {:>8.2?}  {}",
+            stage.name,
+            stage.throughput_mb(),
+            stage.elapsed,
+            stage.note
+        );
+    }
+    println!("🔎 瓶颈: {}", bottleneck(&stages, server_share));
+    Ok(())
+}
+
+/// 阶段 1：顺序读完每个文件
+async fn read_stage(cfg: &Args, files: &[(PathBuf, u64)]) -> Result<Stage> {
+    let chunk = cfg.chunk_size() as usize;
+    let start = Instant::now();
+    let bytes: Vec<u64> = stream::iter(files.iter().cloned())
+        .map(|(path, _)| async move {
+            tokio::task::spawn_blocking(move || {
+                let mut file = File::open(&path).with_context(|| format!("无法打开 {:?}", path))?;
+                let mut buf = vec![0u8; chunk];
+                let mut n = 0u64;
+                loop {
+                    match file.read(&mut buf)? {
+                        0 => return Ok::<_, anyhow::Error>(n),
+                        len => n += len as u64,
+                    }
+                }
+            })
+            .await?
+        })
+        .buffer_unordered(cfg.workers)
+        .try_collect()
+        .await?;
+    Ok(Stage {
+        name: "读取".to_string(),
+        bytes: bytes.iter().sum(),
+        elapsed: start.elapsed(),
+        note: "首次读取为磁盘速度，之后的阶段大多命中页缓存".to_string(),
+    })
+}
+
+/// 阶段 2：读取并压缩，统计压缩比
+async fn compress_stage(cfg: &Args, files: &[(PathBuf, u64)]) -> Result<Stage> {
+    let (chunk, compression, level, threads) = (
+        cfg.chunk_size() as usize,
+        cfg.http_compression,
+        cfg.zstd_level,
+        cfg.compress_threads,
+    );
+    let start = Instant::now();
+    let sizes: Vec<(u64, u64)> = stream::iter(files.iter().cloned())
+        .map(|(path, _)| async move {
+            tokio::task::spawn_blocking(move || {
+                let mut file = File::open(&path).with_context(|| format!("无法打开 {:?}", path))?;
+                let mut encoder =
+                    Encoder::new(compression, level, threads)?.context("未开启压缩")?;
+                let mut buf = vec![0u8; chunk];
+                let (mut raw, mut packed) = (0u64, 0u64);
+                loop {
+                    let len = file.read(&mut buf)?;
+                    if len == 0 {
+                        break;
+                    }
+                    raw += len as u64;
+                    packed += encoder.encode(&buf[..len])?.len() as u64;
+                }
+                packed += encoder.finish()?.len() as u64;
+                Ok::<_, anyhow::Error>((raw, packed))
+            })
+            .await?
+        })
+        .buffer_unordered(cfg.workers)
+        .try_collect()
+        .await?;
+    let raw: u64 = sizes.iter().map(|(r, _)| r).sum();
+    let packed: u64 = sizes.iter().map(|(_, p)| p).sum();
+    Ok(Stage {
+        name: format!("压缩 ({:?})", compression).to_lowercase(),
+        bytes: raw,
+        elapsed: start.elapsed(),
+        note: format!("压缩比 {:.2}", raw as f64 / packed.max(1) as f64),
+    })
+}
+
+/// 阶段 3：经 HTTP 写入 Null 表，返回阶段结果与服务端耗时占各请求耗时的比例
+async fn insert_stage(cfg: &Args, table: &str, files: &[(PathBuf, u64)]) -> Result<(Stage, f64)> {
+    let client = http::build_client()?;
+    let tags = Tags::new();
+    let batch_id = report::new_batch_id();
+    let start = Instant::now();
+    let timings: Vec<(u64, Duration, Option<f64>)> = stream::iter(files.iter().enumerate())
+        .map(|(i, (path, size))| {
+            let (client, tags) = (&client, &tags);
+            let query_id = format!("ckloader-{}-bench{}", batch_id, i + 1);
+            async move {
+                let upload = Arc::new(Upload::new(cfg).query_id(query_id));
+                let started = Instant::now();
+                let summary = http::insert(client, cfg, table, path, tags, &upload)
+                    .await
+                    .map_err(|e| anyhow!("{:?} 导入失败: {}", path, e.to_string().trim()))?;
+                let server = summary.and_then(|s| s.elapsed_secs);
+                Ok::<_, anyhow::Error>((*size, started.elapsed(), server))
+            }
+        })
+        .buffer_unordered(cfg.workers)
+        .try_collect()
+        .await?;
+    let elapsed = start.elapsed();
+    let requests: f64 = timings.iter().map(|(_, d, _)| d.as_secs_f64()).sum();
+    let server: f64 = timings.iter().filter_map(|(_, _, s)| *s).sum();
+    let share = (server / requests.max(1e-9)).min(1.0);
+    let stage = Stage {
+        name: "导入 (Null 表)".to_string(),
+        bytes: timings.iter().map(|(b, _, _)| b).sum(),
+        elapsed,
+        note: format!("服务端耗时占 {:.0}%", share * 100.0),
+    };
+    Ok((stage, share))
+}
+
+/// 导入阶段包含读取与压缩：导入明显慢于其余阶段时，按服务端耗时占比区分网络与服务端解析；
+/// 否则瓶颈是读取与压缩中较慢的一个
+fn bottleneck(stages: &[Stage], server_share: Option<f64>) -> &'static str {
+    let local = stages
+        .iter()
+        .take(if server_share.is_some() {
+            stages.len() - 1
+        } else {
+            stages.len()
+        })
+        .min_by(|a, b| a.throughput_mb().total_cmp(&b.throughput_mb()));
+    let local_name = match local {
+        Some(stage) if stage.name.starts_with("压缩") => {
+            "压缩 (可换用更快的压缩方式或 --http-compression none)"
+        }
+        _ => "磁盘读取",
+    };
+    match (server_share, stages.last(), local) {
+        (Some(share), Some(insert), Some(local))
+            if insert.throughput_mb() < local.throughput_mb() * 0.8 =>
+        {
+            if share >= 0.5 {
+                "服务端解析 ORC (可增加 max_insert_threads 或服务端资源)"
+            } else {
+                "网络传输 (可开启 --http-compression 或提高带宽)"
+            }
+        }
+        _ => local_name,
+    }
+}
@@ -0,0 +1,51 @@
+//! 失败额度：`--fail-fast` / `--max-failures N`，批次中失败的文件数达到上限后所有表都不再启动新文件，
+//! 进行中的导入照常完成。凭据错误、集群故障等系统性问题不必等成百上千个注定失败的插入逐个失败。
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+
+pub struct Budget {
+    limit: Option<u64>,
+    failed: AtomicU64,
+    exhausted: CancellationToken,
+}
+
+impl Budget {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit,
+            failed: AtomicU64::new(0),
+            exhausted: CancellationToken::new(),
+        }
+    }
+
+    /// 同样上限的新额度 (watch 的每一轮、serve 的每个任务各自计数)
+    pub fn renew(&self) -> Self {
+        Self::new(self.limit)
+    }
+
+    /// 记入 `files` 个失败文件；恰好因这次达到上限时返回 true
+    pub fn record(&self, files: u64) -> bool {
+        let Some(limit) = self.limit else {
+            return false;
+        };
+        let before = self.failed.fetch_add(files, Ordering::Relaxed);
+        if before < limit && before + files >= limit {
+            self.exhausted.cancel();
+            return true;
+        }
+        false
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted.is_cancelled()
+    }
+
+    pub fn exhausted(&self) -> WaitForCancellationFuture<'_> {
+        self.exhausted.cancelled()
+    }
+}
@@ -0,0 +1,209 @@
+//! 辅助查询 (DESCRIBE / 系统表检查等) 与 `ck-loader sql` 的语句执行，按 --transport 选择
+//! clickhouse-client 或 HTTP；native 传输只用于导入数据，这里走 HTTP
+
+use crate::cli::{self, Args, Transport};
+use crate::error::{ClickHouseError, ErrorClass};
+use crate::report::Tags;
+use crate::{error, http};
+use anyhow::{bail, Context, Result};
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time;
+
+/// 执行一条查询并返回 TSV 格式的结果
+pub async fn query(cfg: &Args, sql: &str) -> Result<String> {
+    match cfg.transport {
+        Transport::Client => {
+            let result = client_query(cfg, &cfg.password.get().await?, sql).await;
+            if let Err(e) = &result {
+                if error::is_auth_error(&format!("{:#}", e)) {
+                    cfg.password.invalidate();
+                }
+            }
+            result
+        }
+        Transport::Http | Transport::Native => http::query(&http::build_client()?, cfg, sql).await,
+    }
+}
+
+async fn client_query(cfg: &Args, password: &str, sql: &str) -> Result<String> {
+    let output = Command::new("clickhouse-client")
+        .args(cfg.client_args())
+        .arg("--password")
+        .arg(password)
+        .arg("--format")
+        .arg("TabSeparated")
+        .arg("-q")
+        .arg(sql)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("无法启动 clickhouse-client 进程")?;
+
+    if !output.status.success() {
+        bail!(
+            "查询失败: {} | {}",
+            sql,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 执行一条语句并返回 `format` 格式的输出：与导入相同的传输链、凭据与标签 (log_comment)，
+/// 连接失败时换下一种传输方式并按 --retries / --retry-backoff 重试，每次尝试受 --timeout-secs 限制。
+/// 其他暂时性错误 (传输中断、502 / 504 等) 与服务端过载时语句可能已经执行，非幂等的语句 (INSERT SELECT 等)
+/// 重试会重复写入，只有 `retry_unsafe` 时才重试，过载时先等待 --overload-cooldown
+pub async fn execute(
+    cfg: &Args,
+    sql: &str,
+    format: &str,
+    tags: &Tags,
+    query_id: &str,
+    retry_unsafe: bool,
+) -> Result<String, ClickHouseError> {
+    let timeout = cfg.timeout_for(None);
+    let (mut overloads, mut retries) = (0, 0);
+    loop {
+        // 同一 query_id 不能同时运行两次，上一次尝试可能仍在服务端执行
+        let attempt_id = match overloads + retries {
+            0 => query_id.to_string(),
+            n => format!("{}-r{}", query_id, n),
+        };
+        let result = time::timeout(timeout, execute_chain(cfg, sql, format, tags, &attempt_id))
+            .await
+            .unwrap_or(Err(ClickHouseError::Timeout(timeout)));
+        match result {
+            Err(e) if retry_unsafe && e.is_overload() && overloads < cfg.overload_retries => {
+                overloads += 1;
+                eprintln!(
+                    "🧊 服务端过载 ({})，冷却 {:?} 后重试 ({}/{})",
+                    e.name().unwrap_or("-"),
+                    cfg.overload_cooldown,
+                    overloads,
+                    cfg.overload_retries
+                );
+                time::sleep(cfg.overload_cooldown).await;
+            }
+            Err(e)
+                if e.class() == ErrorClass::Retryable
+                    && !e.is_overload()
+                    && (retry_unsafe || e.is_connect())
+                    && retries < cfg.retries =>
+            {
+                retries += 1;
+                let backoff = cfg
+                    .retry_backoff
+                    .saturating_mul(2u32.saturating_pow(retries - 1));
+                eprintln!(
+                    "🔁 暂时性错误，{:?} 后重试 ({}/{}): {}",
+                    backoff,
+                    retries,
+                    cfg.retries,
+                    e.to_string().trim()
+                );
+                time::sleep(backoff).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 按传输链依次尝试，native 与 http 都走 HTTP 接口，只尝试一次
+async fn execute_chain(
+    cfg: &Args,
+    sql: &str,
+    format: &str,
+    tags: &Tags,
+    query_id: &str,
+) -> Result<String, ClickHouseError> {
+    let mut chain: Vec<Transport> = Vec::new();
+    for t in cfg.transport_chain() {
+        let t = if t == Transport::Native {
+            Transport::Http
+        } else {
+            t
+        };
+        if !chain.contains(&t) {
+            chain.push(t);
+        }
+    }
+    for (i, transport) in chain.iter().enumerate() {
+        let result = match transport {
+            Transport::Client => client_execute(cfg, sql, format, tags, query_id).await,
+            Transport::Http | Transport::Native => {
+                let client = http::build_client()?;
+                http::execute(&client, cfg, sql, format, tags, query_id).await
+            }
+        };
+        match (result, chain.get(i + 1)) {
+            (Err(e), Some(next)) if e.is_connect() => {
+                eprintln!(
+                    "⚠️ {} 传输失败，改用 {}: {}",
+                    transport.name(),
+                    next.name(),
+                    e
+                );
+            }
+            (result, _) => return result,
+        }
+    }
+    unreachable!("传输链中最后一种方式的结果总是直接返回")
+}
+
+async fn client_execute(
+    cfg: &Args,
+    sql: &str,
+    format: &str,
+    tags: &Tags,
+    query_id: &str,
+) -> Result<String, ClickHouseError> {
+    let password = cfg.password.get().await?;
+    let mut cmd = Command::new("clickhouse-client");
+    cmd.args(cfg.client_args())
+        .arg("--password")
+        .arg(password)
+        .arg("--format")
+        .arg(format)
+        .arg("--query_id")
+        .arg(query_id);
+    if !tags.is_empty() {
+        cmd.arg("--log_comment")
+            .arg(serde_json::to_string(tags).unwrap_or_default());
+    }
+    let output = cmd
+        .arg("-q")
+        .arg(sql)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| ClickHouseError::Connect(format!("无法启动 clickhouse-client: {}", e)))?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let err = ClickHouseError::from_client(
+        output.status.code(),
+        &String::from_utf8_lossy(&output.stderr),
+    );
+    if err.is_auth() {
+        cfg.password.invalidate();
+    }
+    Err(err)
+}
+
+/// 将 TSV 输出拆分为行和列
+pub fn rows(tsv: &str) -> Vec<Vec<&str>> {
+    tsv.lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.split('\t').collect())
+        .collect()
+}
+
+/// `db.table` 拆为 system 表查询条件中的库名表达式与表名
+pub fn locate(table: &str) -> (String, String) {
+    match table.split_once('.') {
+        Some((db, name)) => (format!("'{}'", cli::sql_string(db)), name.to_string()),
+        None => ("currentDatabase()".to_string(), table.to_string()),
+    }
+}
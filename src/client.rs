@@ -0,0 +1,79 @@
+//! clickhouse-client 子进程导入：文件句柄 (或本进程的 stdin) 直接作为子进程 stdin
+
+use crate::cli::Args;
+use crate::error::ClickHouseError;
+use crate::report::Tags;
+use crate::stall::Attempt;
+use std::process::Stdio;
+use tokio::process::Command;
+
+pub async fn insert(
+    cfg: &Args,
+    table: &str,
+    query: &str,
+    input: Stdio,
+    tags: &Tags,
+    attempt: Option<&Attempt>,
+) -> Result<(), ClickHouseError> {
+    let password = cfg.password.get().await?;
+    let mut cmd = Command::new("nice");
+    cmd.arg("-n")
+        .arg("10")
+        .arg("clickhouse-client")
+        .args(cfg.client_args())
+        .arg("--password")
+        .arg(password);
+    for (key, value) in cfg.insert_settings(table) {
+        cmd.arg(format!("--{}", key)).arg(value);
+    }
+    // 标签写入 log_comment，便于在 system.query_log 中按标签归类
+    if !tags.is_empty() {
+        cmd.arg("--log_comment")
+            .arg(serde_json::to_string(tags).unwrap_or_default());
+    }
+    if let Some(attempt) = attempt {
+        cmd.arg("--query_id").arg(&attempt.query_id);
+        if let Some(traceparent) = &attempt.traceparent {
+            cmd.arg("--opentelemetry-traceparent").arg(traceparent);
+        }
+        if let Some(token) = &attempt.dedup_token {
+            cmd.arg("--insert_deduplication_token").arg(token);
+        }
+    }
+    let mut child = cmd
+        .arg("-q")
+        .arg(query)
+        .stdin(input)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| ClickHouseError::Connect(format!("无法启动 clickhouse-client: {}", e)))?;
+    // nice 以 exec 方式启动 clickhouse-client，pid 不变
+    if let (Some(attempt), Some(pid)) = (attempt, child.id()) {
+        attempt.track_child(pid);
+    }
+
+    // 超时由调用方按文件大小控制，超时后 future 被丢弃，子进程随之被 kill
+    match child.wait().await {
+        Ok(status) if status.success() => Ok(()),
+        // nice 找不到或无法执行 clickhouse-client
+        Ok(status) if matches!(status.code(), Some(126 | 127)) => Err(ClickHouseError::Connect(
+            "无法启动 clickhouse-client (未安装或不可执行)".to_string(),
+        )),
+        Ok(status) => {
+            // 失败时提取 stderr 并解析为服务端异常
+            let stderr = child
+                .wait_with_output()
+                .await
+                .map(|o| String::from_utf8_lossy(&o.stderr).to_string())
+                .unwrap_or_default();
+            let err = ClickHouseError::from_client(status.code(), &stderr);
+            if err.is_auth() {
+                cfg.password.invalidate();
+            }
+            Err(err)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
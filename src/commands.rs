@@ -0,0 +1,625 @@
+//! 各子命令的入口：load / watch / verify / retry / status / sql
+
+use crate::archive::{self, fingerprint};
+use crate::cli::{Args, LoadArgs, RetryArgs, SqlArgs, StatusArgs, VerifyArgs, WatchArgs};
+use crate::dashboard::{Board, Tui};
+use crate::events::EventStream;
+use crate::ledger::{Ledger, LedgerQuery};
+use crate::loader::{self, Job, Pool};
+use crate::reconcile::Reconciliation;
+use crate::replay::LoadManifest;
+use crate::report::{self, BatchReport, FileStatus, SkipReason, Tags};
+use crate::shutdown::Shutdown;
+use crate::{
+    clickhouse, effective, error, interactive, lock, manifest, orc, partitions, route, systemd,
+};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::time;
+
+pub async fn load(args: LoadArgs, events: EventStream) -> Result<ExitCode> {
+    let start_time = Instant::now();
+    let started_at = report::unix_now();
+    // 在扫描目录之前加锁，另一个进程不会拾取同一批文件
+    let _lock = match (&args.files_from, &args.manifest, &args.dir) {
+        (None, None, Some(dir)) => match lock::acquire(&args.opts, dir).await? {
+            Some(lock) => Some(lock),
+            None => return Ok(error::locked()),
+        },
+        _ => None,
+    };
+    let mut unrouted = Vec::new();
+    let jobs = if let Some(path) = &args.files_from {
+        let replay = LoadManifest::read(path)?;
+        println!(
+            "📋 导入清单 {:?}: {} 个文件 (生成于 {})，正在校验...",
+            path,
+            replay.files.len(),
+            replay.created_at
+        );
+        replay.verify()?;
+        replay.check_settings(&args.opts);
+        replay.jobs()?
+    } else {
+        match (&args.manifest, &args.dir) {
+            (Some(path), _) => {
+                let entries = manifest::read(path)?;
+                println!("📋 清单 {:?}: {} 个文件", path, entries.len());
+                manifest::jobs(entries, &args.opts.routes, args.table.as_deref())?
+            }
+            (None, Some(dir)) => {
+                let mut jobs = Vec::new();
+                for (dir, table) in target_dirs(dir, args.table.as_deref(), &args.opts)? {
+                    if args.opts.routes.is_empty() {
+                        jobs.push(Job {
+                            dir,
+                            table: table.context("缺少 -t/--table")?,
+                            files: None,
+                        });
+                    } else {
+                        let files = loader::discover(&dir)?;
+                        let (split, mut skipped) =
+                            route::split(&dir, files, &args.opts.routes, table.as_deref());
+                        jobs.extend(split);
+                        unrouted.append(&mut skipped);
+                    }
+                }
+                jobs
+            }
+            (None, None) => Vec::new(),
+        }
+    };
+    let mut jobs = route::order(jobs, &args.opts.depends)?;
+    // 按清单导入时在清单涉及的每个目录加锁；按路径顺序加锁，两个进程用 --wait-lock 等待同一组目录时不会互相卡住
+    let mut _manifest_locks = Vec::new();
+    if args.files_from.is_some() || args.manifest.is_some() {
+        let dirs: BTreeSet<&Path> = jobs.iter().map(|j| j.dir.as_path()).collect();
+        for dir in dirs {
+            match lock::acquire(&args.opts, dir).await? {
+                Some(lock) => _manifest_locks.push(lock),
+                None => return Ok(error::locked()),
+            }
+        }
+    }
+    if let Some(path) = &args.write_manifest {
+        for job in &mut jobs {
+            if job.files.is_none() {
+                job.files = Some(loader::discover(&job.dir)?);
+            }
+        }
+        LoadManifest::build(&jobs, &args.opts, &args.format)?.write(path)?;
+        println!("📝 导入清单已写入: {:?}", path);
+    }
+
+    if args.interactive && !interactive::confirm(&mut jobs, &args.opts)? {
+        println!("🚫 已取消，未导入任何文件");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // 清单可能涉及多张表，报告中列出全部表名
+    let mut tables: Vec<&str> = Vec::new();
+    for job in &jobs {
+        if !tables.contains(&job.table.as_str()) {
+            tables.push(&job.table);
+        }
+    }
+    let report_table = if tables.is_empty() {
+        args.table.clone().unwrap_or_default()
+    } else {
+        tables.join(",")
+    };
+
+    // 报告在批次结束时才写出，先确认其所在目录可写
+    if let Some(path) = &args.report {
+        let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+        archive::probe_writable(parent.unwrap_or(Path::new(".")))?;
+    }
+
+    let cfg = Arc::new(args.opts);
+    let mut pool = Pool::new(&cfg, events).await?;
+    let batch_id = Arc::clone(&pool.batch_id);
+    let metrics = Arc::clone(&pool.metrics);
+    let errors = Arc::clone(&pool.errors);
+    let batch_span = Arc::clone(&pool.span);
+    let keeper = pool.keeper.clone();
+    let tracer = pool.tracer.clone();
+    println!("🏷️ 批次 id: {}", batch_id);
+    for path in &unrouted {
+        metrics.exclude(path, SkipReason::Unrouted);
+    }
+    let shutdown = Shutdown::on_signals(cfg.shutdown_grace);
+    let tui = if args.tui {
+        let board = Arc::new(Board::new(cfg.workers, Arc::clone(&metrics)));
+        pool.board = Some(Arc::clone(&board));
+        Some(Tui::start(board, &batch_id, args.tui_log.as_deref())?)
+    } else {
+        None
+    };
+    let mut records = Vec::new();
+    if args.stdin {
+        let table = args.table.clone().context("缺少 -t/--table")?;
+        let record = loader::run_stdin(
+            Arc::clone(&cfg),
+            table,
+            args.format,
+            args.normalize_header,
+            args.validate_rows,
+            pool,
+            shutdown.clone(),
+        );
+        records.push(record.await?);
+    } else {
+        // 有文件失败 (或因依赖被跳过) 的表，依赖它们的表不再导入
+        let mut failed_tables: Vec<String> = Vec::new();
+        for job in jobs {
+            if pool.budget.is_exhausted() {
+                let files = match job.files {
+                    Some(files) => files,
+                    None => loader::discover(&job.dir)?,
+                };
+                for path in &files {
+                    metrics.exclude(path, SkipReason::FailureBudget);
+                }
+                continue;
+            }
+            if let Some(dep) = failed_tables
+                .iter()
+                .find(|t| route::depends_on(&cfg.depends, &job.table, t))
+            {
+                eprintln!("⛔ {} 依赖的表 {} 有文件导入失败，跳过", job.table, dep);
+                let files = match job.files {
+                    Some(files) => files,
+                    None => loader::discover(&job.dir)?,
+                };
+                for path in &files {
+                    metrics.exclude(path, SkipReason::DependencyFailed);
+                }
+                failed_tables.push(job.table);
+                continue;
+            }
+            let table = job.table.clone();
+            let done =
+                loader::run(Arc::clone(&cfg), job, pool.clone(), shutdown.clone(), None).await?;
+            if done.iter().any(|r| r.status == FileStatus::Failed) {
+                failed_tables.push(table);
+            }
+            records.extend(done);
+        }
+    }
+
+    drop(tui);
+    println!("\n🏁 批次执行完毕！");
+    let snapshot = metrics.snapshot();
+    snapshot.print_summary();
+    errors.print_summary();
+    let skipped = metrics.skipped_files();
+    let reconciliation = Reconciliation::build(&records, snapshot.skipped, &skipped);
+    reconciliation.print();
+    let partitions = if args.partition_summary {
+        match partitions::summarize(&cfg, &records, started_at).await {
+            Ok(partitions) => {
+                partitions::print(&partitions);
+                partitions
+            }
+            Err(e) => {
+                eprintln!("⚠️ 分区汇总失败: {:#}", e);
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    // 老版本服务端会静默忽略不认识的设置，读回实际生效的值，避免误以为调优参数起了作用
+    let settings = match effective::check(&cfg, &records, started_at).await {
+        Ok(settings) => {
+            if !settings.is_empty() {
+                effective::print(&settings);
+            }
+            settings
+        }
+        Err(e) => {
+            eprintln!("⚠️ 无法核对插入设置: {:#}", e);
+            Vec::new()
+        }
+    };
+    let exit = if shutdown.is_stopping() {
+        println!("🛑 批次被中断，未启动的文件需要重新导入");
+        error::interrupted()
+    } else {
+        error::exit_code(&records)
+    };
+    batch_span.set("ck_loader.succeeded", snapshot.succeeded);
+    batch_span.set("ck_loader.failed", snapshot.failed);
+    batch_span.set("ck_loader.skipped", snapshot.skipped);
+    if snapshot.failed > 0 {
+        batch_span.fail(&format!("{} 个文件导入失败", snapshot.failed));
+    }
+    batch_span.end();
+    tracer.flush().await;
+    if let Some(keeper) = keeper {
+        keeper.close().await;
+    }
+
+    if let Some(path) = &args.report {
+        let batch = BatchReport {
+            batch_id: batch_id.to_string(),
+            started_at,
+            elapsed_secs: start_time.elapsed().as_secs_f64(),
+            table: report_table,
+            tags: cfg.tags.iter().cloned().collect(),
+            files: records,
+            skipped,
+            partitions,
+            settings,
+            reconciliation,
+        };
+        batch.write(path)?;
+        println!("📝 报告已写入: {:?}", path);
+    }
+
+    Ok(exit)
+}
+
+/// 需要扫描的 (目录, 默认表)：--multi-table 时为各子目录与同名表，否则为 `-d` 本身
+fn target_dirs(
+    dir: &Path,
+    table: Option<&str>,
+    cfg: &Args,
+) -> Result<Vec<(PathBuf, Option<String>)>> {
+    if !cfg.multi_table {
+        return Ok(vec![(dir.to_path_buf(), table.map(str::to_string))]);
+    }
+    let dirs = route::table_dirs(dir)?;
+    Ok(dirs.into_iter().map(|(d, t)| (d, Some(t))).collect())
+}
+
+/// 周期性扫描目录。成功的文件会被移走；失败的文件在内容 (大小/mtime) 变化前不再重复尝试
+pub async fn watch(args: WatchArgs, events: EventStream) -> Result<ExitCode> {
+    let Some(_lock) = lock::acquire(&args.opts, &args.dir).await? else {
+        return Ok(error::locked());
+    };
+    let cfg = Arc::new(args.opts);
+    let pool = Pool::new(&cfg, events).await?;
+    let shutdown = Shutdown::on_signals(cfg.shutdown_grace);
+    let mut failed: HashMap<PathBuf, (u64, Option<SystemTime>)> = HashMap::new();
+    println!("👀 开始监视 {:?} (间隔 {:?})", args.dir, args.interval);
+    systemd::ready(
+        &format!("监视 {}", args.dir.display()),
+        &shutdown,
+        cfg.shutdown_grace,
+    );
+
+    let mut round = 0u64;
+    loop {
+        let pool = pool.next_batch();
+        round += 1;
+        let (mut succeeded, mut failures) = (0, 0);
+        for (dir, table) in target_dirs(&args.dir, args.table.as_deref(), &cfg)? {
+            let files: Vec<PathBuf> = loader::discover(&dir)?
+                .into_iter()
+                .filter(|p| failed.get(p) != Some(&fingerprint(p)))
+                .collect();
+            let (jobs, _) = route::split(&dir, files, &cfg.routes, table.as_deref());
+            let jobs = route::order(jobs, &cfg.depends)?;
+            for job in jobs {
+                let records =
+                    loader::run(Arc::clone(&cfg), job, pool.clone(), shutdown.clone(), None)
+                        .await?;
+                for r in records {
+                    match r.status {
+                        FileStatus::Success => {
+                            succeeded += 1;
+                            failed.remove(&r.path)
+                        }
+                        FileStatus::Failed => {
+                            failures += 1;
+                            failed.insert(r.path.clone(), fingerprint(&r.path))
+                        }
+                    };
+                }
+            }
+        }
+        systemd::notify(&format!(
+            "STATUS=第 {} 轮扫描完成: 成功 {}，失败 {}，{} 个失败文件等待变化",
+            round,
+            succeeded,
+            failures,
+            failed.len()
+        ));
+
+        if !shutdown.is_stopping() {
+            tokio::select! {
+                _ = time::sleep(args.interval) => continue,
+                _ = shutdown.stopping() => {}
+            }
+        }
+        println!("👋 收到中断信号，停止监视");
+        pool.span.end();
+        pool.tracer.flush().await;
+        if let Some(keeper) = &pool.keeper {
+            keeper.close().await;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+}
+
+/// 以 ORC Footer 中的行数为准，核对 done 目录与目标表的行数
+pub async fn verify(args: VerifyArgs) -> Result<()> {
+    let done_dir = args.opts.done_dir(&args.dir);
+    // --on-success compress 归档的 .gz 文件无法直接读取 Footer，不参与核对
+    let (archived, files): (Vec<PathBuf>, Vec<PathBuf>) = walk(&done_dir)?
+        .into_iter()
+        .partition(|p| p.extension().is_some_and(|e| e == "gz"));
+    if !archived.is_empty() {
+        println!("⚠️ 跳过 {} 个 gzip 归档文件", archived.len());
+    }
+    let mut expected = 0u64;
+    for path in &files {
+        let meta = orc::read_meta(path).with_context(|| format!("无法读取 {:?}", path))?;
+        expected += meta.num_rows;
+    }
+
+    let mut sql = format!("SELECT count() FROM {}", args.table);
+    if let Some(cond) = &args.r#where {
+        sql.push_str(&format!(" WHERE {}", cond));
+    }
+    let out = clickhouse::query(&args.opts, &sql).await?;
+    let actual: u64 = out.trim().parse().context("无法解析 count() 结果")?;
+
+    println!("📄 文件: {} 个 | 文件行数合计: {}", files.len(), expected);
+    println!("🗄️ 表 {} 行数: {}", args.table, actual);
+    if actual != expected {
+        bail!(
+            "行数不一致: 表 {} - 文件 {} = {}",
+            actual,
+            expected,
+            actual as i64 - expected as i64
+        );
+    }
+    println!("✅ 行数一致");
+    Ok(())
+}
+
+/// 递归列出目录下的文件，done 可能按 --done-layout 分成了多级日期子目录；
+/// 隐藏文件 (如预写日志) 不计入
+fn walk(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries = std::fs::read_dir(dir).with_context(|| format!("无法读取目录: {:?}", dir))?;
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(walk(&path)?);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// 失败且源文件仍在原位置的文件 (--dir 的 failed/ 错误日志，或台账中最近一次失败)，按 (目录, 表) 分组重新导入；
+/// `--max-attempts` 大于 1 时，仍失败的文件隔 `--attempt-interval` 后再导入一轮
+pub async fn retry(args: RetryArgs, events: EventStream) -> Result<ExitCode> {
+    let _lock = match &args.dir {
+        Some(dir) => match lock::acquire(&args.opts, dir).await? {
+            Some(lock) => Some(lock),
+            None => return Ok(error::locked()),
+        },
+        None => None,
+    };
+    let failed = match (&args.dir, &args.opts.ledger) {
+        (Some(dir), _) => failed_from_logs(dir, &args.opts.failed_dir(dir), args.table.as_deref())?,
+        (None, Some(ledger_path)) => {
+            Ledger::open(ledger_path)?.latest_failed(args.table.as_deref())?
+        }
+        (None, None) => bail!("retry 需要 --dir 或 --ledger"),
+    };
+    let mut groups: BTreeMap<(PathBuf, String), Vec<PathBuf>> = BTreeMap::new();
+    let mut causes: BTreeMap<String, usize> = BTreeMap::new();
+    for (path, table, error_name) in failed {
+        let path = PathBuf::from(path);
+        let Some(dir) = path.parent().map(|d| d.to_path_buf()) else {
+            continue;
+        };
+        if path.is_file() {
+            groups.entry((dir, table)).or_default().push(path);
+            *causes
+                .entry(error_name.unwrap_or_else(|| "未分类".to_string()))
+                .or_default() += 1;
+        }
+    }
+
+    if groups.is_empty() {
+        match &args.dir {
+            Some(dir) => println!("📭 {:?} 中没有需要重试的失败文件", dir),
+            None => println!("📭 台账中没有需要重试的失败文件"),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    let causes: Vec<String> = causes
+        .iter()
+        .map(|(name, n)| format!("{} × {}", name, n))
+        .collect();
+    println!("🔎 上次失败原因: {}", causes.join(", "));
+
+    let cfg = Arc::new(args.opts);
+    let pool = Pool::new(&cfg, events).await?;
+    let shutdown = Shutdown::on_signals(cfg.shutdown_grace);
+    // 同一文件多轮重试时只保留最后一次的结果
+    let mut latest: BTreeMap<PathBuf, report::FileRecord> = BTreeMap::new();
+    for attempt in 1..=args.max_attempts {
+        if attempt > 1 {
+            println!(
+                "⏳ 第 {}/{} 轮重试将在 {:?} 后开始",
+                attempt, args.max_attempts, args.attempt_interval
+            );
+            tokio::select! {
+                _ = time::sleep(args.attempt_interval) => {}
+                _ = shutdown.stopping() => break,
+            }
+        }
+        for ((dir, table), files) in std::mem::take(&mut groups) {
+            if pool.budget.is_exhausted() || shutdown.is_stopping() {
+                break;
+            }
+            println!("🔁 重试 {} 个文件: {:?} → {}", files.len(), dir, table);
+            let job = Job {
+                dir: dir.clone(),
+                table: table.clone(),
+                files: Some(files),
+            };
+            let done =
+                loader::run(Arc::clone(&cfg), job, pool.clone(), shutdown.clone(), None).await?;
+            for record in done {
+                match record.status {
+                    // 成功后删除旧的错误日志，下次 retry --dir 不再找到它
+                    FileStatus::Success => {
+                        let _ = std::fs::remove_file(error_log_path(
+                            &cfg.failed_dir(&dir),
+                            &record.path,
+                        ));
+                    }
+                    FileStatus::Failed if record.path.is_file() => groups
+                        .entry((dir.clone(), table.clone()))
+                        .or_default()
+                        .push(record.path.clone()),
+                    _ => {}
+                }
+                latest.insert(record.path.clone(), record);
+            }
+        }
+        if groups.is_empty() || pool.budget.is_exhausted() || shutdown.is_stopping() {
+            break;
+        }
+    }
+    println!("\n🏁 重试完毕");
+    pool.metrics.snapshot().print_summary();
+    pool.errors.print_summary();
+    pool.span.end();
+    pool.tracer.flush().await;
+    if let Some(keeper) = &pool.keeper {
+        keeper.close().await;
+    }
+    if shutdown.is_stopping() {
+        return Ok(error::interrupted());
+    }
+    let records: Vec<report::FileRecord> = latest.into_values().collect();
+    Ok(error::exit_code(&records))
+}
+
+/// 落地目录的 failed 目录下各错误日志对应的 (文件路径, 目标表, 错误名)
+fn failed_from_logs(
+    dir: &Path,
+    failed_dir: &Path,
+    table: Option<&str>,
+) -> Result<Vec<(String, String, Option<String>)>> {
+    if !failed_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut failed = Vec::new();
+    for entry in
+        std::fs::read_dir(failed_dir).with_context(|| format!("无法读取目录: {:?}", failed_dir))?
+    {
+        let log = entry?.path();
+        if !log.to_string_lossy().ends_with(".error.log") {
+            continue;
+        }
+        match error::read_log(&log) {
+            Some((path, t, name)) if table.is_none_or(|want| want == t) => {
+                // 日志中是导入时的路径；以相对路径导入后换了工作目录时按落地目录找回
+                let path = match path.file_name() {
+                    Some(file_name) if !path.is_file() => dir.join(file_name),
+                    _ => path,
+                };
+                failed.push((path.to_string_lossy().into_owned(), t, name))
+            }
+            Some(_) => {}
+            None => eprintln!("⚠️ 无法解析错误日志: {:?}", log),
+        }
+    }
+    failed.sort();
+    Ok(failed)
+}
+
+/// 与 `ClickHouseError::write_log` 相同的错误日志路径
+fn error_log_path(failed_dir: &Path, path: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    failed_dir.join(format!("{}.error.log", file_name))
+}
+
+pub fn status(args: StatusArgs) -> Result<()> {
+    let ledger = Ledger::open(&args.ledger)?;
+    let since = args
+        .since
+        .map(|d| report::unix_now().saturating_sub(d.as_secs()))
+        .unwrap_or(0);
+
+    println!(
+        "{:<32} {:<8} {:>8} {:>14}",
+        "表", "状态", "文件数", "字节数"
+    );
+    for (table, status, files, bytes) in ledger.summary(since)? {
+        println!("{:<32} {:<8} {:>8} {:>14}", table, status, files, bytes);
+    }
+
+    let failures = ledger.query(&LedgerQuery {
+        status: Some("failed".to_string()),
+        since: Some(since),
+        limit: 10,
+        ..Default::default()
+    })?;
+    if !failures.is_empty() {
+        println!("\n最近的失败:");
+        for f in failures {
+            let kind = match (f.error_code, &f.error_name) {
+                (Some(code), Some(name)) => format!("[{} {}] ", code, name),
+                (Some(code), None) => format!("[{}] ", code),
+                _ => String::new(),
+            };
+            println!(
+                "  ❌ {} → {} | {}{}",
+                f.path,
+                f.table,
+                kind,
+                f.error.unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 用导入相同的连接配置依次执行 SQL。结果原样写到 stdout，进度与错误写到 stderr，便于在脚本中使用
+pub async fn sql(args: SqlArgs) -> Result<ExitCode> {
+    let cfg = args.opts;
+    let tags: Tags = cfg.tags.iter().cloned().collect();
+    let batch_id = report::new_batch_id();
+    for (i, sql) in args.queries.iter().enumerate() {
+        let query_id = format!("ckloader-{}-sql{}", batch_id, i + 1);
+        eprintln!("🛠️ 执行 [{}]: {}", query_id, sql.trim());
+        let start = Instant::now();
+        match clickhouse::execute(&cfg, sql, &args.format, &tags, &query_id, args.retry_unsafe)
+            .await
+        {
+            Ok(output) => {
+                print!("{}", output);
+                eprintln!("✅ 执行完成 | 耗时: {:.2?}", start.elapsed());
+            }
+            Err(e) => {
+                eprintln!("❌ ERROR: {}", e.to_string().trim());
+                let rest = args.queries.len() - i - 1;
+                if rest > 0 {
+                    eprintln!("⛔ 其余 {} 条语句不再执行", rest);
+                }
+                return Ok(error::exit_code_for(&e));
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,222 @@
+//! copy 子命令：把一张表从源集群直接复制到目标集群，取代 `clickhouse-client -q "SELECT ..." | clickhouse-client -q "INSERT ..."` 式的管道。
+//!
+//! 与 export 相同按源表 system.parts 中的活跃分区切片，每个分区一条 `SELECT * ... FORMAT Native`，
+//! 响应体不落地，直接作为目标端 `INSERT INTO ... FORMAT Native` 的请求体 (--http-compression、--per-file-bandwidth 等照常生效)。
+//! 各分区受 --workers 个并行许可限制，暂时性错误、服务端过载按导入时的 --retries / --overload-retries 重试，
+//! 每次尝试受 --timeout-secs (或按分区大小的 --timeout-per-gb) 限制。
+//!
+//! 重试前已写入的部分由 insert_deduplication_token (批次 id + 分区 id) 去重，前提是目标表开启了插入去重
+//! (Replicated 表默认开启，普通 MergeTree 需设置 non_replicated_deduplication_window)。
+//! 源端与目标端都经 HTTP 接口访问；源端的连接配置由 --source 给出，其余参数均作用于目标端。
+
+use crate::cli::{Args, CopyArgs, Transport};
+use crate::error::{self, ClickHouseError, ErrorClass};
+use crate::export::{self, Slice};
+use crate::report::{self, Tags};
+use crate::secrets::Secret;
+use crate::shutdown::Shutdown;
+use crate::wire::Upload;
+use crate::{cli, dsn, http};
+use anyhow::{anyhow, Result};
+use futures::future;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::time;
+
+pub async fn run(mut args: CopyArgs) -> Result<ExitCode> {
+    if args.opts.transport != Transport::Http {
+        println!("ℹ️ 复制固定使用 HTTP 传输，源端响应体直接作为目标端的请求体");
+        args.opts.transport = Transport::Http;
+    }
+    let source = source_config(&args)?;
+    let (args, cfg) = (&args, &args.opts);
+    let table = args.table.as_deref().unwrap_or(&args.source_table);
+
+    let slices = export::list_slices(&source, &args.source_table).await?;
+    let total_rows: u64 = slices.iter().filter_map(|s| s.rows).sum();
+    println!(
+        "🚚 复制 {} ({}) → {} ({}): {} 个分区, 约 {} 行, 并行数 {}",
+        args.source_table,
+        source.http_url(),
+        table,
+        cfg.http_url(),
+        slices.len(),
+        total_rows,
+        cfg.workers
+    );
+
+    let client = http::build_client()?;
+    let tags: Tags = cfg.tags.iter().cloned().collect();
+    let batch_id = report::new_batch_id();
+    let semaphore = Arc::new(Semaphore::new(cfg.workers.max(1)));
+    let shutdown = Shutdown::on_signals(cfg.shutdown_grace);
+    let start = Instant::now();
+    let tasks = slices.iter().enumerate().map(|(i, slice)| {
+        let (client, source, tags, batch_id) = (&client, &source, &tags, &batch_id);
+        let (semaphore, shutdown) = (&semaphore, &shutdown);
+        async move {
+            let name = slice.partition_id.as_deref().unwrap_or("all").to_string();
+            let _permit = semaphore.acquire().await.expect("信号量不会关闭");
+            if shutdown.is_stopping() {
+                return (name, Err(ClickHouseError::Cancelled));
+            }
+            let started = Instant::now();
+            let query_id = format!("ckloader-{}-copy{}", batch_id, i + 1);
+            let result = copy_slice(
+                client, source, cfg, args, table, slice, tags, batch_id, &query_id, shutdown,
+            )
+            .await;
+            match &result {
+                Ok(rows) => println!(
+                    "✅ 分区 {} | {} 行 | 耗时: {:.2?}",
+                    name,
+                    rows,
+                    started.elapsed()
+                ),
+                Err(e) => eprintln!("❌ 分区 {} 复制失败: {}", name, e.to_string().trim()),
+            }
+            (name, result)
+        }
+    });
+    let results = future::join_all(tasks).await;
+
+    let rows: u64 = results.iter().filter_map(|(_, r)| r.as_ref().ok()).sum();
+    let failed: Vec<&(String, Result<u64, ClickHouseError>)> =
+        results.iter().filter(|(_, r)| r.is_err()).collect();
+    println!(
+        "📦 复制完成: {} 个分区, 写入 {} 行, 失败 {} 个 | 耗时: {:.2?}",
+        results.len() - failed.len(),
+        rows,
+        failed.len(),
+        start.elapsed()
+    );
+    if shutdown.is_stopping() {
+        return Ok(error::interrupted());
+    }
+    let Some((_, Err(first))) = failed.first() else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    for (name, _) in &failed {
+        eprintln!("   ❌ 分区 {}", name);
+    }
+    Ok(if failed.len() < results.len() {
+        error::partial()
+    } else {
+        error::exit_code_for(first)
+    })
+}
+
+/// 源端的连接配置：--source 为连接串时展开 (与 --url 相同)，否则为 HTTP 地址；
+/// 其余参数沿用目标端，用户名、密码、默认库恢复为缺省值后由连接串与 --source-password 给出
+fn source_config(args: &CopyArgs) -> Result<Args> {
+    let mut source = args.opts.clone();
+    source.user = "default".to_string();
+    source.password = Secret::parse("").map_err(|e| anyhow!(e))?;
+    source.database = None;
+    source.url = args.source.clone();
+    if dsn::is_dsn(&args.source) {
+        dsn::parse(&args.source)
+            .map_err(|e| anyhow!(e))?
+            .apply(&mut source, |_| false);
+    }
+    if let Some(password) = &args.source_password {
+        source.password = password.clone();
+    }
+    Ok(source)
+}
+
+/// 复制一个分区，返回目标端报告的写入行数
+#[allow(clippy::too_many_arguments)]
+async fn copy_slice(
+    client: &reqwest::Client,
+    source: &Args,
+    cfg: &Args,
+    args: &CopyArgs,
+    table: &str,
+    slice: &Slice,
+    tags: &Tags,
+    batch_id: &str,
+    query_id: &str,
+    shutdown: &Shutdown,
+) -> Result<u64, ClickHouseError> {
+    let mut filters = Vec::new();
+    if let Some(id) = &slice.partition_id {
+        filters.push(format!("_partition_id = '{}'", cli::sql_string(id)));
+    }
+    if let Some(filter) = &args.filter {
+        filters.push(format!("({})", filter));
+    }
+    let mut select = format!("SELECT * FROM {}", args.source_table);
+    if !filters.is_empty() {
+        select.push_str(&format!(" WHERE {}", filters.join(" AND ")));
+    }
+    select.push_str(" FORMAT Native");
+    // 各次重试相同，目标端据此去重已写入的块
+    let token = format!(
+        "ckloader-{}-{}-{}",
+        batch_id,
+        table,
+        slice.partition_id.as_deref().unwrap_or("all")
+    );
+    let extra = [("insert_deduplication_token", token)];
+
+    let timeout = cfg.timeout_for(slice.bytes);
+    let (mut overloads, mut retries) = (0, 0);
+    loop {
+        // 同一 query_id 不能同时运行两次，上一次尝试可能仍在服务端执行
+        let attempt_id = match overloads + retries {
+            0 => query_id.to_string(),
+            n => format!("{}-r{}", query_id, n),
+        };
+        let upload = Arc::new(Upload::new(cfg).query_id(attempt_id));
+        let attempt =
+            http::insert_select(client, source, &select, cfg, table, tags, &upload, &extra);
+        let result = tokio::select! {
+            res = time::timeout(timeout, attempt) => {
+                res.unwrap_or(Err(ClickHouseError::Timeout(timeout)))
+            }
+            _ = shutdown.aborted() => Err(ClickHouseError::Cancelled),
+        };
+        let name = slice.partition_id.as_deref().unwrap_or("all");
+        match result {
+            Ok(summary) => return Ok(summary.map(|s| s.written_rows).unwrap_or(0)),
+            Err(e) if e.is_overload() && overloads < cfg.overload_retries => {
+                overloads += 1;
+                eprintln!(
+                    "🧊 分区 {} 服务端过载 ({})，冷却 {:?} 后重试 ({}/{})",
+                    name,
+                    e.name().unwrap_or("-"),
+                    cfg.overload_cooldown,
+                    overloads,
+                    cfg.overload_retries
+                );
+                time::sleep(cfg.overload_cooldown).await;
+            }
+            Err(e)
+                if e.class() == ErrorClass::Retryable
+                    && !e.is_overload()
+                    && retries < cfg.retries =>
+            {
+                retries += 1;
+                let backoff = cfg
+                    .retry_backoff
+                    .saturating_mul(2u32.saturating_pow(retries - 1));
+                eprintln!(
+                    "🔁 分区 {} 暂时性错误，{:?} 后重试 ({}/{}): {}",
+                    name,
+                    backoff,
+                    retries,
+                    cfg.retries,
+                    e.to_string().trim()
+                );
+                tokio::select! {
+                    _ = time::sleep(backoff) => {}
+                    _ = shutdown.aborted() => return Err(ClickHouseError::Cancelled),
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! `--tui`：以全屏面板代替滚动的日志，显示进行中的文件 (进度、速度、耗时)、批次总计、
+//! worker 利用率与错误滚动栏，适合长时间无人值守的回填任务。
+//!
+//! 面板直接绘制到 /dev/tty。运行期间本进程 (及 clickhouse-client 子进程) 的 stdout / stderr 重定向到管道：
+//! stderr 的行 (错误、重试、告警) 进入滚动栏，所有行写入 --tui-log (如指定)。面板关闭后恢复原来的输出，
+//! 批次汇总照常打印。不进入 raw 模式，Ctrl-C 仍按原有的信号处理优雅停止。
+
+use crate::metrics::Metrics;
+use crate::stall::Attempt;
+use anyhow::{bail, Context, Result};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::{cursor, execute, terminal};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 面板刷新间隔
+const REFRESH: Duration = Duration::from_millis(500);
+/// 错误滚动栏保留的行数
+const TICKER_LINES: usize = 100;
+
+/// 一个进行中的导入单元 (单个文件或合并组)
+struct InFlight {
+    name: String,
+    bytes: u64,
+    started: Instant,
+    /// 当前的导入尝试及其开始时间，速度按当前尝试计算
+    attempt: Option<(Arc<Attempt>, Instant)>,
+}
+
+/// 面板数据：进行中的导入单元与 stderr 的最近几行，由导入 worker 与输出捕获线程更新
+pub struct Board {
+    workers: usize,
+    metrics: Arc<Metrics>,
+    next_id: AtomicU64,
+    units: Mutex<BTreeMap<u64, InFlight>>,
+    ticker: Mutex<VecDeque<String>>,
+    /// stderr 累计输出的行数
+    errors: AtomicU64,
+}
+
+impl Board {
+    pub fn new(workers: usize, metrics: Arc<Metrics>) -> Self {
+        Self {
+            workers,
+            metrics,
+            next_id: AtomicU64::new(0),
+            units: Mutex::new(BTreeMap::new()),
+            ticker: Mutex::new(VecDeque::new()),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// 登记一个开始导入的单元，返回的 Shown 释放时从面板上移除
+    pub fn enter(self: &Arc<Self>, name: &str, bytes: u64) -> Shown {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.units.lock().unwrap().insert(
+            id,
+            InFlight {
+                name: name.to_string(),
+                bytes,
+                started: Instant::now(),
+                attempt: None,
+            },
+        );
+        Shown {
+            board: Arc::clone(self),
+            id,
+        }
+    }
+
+    fn push_error(&self, line: String) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        let mut ticker = self.ticker.lock().unwrap();
+        if ticker.len() == TICKER_LINES {
+            ticker.pop_front();
+        }
+        ticker.push_back(format!(
+            "{} {}",
+            chrono::Local::now().format("%H:%M:%S"),
+            line
+        ));
+    }
+}
+
+/// 面板上的一行
+pub struct Shown {
+    board: Arc<Board>,
+    id: u64,
+}
+
+impl Shown {
+    /// 切换到新的导入尝试 (重试时进度从头计算)
+    pub fn track(&self, attempt: &Arc<Attempt>) {
+        if let Some(unit) = self.board.units.lock().unwrap().get_mut(&self.id) {
+            unit.attempt = Some((Arc::clone(attempt), Instant::now()));
+        }
+    }
+}
+
+impl Drop for Shown {
+    fn drop(&mut self) {
+        self.board.units.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// 运行中的面板；drop 时关闭面板并恢复 stdout / stderr
+pub struct Tui {
+    stop: Arc<AtomicBool>,
+    render: Option<JoinHandle<()>>,
+    /// 被重定向的 fd 与其原来指向的副本
+    saved: Vec<(RawFd, OwnedFd)>,
+}
+
+impl Tui {
+    pub fn start(board: Arc<Board>, batch_id: &str, log: Option<&Path>) -> Result<Self> {
+        if !io::stdout().is_terminal() {
+            bail!("--tui 需要在终端中运行 (stdout 被重定向时请去掉 --tui)");
+        }
+        let mut tty = OpenOptions::new()
+            .write(true)
+            .open("/dev/tty")
+            .context("无法打开 /dev/tty")?;
+        let log = match log {
+            Some(path) => Some(Arc::new(Mutex::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("无法打开 --tui-log: {:?}", path))?,
+            ))),
+            None => None,
+        };
+
+        execute!(tty, terminal::EnterAlternateScreen, cursor::Hide)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(tty))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let render = {
+            let stop = Arc::clone(&stop);
+            let board = Arc::clone(&board);
+            let batch_id = batch_id.to_string();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let _ = terminal.draw(|frame| draw(frame, &board, &batch_id));
+                    thread::sleep(REFRESH);
+                }
+                let tty = terminal.backend_mut();
+                let _ = execute!(tty, terminal::LeaveAlternateScreen, cursor::Show);
+            })
+        };
+
+        let mut tui = Self {
+            stop,
+            render: Some(render),
+            saved: Vec::new(),
+        };
+        io::stdout().flush()?;
+        for fd in [io::stdout().as_raw_fd(), io::stderr().as_raw_fd()] {
+            let (saved, pipe) = capture(fd)?;
+            tui.saved.push((fd, saved));
+            let board = Arc::clone(&board);
+            let log = log.clone();
+            let is_err = fd == io::stderr().as_raw_fd();
+            thread::spawn(move || {
+                for line in BufReader::new(pipe).lines() {
+                    let Ok(line) = line else { break };
+                    if let Some(log) = &log {
+                        let _ = writeln!(log.lock().unwrap(), "{}", line);
+                    }
+                    if is_err && !line.trim().is_empty() {
+                        board.push_error(line);
+                    }
+                }
+            });
+        }
+        Ok(tui)
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        for (fd, saved) in self.saved.drain(..) {
+            // SAFETY: 两个 fd 都在本进程中打开且有效，dup2 只替换 fd 指向的文件
+            unsafe {
+                libc::dup2(saved.as_raw_fd(), fd);
+            }
+        }
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(render) = self.render.take() {
+            let _ = render.join();
+        }
+    }
+}
+
+/// 把 `fd` 重定向到新管道的写端，返回 `fd` 原来指向的副本与管道的读端
+fn capture(fd: RawFd) -> Result<(OwnedFd, File)> {
+    let mut pipe = [0; 2];
+    // SAFETY: pipe / dup 返回的新 fd 立即交给 OwnedFd / File 管理；dup2 之后写端已有 `fd` 这个副本，
+    // 原写端随即关闭，进程内不再有其他写端，恢复 `fd` 后读端读到 EOF
+    unsafe {
+        if libc::pipe(pipe.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error()).context("无法创建管道");
+        }
+        let (reader, writer) = (File::from_raw_fd(pipe[0]), OwnedFd::from_raw_fd(pipe[1]));
+        let saved = libc::dup(fd);
+        if saved < 0 {
+            return Err(io::Error::last_os_error()).context("无法复制输出 fd");
+        }
+        let saved = OwnedFd::from_raw_fd(saved);
+        if libc::dup2(writer.as_raw_fd(), fd) < 0 {
+            return Err(io::Error::last_os_error()).context("无法重定向输出");
+        }
+        Ok((saved, reader))
+    }
+}
+
+fn draw(frame: &mut Frame, board: &Board, batch_id: &str) {
+    let snapshot = board.metrics.snapshot();
+    let units = board.units.lock().unwrap();
+    let [summary, workers, table, ticker] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Length(3),
+        Constraint::Min(5),
+        Constraint::Length(10),
+    ])
+    .areas(frame.area());
+
+    let totals = Paragraph::new(vec![
+        format!(
+            "成功 {} | 失败 {} | 跳过 {} | 排队 {} | 进行中 {}",
+            snapshot.succeeded,
+            snapshot.failed,
+            snapshot.skipped,
+            snapshot.queued,
+            snapshot.in_flight
+        )
+        .into(),
+        format!(
+            "已导入 {} | 进行中 {} | 平均吞吐 {:.1} MB/s | 已运行 {}",
+            mb(snapshot.bytes_succeeded),
+            mb(snapshot.bytes_in_flight),
+            snapshot.throughput_mb(),
+            clock(Duration::from_secs_f64(snapshot.elapsed_secs))
+        )
+        .into(),
+    ])
+    .block(titled(format!("ck-loader 批次 {}", batch_id)));
+    frame.render_widget(totals, summary);
+
+    let busy = units.len().min(board.workers);
+    let gauge = Gauge::default()
+        .block(titled("worker 利用率".to_string()))
+        .gauge_style(Style::default().fg(Color::Green))
+        .ratio(busy as f64 / board.workers.max(1) as f64)
+        .label(format!("{} / {}", units.len(), board.workers));
+    frame.render_widget(gauge, workers);
+
+    let rows = units.values().map(|unit| {
+        let (sent, speed) = match &unit.attempt {
+            Some((attempt, since)) => {
+                let sent = attempt.sent().unwrap_or(0);
+                let secs = since.elapsed().as_secs_f64();
+                (sent, if secs > 0.0 { sent as f64 / secs } else { 0.0 })
+            }
+            None => (0, 0.0),
+        };
+        // native 传输发送的是编码后的数据块，可能超过文件大小
+        let percent = match unit.bytes {
+            0 => 0.0,
+            bytes => (sent as f64 / bytes as f64 * 100.0).min(100.0),
+        };
+        Row::new(vec![
+            unit.name.clone(),
+            mb(unit.bytes),
+            format!("{:>5.1}%", percent),
+            format!("{}/s", mb(speed as u64)),
+            clock(unit.started.elapsed()),
+        ])
+    });
+    let table_widget = Table::new(
+        rows,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(12),
+            Constraint::Length(8),
+            Constraint::Length(14),
+            Constraint::Length(10),
+        ],
+    )
+    .header(
+        Row::new(vec!["文件", "大小", "进度", "速度", "耗时"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(titled(format!("进行中 ({})", units.len())));
+    frame.render_widget(table_widget, table);
+    drop(units);
+
+    let lines = board.ticker.lock().unwrap();
+    let visible = ticker.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = lines
+        .iter()
+        .skip(lines.len().saturating_sub(visible))
+        .map(|line| ListItem::new(line.as_str()).style(Style::default().fg(Color::Red)))
+        .collect();
+    let list = List::new(items).block(titled(format!(
+        "错误与告警 (共 {} 条)",
+        board.errors.load(Ordering::Relaxed)
+    )));
+    frame.render_widget(list, ticker);
+}
+
+fn titled(title: String) -> Block<'static> {
+    Block::default().borders(Borders::ALL).title(title)
+}
+
+fn mb(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0)
+}
+
+fn clock(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
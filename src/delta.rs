@@ -0,0 +1,207 @@
+//! delta 模式：上游每天把整份数据重新导出到同名目录时，只导入内容真正变化的文件
+//!
+//! 与台账中该路径最近一次成功导入的记录比较：大小与 mtime 都相同视为未变化；
+//! 否则计算 xxh3 摘要，摘要相同 (只是被重新写了一遍) 同样跳过。
+//! 指定 `--delta-partition` 时把目录视为一个分区，其中任一已导入过的文件变化，
+//! 就先 DROP 该分区再重导目录下的全部文件，避免新旧数据并存。
+
+use crate::ledger::Ledger;
+use crate::report;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::Xxh3;
+
+pub struct Plan {
+    /// 需要导入的文件
+    pub load: Vec<PathBuf>,
+    /// 已计算的文件摘要，随导入结果写入台账
+    pub hashes: HashMap<PathBuf, String>,
+    /// 内容未变化、不再导入的文件
+    pub unchanged: Vec<PathBuf>,
+    /// 导入前需要删除的分区表达式
+    pub drop_partition: Option<String>,
+}
+
+pub fn plan(
+    ledger: &Ledger,
+    table: &str,
+    dir: &Path,
+    files: Vec<PathBuf>,
+    partition: Option<&str>,
+) -> Result<Plan> {
+    let mut hashes = HashMap::new();
+    let mut changed = Vec::new();
+    let mut unchanged = Vec::new();
+    let mut replaced = 0usize;
+
+    for path in files {
+        let meta = std::fs::metadata(&path).with_context(|| format!("无法读取 {:?}", path))?;
+        let mtime = meta.modified().ok().map(report::unix_secs);
+        let Some(prev) = ledger.last_success(&path.to_string_lossy(), table)? else {
+            hashes.insert(path.clone(), hash_file(&path)?);
+            changed.push(path);
+            continue;
+        };
+        if prev.bytes == meta.len() && prev.mtime.is_some() && prev.mtime == mtime {
+            unchanged.push(path);
+            continue;
+        }
+        let hash = hash_file(&path)?;
+        let same = prev.hash.as_deref() == Some(hash.as_str());
+        hashes.insert(path.clone(), hash);
+        if same {
+            unchanged.push(path);
+        } else {
+            replaced += 1;
+            changed.push(path);
+        }
+    }
+
+    // 有已导入过的文件发生变化时才需要清理旧数据；只是新增文件则直接追加
+    let drop_partition = match partition {
+        Some(expr) if replaced > 0 => Some(render_partition(expr, dir)),
+        _ => None,
+    };
+    if drop_partition.is_some() {
+        for path in &unchanged {
+            if !hashes.contains_key(path) {
+                hashes.insert(path.clone(), hash_file(path)?);
+            }
+        }
+        changed.append(&mut unchanged);
+    } else if replaced > 0 {
+        eprintln!(
+            "⚠️ delta: {} 个已导入过的文件内容发生变化，旧数据不会被删除 (可用 --delta-partition 整分区重导)",
+            replaced
+        );
+    }
+
+    Ok(Plan {
+        load: changed,
+        hashes,
+        unchanged,
+        drop_partition,
+    })
+}
+
+/// `{dir}` 替换为目录名；`{value}` 替换为 hive 风格目录名 `key=value` 中的 value
+fn render_partition(expr: &str, dir: &Path) -> String {
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let value = name
+        .split_once('=')
+        .map(|(_, v)| v.to_string())
+        .unwrap_or_else(|| name.clone());
+    expr.replace("{dir}", &name).replace("{value}", &value)
+}
+
+/// 文件内容的 xxh3-128 摘要 (十六进制)
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("无法打开 {:?}", path))?;
+    let mut hasher = Xxh3::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:032x}", hasher.digest128()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{FileRecord, FileStatus};
+
+    /// 台账中一条成功导入的记录，大小与 mtime 取自当前文件，`hash` 为当时的摘要
+    fn loaded(ledger: &Ledger, path: &Path, mtime: Option<u64>, hash: &str) {
+        let bytes = std::fs::metadata(path).unwrap().len();
+        ledger
+            .record(&FileRecord {
+                file: path.file_name().unwrap().to_string_lossy().into_owned(),
+                path: path.to_path_buf(),
+                table: "t".to_string(),
+                status: FileStatus::Success,
+                bytes,
+                elapsed_secs: 0.0,
+                finished_at: 0,
+                error: None,
+                error_code: None,
+                error_name: None,
+                tags: Default::default(),
+                mtime,
+                hash: Some(hash.to_string()),
+                skipped_rows: None,
+                written_rows: None,
+                written_bytes: None,
+                server_elapsed_secs: None,
+                raw_bytes: None,
+                wire_bytes: None,
+                pack: None,
+                query_id: None,
+                overflow_policy: None,
+                overflow_values: None,
+                verified: None,
+                archived: None,
+            })
+            .unwrap();
+    }
+
+    fn setup(name: &str) -> (PathBuf, Ledger, Vec<PathBuf>) {
+        let root = std::env::temp_dir().join(format!("ck-delta-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let dir = root.join("dt=2024-01-01");
+        std::fs::create_dir_all(&dir).unwrap();
+        let ledger = Ledger::open(&root.join("ledger.db")).unwrap();
+        let files: Vec<PathBuf> = ["same", "new", "rewritten", "changed"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(format!("{}.orc", name));
+                std::fs::write(&path, name.as_bytes()).unwrap();
+                path
+            })
+            .collect();
+        let mtime = |path: &Path| {
+            let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+            Some(report::unix_secs(modified))
+        };
+        // same: 大小与 mtime 均未变；rewritten: mtime 不同但内容相同；changed: 内容不同
+        loaded(&ledger, &files[0], mtime(&files[0]), "unused");
+        loaded(&ledger, &files[2], Some(1), &hash_file(&files[2]).unwrap());
+        loaded(&ledger, &files[3], Some(1), "old");
+        (dir, ledger, files)
+    }
+
+    #[test]
+    fn skips_unchanged_files() {
+        let (dir, ledger, files) = setup("skip");
+        let planned = plan(&ledger, "t", &dir, files.clone(), None).unwrap();
+        assert_eq!(planned.load, [files[1].clone(), files[3].clone()]);
+        assert_eq!(planned.unchanged, [files[0].clone(), files[2].clone()]);
+        assert!(planned.drop_partition.is_none());
+        // 大小与 mtime 相同时不计算摘要
+        assert!(!planned.hashes.contains_key(&files[0]));
+        assert_eq!(planned.hashes[&files[1]], hash_file(&files[1]).unwrap());
+
+        // 其他表没有导入记录，全部需要导入
+        let planned = plan(&ledger, "other", &dir, files.clone(), None).unwrap();
+        assert_eq!(planned.load, files);
+    }
+
+    #[test]
+    fn reloads_partition_when_a_loaded_file_changed() {
+        let (dir, ledger, files) = setup("partition");
+        let planned = plan(&ledger, "t", &dir, files.clone(), Some("'{value}'")).unwrap();
+        assert_eq!(planned.drop_partition.as_deref(), Some("'2024-01-01'"));
+        assert_eq!(planned.load.len(), 4);
+        assert!(files.iter().all(|f| planned.hashes.contains_key(f)));
+        assert_eq!(render_partition("{dir}", Path::new("/in/day1")), "day1");
+    }
+}
@@ -0,0 +1,98 @@
+//! 导入前的服务端磁盘空间检查：估算待导入文件写入后占用的空间，与目标表存储策略首个卷上的磁盘剩余空间比较，
+//! 可能写满磁盘时告警，指定 `--require-free-space` 时中止批次。
+//!
+//! 估算：表中已有数据时按现有分区片段的平均每行字节数 × 文件的 ORC 总行数，空表时按文件大小
+//! (ORC 与 MergeTree 都是按列压缩，量级接近)。剩余空间扣除各磁盘的 keep_free_space，
+//! 只检查当前连接的服务端，其他副本的磁盘不在此列。
+
+use crate::cli::{self, Args};
+use crate::clickhouse::{self, locate};
+use crate::orc;
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+
+pub async fn check(cfg: &Args, table: &str, files: &[PathBuf]) -> Result<()> {
+    let (db, name) = locate(table);
+    let filter = format!("database = {} AND name = '{}'", db, cli::sql_string(&name));
+    // 新写入的分区片段落在优先级最高的卷上
+    let sql = format!(
+        "SELECT d.name, d.free_space, d.keep_free_space FROM system.disks AS d
+         WHERE has((SELECT disks FROM system.storage_policies
+                    WHERE policy_name = (SELECT storage_policy FROM system.tables WHERE {})
+                    ORDER BY volume_priority LIMIT 1), d.name)",
+        filter
+    );
+    let out = clickhouse::query(cfg, &sql).await?;
+    let disks = clickhouse::rows(&out);
+    if disks.is_empty() {
+        // 非 MergeTree 表 (如 Distributed) 没有存储策略，数据写在其他表中
+        return Ok(());
+    }
+    let mut available = 0u64;
+    let mut names = Vec::new();
+    for row in &disks {
+        let &[disk, free, keep, ..] = row.as_slice() else {
+            bail!("无法解析 system.disks 查询结果: {:?}", out);
+        };
+        let free: u64 = free.parse().context("无法解析磁盘剩余空间")?;
+        let keep: u64 = keep.parse().unwrap_or(0);
+        available = available.saturating_add(free.saturating_sub(keep));
+        names.push(disk);
+    }
+
+    let estimate = estimate(cfg, &db, &name, files).await?;
+    let headroom = cfg.require_free_space.unwrap_or(0);
+    if estimate.saturating_add(headroom) <= available {
+        return Ok(());
+    }
+    let message = format!(
+        "{} 预计写入 {}，所在磁盘 ({}) 剩余 {}{}",
+        table,
+        gb(estimate),
+        names.join(", "),
+        gb(available),
+        if headroom > 0 {
+            format!("，要求至少保留 {}", gb(headroom))
+        } else {
+            String::new()
+        }
+    );
+    if cfg.require_free_space.is_some() {
+        bail!("磁盘空间不足: {}", message);
+    }
+    eprintln!("⚠️ 磁盘空间可能不足: {}", message);
+    Ok(())
+}
+
+/// 待导入文件写入后预计占用的字节数
+async fn estimate(cfg: &Args, db: &str, name: &str, files: &[PathBuf]) -> Result<u64> {
+    let sql = format!(
+        "SELECT sum(rows), sum(bytes_on_disk) FROM system.parts
+         WHERE active AND database = {} AND table = '{}'",
+        db,
+        cli::sql_string(name)
+    );
+    let out = clickhouse::query(cfg, &sql).await?;
+    let stats = clickhouse::rows(&out);
+    let (table_rows, table_bytes) = match stats.first().and_then(|r| r.get(..2)) {
+        Some([rows, bytes]) => (rows.parse().unwrap_or(0u64), bytes.parse().unwrap_or(0u64)),
+        _ => (0, 0),
+    };
+    let files = files.to_vec();
+    let (rows, bytes) = tokio::task::spawn_blocking(move || {
+        files.iter().fold((0u64, 0u64), |(rows, bytes), path| {
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            let n = orc::read_meta(path).map(|m| m.num_rows).unwrap_or(0);
+            (rows + n, bytes + size)
+        })
+    })
+    .await?;
+    if table_rows == 0 {
+        return Ok(bytes);
+    }
+    Ok((rows as f64 * table_bytes as f64 / table_rows as f64) as u64)
+}
+
+fn gb(bytes: u64) -> String {
+    format!("{:.1} GB", bytes as f64 / 1024.0 / 1024.0 / 1024.0)
+}
@@ -0,0 +1,147 @@
+//! 核对插入设置是否真正生效：批次结束后按第一个成功文件的 query_id 从 system.query_log 读回
+//! 服务端实际使用的设置，与命令行要求的逐项比较。
+//!
+//! query_log 的 Settings 列只记录与默认值不同的设置，缺失的项再查 system.settings：
+//! 服务端不认识的设置 (老版本) 与默认值不同却没有生效的设置都会被标出，
+//! 而不是让人以为调优参数起了作用。
+
+use crate::cli::{self, Args};
+use crate::clickhouse;
+use crate::report::{FileRecord, FileStatus};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingStatus {
+    /// 按要求生效
+    Applied,
+    /// 生效的值与要求的不同 (如被 profile 约束改写)
+    Overridden,
+    /// 服务端没有这个设置
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveSetting {
+    pub name: String,
+    pub requested: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective: Option<String>,
+    pub status: SettingStatus,
+}
+
+/// 读回第一个成功文件所在表的插入设置，没有成功的文件时返回空列表；`since` 为批次开始时间 (unix 秒)
+pub async fn check(
+    cfg: &Args,
+    records: &[FileRecord],
+    since: u64,
+) -> Result<Vec<EffectiveSetting>> {
+    let Some((record, query_id)) = records
+        .iter()
+        .filter(|r| r.status == FileStatus::Success)
+        .find_map(|r| Some((r, r.query_id.as_deref()?)))
+    else {
+        return Ok(Vec::new());
+    };
+    if let Err(e) = clickhouse::query(cfg, "SYSTEM FLUSH LOGS").await {
+        eprintln!("⚠️ SYSTEM FLUSH LOGS 失败，设置核对可能查不到记录: {:#}", e);
+    }
+    // 拆分导入的各组 query_id 为 `<id>-<组号>`，任取一组即可
+    let id = cli::sql_string(query_id);
+    let tsv = clickhouse::query(
+        cfg,
+        &format!(
+            "SELECT k, v FROM (
+                 SELECT Settings FROM system.query_log
+                 WHERE type = 'QueryFinish'
+                   AND event_date >= toDate(toDateTime({since})) AND event_time >= toDateTime({since})
+                   AND (query_id = '{id}' OR startsWith(query_id, '{id}-'))
+                 LIMIT 1
+             ) ARRAY JOIN mapKeys(Settings) AS k, mapValues(Settings) AS v",
+            id = id,
+            since = since
+        ),
+    )
+    .await?;
+    let logged: HashMap<&str, &str> = clickhouse::rows(&tsv)
+        .into_iter()
+        .filter_map(|row| Some((*row.first()?, *row.get(1)?)))
+        .collect();
+    if logged.is_empty() {
+        bail!(
+            "system.query_log 中没有 {} 的记录 (服务端可能未开启 query_log)",
+            query_id
+        );
+    }
+
+    let requested = cfg.insert_settings(&record.table);
+    let missing: Vec<String> = requested
+        .iter()
+        .filter(|(k, _)| !logged.contains_key(k.as_str()))
+        .map(|(k, _)| format!("'{}'", cli::sql_string(k)))
+        .collect();
+    let defaults_tsv = if missing.is_empty() {
+        String::new()
+    } else {
+        let sql = format!(
+            "SELECT name, value FROM system.settings WHERE name IN ({})",
+            missing.join(", ")
+        );
+        clickhouse::query(cfg, &sql).await?
+    };
+    let defaults: HashMap<&str, &str> = clickhouse::rows(&defaults_tsv)
+        .into_iter()
+        .filter_map(|row| Some((*row.first()?, *row.get(1)?)))
+        .collect();
+
+    Ok(requested
+        .into_iter()
+        .map(|(name, requested)| {
+            let effective = logged
+                .get(name.as_str())
+                .or_else(|| defaults.get(name.as_str()))
+                .map(|v| v.to_string());
+            let status = match &effective {
+                None => SettingStatus::Unknown,
+                Some(v) if *v == requested => SettingStatus::Applied,
+                Some(_) => SettingStatus::Overridden,
+            };
+            EffectiveSetting {
+                name,
+                requested,
+                effective,
+                status,
+            }
+        })
+        .collect())
+}
+
+/// 只输出没有按要求生效的设置
+pub fn print(settings: &[EffectiveSetting]) {
+    let mismatched: Vec<&EffectiveSetting> = settings
+        .iter()
+        .filter(|s| s.status != SettingStatus::Applied)
+        .collect();
+    if mismatched.is_empty() {
+        println!("⚙️ 插入设置核对: {} 项均已生效", settings.len());
+        return;
+    }
+    for s in mismatched {
+        match s.status {
+            SettingStatus::Unknown => {
+                eprintln!(
+                    "⚠️ 设置 {}={} 未生效: 服务端不支持该设置",
+                    s.name, s.requested
+                )
+            }
+            _ => eprintln!(
+                "⚠️ 设置 {}={} 未生效: 实际值为 {}",
+                s.name,
+                s.requested,
+                s.effective.as_deref().unwrap_or("-")
+            ),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! 控制台错误输出限流：服务端对每个文件都返回同一个异常时，相同的错误按令牌桶限流，
+//! 前几条照常输出，之后只计数，令牌恢复时附带折叠的条数再输出一条，
+//! 最早的那条错误不会被成千上万条重复内容刷走。完整错误仍逐个写入 failed/<file>.error.log。
+
+use crate::error::ClickHouseError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+    /// 上次输出以来折叠的条数
+    suppressed: u64,
+}
+
+pub struct ErrorLog {
+    burst: u32,
+    /// 恢复一个令牌的间隔
+    interval: Duration,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl ErrorLog {
+    pub fn new(burst: u32, interval: Duration) -> Self {
+        Self {
+            burst,
+            interval,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 服务端异常按错误码与名称归为同一种 (消息里常带文件名、行号)，其余按完整文本
+    fn key(err: &ClickHouseError) -> String {
+        match (err, err.code()) {
+            (ClickHouseError::Server { name, .. }, Some(code)) => {
+                format!("{}:{}", code, name.as_deref().unwrap_or_default())
+            }
+            _ => err.to_string(),
+        }
+    }
+
+    /// 是否输出这条错误；输出时返回此前被折叠的相同错误条数
+    pub fn admit(&self, err: &ClickHouseError) -> Option<u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(Self::key(err)).or_insert(Bucket {
+            tokens: self.burst as f64,
+            refilled: now,
+            suppressed: 0,
+        });
+        if !self.interval.is_zero() {
+            let refill =
+                now.duration_since(bucket.refilled).as_secs_f64() / self.interval.as_secs_f64();
+            bucket.tokens = (bucket.tokens + refill).min(self.burst as f64);
+        }
+        bucket.refilled = now;
+        if bucket.tokens < 1.0 {
+            bucket.suppressed += 1;
+            return None;
+        }
+        bucket.tokens -= 1.0;
+        Some(std::mem::take(&mut bucket.suppressed))
+    }
+
+    /// 批次结束时输出仍未报告的折叠条数并清零
+    pub fn print_summary(&self) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let suppressed: u64 = buckets
+            .values_mut()
+            .map(|b| std::mem::take(&mut b.suppressed))
+            .sum();
+        if suppressed > 0 {
+            println!(
+                "🔇 另有 {} 条重复错误未在控制台输出，完整内容见 failed/ 下的 .error.log",
+                suppressed
+            );
+        }
+    }
+}
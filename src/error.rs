@@ -0,0 +1,472 @@
+//! 导入错误的结构化表示：HTTP 响应体与 clickhouse-client 的 stderr 统一解析为错误码 + 名称 + 消息，
+//! 重试、报告与进程退出码据此判断，不再各自匹配错误文本
+
+use crate::report::{FileRecord, FileStatus};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio::time::Duration;
+
+/// 认证失败 / 用户不存在 / 缺少密码
+const AUTH_CODES: [u32; 3] = [516, 192, 194];
+/// 表或库不存在
+const UNKNOWN_TABLE_CODES: [u32; 2] = [60, 81];
+/// 服务端超时 / 套接字超时 (本地超时也归入 TIMEOUT_EXCEEDED)
+const TIMEOUT_CODES: [u32; 2] = [159, 209];
+const TIMEOUT_EXCEEDED: u32 = 159;
+const QUERY_WAS_CANCELLED: u32 = 394;
+/// 服务端过载：TOO_MANY_SIMULTANEOUS_QUERIES / MEMORY_LIMIT_EXCEEDED / TOO_MANY_PARTS，
+/// 等合并追上或负载下降后重试通常能成功
+const OVERLOAD_CODES: [u32; 3] = [202, 241, 252];
+/// clickhouse-client 连接服务端失败 (NETWORK_ERROR)
+const NETWORK_ERROR: u32 = 210;
+/// 网络错误 / 服务端超时 / 套接字超时 / 并发查询过多，以及 --insert-quorum 时存活副本不足 (TOO_FEW_LIVE_REPLICAS) /
+/// 上一次法定写入尚未完成 (UNSATISFIED_QUORUM_FOR_PREVIOUS_WRITE)，稍后重试同一文件通常能成功
+const RETRYABLE_CODES: [u32; 6] = [NETWORK_ERROR, TIMEOUT_EXCEEDED, 209, 202, 285, 286];
+/// 表结构或权限层面的错误，同一批的其他文件也必然失败：
+/// THERE_IS_NO_COLUMN / NO_SUCH_COLUMN_IN_TABLE / TYPE_MISMATCH / READONLY / ACCESS_DENIED
+const FATAL_CODES: [u32; 5] = [8, 16, 53, 164, 497];
+
+/// 错误信息在控制台与报告中的最大长度，完整内容另写入 failed/<file>.error.log
+const DISPLAY_LIMIT: usize = 2000;
+
+/// 进程退出码：0 全部成功，1 运行出错 (参数、预检查等)，2-5 没有文件导入成功 (按失败类别)
+const EXIT_FAILED: u8 = 2;
+const EXIT_AUTH: u8 = 3;
+const EXIT_UNKNOWN_TABLE: u8 = 4;
+const EXIT_TIMEOUT: u8 = 5;
+/// 部分文件导入成功、部分失败，重跑时只需处理失败的文件
+const EXIT_PARTIAL: u8 = 6;
+/// 目录正由另一个 ck-loader 进程处理，本次没有导入任何文件
+const EXIT_LOCKED: u8 = 7;
+/// 收到退出信号提前结束 (128 + SIGINT)，批次不完整，需要重新运行
+const EXIT_INTERRUPTED: u8 = 130;
+
+/// 错误的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// 网络、超时、过载等暂时性错误，可以重试
+    Retryable,
+    /// 表不存在、类型不匹配、认证失败等，整批文件都会失败，不必逐个尝试
+    Fatal,
+    /// 与单个文件相关 (数据解析失败等)，重试无用，但不影响其他文件
+    File,
+}
+
+#[derive(Debug, Clone)]
+pub enum ClickHouseError {
+    /// 服务端异常 `Code: N. DB::Exception: <message> (NAME)`，`raw` 为未截断的完整输出 (含堆栈)
+    Server {
+        code: u32,
+        name: Option<String>,
+        message: String,
+        raw: String,
+    },
+    /// 非 2xx 且响应体不是可识别的服务端异常
+    Http {
+        status: u16,
+        body: String,
+    },
+    /// clickhouse-client 非零退出且 stderr 不是可识别的服务端异常
+    Client {
+        exit: Option<i32>,
+        stderr: String,
+    },
+    /// 超过 --timeout-secs
+    Timeout(Duration),
+    /// 数据仍在发送但超过 --stall-timeout 没有进度
+    Stalled(Duration),
+    Cancelled,
+    /// 无法连接服务端或无法启动客户端，数据尚未开始写入，可以换一种传输方式重试
+    Connect(String),
+    /// 读取本地或远端源失败、上传中途断开等传输层错误
+    Transport(String),
+    /// 本地解码或校验数据失败 (native 传输的 ORC 解码、--validate-rows 的抽样校验)，重试同一文件无用
+    Decode(String),
+}
+
+impl ClickHouseError {
+    pub fn from_http(status: u16, body: &str) -> Self {
+        parse(body).unwrap_or_else(|| Self::Http {
+            status,
+            body: body.trim().to_string(),
+        })
+    }
+
+    pub fn from_client(exit: Option<i32>, stderr: &str) -> Self {
+        parse(stderr).unwrap_or_else(|| Self::Client {
+            exit,
+            stderr: stderr.trim().to_string(),
+        })
+    }
+
+    /// 原生协议的 Exception 包：消息与文本输出一样末尾带 `(NAME)`，按同样的规则解析
+    pub fn from_native(code: u32, message: &str, raw: String) -> Self {
+        match parse(&format!("Code: {}. DB::Exception: {}", code, message)) {
+            Some(Self::Server { name, message, .. }) => Self::Server {
+                code,
+                name,
+                message,
+                raw,
+            },
+            _ => Self::Server {
+                code,
+                name: None,
+                message: message.to_string(),
+                raw,
+            },
+        }
+    }
+
+    /// ClickHouse 错误码；本地超时与取消按服务端对应的错误码归类
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            Self::Server { code, .. } => Some(*code),
+            Self::Timeout(_) => Some(TIMEOUT_EXCEEDED),
+            Self::Cancelled => Some(QUERY_WAS_CANCELLED),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Server { name, .. } => name.as_deref(),
+            Self::Timeout(_) => Some("TIMEOUT_EXCEEDED"),
+            Self::Cancelled => Some("QUERY_WAS_CANCELLED"),
+            _ => None,
+        }
+    }
+
+    /// 服务端返回的完整错误输出，不做截断
+    pub fn full_output(&self) -> String {
+        match self {
+            Self::Server { raw, .. } => raw.clone(),
+            Self::Http { body, .. } => body.clone(),
+            Self::Client { stderr, .. } => stderr.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    /// 把完整错误输出写入 `<dir>/<file>.error.log`，返回日志路径
+    pub fn write_log(&self, dir: &Path, path: &Path, table: &str) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let log_path = dir.join(format!("{}.error.log", file_name));
+        let header = format!(
+            "file: {}\ntable: {}\ntime: {}\ncode: {}\nname: {}\n\n",
+            path.display(),
+            table,
+            chrono::Local::now().to_rfc3339(),
+            self.code()
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| "-".into()),
+            self.name().unwrap_or("-"),
+        );
+        std::fs::write(&log_path, header + &self.full_output() + "\n")?;
+        Ok(log_path)
+    }
+
+    /// 连接层失败：换用下一种传输方式重试不会重复写入
+    pub fn is_connect(&self) -> bool {
+        matches!(self, Self::Connect(_)) || self.code() == Some(NETWORK_ERROR)
+    }
+
+    /// 按错误码与错误类型分类；本地超时已用完整个时限，不再重试
+    pub fn class(&self) -> ErrorClass {
+        let code = self.code();
+        match self {
+            _ if self.is_auth() => ErrorClass::Fatal,
+            _ if code
+                .is_some_and(|c| UNKNOWN_TABLE_CODES.contains(&c) || FATAL_CODES.contains(&c)) =>
+            {
+                ErrorClass::Fatal
+            }
+            Self::Timeout(_) | Self::Cancelled => ErrorClass::File,
+            Self::Connect(_) | Self::Transport(_) | Self::Stalled(_) => ErrorClass::Retryable,
+            Self::Http {
+                status: 502..=504, ..
+            } => ErrorClass::Retryable,
+            _ if self.is_overload() || code.is_some_and(|c| RETRYABLE_CODES.contains(&c)) => {
+                ErrorClass::Retryable
+            }
+            _ => ErrorClass::File,
+        }
+    }
+
+    /// 服务端过载，冷却一段时间后可以重试
+    pub fn is_overload(&self) -> bool {
+        self.code().is_some_and(|c| OVERLOAD_CODES.contains(&c))
+    }
+
+    pub fn is_auth(&self) -> bool {
+        match self {
+            Self::Http { status, .. } => *status == 401 || *status == 403,
+            _ => self.code().is_some_and(|c| AUTH_CODES.contains(&c)),
+        }
+    }
+}
+
+impl fmt::Display for ClickHouseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Server {
+                code,
+                name: Some(name),
+                message,
+                ..
+            } => write!(f, "Code: {}. DB::Exception: {} ({})", code, message, name),
+            Self::Server { code, message, .. } => {
+                write!(f, "Code: {}. DB::Exception: {}", code, message)
+            }
+            Self::Http { status, body } => write!(f, "HTTP {} | {}", status, truncate(body)),
+            Self::Client { exit, stderr } if stderr.is_empty() => {
+                write!(f, "退出代码: {:?}", exit)
+            }
+            Self::Client { stderr, .. } => f.write_str(truncate(stderr)),
+            Self::Timeout(d) => write!(f, "⏰ 导入超时 (已运行超过 {:?})", d),
+            Self::Stalled(d) => write!(f, "🐌 导入停滞 (超过 {:?} 没有进度)", d),
+            Self::Cancelled => f.write_str("任务已取消"),
+            Self::Connect(msg) | Self::Transport(msg) | Self::Decode(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for ClickHouseError {}
+
+fn truncate(s: &str) -> &str {
+    match s.char_indices().nth(DISPLAY_LIMIT) {
+        Some((i, _)) => &s[..i],
+        None => s,
+    }
+}
+
+impl From<std::io::Error> for ClickHouseError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Transport(e.to_string())
+    }
+}
+
+impl From<anyhow::Error> for ClickHouseError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Transport(format!("{:#}", e))
+    }
+}
+
+impl From<&str> for ClickHouseError {
+    fn from(msg: &str) -> Self {
+        Self::Transport(msg.to_string())
+    }
+}
+
+/// 从任意文本中找出第一个 `Code: N. ...` 异常；客户端 stderr 前面可能还有其他输出
+fn parse(text: &str) -> Option<ClickHouseError> {
+    let start = text.find("Code: ")?;
+    let rest = &text[start + "Code: ".len()..];
+    let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+    let code: u32 = rest[..digits].parse().ok()?;
+    let rest = rest[digits..].trim_start_matches('.').trim_start();
+    let rest = rest.strip_prefix("DB::Exception:").unwrap_or(rest);
+    // 消息只取第一行，后面通常是堆栈或客户端提示；新版本服务端在末尾附带 `(version x.y)`
+    let mut line = rest.lines().next().unwrap_or_default().trim();
+    if let Some(i) = line.rfind(" (version ") {
+        line = &line[..i];
+    }
+    let name = line
+        .strip_suffix(')')
+        .and_then(|l| l.rsplit_once(" ("))
+        .filter(|(_, n)| {
+            n.chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        });
+    let (message, name) = match name {
+        Some((message, name)) => (message, Some(name.to_string())),
+        None => (line, None),
+    };
+    Some(ClickHouseError::Server {
+        code,
+        name,
+        message: message.to_string(),
+        raw: text.trim().to_string(),
+    })
+}
+
+/// 对无法拿到类型化错误的场景 (如辅助查询的 anyhow 错误) 按文本判断是否为认证失败
+pub fn is_auth_error(text: &str) -> bool {
+    parse(text).is_some_and(|e| e.is_auth()) || text.contains("Authentication failed")
+}
+
+/// 读取 `write_log` 写下的错误日志头，返回 (文件路径, 目标表, 错误名)
+pub fn read_log(log: &Path) -> Option<(PathBuf, String, Option<String>)> {
+    let text = std::fs::read_to_string(log).ok()?;
+    let header = |key: &str| {
+        text.lines()
+            .take_while(|l| !l.is_empty())
+            .find_map(|l| l.strip_prefix(key))
+            .map(str::to_string)
+    };
+    let name = header("name: ").filter(|n| n != "-");
+    Some((PathBuf::from(header("file: ")?), header("table: ")?, name))
+}
+
+fn exit_code_of(code: Option<u32>) -> u8 {
+    match code {
+        Some(c) if AUTH_CODES.contains(&c) => EXIT_AUTH,
+        Some(c) if UNKNOWN_TABLE_CODES.contains(&c) => EXIT_UNKNOWN_TABLE,
+        Some(c) if TIMEOUT_CODES.contains(&c) => EXIT_TIMEOUT,
+        _ => EXIT_FAILED,
+    }
+}
+
+/// 单个错误 (如 ck-loader sql 的失败语句) 对应的退出码
+pub fn exit_code_for(err: &ClickHouseError) -> ExitCode {
+    ExitCode::from(exit_code_of(err.code()))
+}
+
+/// 批次的退出码：全部成功为 0；有文件成功也有文件失败为 EXIT_PARTIAL；全部失败时
+/// 失败文件的错误类别一致则用该类别的退出码，否则为 EXIT_FAILED
+pub fn exit_code(records: &[FileRecord]) -> ExitCode {
+    let succeeded = records.iter().any(|r| r.status == FileStatus::Success);
+    let mut codes = records
+        .iter()
+        .filter(|r| r.status == FileStatus::Failed)
+        .map(|r| exit_code_of(r.error_code));
+    let Some(first) = codes.next() else {
+        return ExitCode::SUCCESS;
+    };
+    if succeeded {
+        ExitCode::from(EXIT_PARTIAL)
+    } else if codes.all(|c| c == first) {
+        ExitCode::from(first)
+    } else {
+        ExitCode::from(EXIT_FAILED)
+    }
+}
+
+pub fn partial() -> ExitCode {
+    ExitCode::from(EXIT_PARTIAL)
+}
+
+pub fn failed() -> ExitCode {
+    ExitCode::from(EXIT_FAILED)
+}
+
+pub fn interrupted() -> ExitCode {
+    ExitCode::from(EXIT_INTERRUPTED)
+}
+
+pub fn locked() -> ExitCode {
+    ExitCode::from(EXIT_LOCKED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(code: u32) -> ClickHouseError {
+        ClickHouseError::Server {
+            code,
+            name: None,
+            message: String::new(),
+            raw: String::new(),
+        }
+    }
+
+    #[test]
+    fn parses_server_exceptions() {
+        let body = "Code: 60. DB::Exception: Table default.t does not exist. (UNKNOWN_TABLE) (version 24.3.1.1)\n0. stack";
+        let Some(ClickHouseError::Server {
+            code,
+            name,
+            message,
+            raw,
+        }) = parse(body)
+        else {
+            panic!("未解析出服务端异常");
+        };
+        assert_eq!(code, 60);
+        assert_eq!(name.as_deref(), Some("UNKNOWN_TABLE"));
+        assert_eq!(message, "Table default.t does not exist.");
+        assert_eq!(raw, body);
+
+        // 客户端 stderr 前面可能有其他输出，旧版本没有错误名
+        let stderr = "Received exception from server (version 23.8.1):\nCode: 27. DB::Exception: Cannot parse input (at row 3)";
+        let err = ClickHouseError::from_client(Some(27), stderr);
+        assert_eq!(err.code(), Some(27));
+        assert_eq!(err.name(), None);
+        assert_eq!(
+            err.to_string(),
+            "Code: 27. DB::Exception: Cannot parse input (at row 3)"
+        );
+
+        // 不是服务端异常时保留原始状态与内容
+        let err = ClickHouseError::from_http(502, " Bad Gateway\n");
+        assert!(
+            matches!(&err, ClickHouseError::Http { status: 502, body } if body == "Bad Gateway")
+        );
+        assert_eq!(err.code(), None);
+        assert!(parse("Code: abc").is_none());
+    }
+
+    #[test]
+    fn native_exceptions_keep_code() {
+        let err = ClickHouseError::from_native(
+            252,
+            "Too many parts (300). (TOO_MANY_PARTS)",
+            "raw".to_string(),
+        );
+        assert_eq!(err.code(), Some(252));
+        assert_eq!(err.name(), Some("TOO_MANY_PARTS"));
+        assert_eq!(err.full_output(), "raw");
+        assert!(err.is_overload());
+    }
+
+    #[test]
+    fn classifies_errors() {
+        for code in RETRYABLE_CODES {
+            assert_eq!(server(code).class(), ErrorClass::Retryable, "code {}", code);
+        }
+        for code in FATAL_CODES.into_iter().chain(UNKNOWN_TABLE_CODES) {
+            assert_eq!(server(code).class(), ErrorClass::Fatal, "code {}", code);
+        }
+        for code in OVERLOAD_CODES {
+            assert!(server(code).is_overload());
+            assert_eq!(server(code).class(), ErrorClass::Retryable);
+        }
+        // 认证失败优先于其他分类
+        for code in AUTH_CODES {
+            assert_eq!(server(code).class(), ErrorClass::Fatal);
+        }
+        let http = |status| ClickHouseError::Http {
+            status,
+            body: String::new(),
+        };
+        assert_eq!(http(401).class(), ErrorClass::Fatal);
+        assert_eq!(http(503).class(), ErrorClass::Retryable);
+        assert_eq!(http(500).class(), ErrorClass::File);
+        // 数据错误与本地超时只影响单个文件，不重试
+        assert_eq!(server(27).class(), ErrorClass::File);
+        assert_eq!(
+            ClickHouseError::Timeout(Duration::from_secs(1)).class(),
+            ErrorClass::File
+        );
+        assert_eq!(
+            ClickHouseError::Connect(String::new()).class(),
+            ErrorClass::Retryable
+        );
+        assert!(server(NETWORK_ERROR).is_connect());
+        assert!(!server(209).is_connect());
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(exit_code_of(Some(516)), EXIT_AUTH);
+        assert_eq!(exit_code_of(Some(60)), EXIT_UNKNOWN_TABLE);
+        assert_eq!(exit_code_of(Some(TIMEOUT_EXCEEDED)), EXIT_TIMEOUT);
+        assert_eq!(exit_code_of(Some(27)), EXIT_FAILED);
+        assert_eq!(exit_code_of(None), EXIT_FAILED);
+        assert!(is_auth_error(
+            "Code: 516. DB::Exception: default: Authentication failed (AUTHENTICATION_FAILED)"
+        ));
+        assert!(!is_auth_error("Code: 27. DB::Exception: Cannot parse"));
+    }
+}
@@ -0,0 +1,175 @@
+//! `--events ndjson`：每个生命周期事件 (discovered / started / progress / succeeded / failed) 输出一行 JSON，
+//! 外层脚本据此跟踪各文件的状态，不必解析面向人的日志。
+//!
+//! 事件独占 stdout：开启后本进程 (及 clickhouse-client 子进程) 原本写到 stdout 的日志改写到 stderr。
+//! 每行带事件名、时间、批次 id 与目标表；progress 按导入单元 (单个文件或合并组) 输出，其余事件按文件输出。
+
+use crate::report::{FileRecord, FileStatus};
+use crate::stall::Attempt;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time;
+
+/// 输出 progress 事件的间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 事件流的输出端，未开启时所有事件直接丢弃
+#[derive(Debug, Clone, Default)]
+pub struct EventStream {
+    out: Option<Arc<Mutex<File>>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// 待导入的文件 (已按 mtime / 大小、delta、--skip-loaded 等筛选)
+    Discovered {
+        path: &'a Path,
+    },
+    Started {
+        path: &'a Path,
+        bytes: u64,
+    },
+    Progress {
+        paths: &'a [PathBuf],
+        sent_bytes: u64,
+        total_bytes: u64,
+        /// 服务端已写入的行数，仅 --server-progress 时有
+        #[serde(skip_serializing_if = "Option::is_none")]
+        written_rows: Option<u64>,
+    },
+    Succeeded {
+        path: &'a Path,
+        bytes: u64,
+        written_rows: Option<u64>,
+        elapsed_secs: f64,
+        query_id: Option<&'a str>,
+    },
+    Failed {
+        path: &'a Path,
+        bytes: u64,
+        error: Option<&'a str>,
+        error_code: Option<u32>,
+        error_name: Option<&'a str>,
+        elapsed_secs: f64,
+        query_id: Option<&'a str>,
+    },
+}
+
+impl<'a> Event<'a> {
+    /// 文件的导入结果
+    pub fn finished(record: &'a FileRecord) -> Self {
+        match record.status {
+            FileStatus::Success => Event::Succeeded {
+                path: &record.path,
+                bytes: record.bytes,
+                written_rows: record.written_rows,
+                elapsed_secs: record.elapsed_secs,
+                query_id: record.query_id.as_deref(),
+            },
+            FileStatus::Failed => Event::Failed {
+                path: &record.path,
+                bytes: record.bytes,
+                error: record.error.as_deref(),
+                error_code: record.error_code,
+                error_name: record.error_name.as_deref(),
+                elapsed_secs: record.elapsed_secs,
+                query_id: record.query_id.as_deref(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    #[serde(flatten)]
+    event: Event<'a>,
+    time: String,
+    batch_id: &'a str,
+    table: &'a str,
+}
+
+impl EventStream {
+    /// 把 stdout 留给事件流：复制一份原来的 stdout 写事件，再把 stdout 指向 stderr
+    pub fn start() -> Result<Self> {
+        io::stdout().flush()?;
+        let (stdout, stderr) = (io::stdout().as_raw_fd(), io::stderr().as_raw_fd());
+        // SAFETY: dup 返回的新 fd 立即交给 File 管理；dup2 只替换 stdout 指向的文件
+        let out = unsafe {
+            let saved = libc::dup(stdout);
+            if saved < 0 {
+                return Err(io::Error::last_os_error()).context("无法复制 stdout");
+            }
+            let out = File::from_raw_fd(saved);
+            if libc::dup2(stderr, stdout) < 0 {
+                return Err(io::Error::last_os_error()).context("无法把日志重定向到 stderr");
+            }
+            out
+        };
+        Ok(Self {
+            out: Some(Arc::new(Mutex::new(out))),
+        })
+    }
+
+    pub fn emit(&self, batch_id: &str, table: &str, event: Event) {
+        let Some(out) = &self.out else {
+            return;
+        };
+        let line = Line {
+            event,
+            time: chrono::Local::now().to_rfc3339(),
+            batch_id,
+            table,
+        };
+        let Ok(mut json) = serde_json::to_string(&line) else {
+            return;
+        };
+        json.push('\n');
+        // 读取方已退出 (管道关闭) 时丢弃事件，不影响导入
+        let _ = out.lock().unwrap().write_all(json.as_bytes());
+    }
+
+    /// 定期输出一次导入尝试的 progress 事件，没有变化时不输出；不会返回 (未开启时一直等待)
+    pub async fn progress(
+        &self,
+        batch_id: &str,
+        table: &str,
+        attempt: &Attempt,
+        paths: &[PathBuf],
+    ) {
+        if self.out.is_none() {
+            return std::future::pending().await;
+        }
+        let mut last = None;
+        loop {
+            time::sleep(PROGRESS_INTERVAL).await;
+            let Some(sent) = attempt.sent() else {
+                continue;
+            };
+            let written = attempt
+                .current_upload()
+                .map(|u| u.server_rows())
+                .filter(|rows| *rows > 0);
+            if last == Some((sent, written)) {
+                continue;
+            }
+            last = Some((sent, written));
+            self.emit(
+                batch_id,
+                table,
+                Event::Progress {
+                    paths,
+                    sent_bytes: sent,
+                    total_bytes: attempt.bytes(),
+                    written_rows: written,
+                },
+            );
+        }
+    }
+}
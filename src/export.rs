@@ -0,0 +1,331 @@
+//! export 子命令：把表导出为文件，与导入方向相反。
+//!
+//! 按 system.parts 中的活跃分区切片，每个分区一条 `SELECT * ... WHERE _partition_id = '<id>'`，
+//! 按 --workers 并行执行，响应体流式写入 `<目录>/<表>-<分区 id>.<扩展名>`；没有分区信息的表 (非 MergeTree 等)
+//! 整表导出为一个文件。ORC / Parquet 按 --compression 设置文件内的列压缩，其他格式由服务端压缩整个响应
+//! (文件名追加 .gz / .zst / .lz4)。
+//!
+//! 每个文件先写入 `.part` 临时文件，写完 fsync 后再改名，中途失败不会留下残缺的文件；
+//! 目标文件已存在时跳过 (重跑只补导失败的分区)，--overwrite 时重新导出。
+//! 导出 ORC 且没有 --where 时用文件尾的行数核对 system.parts 中的分区行数，导出期间有写入的分区会报告不一致。
+
+use crate::cli::{self, Args, ExportArgs, Transport};
+use crate::clickhouse::{self, locate};
+use crate::error::{self, ClickHouseError, ErrorClass};
+use crate::report::{self, Tags};
+use crate::wire::Compression;
+use crate::{archive, http, orc};
+use anyhow::{bail, Context, Result};
+use futures::stream::{self, StreamExt};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use tokio::time;
+
+/// 一个导出切片：一个分区，或没有分区信息时的整张表 (copy 子命令同样按它切分)
+pub struct Slice {
+    pub partition_id: Option<String>,
+    /// system.parts 中的行数与磁盘字节数
+    pub rows: Option<u64>,
+    pub bytes: Option<u64>,
+}
+
+pub async fn run(mut args: ExportArgs) -> Result<ExitCode> {
+    if args.opts.transport != Transport::Http {
+        println!("ℹ️ 导出固定使用 HTTP 传输，响应体流式写入文件");
+        args.opts.transport = Transport::Http;
+    }
+    if args.compression == Compression::Auto {
+        bail!("export 的 --compression 不支持 auto，请指定 none / gzip / zstd / lz4");
+    }
+    let (args, cfg) = (&args, &args.opts);
+    std::fs::create_dir_all(&args.dir).with_context(|| format!("无法创建目录 {:?}", args.dir))?;
+    archive::probe_writable(&args.dir)?;
+
+    let slices = list_slices(cfg, &args.table).await?;
+    let format = args.format.as_str();
+    let (settings, encoding) = compression_settings(format, args.compression);
+    let ext = extension(format, encoding);
+    println!(
+        "📤 导出 {} → {:?}: {} 个切片, 格式 {}, 压缩 {:?}, 并行数 {}",
+        args.table,
+        args.dir,
+        slices.len(),
+        format,
+        args.compression,
+        cfg.workers
+    );
+
+    let client = http::build_client()?;
+    let tags: Tags = cfg.tags.iter().cloned().collect();
+    let batch_id = report::new_batch_id();
+    let start = Instant::now();
+    let results: Vec<(PathBuf, Result<Option<u64>>)> = stream::iter(slices.iter().enumerate())
+        .map(|(i, slice)| {
+            let (client, tags, settings) = (&client, &tags, &settings);
+            let name = match &slice.partition_id {
+                Some(id) => format!("{}-{}.{}", args.table, id, ext),
+                None => format!("{}.{}", args.table, ext),
+            };
+            let target = args.dir.join(name);
+            let query_id = format!("ckloader-{}-export{}", batch_id, i + 1);
+            async move {
+                if target.exists() && !args.overwrite {
+                    println!("⏭️ {:?} 已存在，跳过 (--overwrite 可重新导出)", target);
+                    return (target, Ok(None));
+                }
+                let sql = slice_sql(args, slice.partition_id.as_deref());
+                let timeout = cfg.timeout_for(slice.bytes);
+                let started = Instant::now();
+                let part = archive::part_path(&target);
+                let result = export_slice(
+                    client, cfg, &sql, settings, encoding, tags, &query_id, &part, timeout,
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("{}", e.to_string().trim()))
+                .and_then(|bytes| {
+                    check_rows(args, slice, &part)?;
+                    commit(&part, &target)?;
+                    Ok(bytes)
+                });
+                if result.is_err() {
+                    let _ = std::fs::remove_file(&part);
+                }
+                match &result {
+                    Ok(bytes) => println!(
+                        "✅ {:?} | {:.1} MB | 耗时: {:.2?}",
+                        target,
+                        *bytes as f64 / 1024.0 / 1024.0,
+                        started.elapsed()
+                    ),
+                    Err(e) => eprintln!("❌ {:?} 导出失败: {:#}", target, e),
+                }
+                (target, result.map(Some))
+            }
+        })
+        .buffer_unordered(cfg.workers)
+        .collect()
+        .await;
+
+    let written: u64 = results.iter().filter_map(|(_, r)| *r.as_ref().ok()?).sum();
+    let exported = results
+        .iter()
+        .filter(|(_, r)| matches!(r, Ok(Some(_))))
+        .count();
+    let skipped = results
+        .iter()
+        .filter(|(_, r)| matches!(r, Ok(None)))
+        .count();
+    let failed: Vec<&Path> = results
+        .iter()
+        .filter(|(_, r)| r.is_err())
+        .map(|(p, _)| p.as_path())
+        .collect();
+    println!(
+        "📦 导出完成: {} 个文件, {:.1} MB, 跳过 {} 个, 失败 {} 个 | 耗时: {:.2?}",
+        exported,
+        written as f64 / 1024.0 / 1024.0,
+        skipped,
+        failed.len(),
+        start.elapsed()
+    );
+    if failed.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    for path in &failed {
+        eprintln!("   ❌ {:?}", path);
+    }
+    Ok(if failed.len() < results.len() {
+        error::partial()
+    } else {
+        error::failed()
+    })
+}
+
+/// 按活跃 part 的分区列出切片；表没有 part (非 MergeTree，或是空表) 时整表作为一个切片
+pub async fn list_slices(cfg: &Args, table: &str) -> Result<Vec<Slice>> {
+    let (db, name) = locate(table);
+    let sql = format!(
+        "SELECT partition_id, sum(rows), sum(bytes_on_disk) FROM system.parts
+         WHERE database = {} AND table = '{}' AND active
+         GROUP BY partition_id ORDER BY partition_id",
+        db,
+        cli::sql_string(&name)
+    );
+    let tsv = clickhouse::query(cfg, &sql).await?;
+    let slices: Vec<Slice> = clickhouse::rows(&tsv)
+        .iter()
+        .filter_map(|row| match row.as_slice() {
+            [id, rows, bytes, ..] => Some(Slice {
+                partition_id: Some(id.to_string()),
+                rows: rows.parse().ok(),
+                bytes: bytes.parse().ok(),
+            }),
+            _ => None,
+        })
+        .collect();
+    if slices.is_empty() {
+        return Ok(vec![Slice {
+            partition_id: None,
+            rows: None,
+            bytes: None,
+        }]);
+    }
+    Ok(slices)
+}
+
+fn slice_sql(args: &ExportArgs, partition_id: Option<&str>) -> String {
+    let mut filters = Vec::new();
+    if let Some(id) = partition_id {
+        filters.push(format!("_partition_id = '{}'", cli::sql_string(id)));
+    }
+    if let Some(filter) = &args.filter {
+        filters.push(format!("({})", filter));
+    }
+    let mut sql = format!("SELECT * FROM {}", args.table);
+    if !filters.is_empty() {
+        sql.push_str(&format!(" WHERE {}", filters.join(" AND ")));
+    }
+    sql.push_str(&format!(" FORMAT {}", args.format));
+    sql
+}
+
+/// ORC / Parquet 使用文件内的列压缩 (返回对应的输出设置)，其他格式返回 HTTP 响应的压缩方式
+fn compression_settings(
+    format: &str,
+    compression: Compression,
+) -> (Vec<(&'static str, String)>, Option<&'static str>) {
+    let method = match compression {
+        Compression::None => "none",
+        Compression::Gzip => "gzip",
+        Compression::Zstd => "zstd",
+        Compression::Lz4 => "lz4",
+        Compression::Auto => unreachable!("export 启动时已拒绝 auto"),
+    };
+    match format.to_ascii_lowercase().as_str() {
+        "orc" => {
+            // ORC 的 gzip 即 zlib
+            let method = if method == "gzip" { "zlib" } else { method };
+            (
+                vec![("output_format_orc_compression_method", method.to_string())],
+                None,
+            )
+        }
+        "parquet" => (
+            vec![(
+                "output_format_parquet_compression_method",
+                method.to_string(),
+            )],
+            None,
+        ),
+        _ => (Vec::new(), compression.content_encoding()),
+    }
+}
+
+/// 输出文件的扩展名，服务端压缩时追加 .gz / .zst
+fn extension(format: &str, encoding: Option<&str>) -> String {
+    let base = match format.to_ascii_lowercase().as_str() {
+        "tabseparated" | "tsv" => "tsv".to_string(),
+        "csv" | "csvwithnames" => "csv".to_string(),
+        "jsoneachrow" | "jsonlines" | "ndjson" => "jsonl".to_string(),
+        other => other.to_string(),
+    };
+    match encoding {
+        Some("gzip") => format!("{}.gz", base),
+        Some("zstd") => format!("{}.zst", base),
+        Some("lz4") => format!("{}.lz4", base),
+        _ => base,
+    }
+}
+
+/// 导出一个切片到 `.part` 临时文件并 fsync；暂时性错误按 --retries / --retry-backoff 重试，
+/// 每次尝试受 --timeout-secs (或按分区大小的 --timeout-per-gb) 限制
+#[allow(clippy::too_many_arguments)]
+async fn export_slice(
+    client: &reqwest::Client,
+    cfg: &Args,
+    sql: &str,
+    settings: &[(&str, String)],
+    encoding: Option<&str>,
+    tags: &Tags,
+    query_id: &str,
+    part: &Path,
+    timeout: Duration,
+) -> Result<u64, ClickHouseError> {
+    let mut retries = 0;
+    loop {
+        // 同一 query_id 不能同时运行两次，上一次尝试可能仍在服务端执行
+        let attempt_id = match retries {
+            0 => query_id.to_string(),
+            n => format!("{}-r{}", query_id, n),
+        };
+        let attempt = async {
+            let mut out = tokio::fs::File::create(part).await?;
+            let bytes = http::select_into(
+                client,
+                cfg,
+                sql,
+                settings,
+                encoding,
+                tags,
+                &attempt_id,
+                &mut out,
+            )
+            .await?;
+            out.sync_all().await?;
+            Ok::<_, ClickHouseError>(bytes)
+        };
+        let result = time::timeout(timeout, attempt)
+            .await
+            .unwrap_or(Err(ClickHouseError::Timeout(timeout)));
+        match result {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) if e.class() == ErrorClass::Retryable && retries < cfg.retries => {
+                retries += 1;
+                let backoff = cfg
+                    .retry_backoff
+                    .saturating_mul(2u32.saturating_pow(retries - 1));
+                eprintln!(
+                    "🔁 {:?} 暂时性错误，{:?} 后重试 ({}/{}): {}",
+                    part.with_extension(""),
+                    backoff,
+                    retries,
+                    cfg.retries,
+                    e.to_string().trim()
+                );
+                time::sleep(backoff).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// 临时文件改名为目标文件并同步目录
+fn commit(part: &Path, target: &Path) -> Result<()> {
+    std::fs::rename(part, target)?;
+    if let Some(dir) = target.parent() {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// ORC 文件尾的行数与 system.parts 中的分区行数核对；有 --where 或不是 ORC 时不核对
+fn check_rows(args: &ExportArgs, slice: &Slice, path: &Path) -> Result<()> {
+    let Some(expected) = slice.rows else {
+        return Ok(());
+    };
+    if args.filter.is_some() || !args.format.eq_ignore_ascii_case("orc") {
+        return Ok(());
+    }
+    let actual = orc::read_meta(path)
+        .with_context(|| format!("无法解析导出的 ORC 文件 {:?}", path))?
+        .num_rows;
+    if actual != expected {
+        anyhow::bail!(
+            "行数不一致: 文件 {} 行，分区 {} 行 (导出期间可能有写入或合并)",
+            actual,
+            expected
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! 批次结束后的可见性检查：确认数据确实写进了目标表 (而不是因为 DNS/配置错误写到了别的集群)
+//!
+//! - 行数：导入前后各查一次 count()，增量不得少于成功文件的 ORC 行数之和
+//! - `--freshness-column`：表中该列的 max 不得小于成功文件 ORC 统计中的最大值
+//!
+//! Distributed 表等写入后异步可见的场景，在 `--freshness-timeout` 内轮询直到满足。
+
+use crate::cli::{self, Args};
+use crate::clickhouse;
+use crate::orc::{self, StatValue, TypeKind};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::time::{self, Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 单个文件的期望值，在导入前从 ORC 尾部读取 (导入后文件可能已被移走或删除)
+#[derive(Debug, Clone)]
+pub struct Expectation {
+    pub rows: u64,
+    pub max: Option<StatValue>,
+    pub kind: Option<TypeKind>,
+}
+
+pub struct Baseline {
+    rows: u64,
+    expectations: HashMap<PathBuf, Expectation>,
+}
+
+/// 导入前记录表的当前行数与各文件的期望值
+pub async fn baseline(
+    cfg: &Args,
+    table: &str,
+    files: &[PathBuf],
+    column: Option<&str>,
+) -> Result<Baseline> {
+    let mut expectations = HashMap::new();
+    for path in files {
+        match orc::read_meta(path) {
+            Ok(meta) => {
+                let range = column.and_then(|c| meta.column_range(c));
+                expectations.insert(
+                    path.clone(),
+                    Expectation {
+                        rows: meta.num_rows,
+                        max: range.map(|(_, max)| max),
+                        kind: column.and_then(|c| meta.column_kind(c)),
+                    },
+                );
+            }
+            Err(e) => eprintln!("⚠️ 可见性检查: 无法读取 {:?}，不计入期望值: {:#}", path, e),
+        }
+    }
+    Ok(Baseline {
+        rows: count(cfg, table).await?,
+        expectations,
+    })
+}
+
+/// 按成功导入的文件核对目标表，超时仍不满足时返回错误
+pub async fn check(
+    cfg: &Args,
+    table: &str,
+    base: &Baseline,
+    loaded: &[&Path],
+    column: Option<&str>,
+) -> Result<()> {
+    let expected: Vec<&Expectation> = loaded
+        .iter()
+        .filter_map(|p| base.expectations.get(*p))
+        .collect();
+    if expected.is_empty() {
+        return Ok(());
+    }
+    let expected_rows: u64 = expected.iter().map(|e| e.rows).sum();
+    let expected_max = expected
+        .iter()
+        .filter_map(|e| e.max.as_ref().map(|m| (m, e.kind)))
+        .max_by(|a, b| a.0.partial_cmp(b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let deadline = cli::deadline_after(cfg.freshness_timeout);
+    loop {
+        let added = count(cfg, table).await?.saturating_sub(base.rows);
+        let mut problem = None;
+        if added < expected_rows {
+            problem = Some(format!(
+                "表 {} 新增 {} 行，少于成功文件的 {} 行 (数据可能写到了其他集群，或被去重)",
+                table, added, expected_rows
+            ));
+        }
+        if let (Some(col), Some((want, kind))) = (column, expected_max) {
+            let got = table_max(cfg, table, col, kind).await?;
+            if !matches!(&got, Some(g) if g >= want) {
+                problem = Some(format!(
+                    "{}.max({}) = {} 小于文件中的最大值 {}",
+                    table,
+                    col,
+                    got.map(|g| g.to_string()).unwrap_or_else(|| "NULL".into()),
+                    want
+                ));
+            }
+        }
+
+        match problem {
+            None => {
+                println!(
+                    "👁️ 可见性检查通过: 新增 {} 行 (期望至少 {})",
+                    added, expected_rows
+                );
+                return Ok(());
+            }
+            Some(msg) if Instant::now() >= deadline => bail!("可见性检查失败: {}", msg),
+            Some(_) => time::sleep(POLL_INTERVAL).await,
+        }
+    }
+}
+
+async fn count(cfg: &Args, table: &str) -> Result<u64> {
+    let out = clickhouse::query(cfg, &format!("SELECT count() FROM {}", table)).await?;
+    out.trim().parse().context("无法解析 count() 结果")
+}
+
+/// 把表中的 max 转换成与 ORC 统计相同的表示后返回：
+/// Date 为天数，时间戳为 UTC 毫秒，整数/浮点/字符串保持原样
+async fn table_max(
+    cfg: &Args,
+    table: &str,
+    column: &str,
+    kind: Option<TypeKind>,
+) -> Result<Option<StatValue>> {
+    let expr = match kind {
+        Some(TypeKind::Date) => format!("toInt64(toInt32(max({})))", column),
+        Some(TypeKind::Timestamp | TypeKind::TimestampInstant) => format!(
+            "toUnixTimestamp64Milli(toDateTime64(max({}), 3, 'UTC'))",
+            column
+        ),
+        Some(TypeKind::Float | TypeKind::Double | TypeKind::Decimal) => {
+            format!("toFloat64(max({}))", column)
+        }
+        Some(TypeKind::String | TypeKind::Varchar | TypeKind::Char) => {
+            format!("toString(max({}))", column)
+        }
+        _ => format!("toInt64(max({}))", column),
+    };
+    let sql = format!("SELECT {} FROM {}", expr, table);
+    let out = clickhouse::query(cfg, &sql).await?;
+    let raw = out.trim_end_matches('\n');
+    if raw == "\\N" {
+        return Ok(None);
+    }
+    Ok(Some(match kind {
+        Some(TypeKind::Float | TypeKind::Double | TypeKind::Decimal) => {
+            StatValue::Float(raw.parse().context("无法解析 max() 结果")?)
+        }
+        Some(TypeKind::String | TypeKind::Varchar | TypeKind::Char) => {
+            StatValue::Str(raw.to_string())
+        }
+        _ => StatValue::Int(raw.parse().context("无法解析 max() 结果")?),
+    }))
+}
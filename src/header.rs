@@ -0,0 +1,120 @@
+//! CSVWithNames / TSVWithNames 表头规范化：合作方的文件常带 `" User ID "` 这样的列名，
+//! 与表 (或 input() 的输入结构) 的列名对不上。发送前改写流的第一行：去掉首尾空白、
+//! 驼峰拆分、转小写、非字母数字替换为下划线，之后由服务端照常按表头匹配列。
+
+use anyhow::{bail, Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+/// 带表头的格式使用的列分隔符，不支持的格式返回 None
+fn delimiter(format: &str) -> Option<u8> {
+    let format = format.to_ascii_lowercase();
+    let with_names = format.ends_with("withnames") || format.ends_with("withnamesandtypes");
+    if !with_names {
+        None
+    } else if format.starts_with("csv") {
+        Some(b',')
+    } else if format.starts_with("tsv") || format.starts_with("tabseparated") {
+        Some(b'\t')
+    } else {
+        None
+    }
+}
+
+/// 检查格式是否带表头，在开始读取 stdin 之前调用
+pub fn check_format(format: &str) -> Result<()> {
+    if delimiter(format).is_none() {
+        bail!(
+            "--normalize-header 仅支持 CSVWithNames / TSVWithNames 系列格式: {}",
+            format
+        );
+    }
+    Ok(())
+}
+
+/// `" User ID "` → `user_id`，`orderDate` → `order_date`
+fn normalize_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut prev_lower = false;
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            if c.is_uppercase() && prev_lower && !out.ends_with('_') {
+                out.push('_');
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            out.extend(c.to_lowercase());
+        } else {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            prev_lower = false;
+        }
+    }
+    out.trim_end_matches('_').to_string()
+}
+
+/// 拆分一行字段；CSV 的字段可以带双引号 (`""` 为转义的引号)
+pub fn split_fields(line: &str, delimiter: u8) -> Vec<String> {
+    let delimiter = delimiter as char;
+    if delimiter != ',' {
+        return line.split(delimiter).map(str::to_string).collect();
+    }
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// 读出并改写第一行表头，返回改写后的完整数据流
+pub async fn normalize<R>(reader: R, format: &str) -> Result<impl AsyncRead + Send + Unpin>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let Some(delimiter) = delimiter(format) else {
+        bail!("{} 格式没有表头", format);
+    };
+    let mut reader = BufReader::new(reader);
+    let mut raw = Vec::new();
+    reader
+        .read_until(b'\n', &mut raw)
+        .await
+        .context("无法读取表头")?;
+    let line = String::from_utf8(raw).context("表头不是有效的 UTF-8")?;
+    let body = line.trim_end_matches(['\r', '\n']);
+    let ending = &line[body.len()..];
+
+    let original = split_fields(body, delimiter);
+    let names: Vec<String> = original.iter().map(|n| normalize_name(n)).collect();
+    for (i, name) in names.iter().enumerate() {
+        if name.is_empty() {
+            bail!("第 {} 列的表头规范化后为空: {:?}", i + 1, original[i]);
+        }
+        if names[..i].contains(name) {
+            bail!("表头规范化后列名重复: {}", name);
+        }
+    }
+    let renamed: Vec<String> = original
+        .iter()
+        .zip(&names)
+        .filter(|(from, to)| from != to)
+        .map(|(from, to)| format!("{:?} → {}", from, to))
+        .collect();
+    if !renamed.is_empty() {
+        println!("🔤 规范化表头 {} 列: {}", renamed.len(), renamed.join(", "));
+    }
+
+    let header = format!("{}{}", names.join(&(delimiter as char).to_string()), ending);
+    Ok(std::io::Cursor::new(header.into_bytes()).chain(reader))
+}
@@ -0,0 +1,80 @@
+//! `--on-success-cmd` / `--on-failure-cmd`：每个文件有结果后经 `sh -c` 运行用户命令，
+//! 文件路径与导入结果通过 `CK_LOADER_*` 环境变量传入，用于自定义归档、开工单或触发下游任务。
+//!
+//! 成功钩子在源文件处置 (--on-success) 之前运行，此时文件仍在 `CK_LOADER_FILE`；--atomic 时在整批并入目标表之后。
+//! 失败钩子在错误日志写入之后运行。钩子运行期间占用该文件的 worker，退出码非 0 或超时只告警，不改变文件的导入结果。
+
+use crate::cli::Args;
+use crate::report::{FileRecord, FileStatus};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time;
+
+pub async fn run(cfg: &Args, batch_id: &str, record: &FileRecord, error_log: Option<&Path>) {
+    let cmd = match record.status {
+        FileStatus::Success => &cfg.on_success_cmd,
+        FileStatus::Failed => &cfg.on_failure_cmd,
+    };
+    let Some(cmd) = cmd else {
+        return;
+    };
+    let status = match record.status {
+        FileStatus::Success => "success",
+        FileStatus::Failed => "failed",
+    };
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(cmd)
+        .env("CK_LOADER_FILE", &record.path)
+        .env("CK_LOADER_TABLE", &record.table)
+        .env("CK_LOADER_STATUS", status)
+        .env("CK_LOADER_BATCH_ID", batch_id)
+        .env("CK_LOADER_BYTES", record.bytes.to_string())
+        .env(
+            "CK_LOADER_ELAPSED_SECS",
+            format!("{:.3}", record.elapsed_secs),
+        )
+        .env(
+            "CK_LOADER_TAGS",
+            serde_json::to_string(&record.tags).unwrap_or_default(),
+        );
+    let optional = [
+        ("CK_LOADER_QUERY_ID", record.query_id.clone()),
+        ("CK_LOADER_ROWS", record.written_rows.map(|n| n.to_string())),
+        ("CK_LOADER_ERROR", record.error.clone()),
+        (
+            "CK_LOADER_ERROR_CODE",
+            record.error_code.map(|c| c.to_string()),
+        ),
+        ("CK_LOADER_ERROR_NAME", record.error_name.clone()),
+        (
+            "CK_LOADER_ERROR_LOG",
+            error_log.map(|p| p.display().to_string()),
+        ),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            command.env(key, value);
+        }
+    }
+    let child = command.stdin(Stdio::null()).kill_on_drop(true).spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("⚠️ 无法启动钩子命令: {}, 错误: {}", record.file, e);
+            return;
+        }
+    };
+    // 超时后 future 被丢弃，钩子进程随之被 kill
+    match time::timeout(cfg.hook_timeout, child.wait()).await {
+        Ok(Ok(exit)) if exit.success() => {}
+        Ok(Ok(exit)) => eprintln!("⚠️ 钩子命令失败: {} ({})", record.file, exit),
+        Ok(Err(e)) => eprintln!("⚠️ 钩子命令异常: {}, 错误: {}", record.file, e),
+        Err(_) => eprintln!(
+            "⚠️ 钩子命令超过 {:?} 未结束，已终止: {}",
+            cfg.hook_timeout, record.file
+        ),
+    }
+}
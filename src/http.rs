@@ -0,0 +1,749 @@
+//! HTTP 接口导入：流式读取文件作为 POST body，不在内存中缓存整个文件
+
+use crate::cli::Args;
+use crate::error::{self, ClickHouseError};
+use crate::report::Tags;
+use crate::wire::Upload;
+use crate::{orc, pack, precompressed, progress, remote, schema, uring};
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+use reqwest::{Body, Client, StatusCode};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::time::Duration;
+use tokio_util::io::ReaderStream;
+
+/// 服务端在 X-ClickHouse-Summary 响应头中返回的写入统计
+#[derive(Debug, Clone, Default)]
+pub struct InsertSummary {
+    pub written_rows: u64,
+    pub written_bytes: u64,
+    /// 服务端执行耗时 (elapsed_ns)，native 传输不返回
+    pub elapsed_secs: Option<f64>,
+    /// X-ClickHouse-Query-Id 响应头，拆分导入时各组的 query_id 以逗号连接
+    pub query_id: Option<String>,
+    /// native 传输在本地按越界策略截断或置空的值个数，HTTP 传输为 None
+    pub overflow_values: Option<u64>,
+}
+
+impl InsertSummary {
+    /// 响应头中的数值均为字符串，如 `{"written_rows":"100",...}`
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+        let raw = headers.get("X-ClickHouse-Summary")?.to_str().ok()?;
+        let json: serde_json::Value = serde_json::from_str(raw).ok()?;
+        let field = |name: &str| json[name].as_str().and_then(|v| v.parse().ok());
+        Some(Self {
+            written_rows: field("written_rows")?,
+            written_bytes: field("written_bytes").unwrap_or(0),
+            elapsed_secs: field("elapsed_ns").map(|ns| ns as f64 / 1e9),
+            query_id: headers
+                .get("X-ClickHouse-Query-Id")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            overflow_values: None,
+        })
+    }
+
+    /// 成功日志中附带的服务端统计，如 ` | 写入 100000 行 / 3.2 MB | 服务端耗时 1.52s`
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            " | 写入 {} 行 / {:.1} MB",
+            self.written_rows,
+            self.written_bytes as f64 / 1024.0 / 1024.0
+        );
+        if let Some(secs) = self.elapsed_secs {
+            text.push_str(&format!(" | 服务端耗时 {:.2}s", secs));
+        }
+        text
+    }
+}
+
+impl std::iter::Sum for InsertSummary {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |a, b| Self {
+            written_rows: a.written_rows + b.written_rows,
+            written_bytes: a.written_bytes + b.written_bytes,
+            // 拆分导入的各组依次执行，耗时相加
+            elapsed_secs: match (a.elapsed_secs, b.elapsed_secs) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            },
+            query_id: match (a.query_id, b.query_id) {
+                (Some(a), Some(b)) => Some(format!("{},{}", a, b)),
+                (a, b) => a.or(b),
+            },
+            overflow_values: match (a.overflow_values, b.overflow_values) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            },
+        })
+    }
+}
+
+/// --session-under-mb 会话中空闲连接的保留时间，低于服务端 keep_alive_timeout 的默认值 (3s)，
+/// 避免把小文件发到服务端已经关闭的 keep-alive 连接上
+const SESSION_IDLE: Duration = Duration::from_secs(2);
+
+pub fn build_client() -> Result<Client> {
+    Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .tcp_keepalive(Duration::from_secs(60))
+        .build()
+        .context("无法创建 HTTP 客户端")
+}
+
+/// --session-under-mb 的小文件专用客户端，空闲连接的回收时间较短；其他请求仍走默认的共享客户端
+pub fn build_session_client() -> Result<Client> {
+    Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .tcp_keepalive(Duration::from_secs(60))
+        .pool_idle_timeout(SESSION_IDLE)
+        .build()
+        .context("无法创建 HTTP 会话客户端")
+}
+
+fn request(http: &Client, cfg: &Args, password: &str, sql: &str) -> reqwest::RequestBuilder {
+    let mut req = http
+        .post(cfg.http_url())
+        .header("X-ClickHouse-User", &cfg.user)
+        .header("X-ClickHouse-Key", password);
+    if let Some(database) = &cfg.database {
+        req = req.header("X-ClickHouse-Database", database);
+    }
+    req.query(&[("query", sql)])
+}
+
+/// 认证失败时作废凭据缓存，之后的请求会重新读取 (可能已被轮换的) 密码
+fn check_auth(cfg: &Args, status: StatusCode, body: &str) {
+    if status == StatusCode::UNAUTHORIZED
+        || status == StatusCode::FORBIDDEN
+        || error::is_auth_error(body)
+    {
+        cfg.password.invalidate();
+    }
+}
+
+/// 执行一条查询并返回 TSV 格式的响应体
+pub async fn query(http: &Client, cfg: &Args, sql: &str) -> Result<String> {
+    let password = cfg.password.get().await?;
+    let resp = request(http, cfg, &password, sql)
+        .query(&[("default_format", "TabSeparated")])
+        .send()
+        .await
+        .with_context(|| format!("无法连接 ClickHouse: {}", cfg.http_url()))?;
+    let status = resp.status();
+    let body = resp.text().await?;
+    if !status.is_success() {
+        check_auth(cfg, status, &body);
+        bail!("查询失败: {} | HTTP {} {}", sql, status, body.trim());
+    }
+    Ok(body)
+}
+
+/// 把一段数据作为请求体发送给服务端后丢弃 (写入 null 表函数)，用于测量到服务端的网络吞吐
+pub async fn discard(http: &Client, cfg: &Args, data: Bytes) -> Result<()> {
+    let password = cfg.password.get().await?;
+    let sql = "INSERT INTO FUNCTION null('data String') FORMAT RawBLOB";
+    let resp = request(http, cfg, &password, sql)
+        .body(data)
+        .send()
+        .await
+        .with_context(|| format!("无法连接 ClickHouse: {}", cfg.http_url()))?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        check_auth(cfg, status, &body);
+        bail!("HTTP {} {}", status, body.trim());
+    }
+    Ok(())
+}
+
+/// 执行一条语句 (ck-loader sql)，返回 `format` 格式的响应体；错误按服务端异常解析，供重试分类
+pub async fn execute(
+    http: &Client,
+    cfg: &Args,
+    sql: &str,
+    format: &str,
+    tags: &Tags,
+    query_id: &str,
+) -> Result<String, ClickHouseError> {
+    let password = cfg.password.get().await?;
+    let mut req = request(http, cfg, &password, sql)
+        .query(&[("default_format", format), ("query_id", query_id)]);
+    if !tags.is_empty() {
+        req = req.query(&[(
+            "log_comment",
+            serde_json::to_string(tags).unwrap_or_default(),
+        )]);
+    }
+    let resp = req.send().await.map_err(|e| {
+        if e.is_connect() {
+            ClickHouseError::Connect(format!("无法连接 {}: {}", cfg.http_url(), e))
+        } else {
+            ClickHouseError::Transport(format!("HTTP 请求失败: {}", e))
+        }
+    })?;
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    if status.is_success() {
+        return Ok(body);
+    }
+    check_auth(cfg, status, &body);
+    Err(ClickHouseError::from_http(status.as_u16(), &body))
+}
+
+/// 执行一条 SELECT 并把响应体原样流式写入 `out`，返回写入的字节数 (ck-loader export)。
+/// `encoding` 非空时请求服务端按该方式压缩响应 (enable_http_compression)，写入的是压缩后的数据
+#[allow(clippy::too_many_arguments)]
+pub async fn select_into(
+    http: &Client,
+    cfg: &Args,
+    sql: &str,
+    settings: &[(&str, String)],
+    encoding: Option<&str>,
+    tags: &Tags,
+    query_id: &str,
+    out: &mut tokio::fs::File,
+) -> Result<u64, ClickHouseError> {
+    let password = cfg.password.get().await?;
+    let mut req = request(http, cfg, &password, sql)
+        .query(&[("query_id", query_id)])
+        .query(settings);
+    if let Some(encoding) = encoding {
+        req = req
+            .query(&[("enable_http_compression", "1")])
+            .header("Accept-Encoding", encoding);
+    }
+    if !tags.is_empty() {
+        req = req.query(&[(
+            "log_comment",
+            serde_json::to_string(tags).unwrap_or_default(),
+        )]);
+    }
+    let resp = req.send().await.map_err(|e| {
+        if e.is_connect() {
+            ClickHouseError::Connect(format!("无法连接 {}: {}", cfg.http_url(), e))
+        } else {
+            ClickHouseError::Transport(format!("HTTP 请求失败: {}", e))
+        }
+    })?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        check_auth(cfg, status, &body);
+        return Err(ClickHouseError::from_http(status.as_u16(), &body));
+    }
+    let mut written = 0u64;
+    let mut body = resp.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk =
+            chunk.map_err(|e| ClickHouseError::Transport(format!("读取响应失败: {}", e)))?;
+        out.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    out.flush().await?;
+    Ok(written)
+}
+
+pub async fn insert(
+    http: &Client,
+    cfg: &Args,
+    table: &str,
+    path: &Path,
+    tags: &Tags,
+    upload: &Arc<Upload>,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    if remote::is_remote(path) {
+        return insert_remote(http, cfg, table, path, tags, upload).await;
+    }
+    // 只有未压缩的 ORC 文件能按 stripe 拆分或对齐，预压缩文件与行格式文件原样上传
+    let plain_orc = precompressed::is_plain_orc(path);
+    if let Some(per_group) = cfg.split_stripes.filter(|_| plain_orc) {
+        let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if size >= cfg.split_min_mb * 1024 * 1024 {
+            let meta = orc::read_meta(path)?;
+            if meta.stripes.len() > per_group.max(1) {
+                return insert_split(http, cfg, table, path, tags, upload, &meta).await;
+            }
+        }
+    }
+
+    let body = if cfg.align_stripes && plain_orc {
+        aligned_body(cfg, path, upload).await?
+    } else {
+        upload.file_body(file_chunks(cfg, path, upload).await?)?
+    };
+
+    let query = schema::file_insert_sql(cfg, table, path)?;
+    let extra: Vec<_> = upload
+        .query_id_param(None)
+        .into_iter()
+        .chain(upload.dedup_param())
+        .collect();
+    send_insert(http, cfg, table, &query, body, tags, &extra, upload).await
+}
+
+/// 远端文件内容由读取子进程 (aws / curl) 的 stdout 直接作为 body 上传；上传结束后再检查子进程退出码，
+/// 避免下载中途失败时把截断的数据当成成功
+async fn insert_remote(
+    http: &Client,
+    cfg: &Args,
+    table: &str,
+    path: &Path,
+    tags: &Tags,
+    upload: &Arc<Upload>,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let query = schema::file_insert_sql(cfg, table, path)?;
+    let mut child = remote::stream(path)?;
+    let stdout = child.stdout.take().ok_or("无法读取子进程输出")?;
+    let body = upload.file_body(ReaderStream::with_capacity(
+        stdout,
+        cfg.chunk_size() as usize,
+    ))?;
+    let extra: Vec<_> = upload.query_id_param(None).into_iter().collect();
+    let sent = send_insert(http, cfg, table, &query, body, tags, &extra, upload).await;
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(ClickHouseError::Transport(format!(
+            "读取远端文件失败: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    sent
+}
+
+/// 将任意字节流 (如本进程的 stdin) 按给定的 INSERT 语句导入
+pub async fn insert_stream<R>(
+    http: &Client,
+    cfg: &Args,
+    table: &str,
+    query: &str,
+    reader: R,
+    tags: &Tags,
+    upload: &Arc<Upload>,
+) -> Result<Option<InsertSummary>, ClickHouseError>
+where
+    R: AsyncRead + Send + 'static,
+{
+    let chunks = ReaderStream::with_capacity(reader, cfg.chunk_size() as usize);
+    let body = upload.body(chunks)?;
+    let extra: Vec<_> = upload.query_id_param(None).into_iter().collect();
+    send_insert(http, cfg, table, query, body, tags, &extra, upload).await
+}
+
+/// 源集群 `SELECT ... FORMAT Native` 的响应体直接作为目标端 INSERT 的请求体，不落地 (ck-loader copy)。
+/// 源端读取中断时请求体随之出错，整个插入失败；源端单线程读取，同一分区重试时数据块的切分保持一致，
+/// 配合 `extra` 中的 insert_deduplication_token，已写入的块会被目标端去重
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_select(
+    http: &Client,
+    source: &Args,
+    select: &str,
+    cfg: &Args,
+    table: &str,
+    tags: &Tags,
+    upload: &Arc<Upload>,
+    extra: &[(&str, String)],
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let password = source.password.get().await?;
+    let resp = request(http, source, &password, select)
+        .query(&[("max_threads", "1")])
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_connect() {
+                ClickHouseError::Connect(format!("无法连接源端 {}: {}", source.http_url(), e))
+            } else {
+                ClickHouseError::Transport(format!("源端 HTTP 请求失败: {}", e))
+            }
+        })?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        check_auth(source, status, &body);
+        return Err(ClickHouseError::from_http(status.as_u16(), &body));
+    }
+    let body = upload.body(
+        resp.bytes_stream()
+            .map(|c| c.map_err(std::io::Error::other)),
+    )?;
+    let query = format!("INSERT INTO {} FORMAT Native", table);
+    let mut extra = extra.to_vec();
+    extra.extend(upload.query_id_param(None));
+    send_insert(http, cfg, table, &query, body, tags, &extra, upload).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn send_insert(
+    http: &Client,
+    cfg: &Args,
+    table: &str,
+    query: &str,
+    body: Body,
+    tags: &Tags,
+    extra: &[(&str, String)],
+    upload: &Arc<Upload>,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let password = cfg.password.get().await?;
+    let mut req = request(http, cfg, &password, query)
+        .query(&cfg.insert_settings(table))
+        .query(extra);
+    if let Some(encoding) = upload.content_encoding() {
+        req = req.header("Content-Encoding", encoding);
+    }
+    if let Some(traceparent) = upload.traceparent_header() {
+        req = req.header("traceparent", traceparent);
+    }
+    if !tags.is_empty() {
+        req = req.query(&[(
+            "log_comment",
+            serde_json::to_string(tags).unwrap_or_default(),
+        )]);
+    }
+
+    // 超时由调用方按文件大小控制，整个文件 (含拆分后的各组) 共用一个截止时间
+    if cfg.server_progress {
+        let request = req
+            .query(&[
+                ("send_progress_in_http_headers", "1".to_string()),
+                (
+                    "http_headers_progress_interval_ms",
+                    progress::HEADER_INTERVAL_MS.to_string(),
+                ),
+            ])
+            .body(body)
+            .build()
+            .map_err(|e| ClickHouseError::Transport(format!("无法构造 HTTP 请求: {}", e)))?;
+        let resp = progress::send(request, upload).await?;
+        // 响应头已开始发送后出现的异常只能写在响应体中，此时状态码仍是 200
+        if resp.status.is_success() && !resp.body.contains("DB::Exception") {
+            return Ok(InsertSummary::from_headers(&resp.headers));
+        }
+        check_auth(cfg, resp.status, &resp.body);
+        return Err(ClickHouseError::from_http(resp.status.as_u16(), &resp.body));
+    }
+    let resp = req.body(body).send().await.map_err(|e| {
+        if e.is_connect() {
+            ClickHouseError::Connect(format!("无法连接 {}: {}", cfg.http_url(), e))
+        } else {
+            ClickHouseError::Transport(format!("HTTP 请求失败: {}", e))
+        }
+    })?;
+
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(InsertSummary::from_headers(resp.headers()));
+    }
+    let body = resp.text().await.unwrap_or_default();
+    check_auth(cfg, status, &body);
+    Err(ClickHouseError::from_http(status.as_u16(), &body))
+}
+
+/// 合并组导入：各成员的 stripe 依次拼接，加上按合并后的 stripe 列表重写的文件尾，作为一个 ORC 文件上传。
+/// 组内文件的 pack_key 相同，类型定义等取自第一个成员；成员文件在上传到它时才打开
+pub async fn insert_pack(
+    http: &Client,
+    cfg: &Args,
+    table: &str,
+    paths: &[PathBuf],
+    tags: &Tags,
+    upload: &Arc<Upload>,
+    token: &str,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let metas = paths
+        .iter()
+        .map(|p| orc::read_meta(p))
+        .collect::<Result<Vec<_>>>()?;
+    let key = metas[0].pack_key()?;
+    for (path, meta) in paths.iter().zip(&metas) {
+        if meta.pack_key()? != key {
+            return Err(ClickHouseError::Transport(format!(
+                "{:?} 的 ORC 参数与组内其他文件不同，无法合并",
+                path
+            )));
+        }
+    }
+    let stripes: Vec<orc::StripeInfo> = metas.iter().flat_map(|m| m.stripes.clone()).collect();
+    let mut tail = orc::build_split_tail(&metas[0], &stripes)?;
+
+    let last = paths.len() - 1;
+    let mut plans = Vec::with_capacity(paths.len());
+    for (i, (path, meta)) in paths.iter().zip(&metas).enumerate() {
+        let mut segments = Vec::new();
+        if i == 0 {
+            segments.push(Segment::Bytes(orc::MAGIC.to_vec()));
+        }
+        segments.extend(
+            meta.stripes
+                .iter()
+                .map(|s| Segment::Range(s.offset, s.total_length())),
+        );
+        if i == last {
+            segments.push(Segment::Bytes(std::mem::take(&mut tail)));
+        }
+        plans.push((path.clone(), segments));
+    }
+    let chunk = cfg.chunk_size();
+    let chunks = stream::iter(plans)
+        .then(move |(path, segments)| async move {
+            let file = tokio::fs::File::open(path).await?;
+            Ok::<_, std::io::Error>(segment_stream(file, segments, chunk))
+        })
+        .try_flatten();
+    let body = upload.body(upload.read_limiter().stream(chunks))?;
+
+    let query = schema::file_insert_sql(cfg, table, &paths[0])?;
+    let mut extra = vec![("insert_deduplication_token", token.to_string())];
+    extra.extend(upload.query_id_param(None));
+    send_insert(http, cfg, table, &query, body, tags, &extra, upload).await
+}
+
+/// 行格式小文件合并导入 (--batch-small-files)：成员依次拼接为一个请求体，带表头的格式从第二个成员起去掉首行，
+/// 不以换行结尾的成员补一个换行。成员都是小文件，上传到它时才整体读入
+pub async fn insert_batch(
+    http: &Client,
+    cfg: &Args,
+    table: &str,
+    paths: &[PathBuf],
+    tags: &Tags,
+    upload: &Arc<Upload>,
+    token: &str,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let Some(format) = pack::row_format(&paths[0]) else {
+        return Err(ClickHouseError::Transport(format!(
+            "{:?} 不是可拼接的行格式文件",
+            paths[0]
+        )));
+    };
+    let skip_header = format.ends_with("WithNames");
+    let members: Vec<(usize, PathBuf)> = paths.iter().cloned().enumerate().collect();
+    let chunks = stream::iter(members).then(move |(i, path)| async move {
+        let data = tokio::fs::read(&path).await?;
+        Ok::<_, std::io::Error>(pack::batch_member(data, skip_header && i > 0))
+    });
+    let body = upload.body(upload.read_limiter().stream(chunks))?;
+
+    let query = schema::file_insert_sql(cfg, table, &paths[0])?;
+    let mut extra = vec![("insert_deduplication_token", token.to_string())];
+    extra.extend(upload.query_id_param(None));
+    send_insert(http, cfg, table, &query, body, tags, &extra, upload).await
+}
+
+/// 将大文件按每组 `per_group` 个 stripe 拆成若干独立 ORC 并行导入。
+/// 每组带上由文件标识与组号派生的 insert_deduplication_token，整文件重试时已成功的组会被服务端去重；
+/// 同名文件换了内容后重新导入，token 随之改变，不会被误当成重复
+async fn insert_split(
+    http: &Client,
+    cfg: &Args,
+    table: &str,
+    path: &Path,
+    tags: &Tags,
+    upload: &Arc<Upload>,
+    meta: &orc::OrcMeta,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let per_group = cfg.split_stripes.unwrap_or(1).max(1);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let groups: Vec<&[orc::StripeInfo]> = meta.stripes.chunks(per_group).collect();
+    let total = groups.len();
+    let query = schema::file_insert_sql(cfg, table, path)?;
+    // 调用方没有给出 token 时 (如 bench) 由文件元数据派生，不为此多读一遍文件
+    let base = match upload.dedup_param() {
+        Some((_, token)) => token,
+        None => {
+            let stat = tokio::fs::metadata(path).await?;
+            let id = pack::meta_id(path, stat.len(), stat.modified().ok()).ok_or_else(|| {
+                ClickHouseError::Transport(format!("无法读取 {:?} 的修改时间", path))
+            })?;
+            pack::file_token(&id)
+        }
+    };
+    println!(
+        "✂️ 拆分导入: {} | {} 个 stripe → {} 组 (并行 {})",
+        file_name,
+        meta.stripes.len(),
+        total,
+        cfg.split_parallel
+    );
+
+    // 先收集成 future 列表再限流执行，避免在 stream 组合子中借用导致的 Send 推断问题
+    let inserts: Vec<_> = groups
+        .into_iter()
+        .enumerate()
+        .map(|(idx, stripes)| {
+            let token = format!("{}#{}/{}", base, idx + 1, total);
+            let file_name = &file_name;
+            let query = &query;
+            async move {
+                let tail = orc::build_split_tail(meta, stripes)?;
+                let mut segments = vec![Segment::Bytes(orc::MAGIC.to_vec())];
+                for s in stripes {
+                    segments.push(Segment::Range(s.offset, s.total_length()));
+                }
+                segments.push(Segment::Bytes(tail));
+                let file = tokio::fs::File::open(path).await?;
+                let chunks = segment_stream(file, segments, cfg.chunk_size());
+                let body = upload.body(upload.read_limiter().stream(chunks))?;
+                let mut extra = vec![("insert_deduplication_token", token)];
+                extra.extend(upload.query_id_param(Some(idx + 1)));
+                send_insert(http, cfg, table, query, body, tags, &extra, upload)
+                    .await
+                    .inspect_err(|e| {
+                        eprintln!("❌ {} 第 {}/{} 组: {}", file_name, idx + 1, total, e)
+                    })
+            }
+        })
+        .collect();
+    let results: Vec<Result<Option<InsertSummary>, ClickHouseError>> = stream::iter(inserts)
+        .buffer_unordered(cfg.split_parallel.max(1))
+        .collect()
+        .await;
+
+    // 各组的错误已逐条输出，整个文件以第一个失败组的错误作为结果；全部成功时汇总各组的写入统计
+    let (oks, errs): (Vec<_>, Vec<_>) = results.into_iter().partition(|r| r.is_ok());
+    let mut errors = errs.into_iter().filter_map(|r| r.err());
+    match errors.next() {
+        None => Ok(oks
+            .into_iter()
+            .map(|r| r.ok().flatten())
+            .sum::<Option<InsertSummary>>()),
+        Some(first) => {
+            eprintln!(
+                "✂️ {}: {}/{} 组导入失败",
+                file_name,
+                errors.count() + 1,
+                total
+            );
+            Err(first)
+        }
+    }
+}
+
+enum Segment {
+    Bytes(Vec<u8>),
+    Range(u64, u64),
+}
+
+/// 依次输出内存块与文件区间，文件区间按 chunk 大小分段读取
+fn segment_stream(
+    file: tokio::fs::File,
+    segments: Vec<Segment>,
+    chunk: u64,
+) -> impl Stream<Item = std::io::Result<Vec<u8>>> {
+    stream::try_unfold(
+        (file, segments.into_iter(), None::<(u64, u64)>),
+        move |(mut file, mut segments, mut pending)| async move {
+            loop {
+                if let Some((offset, remaining)) = pending.take() {
+                    let len = chunk.min(remaining);
+                    let mut buf = vec![0u8; len as usize];
+                    file.seek(SeekFrom::Start(offset)).await?;
+                    file.read_exact(&mut buf).await?;
+                    if remaining > len {
+                        pending = Some((offset + len, remaining - len));
+                    }
+                    return Ok(Some((buf, (file, segments, pending))));
+                }
+                match segments.next() {
+                    None => return Ok(None),
+                    Some(Segment::Bytes(b)) => return Ok(Some((b, (file, segments, None)))),
+                    Some(Segment::Range(offset, len)) => pending = Some((offset, len)),
+                }
+            }
+        },
+    )
+}
+
+/// 按 stripe 边界切分读取区间：文件头、每个 stripe (超过 chunk 时再细分)、文件尾各自成块，
+/// 任何一个块都不会跨越两个 stripe
+pub fn stripe_chunks(meta: &orc::OrcMeta, chunk: u64) -> Option<Vec<(u64, u64)>> {
+    let mut plan = Vec::new();
+    let mut pos = 0u64;
+    for s in &meta.stripes {
+        if s.offset < pos {
+            return None;
+        }
+        if s.offset > pos {
+            plan.push((pos, s.offset - pos));
+        }
+        let end = s.offset + s.index_length + s.data_length + s.footer_length;
+        let mut cur = s.offset;
+        while cur < end {
+            let len = chunk.min(end - cur);
+            plan.push((cur, len));
+            cur += len;
+        }
+        pos = end;
+    }
+    if pos > meta.file_len {
+        return None;
+    }
+    // 文件尾 (元数据 + Footer + PostScript) 同样按 chunk 细分
+    while pos < meta.file_len {
+        let len = chunk.min(meta.file_len - pos);
+        plan.push((pos, len));
+        pos += len;
+    }
+    Some(plan)
+}
+
+async fn aligned_body(
+    cfg: &Args,
+    path: &Path,
+    upload: &Arc<Upload>,
+) -> Result<Body, ClickHouseError> {
+    let meta = orc::read_meta(path)?;
+    let plan = stripe_chunks(&meta, cfg.chunk_size())
+        .ok_or("stripe 信息与文件长度不一致，无法对齐分块")?;
+    if cfg.io_uring {
+        if let Some(chunks) = uring::read_ranges(path, plan.clone())? {
+            return Ok(upload.file_body(upload.read_limiter().stream(chunks))?);
+        }
+    }
+    let file = tokio::fs::File::open(path).await?;
+    Ok(upload.file_body(upload.read_limiter().stream(chunk_stream(file, plan)))?)
+}
+
+/// 整个文件按 --chunk-size-mb 顺序分块读取；--io-uring 时经 io_uring 读取，--max-read-mbps 时限速
+async fn file_chunks(
+    cfg: &Args,
+    path: &Path,
+    upload: &Upload,
+) -> std::io::Result<BoxStream<'static, std::io::Result<Bytes>>> {
+    let chunk = cfg.chunk_size();
+    if cfg.io_uring {
+        let len = tokio::fs::metadata(path).await?.len();
+        let plan = (0..len)
+            .step_by(chunk as usize)
+            .map(|offset| (offset, chunk.min(len - offset)))
+            .collect();
+        if let Some(chunks) = uring::read_ranges(path, plan)? {
+            return Ok(upload.read_limiter().stream(chunks));
+        }
+    }
+    let file = tokio::fs::File::open(path).await?;
+    Ok(upload
+        .read_limiter()
+        .stream(ReaderStream::with_capacity(file, chunk as usize)))
+}
+
+/// 按给定的 (offset, len) 顺序读取；区间首尾相接，因此只需顺序读，无需 seek
+fn chunk_stream(
+    file: tokio::fs::File,
+    plan: Vec<(u64, u64)>,
+) -> impl Stream<Item = std::io::Result<Vec<u8>>> {
+    stream::try_unfold(
+        (file, plan.into_iter()),
+        |(mut file, mut plan)| async move {
+            let Some((_, len)) = plan.next() else {
+                return Ok(None);
+            };
+            let mut buf = vec![0u8; len as usize];
+            file.read_exact(&mut buf).await?;
+            Ok(Some((buf, (file, plan))))
+        },
+    )
+}
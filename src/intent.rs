@@ -0,0 +1,128 @@
+//! 成功文件处置的预写日志 (done/.ck-loader-intent.log)
+//!
+//! 导入成功后、移动/删除/归档源文件之前先追加一条 `finish` 记录并落盘，处置完成后再追加 `done`。
+//! 进程在两者之间崩溃时，下次对同一目录启动导入会先重放日志，把未完成的处置做完，
+//! 源文件不会被当作新文件再导入一次。
+
+use crate::archive::{self, OnSuccess};
+use crate::remote;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const LOG_NAME: &str = ".ck-loader-intent.log";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Intent {
+    Finish {
+        path: PathBuf,
+        policy: OnSuccess,
+        target: PathBuf,
+    },
+    Done {
+        path: PathBuf,
+    },
+}
+
+pub struct IntentLog {
+    file: Mutex<File>,
+}
+
+impl IntentLog {
+    /// 打开 done 目录下的日志，先重放上次遗留的未完成记录
+    pub fn open(done_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(done_dir).context("无法创建 done 目录")?;
+        let path = done_dir.join(LOG_NAME);
+        if path.exists() {
+            replay(&path)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("无法打开预写日志: {:?}", path))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn begin(&self, path: &Path, policy: OnSuccess, target: &Path) -> Result<()> {
+        self.append(&Intent::Finish {
+            path: path.to_path_buf(),
+            policy,
+            target: target.to_path_buf(),
+        })
+    }
+
+    pub fn commit(&self, path: &Path) -> Result<()> {
+        self.append(&Intent::Done {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn append(&self, intent: &Intent) -> Result<()> {
+        let mut line = serde_json::to_string(intent)?;
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.sync_data().context("预写日志落盘失败")
+    }
+}
+
+/// 完成遗留的处置后重写日志，只保留仍未能完成的记录
+fn replay(log: &Path) -> Result<()> {
+    let reader = BufReader::new(File::open(log)?);
+    let mut pending: Vec<(PathBuf, OnSuccess, PathBuf)> = Vec::new();
+    for line in reader.lines() {
+        // 崩溃时最后一行可能只写了一半，解析失败的行直接忽略
+        match serde_json::from_str::<Intent>(&line?) {
+            Ok(Intent::Finish {
+                path,
+                policy,
+                target,
+            }) => {
+                pending.retain(|(p, _, _)| p != &path);
+                pending.push((path, policy, target));
+            }
+            Ok(Intent::Done { path }) => pending.retain(|(p, _, _)| p != &path),
+            Err(_) => {}
+        }
+    }
+
+    let mut remaining = Vec::new();
+    for (path, policy, target) in pending {
+        if !path.exists() {
+            continue;
+        }
+        // --archive-to 的目标是 S3 前缀，不需要创建目录
+        let result = if remote::is_remote(&target) {
+            Ok(())
+        } else {
+            std::fs::create_dir_all(&target).map_err(anyhow::Error::from)
+        }
+        .and_then(|_| archive::finish(policy, &path, &target));
+        match result {
+            Ok(_) => println!("♻️ 恢复未完成的处置: {:?} ({:?})", path, policy),
+            Err(e) => {
+                eprintln!("⚠️ 恢复处置失败: {:?}, 错误: {:#}", path, e);
+                remaining.push(Intent::Finish {
+                    path,
+                    policy,
+                    target,
+                });
+            }
+        }
+    }
+
+    let tmp = log.with_extension("tmp");
+    let mut out = File::create(&tmp)?;
+    for intent in &remaining {
+        writeln!(out, "{}", serde_json::to_string(intent)?)?;
+    }
+    out.sync_all()?;
+    std::fs::rename(&tmp, log).context("重写预写日志失败")
+}
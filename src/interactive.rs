@@ -0,0 +1,147 @@
+//! `--interactive`：执行前打印解析后的导入计划 (文件、总大小、目标表、模式、破坏性操作)，
+//! 需要手动输入确认才开始，防止在生产环境手误写错 `--table`。
+//!
+//! 只有一张目标表时要求输入表名本身，多张表时输入 yes。
+
+use crate::archive::OnSuccess;
+use crate::cli::Args;
+use crate::loader::{self, Job};
+use crate::remote;
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use std::io::{BufRead, IsTerminal, Write};
+
+/// 每个任务最多列出的文件数
+const SHOW_FILES: usize = 5;
+
+/// 打印导入计划并等待确认，返回用户是否确认执行。未显式列出文件的任务在这里展开
+pub fn confirm(jobs: &mut [Job], cfg: &Args) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        bail!("--interactive 需要在终端中运行");
+    }
+    println!("📋 导入计划:");
+    let mut tables: Vec<&str> = Vec::new();
+    for job in jobs.iter_mut() {
+        let files = match &mut job.files {
+            Some(files) => files,
+            None => job.files.insert(loader::discover(&job.dir)?),
+        };
+        let sizes: Vec<Option<u64>> = files.iter().map(|p| file_size(p)).collect();
+        let known: u64 = sizes.iter().flatten().sum();
+        let unknown = sizes.iter().filter(|s| s.is_none()).count();
+        println!(
+            "  🎯 {} ← {:?} | {} 个文件 | {:.1} MB{}",
+            job.table,
+            job.dir,
+            files.len(),
+            known as f64 / 1024.0 / 1024.0,
+            if unknown > 0 {
+                format!(" (另有 {} 个文件大小未知)", unknown)
+            } else {
+                String::new()
+            }
+        );
+        for path in files.iter().take(SHOW_FILES) {
+            println!("     {}", path.display());
+        }
+        if files.len() > SHOW_FILES {
+            println!("     ... 另有 {} 个文件", files.len() - SHOW_FILES);
+        }
+        if !tables.contains(&job.table.as_str()) {
+            tables.push(&job.table);
+        }
+    }
+
+    let chain: Vec<&str> = cfg.transport_chain().iter().map(|t| t.name()).collect();
+    println!(
+        "  ⚙️ 传输: {} | 并行数: {} | 成功后: {}{}",
+        chain.join(" → "),
+        cfg.workers,
+        match &cfg.archive_to {
+            Some(url) => format!("上传到 {}", url.display()),
+            None => value_name(cfg.on_success),
+        },
+        mode_flags(cfg)
+    );
+    let warnings = destructive(cfg);
+    for warning in &warnings {
+        println!("  ⚠️ {}", warning);
+    }
+
+    let expected = match tables.as_slice() {
+        [table] => table.to_string(),
+        _ => "yes".to_string(),
+    };
+    print!("❓ 输入 {} 确认执行，其他输入取消: ", expected);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim() == expected)
+}
+
+fn file_size(path: &std::path::Path) -> Option<u64> {
+    if remote::is_remote(path) {
+        remote::size(path).ok()
+    } else {
+        std::fs::metadata(path).map(|m| m.len()).ok()
+    }
+}
+
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// 影响写入方式的开关
+fn mode_flags(cfg: &Args) -> String {
+    let mut flags = Vec::new();
+    if cfg.delta {
+        flags.push("delta");
+    }
+    if cfg.canary {
+        flags.push("canary");
+    }
+    if cfg.split_stripes.is_some() {
+        flags.push("split-stripes");
+    }
+    if cfg.pack_under_mb.is_some() {
+        flags.push("pack");
+    }
+    if cfg.batch_small_files.is_some() {
+        flags.push("batch-small-files");
+    }
+    if cfg.transform_sql.is_some() {
+        flags.push("transform-sql");
+    }
+    if cfg.with_metadata {
+        flags.push("with-metadata");
+    }
+    if flags.is_empty() {
+        String::new()
+    } else {
+        format!(" | 模式: {}", flags.join(", "))
+    }
+}
+
+/// 执行过程中可能发生的破坏性或不可逆操作
+fn destructive(cfg: &Args) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if let Some(partition) = &cfg.delta_partition {
+        warnings.push(format!(
+            "目录内有文件变化时会先 DROP PARTITION {} 再重导",
+            partition
+        ));
+    }
+    if let Some(engine) = &cfg.create_table {
+        warnings.push(format!("目标表不存在时以 {} 引擎创建", engine));
+    }
+    match cfg.success_policy() {
+        OnSuccess::Delete => warnings.push("导入成功的源文件将被删除".to_string()),
+        OnSuccess::Compress => warnings.push("导入成功的源文件将被压缩后删除".to_string()),
+        OnSuccess::Archive => warnings.push("导入成功的源文件将在上传归档后删除".to_string()),
+        OnSuccess::Move => {}
+    }
+    warnings
+}
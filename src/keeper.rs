@@ -0,0 +1,556 @@
+//! `--keeper`：多个 loader 节点共用一个 S3 / NFS 落地目录时，经 ClickHouse Keeper (或 ZooKeeper) 认领文件，
+//! 同一文件只会被一个节点导入。
+//!
+//! 工作任务拿到许可后才在 `<--keeper-path>/<表>/<文件名>` 创建临时节点 (ephemeral) 认领文件，已存在则说明
+//! 其他节点正在处理或处理过，跳过；空闲的节点才会认领，文件随各节点的处理能力分摊。
+//! 导入结束后，失败或认领后没有启动的文件立即删除认领，其他节点 (或 watch 的下一轮) 可以重试；
+//! 导入成功且源文件已被移走的同样删除；导入成功但源文件仍在原处的保持认领直到本节点退出，
+//! 本节点之后的扫描按已导入跳过。节点崩溃时会话超时，Keeper 自动删除它认领的节点，其他节点在下一轮扫描时接手。
+//!
+//! 协议只实现了认领所需的部分 (建立会话、create / delete、心跳、关闭)，不使用 watch 与 ACL 认证。
+
+use crate::cli::Args;
+use crate::remote;
+use crate::report::{FileRecord, FileStatus};
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const OP_CREATE: i32 = 1;
+const OP_DELETE: i32 = 2;
+const OP_PING: i32 = 11;
+const OP_CLOSE: i32 = -11;
+
+/// 心跳与服务端推送的 watch 通知使用固定的 xid
+const XID_PING: i32 = -2;
+const XID_WATCH: i32 = -1;
+
+const ZNONODE: i32 = -101;
+const ZNODEEXISTS: i32 = -110;
+
+const PERSISTENT: i32 = 0;
+const EPHEMERAL: i32 = 1;
+
+/// 单个响应的最大长度，超出说明协议错乱
+const MAX_FRAME: usize = 16 << 20;
+
+pub struct Keeper {
+    conn: Mutex<Conn>,
+    root: String,
+    /// 写入认领节点的内容，便于排查是哪个节点认领的
+    owner: String,
+    session_timeout: Duration,
+    /// 连接断开或心跳失败后，已认领的节点可能随会话过期被删除，不再认领新的文件
+    lost: AtomicBool,
+    /// 已确认存在的表节点
+    tables: std::sync::Mutex<HashSet<String>>,
+    /// 导入成功但源文件仍在原处、保持认领的节点
+    loaded: std::sync::Mutex<HashSet<String>>,
+}
+
+/// 单个文件的认领结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    /// 由本节点认领
+    Mine,
+    /// 已被其他节点 (或本节点的其他任务) 认领
+    Taken,
+    /// 本节点已导入过且源文件仍在原处
+    Loaded,
+}
+
+struct Conn {
+    stream: TcpStream,
+    xid: i32,
+}
+
+impl Keeper {
+    /// 依次尝试 `--keeper` 中的各个地址建立会话，并开始按会话超时的 1/3 发送心跳
+    pub async fn connect(cfg: &Args, hosts: &str, batch_id: &str) -> Result<Arc<Self>> {
+        let timeout = cfg.keeper_session_timeout;
+        let mut last_err = anyhow!("--keeper 没有地址");
+        for host in hosts.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+            match handshake(host, timeout).await {
+                Ok((stream, session_id)) => {
+                    println!("🤝 已连接 Keeper {} (会话 0x{:x})", host, session_id);
+                    let root = cfg.keeper_path.trim_end_matches('/').to_string();
+                    let keeper = Arc::new(Self {
+                        conn: Mutex::new(Conn { stream, xid: 0 }),
+                        owner: format!(
+                            "{} pid {} batch {}",
+                            hostname(),
+                            std::process::id(),
+                            batch_id
+                        ),
+                        root,
+                        session_timeout: timeout,
+                        lost: AtomicBool::new(false),
+                        tables: Default::default(),
+                        loaded: Default::default(),
+                    });
+                    keeper.ensure_path(&keeper.root).await?;
+                    heartbeat(Arc::downgrade(&keeper), timeout / 3);
+                    return Ok(keeper);
+                }
+                Err(e) => {
+                    eprintln!("⚠️ 无法连接 Keeper {}: {:#}", host, e);
+                    last_err = e;
+                }
+            }
+        }
+        Err(last_err.context("无法连接 --keeper 中的任何地址"))
+    }
+
+    /// 认领单个文件
+    pub async fn claim(&self, table: &str, path: &Path) -> Result<Claim> {
+        let node = format!("{}/{}", self.table_node(table).await?, node_name(path));
+        if self.loaded.lock().unwrap().contains(&node) {
+            return Ok(Claim::Loaded);
+        }
+        match self.create(&node, self.owner.as_bytes(), EPHEMERAL).await? {
+            0 => Ok(Claim::Mine),
+            ZNODEEXISTS => Ok(Claim::Taken),
+            err => bail!("认领 {:?} 失败: {}", path, error_name(err)),
+        }
+    }
+
+    /// 释放本次导入认领的文件：导入成功但源文件仍在原处的保持认领，其余删除认领
+    pub async fn release(&self, table: &str, claimed: &[PathBuf], records: &[FileRecord]) {
+        for path in claimed {
+            let node = format!("{}/{}/{}", self.root, table, node_name(path));
+            let loaded = records
+                .iter()
+                .any(|r| r.path == *path && r.status == FileStatus::Success);
+            let disposed = remote::is_remote(path) || !path.exists();
+            if loaded && !disposed {
+                self.loaded.lock().unwrap().insert(node);
+                continue;
+            }
+            match self.call(OP_DELETE, &delete_body(&node)).await {
+                Ok((0 | ZNONODE, _)) => {}
+                Ok((err, _)) => eprintln!("⚠️ 无法释放认领 {}: {}", node, error_name(err)),
+                Err(e) => eprintln!("⚠️ 无法释放认领 {}: {:#}", node, e),
+            }
+        }
+    }
+
+    /// 结束会话，本节点认领的临时节点立即删除
+    pub async fn close(&self) {
+        let _ = self.call(OP_CLOSE, &[]).await;
+        self.lost.store(true, Ordering::Relaxed);
+    }
+
+    async fn table_node(&self, table: &str) -> Result<String> {
+        let node = format!("{}/{}", self.root, table);
+        if !self.tables.lock().unwrap().contains(&node) {
+            // 根路径在建立会话时已创建
+            match self.create(&node, &[], PERSISTENT).await? {
+                0 | ZNODEEXISTS => {}
+                err => bail!("无法创建 Keeper 节点 {}: 错误码 {}", node, err),
+            }
+            self.tables.lock().unwrap().insert(node.clone());
+        }
+        Ok(node)
+    }
+
+    /// 逐级创建持久节点，已存在时忽略
+    async fn ensure_path(&self, path: &str) -> Result<()> {
+        let mut current = String::new();
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            current.push('/');
+            current.push_str(part);
+            match self.create(&current, &[], PERSISTENT).await? {
+                0 | ZNODEEXISTS => {}
+                err => bail!("无法创建 Keeper 节点 {}: 错误码 {}", current, err),
+            }
+        }
+        Ok(())
+    }
+
+    /// 返回 Keeper 的错误码，0 为成功
+    async fn create(&self, path: &str, data: &[u8], flags: i32) -> Result<i32> {
+        Ok(self
+            .call(OP_CREATE, &create_body(path, data, flags))
+            .await?
+            .0)
+    }
+
+    /// 发送请求并等待对应的响应，返回 (错误码, 响应体)；连接出错时标记会话失效
+    async fn call(&self, op: i32, body: &[u8]) -> Result<(i32, Vec<u8>)> {
+        if self.lost.load(Ordering::Relaxed) {
+            bail!("与 Keeper 的会话已失效，认领可能已被删除");
+        }
+        let mut conn = self.conn.lock().await;
+        let xid = if op == OP_PING {
+            XID_PING
+        } else {
+            conn.xid += 1;
+            conn.xid
+        };
+        let packet = request(xid, op, body);
+        let exchange = async {
+            conn.stream.write_all(&packet).await?;
+            loop {
+                let frame = read_frame(&mut conn.stream).await?;
+                // 服务端推送的 watch 通知跳过
+                if let Some(reply) = parse_reply(&frame, xid)? {
+                    return anyhow::Ok(reply);
+                }
+            }
+        };
+        match time::timeout(self.session_timeout, exchange).await {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(e)) => {
+                self.lost.store(true, Ordering::Relaxed);
+                Err(e.context("与 Keeper 的连接出错"))
+            }
+            Err(_) => {
+                self.lost.store(true, Ordering::Relaxed);
+                bail!("Keeper 超过 {:?} 未响应", self.session_timeout)
+            }
+        }
+    }
+}
+
+/// 建立会话，返回连接与会话 id
+async fn handshake(host: &str, timeout: Duration) -> Result<(TcpStream, i64)> {
+    let mut stream = time::timeout(CONNECT_TIMEOUT, TcpStream::connect(host))
+        .await
+        .context("连接超时")??;
+    stream.set_nodelay(true)?;
+    let mut body = Vec::with_capacity(44);
+    body.extend_from_slice(&0i32.to_be_bytes()); // protocolVersion
+    body.extend_from_slice(&0i64.to_be_bytes()); // lastZxidSeen
+    body.extend_from_slice(&(timeout.as_millis() as i32).to_be_bytes());
+    body.extend_from_slice(&0i64.to_be_bytes()); // sessionId
+    put_bytes(&mut body, &[0u8; 16]); // passwd
+    let mut packet = (body.len() as i32).to_be_bytes().to_vec();
+    packet.extend_from_slice(&body);
+    stream.write_all(&packet).await?;
+    let frame = time::timeout(CONNECT_TIMEOUT, read_frame(&mut stream))
+        .await
+        .context("握手超时")??;
+    if frame.len() < 16 {
+        bail!("握手响应过短");
+    }
+    let negotiated = i32::from_be_bytes(frame[4..8].try_into().unwrap());
+    let session_id = i64::from_be_bytes(frame[8..16].try_into().unwrap());
+    if negotiated <= 0 {
+        bail!("服务端拒绝建立会话");
+    }
+    Ok((stream, session_id))
+}
+
+fn heartbeat(keeper: Weak<Keeper>, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            time::sleep(interval).await;
+            let Some(keeper) = keeper.upgrade() else {
+                return;
+            };
+            if keeper.lost.load(Ordering::Relaxed) {
+                return;
+            }
+            if let Err(e) = keeper.call(OP_PING, &[]).await {
+                eprintln!(
+                    "❌ Keeper 心跳失败，已认领的文件可能被其他节点接手，不再认领新的文件: {:#}",
+                    e
+                );
+                return;
+            }
+        }
+    });
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let len = stream.read_i32().await? as usize;
+    if len > MAX_FRAME {
+        bail!("Keeper 响应长度异常: {}", len);
+    }
+    let mut frame = vec![0u8; len];
+    stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+/// 带长度前缀的请求：长度、xid、操作码、请求体
+fn request(xid: i32, op: i32, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(body.len() + 12);
+    packet.extend_from_slice(&((body.len() + 8) as i32).to_be_bytes());
+    packet.extend_from_slice(&xid.to_be_bytes());
+    packet.extend_from_slice(&op.to_be_bytes());
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// 解析一个响应帧 (不含长度前缀)，返回 (错误码, 响应体)；服务端推送的 watch 通知返回 None
+fn parse_reply(frame: &[u8], xid: i32) -> Result<Option<(i32, Vec<u8>)>> {
+    if frame.len() < 16 {
+        bail!("Keeper 响应过短");
+    }
+    let reply_xid = i32::from_be_bytes(frame[0..4].try_into().unwrap());
+    let err = i32::from_be_bytes(frame[12..16].try_into().unwrap());
+    match reply_xid {
+        XID_WATCH => Ok(None),
+        x if x == xid => Ok(Some((err, frame[16..].to_vec()))),
+        x => bail!("Keeper 响应的 xid {} 与请求 {} 不一致", x, xid),
+    }
+}
+
+fn create_body(path: &str, data: &[u8], flags: i32) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_str(&mut body, path);
+    put_bytes(&mut body, data);
+    // world:anyone 全部权限
+    body.extend_from_slice(&1i32.to_be_bytes());
+    body.extend_from_slice(&31i32.to_be_bytes());
+    put_str(&mut body, "world");
+    put_str(&mut body, "anyone");
+    body.extend_from_slice(&flags.to_be_bytes());
+    body
+}
+
+/// 不校验版本 (-1) 的删除请求
+fn delete_body(path: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_str(&mut body, path);
+    body.extend_from_slice(&(-1i32).to_be_bytes());
+    body
+}
+
+/// 常见错误码的名称，便于排查
+fn error_name(code: i32) -> String {
+    let name = match code {
+        -4 => "CONNECTIONLOSS",
+        -7 => "OPERATIONTIMEOUT",
+        -101 => "NONODE",
+        -102 => "NOAUTH",
+        -110 => "NODEEXISTS",
+        -111 => "NOTEMPTY",
+        -112 => "SESSIONEXPIRED",
+        _ => return format!("Keeper 错误码 {}", code),
+    };
+    format!("Keeper 错误码 {} ({})", code, name)
+}
+
+/// 节点名取文件名：各节点的挂载路径可能不同，文件名才是同一文件的标识
+fn node_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.display().to_string().replace('/', "_"),
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_bytes(out, s.as_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: 缓冲区长度与传入的一致
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return "unknown".to_string();
+    }
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// 服务端响应帧 (不含长度前缀)：xid、zxid、错误码、响应体
+    fn reply(xid: i32, err: i32, body: &[u8]) -> Vec<u8> {
+        let mut frame = xid.to_be_bytes().to_vec();
+        frame.extend_from_slice(&7i64.to_be_bytes());
+        frame.extend_from_slice(&err.to_be_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[test]
+    fn strings() {
+        let mut out = Vec::new();
+        put_str(&mut out, "/ck");
+        put_bytes(&mut out, &[]);
+        assert_eq!(out, [0, 0, 0, 3, b'/', b'c', b'k', 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn create_request() {
+        let packet = request(5, OP_CREATE, &create_body("/a", b"n1", EPHEMERAL));
+        #[rustfmt::skip]
+        let expected = [
+            0, 0, 0, 51, // 长度
+            0, 0, 0, 5, // xid
+            0, 0, 0, 1, // create
+            0, 0, 0, 2, b'/', b'a',
+            0, 0, 0, 2, b'n', b'1',
+            0, 0, 0, 1, // ACL 个数
+            0, 0, 0, 31,
+            0, 0, 0, 5, b'w', b'o', b'r', b'l', b'd',
+            0, 0, 0, 6, b'a', b'n', b'y', b'o', b'n', b'e',
+            0, 0, 0, 1, // ephemeral
+        ];
+        assert_eq!(packet, expected);
+    }
+
+    #[test]
+    fn delete_and_ping_requests() {
+        assert_eq!(
+            request(9, OP_DELETE, &delete_body("/a")),
+            [0, 0, 0, 18, 0, 0, 0, 9, 0, 0, 0, 2, 0, 0, 0, 2, b'/', b'a', 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            request(XID_PING, OP_PING, &[]),
+            [0, 0, 0, 8, 0xff, 0xff, 0xff, 0xfe, 0, 0, 0, 11]
+        );
+    }
+
+    #[test]
+    fn replies() {
+        assert_eq!(
+            parse_reply(&reply(3, 0, b"/a"), 3).unwrap(),
+            Some((0, b"/a".to_vec()))
+        );
+        assert_eq!(
+            parse_reply(&reply(3, ZNODEEXISTS, &[]), 3).unwrap(),
+            Some((ZNODEEXISTS, Vec::new()))
+        );
+        // watch 通知跳过，xid 不一致与过短的帧报错
+        assert_eq!(parse_reply(&reply(XID_WATCH, 0, &[1]), 3).unwrap(), None);
+        assert!(parse_reply(&reply(4, 0, &[]), 3).is_err());
+        assert!(parse_reply(&[0; 15], 3).is_err());
+    }
+
+    #[test]
+    fn error_names() {
+        assert_eq!(error_name(ZNODEEXISTS), "Keeper 错误码 -110 (NODEEXISTS)");
+        assert_eq!(error_name(-112), "Keeper 错误码 -112 (SESSIONEXPIRED)");
+        assert_eq!(error_name(-999), "Keeper 错误码 -999");
+    }
+
+    #[tokio::test]
+    async fn claims() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // 按节点路径返回预设的错误码
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut requests = Vec::new();
+            loop {
+                let Ok(frame) = read_frame(&mut stream).await else {
+                    return requests;
+                };
+                let xid = i32::from_be_bytes(frame[0..4].try_into().unwrap());
+                let op = i32::from_be_bytes(frame[4..8].try_into().unwrap());
+                let len = i32::from_be_bytes(frame[8..12].try_into().unwrap()) as usize;
+                let path = String::from_utf8(frame[12..12 + len].to_vec()).unwrap();
+                let err = match path.as_str() {
+                    "/ck/t/b.orc" if op == OP_CREATE => ZNODEEXISTS,
+                    "/ck/t/c.orc" => -112,
+                    _ => 0,
+                };
+                requests.push((op, path));
+                let frame = reply(xid, err, &[]);
+                stream
+                    .write_all(&(frame.len() as i32).to_be_bytes())
+                    .await
+                    .unwrap();
+                stream.write_all(&frame).await.unwrap();
+            }
+        });
+        let keeper = Keeper {
+            conn: Mutex::new(Conn {
+                stream: TcpStream::connect(addr).await.unwrap(),
+                xid: 0,
+            }),
+            root: "/ck".to_string(),
+            owner: "test".to_string(),
+            session_timeout: Duration::from_secs(5),
+            lost: AtomicBool::new(false),
+            tables: Default::default(),
+            loaded: Default::default(),
+        };
+        let dir = std::env::temp_dir().join(format!("ck-keeper-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.orc");
+        std::fs::write(&a, b"orc").unwrap();
+        assert_eq!(keeper.claim("t", &a).await.unwrap(), Claim::Mine);
+        assert_eq!(
+            keeper.claim("t", Path::new("b.orc")).await.unwrap(),
+            Claim::Taken
+        );
+        let err = keeper.claim("t", Path::new("c.orc")).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("SESSIONEXPIRED"));
+
+        // 导入成功且源文件仍在原处的保持认领，之后按已导入跳过；失败的删除认领
+        let record = |path: &Path, status| FileRecord {
+            file: node_name(path),
+            path: path.to_path_buf(),
+            table: "t".to_string(),
+            status,
+            bytes: 3,
+            elapsed_secs: 0.0,
+            finished_at: 0,
+            error: None,
+            error_code: None,
+            error_name: None,
+            tags: Default::default(),
+            mtime: None,
+            hash: None,
+            skipped_rows: None,
+            written_rows: None,
+            written_bytes: None,
+            server_elapsed_secs: None,
+            raw_bytes: None,
+            wire_bytes: None,
+            pack: None,
+            query_id: None,
+            overflow_policy: None,
+            overflow_values: None,
+            verified: None,
+            archived: None,
+        };
+        let b = PathBuf::from("b.orc");
+        keeper
+            .release(
+                "t",
+                &[a.clone(), b.clone()],
+                &[
+                    record(&a, FileStatus::Success),
+                    record(&b, FileStatus::Failed),
+                ],
+            )
+            .await;
+        assert_eq!(keeper.claim("t", &a).await.unwrap(), Claim::Loaded);
+        drop(keeper);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let requests = server.await.unwrap();
+        let ops: Vec<_> = requests.iter().map(|(op, p)| (*op, p.as_str())).collect();
+        assert_eq!(
+            ops,
+            [
+                (OP_CREATE, "/ck/t"),
+                (OP_CREATE, "/ck/t/a.orc"),
+                (OP_CREATE, "/ck/t/b.orc"),
+                (OP_CREATE, "/ck/t/c.orc"),
+                (OP_DELETE, "/ck/t/b.orc"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,422 @@
+//! 本地 SQLite 导入台账：每个文件每次导入一行，供 serve 模式查询与后续重跑使用
+
+use crate::report::{FileRecord, FileStatus};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+
+pub struct Ledger {
+    conn: Mutex<Connection>,
+}
+
+/// 台账中的一行
+#[derive(Debug, Clone, Serialize)]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub path: String,
+    pub file: String,
+    pub table: String,
+    pub status: String,
+    pub bytes: u64,
+    pub elapsed_secs: f64,
+    pub finished_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_name: Option<String>,
+    pub tags: serde_json::Value,
+    /// 合并导入时所属组的 token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pack: Option<String>,
+    /// 越界时间戳 / Decimal 的处理策略与本地处理的越界值个数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overflow_policy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overflow_values: Option<u64>,
+}
+
+/// 成功导入时记录的文件特征，delta 模式据此判断文件内容是否变化
+#[derive(Debug, Clone)]
+pub struct LoadedFingerprint {
+    pub bytes: u64,
+    pub mtime: Option<u64>,
+    pub hash: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct LedgerQuery {
+    pub status: Option<String>,
+    pub table: Option<String>,
+    pub since: Option<u64>,
+    pub limit: usize,
+}
+
+impl Ledger {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn =
+            Connection::open(path).with_context(|| format!("无法打开台账数据库: {:?}", path))?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS files (
+                 id           INTEGER PRIMARY KEY AUTOINCREMENT,
+                 path         TEXT    NOT NULL,
+                 file         TEXT    NOT NULL,
+                 table_name   TEXT    NOT NULL,
+                 status       TEXT    NOT NULL,
+                 bytes        INTEGER NOT NULL,
+                 elapsed_secs REAL    NOT NULL,
+                 finished_at  INTEGER NOT NULL,
+                 error        TEXT,
+                 tags         TEXT    NOT NULL DEFAULT '{}'
+             );
+             CREATE INDEX IF NOT EXISTS files_status_time ON files (status, finished_at);
+             CREATE INDEX IF NOT EXISTS files_path ON files (path);",
+        )
+        .context("初始化台账表失败")?;
+        // 旧版本创建的台账没有 mtime / hash 列，按需补齐
+        let has_hash = conn
+            .prepare("SELECT 1 FROM pragma_table_info('files') WHERE name = 'hash'")?
+            .exists([])?;
+        if !has_hash {
+            conn.execute_batch(
+                "ALTER TABLE files ADD COLUMN mtime INTEGER;
+                 ALTER TABLE files ADD COLUMN hash TEXT;",
+            )
+            .context("升级台账表失败")?;
+        }
+        let has_error_code = conn
+            .prepare("SELECT 1 FROM pragma_table_info('files') WHERE name = 'error_code'")?
+            .exists([])?;
+        if !has_error_code {
+            conn.execute_batch(
+                "ALTER TABLE files ADD COLUMN error_code INTEGER;
+                 ALTER TABLE files ADD COLUMN error_name TEXT;",
+            )
+            .context("升级台账表失败")?;
+        }
+        let has_pack = conn
+            .prepare("SELECT 1 FROM pragma_table_info('files') WHERE name = 'pack'")?
+            .exists([])?;
+        if !has_pack {
+            conn.execute_batch(
+                "ALTER TABLE files ADD COLUMN pack TEXT;
+                 CREATE INDEX IF NOT EXISTS files_pack ON files (pack);",
+            )
+            .context("升级台账表失败")?;
+        }
+        let has_overflow = conn
+            .prepare("SELECT 1 FROM pragma_table_info('files') WHERE name = 'overflow_policy'")?
+            .exists([])?;
+        if !has_overflow {
+            conn.execute_batch(
+                "ALTER TABLE files ADD COLUMN overflow_policy TEXT;
+                 ALTER TABLE files ADD COLUMN overflow_values INTEGER;",
+            )
+            .context("升级台账表失败")?;
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn record(&self, r: &FileRecord) -> Result<()> {
+        let status = match r.status {
+            FileStatus::Success => "success",
+            FileStatus::Failed => "failed",
+        };
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO files (path, file, table_name, status, bytes, elapsed_secs, finished_at, error, tags, mtime, hash, error_code, error_name, pack, overflow_policy, overflow_values)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params![
+                r.path.to_string_lossy(),
+                r.file,
+                r.table,
+                status,
+                r.bytes as i64,
+                r.elapsed_secs,
+                r.finished_at as i64,
+                r.error,
+                serde_json::to_string(&r.tags)?,
+                r.mtime.map(|t| t as i64),
+                r.hash,
+                r.error_code,
+                r.error_name,
+                r.pack,
+                r.overflow_policy,
+                r.overflow_values.map(|n| n as i64),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 按状态 / 表 / 时间过滤，最新的记录在前
+    pub fn query(&self, q: &LedgerQuery) -> Result<Vec<LedgerEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, path, file, table_name, status, bytes, elapsed_secs, finished_at, error, tags,
+                    error_code, error_name, pack, overflow_policy, overflow_values
+             FROM files
+             WHERE (?1 IS NULL OR status = ?1)
+               AND (?2 IS NULL OR table_name = ?2)
+               AND finished_at >= ?3
+             ORDER BY id DESC
+             LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                q.status,
+                q.table,
+                q.since.unwrap_or(0) as i64,
+                q.limit as i64
+            ],
+            |row| {
+                let tags: String = row.get(9)?;
+                Ok(LedgerEntry {
+                    id: row.get(0)?,
+                    path: row.get(1)?,
+                    file: row.get(2)?,
+                    table: row.get(3)?,
+                    status: row.get(4)?,
+                    bytes: row.get::<_, i64>(5)? as u64,
+                    elapsed_secs: row.get(6)?,
+                    finished_at: row.get::<_, i64>(7)? as u64,
+                    error: row.get(8)?,
+                    error_code: row.get(10)?,
+                    error_name: row.get(11)?,
+                    tags: serde_json::from_str(&tags).unwrap_or_default(),
+                    pack: row.get(12)?,
+                    overflow_policy: row.get(13)?,
+                    overflow_values: row.get::<_, Option<i64>>(14)?.map(|n| n as u64),
+                })
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// 最近一次结果为失败的文件 (path, table, 错误名称)，同一路径只看最新一条记录
+    pub fn latest_failed(
+        &self,
+        table: Option<&str>,
+    ) -> Result<Vec<(String, String, Option<String>)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT f.path, f.table_name, f.error_name
+             FROM files f
+             WHERE f.id = (SELECT max(id) FROM files WHERE path = f.path)
+               AND f.status = 'failed'
+               AND (?1 IS NULL OR f.table_name = ?1)
+             ORDER BY f.id",
+        )?;
+        let rows = stmt.query_map(params![table], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// 该路径最近一次成功导入到指定表时的 (大小, mtime, hash)
+    pub fn last_success(&self, path: &str, table: &str) -> Result<Option<LoadedFingerprint>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT bytes, mtime, hash FROM files
+             WHERE path = ?1 AND table_name = ?2 AND status = 'success'
+             ORDER BY id DESC
+             LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map(params![path, table], |row| {
+            Ok(LoadedFingerprint {
+                bytes: row.get::<_, i64>(0)? as u64,
+                mtime: row.get::<_, Option<i64>>(1)?.map(|t| t as u64),
+                hash: row.get(2)?,
+            })
+        })?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// 已成功导入到指定表的文件摘要
+    pub fn loaded_hashes(&self, table: &str) -> Result<HashSet<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT hash FROM files
+             WHERE table_name = ?1 AND status = 'success' AND hash IS NOT NULL",
+        )?;
+        let rows = stmt.query_map(params![table], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// 已成功导入到指定表的文件摘要，及以该摘要导入过的文件名
+    pub fn loaded_names(&self, table: &str) -> Result<HashMap<String, Vec<String>>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT hash, file FROM files
+             WHERE table_name = ?1 AND status = 'success' AND hash IS NOT NULL",
+        )?;
+        let rows = stmt.query_map(params![table], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut names: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            let (hash, file) = row?;
+            names.entry(hash).or_default().push(file);
+        }
+        Ok(names)
+    }
+
+    /// 按 (表, 状态) 汇总文件数与字节数
+    pub fn summary(&self, since: u64) -> Result<Vec<(String, String, u64, u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT table_name, status, count(*), sum(bytes)
+             FROM files
+             WHERE finished_at >= ?1
+             GROUP BY table_name, status
+             ORDER BY table_name, status",
+        )?;
+        let rows = stmt.query_map(params![since as i64], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get::<_, i64>(2)? as u64,
+                row.get::<_, i64>(3)? as u64,
+            ))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn record(path: &str, table: &str, status: FileStatus, hash: Option<&str>) -> FileRecord {
+        let path = PathBuf::from(path);
+        FileRecord {
+            file: path.file_name().unwrap().to_string_lossy().into_owned(),
+            path,
+            table: table.to_string(),
+            status,
+            bytes: 3,
+            elapsed_secs: 0.5,
+            finished_at: 1_700_000_000,
+            error: None,
+            error_code: None,
+            error_name: None,
+            tags: Default::default(),
+            mtime: Some(1_690_000_000),
+            hash: hash.map(str::to_string),
+            skipped_rows: None,
+            written_rows: None,
+            written_bytes: None,
+            server_elapsed_secs: None,
+            raw_bytes: None,
+            wire_bytes: None,
+            pack: None,
+            query_id: None,
+            overflow_policy: None,
+            overflow_values: None,
+            verified: None,
+            archived: None,
+        }
+    }
+
+    fn ledger(name: &str) -> Ledger {
+        let path =
+            std::env::temp_dir().join(format!("ck-ledger-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        Ledger::open(&path).unwrap()
+    }
+
+    #[test]
+    fn tracks_loaded_files() {
+        let ledger = ledger("loaded");
+        ledger
+            .record(&record("/in/a.orc", "t", FileStatus::Success, Some("h1")))
+            .unwrap();
+        ledger
+            .record(&record("/in/b.orc", "t", FileStatus::Failed, Some("h2")))
+            .unwrap();
+        ledger
+            .record(&record("/in/c.orc", "t", FileStatus::Success, Some("h1")))
+            .unwrap();
+        ledger
+            .record(&record(
+                "/in/d.orc",
+                "other",
+                FileStatus::Success,
+                Some("h3"),
+            ))
+            .unwrap();
+
+        // 只有成功导入到该表的摘要算作已导入
+        let hashes = ledger.loaded_hashes("t").unwrap();
+        assert_eq!(hashes, HashSet::from(["h1".to_string()]));
+        let mut names = ledger.loaded_names("t").unwrap();
+        names.get_mut("h1").unwrap().sort();
+        assert_eq!(
+            names,
+            HashMap::from([(
+                "h1".to_string(),
+                vec!["a.orc".to_string(), "c.orc".to_string()]
+            )])
+        );
+
+        let prev = ledger.last_success("/in/a.orc", "t").unwrap().unwrap();
+        assert_eq!(
+            (prev.bytes, prev.mtime, prev.hash.as_deref()),
+            (3, Some(1_690_000_000), Some("h1"))
+        );
+        assert!(ledger.last_success("/in/b.orc", "t").unwrap().is_none());
+        assert!(ledger.last_success("/in/a.orc", "other").unwrap().is_none());
+    }
+
+    #[test]
+    fn latest_failed_uses_newest_record() {
+        let ledger = ledger("failed");
+        let mut failed = record("/in/a.orc", "t", FileStatus::Failed, None);
+        failed.error_name = Some("CANNOT_PARSE_INPUT".to_string());
+        ledger.record(&failed).unwrap();
+        ledger
+            .record(&record("/in/b.orc", "t", FileStatus::Failed, None))
+            .unwrap();
+        // b 重跑后成功，不再算作失败
+        ledger
+            .record(&record("/in/b.orc", "t", FileStatus::Success, None))
+            .unwrap();
+        ledger
+            .record(&record("/in/c.orc", "u", FileStatus::Failed, None))
+            .unwrap();
+
+        assert_eq!(
+            ledger.latest_failed(Some("t")).unwrap(),
+            [(
+                "/in/a.orc".to_string(),
+                "t".to_string(),
+                Some("CANNOT_PARSE_INPUT".to_string())
+            )]
+        );
+        assert_eq!(ledger.latest_failed(None).unwrap().len(), 2);
+
+        let query = |status: &str| LedgerQuery {
+            status: Some(status.to_string()),
+            limit: 10,
+            ..Default::default()
+        };
+        let rows = ledger.query(&query("success")).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].path, "/in/b.orc");
+        assert_eq!(ledger.query(&query("failed")).unwrap().len(), 3);
+        assert_eq!(
+            ledger.summary(0).unwrap(),
+            [
+                ("t".to_string(), "failed".to_string(), 2, 6),
+                ("t".to_string(), "success".to_string(), 1, 3),
+                ("u".to_string(), "failed".to_string(), 1, 3),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1893 @@
+//! 批次导入流程：发现文件 → 预检查 → 按工作池并发导入 → 移动到 done
+
+use crate::archive::{self, OnSuccess};
+use crate::atomic;
+use crate::audit::AuditTable;
+use crate::budget::Budget;
+use crate::cli::{self, Args, Transport};
+use crate::dashboard::{Board, Shown};
+use crate::errlog::ErrorLog;
+use crate::error::{ClickHouseError, ErrorClass};
+use crate::events::{Event, EventStream};
+use crate::hooks;
+use crate::http::InsertSummary;
+use crate::intent::IntentLog;
+use crate::keeper::{Claim, Keeper};
+use crate::ledger::Ledger;
+use crate::metrics::Metrics;
+use crate::native::Connections;
+use crate::pause::Pause;
+use crate::processed::{self, ProcessedLog};
+use crate::report::{FileRecord, FileStatus, SkipReason, Tags};
+use crate::schema::SchemaCheck;
+use crate::shutdown::Shutdown;
+use crate::stall::{self, Attempt};
+use crate::throttle::ReadLimit;
+use crate::trace::{self, Span, Tracer};
+use crate::wire::Upload;
+use crate::{
+    clickhouse, client, delta, disk, freshness, header, http, lock, memory, native, orc, overlap,
+    pack, precompressed, progress, remote, replicas, report, sample, schema, target,
+};
+use anyhow::{bail, Context, Result};
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncRead;
+use tokio::sync::Semaphore;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+
+/// stdin 流在日志、台账与报告中显示的名称
+const STDIN_NAME: &str = "<stdin>";
+
+/// --max-inflight-bytes 额度的计量单位，许可数为 MB 数
+const INFLIGHT_UNIT: u64 = 1 << 20;
+
+/// 一次导入任务：目录 + 目标表
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub dir: PathBuf,
+    pub table: String,
+    /// 显式指定的文件列表 (均位于 dir 下)，为 None 时扫描 dir
+    pub files: Option<Vec<PathBuf>>,
+}
+
+/// 进程级共享资源：工作池许可、HTTP 连接池、导入指标与暂停开关，serve 模式下跨任务复用
+#[derive(Clone)]
+pub struct Pool {
+    pub semaphore: Arc<Semaphore>,
+    /// 当前批次的 id，写入各文件的 query_id
+    pub batch_id: Arc<str>,
+    /// --max-inflight-bytes 的在途字节额度，以 MB 为单位的许可
+    pub inflight: Option<Arc<Semaphore>>,
+    pub http: reqwest::Client,
+    /// --session-under-mb 下小文件改走 HTTP 时使用的连接池
+    pub session: Option<reqwest::Client>,
+    pub ledger: Option<Arc<Ledger>>,
+    pub processed: Option<Arc<ProcessedLog>>,
+    pub audit: Option<Arc<AuditTable>>,
+    pub metrics: Arc<Metrics>,
+    pub pause: Arc<Pause>,
+    /// 控制台错误输出限流
+    pub errors: Arc<ErrorLog>,
+    /// --tui 面板上的进行中文件
+    pub board: Option<Arc<Board>>,
+    /// --fail-fast / --max-failures 的失败额度，各表共用
+    pub budget: Arc<Budget>,
+    /// 批次的追踪 span，各文件的 span 挂在其下
+    pub span: Arc<Span>,
+    /// --keeper 的会话，各节点经它认领文件
+    pub keeper: Option<Arc<Keeper>>,
+    /// --reuse-connections 的空闲连接
+    pub connections: Connections,
+    /// --max-read-mbps 的读取限速器
+    pub read_limit: ReadLimit,
+    /// --events 的输出端
+    pub events: EventStream,
+    /// 按 OTEL_* 环境变量配置的 span 上报端
+    pub tracer: Tracer,
+}
+
+impl Pool {
+    /// `events` 由调用方在任何输出之前开启 (见 `EventStream::start`)
+    pub async fn new(cfg: &Args, events: EventStream) -> Result<Self> {
+        // 启动时先解析一次凭据，外部密钥服务不可用时尽早失败
+        cfg.password
+            .get()
+            .await
+            .context("无法获取 ClickHouse 密码")?;
+
+        // 开启慢启动时先只放行 1 个许可，再由后台任务在窗口内均匀补齐到 workers
+        let semaphore = match cfg.ramp_up {
+            Some(ramp) if cfg.workers > 1 && !ramp.is_zero() => {
+                let sem = Arc::new(Semaphore::new(1));
+                let step = ramp / (cfg.workers as u32 - 1);
+                let ramp_sem = Arc::clone(&sem);
+                let workers = cfg.workers;
+                println!("🐢 慢启动: {:?} 内并行数从 1 增长到 {}", ramp, workers);
+                tokio::spawn(async move {
+                    for current in 2..=workers {
+                        time::sleep(step).await;
+                        ramp_sem.add_permits(1);
+                        println!("📈 慢启动: 当前并行数 {}/{}", current, workers);
+                    }
+                });
+                sem
+            }
+            _ => Arc::new(Semaphore::new(cfg.workers)),
+        };
+        let inflight = cfg.max_inflight_bytes.map(|limit| {
+            println!(
+                "📦 在途数据上限: {:.1} MB",
+                limit as f64 / INFLIGHT_UNIT as f64
+            );
+            Arc::new(Semaphore::new(inflight_permits(limit, u32::MAX) as usize))
+        });
+        let ledger = match &cfg.ledger {
+            Some(path) => Some(Arc::new(Ledger::open(path)?)),
+            None => None,
+        };
+        let processed = match &cfg.processed_log {
+            Some(dir) => Some(Arc::new(ProcessedLog::open(dir)?)),
+            None => None,
+        };
+        let audit = match &cfg.audit_table {
+            Some(table) => Some(Arc::new(AuditTable::open(cfg, table).await?)),
+            None => None,
+        };
+        let pause = Arc::new(Pause::default());
+        pause.listen_signals();
+        if let Some(limit) = cfg.max_memory {
+            memory::guard(limit, &pause);
+        }
+        let batch_id: Arc<str> = report::new_batch_id().into();
+        let keeper = match &cfg.keeper {
+            Some(hosts) => Some(Keeper::connect(cfg, hosts, &batch_id).await?),
+            None => None,
+        };
+        let tracer = Tracer::from_env();
+        Ok(Self {
+            keeper,
+            semaphore,
+            span: Arc::new(tracer.batch(&batch_id)),
+            batch_id,
+            inflight,
+            http: http::build_client()?,
+            session: match cfg.session_under_mb {
+                Some(_) => Some(http::build_session_client()?),
+                None => None,
+            },
+            ledger,
+            processed,
+            audit,
+            metrics: Arc::new(Metrics::default()),
+            pause,
+            errors: Arc::new(ErrorLog::new(cfg.error_log_burst, cfg.error_log_interval)),
+            board: None,
+            budget: Arc::new(Budget::new(cfg.failure_limit())),
+            connections: Connections::default(),
+            read_limit: ReadLimit::new(cfg.max_read_mbps),
+            events,
+            tracer,
+        })
+    }
+}
+
+impl Pool {
+    /// 共享同一组资源的新批次 (watch 的每一轮、serve 的每个任务)
+    pub fn next_batch(&self) -> Self {
+        let batch_id: Arc<str> = report::new_batch_id().into();
+        Self {
+            span: Arc::new(self.tracer.batch(&batch_id)),
+            batch_id,
+            budget: Arc::new(self.budget.renew()),
+            ..self.clone()
+        }
+    }
+}
+
+/// `bytes` 需要的额度许可数，不超过总额度 `cap`：超大的单个文件独占全部额度，而不是永远等不到
+fn inflight_permits(bytes: u64, cap: u32) -> u32 {
+    bytes.div_ceil(INFLIGHT_UNIT).clamp(1, cap as u64) as u32
+}
+
+/// 每个文件结束后的回调，serve 模式用它实时更新任务进度
+pub type FileHook = Arc<dyn Fn(&FileRecord) + Send + Sync>;
+
+/// 列出目录下的全部文件 (不递归)，`s3://` / `webhdfs://` 等远端路径交给对应的后端列出
+pub fn discover(dir: &PathBuf) -> Result<Vec<PathBuf>> {
+    if remote::is_remote(dir) {
+        return remote::list(dir);
+    }
+    let mut files = Vec::new();
+    let entries = std::fs::read_dir(dir).with_context(|| format!("无法读取目录: {:?}", dir))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && !lock::is_lock_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+pub async fn run(
+    cfg: Arc<Args>,
+    job: Job,
+    pool: Pool,
+    shutdown: Shutdown,
+    on_file: Option<FileHook>,
+) -> Result<Vec<FileRecord>> {
+    let keeper = pool.keeper.clone();
+    let table = job.table.clone();
+    let claimed: Claimed = Arc::default();
+    let result = run_job(cfg, job, pool, shutdown, on_file, Arc::clone(&claimed)).await;
+    if let Some(keeper) = keeper {
+        let claimed = std::mem::take(&mut *claimed.lock().unwrap());
+        let records = result.as_deref().unwrap_or_default();
+        keeper.release(&table, &claimed, records).await;
+    }
+    result
+}
+
+/// 本次导入经 --keeper 认领到的文件，导入结束后统一释放
+type Claimed = Arc<Mutex<Vec<PathBuf>>>;
+
+async fn run_job(
+    cfg: Arc<Args>,
+    job: Job,
+    pool: Pool,
+    shutdown: Shutdown,
+    on_file: Option<FileHook>,
+    claimed: Claimed,
+) -> Result<Vec<FileRecord>> {
+    if shutdown.is_stopping() {
+        return Ok(Vec::new());
+    }
+    if cfg.split_stripes.is_some() && cfg.transport != Transport::Http {
+        bail!("--split-stripes 仅支持 --transport http");
+    }
+    if cfg.per_file_bandwidth.is_some() && cfg.transport != Transport::Http {
+        bail!("--per-file-bandwidth 仅支持 --transport http");
+    }
+    if cfg.max_read_mbps.is_some() && cfg.transport_chain().contains(&Transport::Client) {
+        bail!("--max-read-mbps 仅支持 --transport http / native，也不能回退到 client (clickhouse-client 直接读取文件句柄)");
+    }
+    if cfg.skip_loaded && cfg.audit_table.is_none() && cfg.ledger.is_none() {
+        bail!("--skip-loaded 需要 --audit-table 或 --ledger");
+    }
+    let http_only = cfg.transport_chain().iter().all(|t| *t == Transport::Http);
+    if cfg.server_progress && !cfg.http_url().starts_with("http://") {
+        bail!("--server-progress 仅支持 http:// 地址 (需要逐行读取响应头，不经过 TLS)");
+    }
+    if cfg.session_under_mb.is_some() && cfg.transport != Transport::Client {
+        bail!("--session-under-mb 仅作用于 --transport client (其他传输方式不为每个文件启动进程)");
+    }
+    if cfg.reuse_connections && !cfg.transport_chain().contains(&Transport::Native) {
+        bail!("--reuse-connections 仅作用于 --transport native (或回退链中的 native)");
+    }
+    if cfg.endpoint().is_some_and(|e| e.secure)
+        && cfg.transport_chain().contains(&Transport::Native)
+    {
+        bail!("--transport native 不支持 TLS，连接串带 secure=true 时请改用 http 或 client");
+    }
+    if cfg.wait_for_async_insert == Some(false) && cfg.success_policy() == OnSuccess::Delete {
+        bail!(
+            "--wait-for-async-insert off 时成功不代表数据已落盘，不能与 --on-success delete 同用"
+        );
+    }
+    if cfg.sync_replicas.is_some() && cfg.wait_for_async_insert == Some(false) {
+        bail!(
+            "--sync-replicas 不能与 --wait-for-async-insert off 同用 (数据尚未落盘，无从等待副本)"
+        );
+    }
+    if cfg.atomic && cfg.wait_for_async_insert == Some(false) {
+        bail!("--atomic 需要在并入前核对暂存表行数，不能与 --wait-for-async-insert off 同用");
+    }
+    if cfg.pack_under_mb.is_some() && !http_only {
+        bail!("--pack-under-mb 仅支持 --transport http，也不能回退到其他传输方式");
+    }
+    if cfg.batch_small_files.is_some() && !http_only {
+        bail!("--batch-small-files 仅支持 --transport http，也不能回退到其他传输方式");
+    }
+    if cfg.overflow_needs_native()
+        && cfg
+            .transport_chain()
+            .iter()
+            .any(|t| *t != Transport::Native)
+    {
+        bail!("--timestamp-overflow null / --decimal-overflow clamp|null 仅支持 --transport native，也不能回退到其他传输方式");
+    }
+    let remote = remote::is_remote(&job.dir);
+    if remote {
+        if !http_only {
+            bail!("远端输入源仅支持 --transport http，也不能回退到其他传输方式");
+        }
+        if cfg.split_stripes.is_some()
+            || cfg.align_stripes
+            || cfg.delta
+            || cfg.skip_loaded
+            || cfg.pack_under_mb.is_some()
+            || cfg.batch_small_files.is_some()
+        {
+            bail!("远端输入源不支持 --split-stripes / --align-stripes / --delta / --skip-loaded / --pack-under-mb / --batch-small-files");
+        }
+        if cfg.on_success == OnSuccess::Compress {
+            bail!("远端输入源不支持 --on-success compress");
+        }
+        if cfg.archive_to.is_some() {
+            bail!("远端输入源不支持 --archive-to");
+        }
+        if cfg.done_dir.is_some() {
+            bail!("远端输入源不支持 --done-dir");
+        }
+        if cfg.filters_files() {
+            bail!("远端输入源不支持 --newer-than / --older-than / --min-size / --max-size");
+        }
+    }
+
+    // 先重放预写日志：上次崩溃时已导入但未处置的文件在这里被移走，不会被再次发现
+    let done_dir = cfg.done_dir(&job.dir);
+    let failed_dir = cfg.failed_dir(&job.dir);
+    let intents = if remote {
+        None
+    } else {
+        let log = IntentLog::open(&done_dir)?;
+        archive::prepare_dirs(
+            &job.dir,
+            &done_dir,
+            &failed_dir,
+            cfg.done_layout.as_deref(),
+            cfg.success_policy(),
+        )?;
+        Some(Arc::new(log))
+    };
+
+    let discovery = pool.span.child("discovery");
+    discovery.set("ck_loader.table", job.table.clone());
+    discovery.set("ck_loader.dir", job.dir.display().to_string());
+
+    // 1. 获取所有 ORC 文件列表
+    let mut files = match job.files {
+        Some(files) => files,
+        None => discover(&job.dir)?,
+    };
+    if files.is_empty() {
+        println!("📭 未找到 .orc 文件: {:?}", job.dir);
+        return Ok(Vec::new());
+    }
+    if let Some(shard) = cfg.shard {
+        let total = files.len();
+        files.retain(|p| shard.contains(p));
+        println!(
+            "🧩 分片 {}: 本实例负责 {} / {} 个文件",
+            shard,
+            files.len(),
+            total
+        );
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+    }
+    if cfg.filters_files() {
+        let filter_cfg = Arc::clone(&cfg);
+        let (load, filtered) =
+            tokio::task::spawn_blocking(move || filter_files(&filter_cfg, files)).await?;
+        if !filtered.is_empty() {
+            println!(
+                "🕒 按 mtime / 大小筛选: {} 个文件跳过，{} 个待导入",
+                filtered.len(),
+                load.len()
+            );
+        }
+        for path in &filtered {
+            pool.metrics.exclude(path, SkipReason::Filtered);
+        }
+        if load.is_empty() {
+            return Ok(Vec::new());
+        }
+        files = load;
+    }
+    if !remote {
+        let (load, empty) = tokio::task::spawn_blocking(move || skip_empty(files)).await?;
+        for (path, reason) in &empty {
+            pool.metrics.exclude(path, *reason);
+        }
+        if load.is_empty() {
+            return Ok(Vec::new());
+        }
+        files = load;
+    }
+
+    let mut hashes = HashMap::new();
+    let mut drop_partition = None;
+    if cfg.delta {
+        let Some(ledger) = pool.ledger.clone() else {
+            bail!("--delta 需要 --ledger");
+        };
+        let (table, dir, partition) = (
+            job.table.clone(),
+            job.dir.clone(),
+            cfg.delta_partition.clone(),
+        );
+        let plan = tokio::task::spawn_blocking(move || {
+            delta::plan(&ledger, &table, &dir, files, partition.as_deref())
+        })
+        .await??;
+        println!(
+            "🔍 delta: {} 个文件未变化，{} 个待导入",
+            plan.unchanged.len(),
+            plan.load.len()
+        );
+        for path in &plan.unchanged {
+            pool.metrics.exclude(path, SkipReason::Unchanged);
+        }
+        if plan.load.is_empty() {
+            return Ok(Vec::new());
+        }
+        files = plan.load;
+        hashes = plan.hashes;
+        drop_partition = plan.drop_partition;
+    }
+    if cfg.skip_loaded {
+        let (load, loaded) = skip_loaded(&cfg, &pool, &job.table, files, &mut hashes).await?;
+        if !loaded.is_empty() {
+            println!(
+                "🔁 已导入过: {} 个文件跳过，{} 个待导入",
+                loaded.len(),
+                load.len()
+            );
+        }
+        for path in &loaded {
+            pool.metrics.exclude(path, SkipReason::AlreadyLoaded);
+        }
+        if load.is_empty() {
+            return Ok(Vec::new());
+        }
+        files = load;
+    }
+    if cfg.reject_duplicates {
+        let (load, duplicates) = reject_duplicates(&pool, &job.table, files, &mut hashes).await?;
+        for path in &duplicates {
+            pool.metrics.exclude(path, SkipReason::Duplicate);
+        }
+        if load.is_empty() {
+            return Ok(Vec::new());
+        }
+        files = load;
+    }
+    if cfg.check_footer {
+        let quarantine_dir = job.dir.join("quarantine");
+        let (intact, corrupt) =
+            tokio::task::spawn_blocking(move || check_footers(files, &quarantine_dir)).await?;
+        for path in &corrupt {
+            pool.metrics.exclude(path, SkipReason::Quarantined);
+        }
+        if intact.is_empty() {
+            return Ok(Vec::new());
+        }
+        files = intact;
+    }
+    let total_files = files.len();
+    discovery.set("ck_loader.files", total_files);
+    discovery.end();
+    for path in &files {
+        pool.events
+            .emit(&pool.batch_id, &job.table, Event::Discovered { path });
+    }
+
+    println!(
+        "📂 找到 {} 个文件，准备执行 (并行数: {}, 解析线程: {})...",
+        total_files, cfg.workers, cfg.threads
+    );
+
+    if let Some(engine) = &cfg.create_table {
+        schema::create_table(&cfg, &job.table, &files[0], engine).await?;
+    }
+    target::check(&cfg, &job.table).await?;
+    if !remote {
+        if let Err(e) = disk::check(&cfg, &job.table, &files).await {
+            if cfg.require_free_space.is_some() {
+                return Err(e);
+            }
+            eprintln!("⚠️ 无法检查服务端磁盘空间: {:#}", e);
+        }
+    }
+
+    schema::check(
+        cfg.schema_check,
+        &cfg,
+        &job.table,
+        &files,
+        cfg.schema_check_files,
+    )
+    .await?;
+
+    if let Some(key) = &cfg.dedup_key {
+        overlap::check(&files, key);
+    }
+
+    if let Some(partition) = &drop_partition {
+        println!("🗑️ delta: 删除分区 {} 后重导整个目录", partition);
+        clickhouse::query(
+            &cfg,
+            &format!("ALTER TABLE {} DROP PARTITION {}", job.table, partition),
+        )
+        .await?;
+    }
+
+    let baseline = if cfg.freshness_check {
+        Some(freshness::baseline(&cfg, &job.table, &files, cfg.freshness_column.as_deref()).await?)
+    } else {
+        None
+    };
+
+    // --atomic：各文件写入暂存表，源文件的处置推迟到整批并入目标表之后
+    let staging = if cfg.atomic {
+        Some(atomic::create(&cfg, &job.table, &pool.batch_id).await?)
+    } else {
+        None
+    };
+    let insert_table = Arc::new(
+        staging
+            .as_ref()
+            .map_or(job.table.clone(), |s| s.table.clone()),
+    );
+    let deferred: Arc<Mutex<Vec<(PathBuf, Snapshot)>>> = Arc::default();
+
+    // 2. 构造共享资源
+    let tags: Arc<Tags> = Arc::new(cfg.tags.iter().cloned().collect());
+    let table = Arc::new(job.table);
+    let hashes = Arc::new(hashes);
+
+    // 同一表的文件遇到致命错误后不再启动其余文件 (--keep-going 除外)
+    let halt = CancellationToken::new();
+
+    // 每个导入单元一个任务：单个文件，或 --pack-under-mb 合并的一组小文件；limit 为额外的导入时限 (canary)
+    let spawn_unit = |members: Vec<PathBuf>, limit: Option<Duration>| {
+        let sem = Arc::clone(&pool.semaphore);
+        let batch_id = Arc::clone(&pool.batch_id);
+        let inflight = pool.inflight.clone();
+        let cfg = Arc::clone(&cfg);
+        let d_dir = done_dir.clone();
+        let f_dir = failed_dir.clone();
+        let tags = Arc::clone(&tags);
+        let table = Arc::clone(&table);
+        let insert_table = Arc::clone(&insert_table);
+        let deferred = Arc::clone(&deferred);
+        let pool = pool.clone();
+        let events = pool.events.clone();
+        let shutdown = shutdown.clone();
+        let halt = halt.clone();
+        let on_file = on_file.clone();
+        let ledger = pool.ledger.clone();
+        let processed = pool.processed.clone();
+        let audit = pool.audit.clone();
+        let hashes = Arc::clone(&hashes);
+        let intents = intents.clone();
+        let metrics = Arc::clone(&pool.metrics);
+        let pause = Arc::clone(&pool.pause);
+        let errors = Arc::clone(&pool.errors);
+        let board = pool.board.clone();
+        let budget = Arc::clone(&pool.budget);
+        let batch_span = Arc::clone(&pool.span);
+        let keeper = pool.keeper.clone();
+        let claimed = Arc::clone(&claimed);
+
+        tokio::spawn(async move {
+            let unit_name = unit_name(&members);
+            let span = batch_span.child("file");
+            span.set("ck_loader.table", table.to_string());
+            span.set("ck_loader.file", unit_name.clone());
+            span.set("ck_loader.files", members.len());
+            let waiting = span.child("wait_for_permit");
+
+            // --- 核心点：只有拿到许可后才开始操作 IO ---
+            // 进入停止阶段后，尚未开始的文件不再启动
+            let _permit = tokio::select! {
+                biased;
+                reason = stop_reason(&shutdown, &halt, &budget) => {
+                    for path in &members {
+                        metrics.skip(path, reason);
+                    }
+                    return Vec::new();
+                }
+                permit = sem.acquire() => permit.expect("信号量异常"),
+            };
+            // 暂停期间持有许可等待，恢复后再启动
+            if pause.is_paused() {
+                tokio::select! {
+                    biased;
+                    reason = stop_reason(&shutdown, &halt, &budget) => {
+                        for path in &members {
+                            metrics.skip(path, reason);
+                        }
+                        return Vec::new();
+                    }
+                    _ = pause.wait_resumed() => {}
+                }
+            }
+
+            // --keeper：拿到许可后才逐个认领，空闲的节点才会接手文件，各节点按处理能力分摊
+            let members = match &keeper {
+                Some(keeper) => {
+                    let mut mine = Vec::with_capacity(members.len());
+                    for path in members {
+                        match keeper.claim(&table, &path).await {
+                            Ok(Claim::Mine) => {
+                                claimed.lock().unwrap().push(path.clone());
+                                mine.push(path);
+                            }
+                            Ok(Claim::Taken) => metrics.skip(&path, SkipReason::Claimed),
+                            Ok(Claim::Loaded) => metrics.skip(&path, SkipReason::AlreadyLoaded),
+                            Err(e) => {
+                                eprintln!("⚠️ 无法认领 {:?}，跳过: {:#}", path, e);
+                                metrics.skip(&path, SkipReason::Interrupted);
+                            }
+                        }
+                    }
+                    mine
+                }
+                None => members,
+            };
+
+            // 远端文件单独查询大小，同时确认文件仍然存在；已消失的文件跳过
+            let mut sized = Vec::with_capacity(members.len());
+            for file_path in members {
+                let size = if remote {
+                    remote::size(&file_path).ok()
+                } else {
+                    std::fs::metadata(&file_path).map(|m| m.len()).ok()
+                };
+                match size {
+                    Some(bytes) => sized.push((file_path, bytes)),
+                    None => metrics.skip(&file_path, SkipReason::Vanished),
+                }
+            }
+
+            // 按大小占用在途字节额度，等待期间继续持有工作池许可
+            let _inflight = match &inflight {
+                Some(inflight) => {
+                    let cap = cfg
+                        .max_inflight_bytes
+                        .map_or(u32::MAX, |limit| inflight_permits(limit, u32::MAX));
+                    let need = inflight_permits(sized.iter().map(|(_, b)| b).sum(), cap);
+                    tokio::select! {
+                        biased;
+                        reason = stop_reason(&shutdown, &halt, &budget) => {
+                            for (path, _) in &sized {
+                                metrics.skip(path, reason);
+                            }
+                            return Vec::new();
+                        }
+                        permit = Arc::clone(inflight).acquire_many_owned(need) => {
+                            Some(permit.expect("信号量异常"))
+                        }
+                    }
+                }
+                None => None,
+            };
+            waiting.end();
+
+            let start_task = Instant::now();
+            println!("🚀 正在启动: {}", unit_name);
+            let mut files = Vec::with_capacity(sized.len());
+            for (file_path, bytes) in sized {
+                metrics.start(bytes);
+                let before = archive::fingerprint(&file_path);
+                let record = FileRecord {
+                    file: file_path.file_name().unwrap().to_string_lossy().to_string(),
+                    path: file_path.clone(),
+                    table: table.to_string(),
+                    status: FileStatus::Failed,
+                    bytes,
+                    elapsed_secs: 0.0,
+                    finished_at: 0,
+                    error: None,
+                    error_code: None,
+                    error_name: None,
+                    tags: (*tags).clone(),
+                    mtime: before.1.map(report::unix_secs),
+                    hash: hashes.get(&file_path).cloned(),
+                    skipped_rows: None,
+                    written_rows: None,
+                    written_bytes: None,
+                    server_elapsed_secs: None,
+                    raw_bytes: None,
+                    wire_bytes: None,
+                    pack: None,
+                    query_id: None,
+                    overflow_policy: cfg.overflow_policy(),
+                    overflow_values: None,
+                    verified: None,
+                    archived: None,
+                };
+                files.push((record, before));
+            }
+            if files.is_empty() {
+                return Vec::new();
+            }
+            for (record, _) in &files {
+                let (path, bytes) = (&record.path, record.bytes);
+                events.emit(&batch_id, &table, Event::Started { path, bytes });
+            }
+            let total_bytes: u64 = files.iter().map(|(r, _)| r.bytes).sum();
+            span.set("ck_loader.bytes", total_bytes);
+            let timeout = cfg.timeout_for(Some(total_bytes));
+            let timeout = limit.map_or(timeout, |l| l.min(timeout));
+            let shown = board
+                .as_ref()
+                .map(|board| board.enter(&unit_name, total_bytes));
+
+            // 允许跳过错误行时先从 ORC 尾部记下总行数，导入后与服务端实际写入的行数比较
+            let expected_rows = if cfg.allows_errors() {
+                files
+                    .iter()
+                    .map(|(r, _)| orc::read_meta(&r.path).ok().map(|m| m.num_rows))
+                    .sum::<Option<u64>>()
+            } else {
+                None
+            };
+
+            // 单个文件的每次尝试都带上由文件标识派生的 insert_deduplication_token (合并组在 insert_pack 中
+            // 派生组级 token)：失败前服务端可能已提交部分块，KILL QUERY 也不会回滚，重新发送时据此去重。
+            // 没有现成摘要时用元数据标识，不为此多读一遍文件；远端文件没有修改时间，不带 token；
+            // 目标表关闭了去重时 token 不起作用
+            let dedup_token = match files.as_slice() {
+                [(record, before)] => file_id(record, before).map(|id| pack::file_token(&id)),
+                _ => None,
+            };
+            // 没有去重保护时，只有连接失败 (数据尚未发出) 才自动重新发送，停滞与其他暂时性错误直接失败
+            let resend_safe = (files.len() > 1 || dedup_token.is_some())
+                && cfg.insert_deduplicate_for(&insert_table) != Some(false);
+
+            // 3. 按传输方式执行导入
+            let mut upload = None;
+            let unit = Unit {
+                name: &unit_name,
+                insert_table: &insert_table,
+                tags: &tags,
+                span: &span,
+                shown: shown.as_ref(),
+                total_bytes,
+                timeout,
+                dedup_token,
+                resend_safe,
+            };
+            let (result, query_id) = load_unit(
+                &cfg,
+                &pool,
+                &table,
+                &shutdown,
+                &unit,
+                &mut files,
+                &mut upload,
+            )
+            .await;
+
+            // 上传时顺带计算的摘要，记入审计表、已处理日志与台账，供之后的 --skip-loaded / --reject-duplicates 使用
+            if let ([(record, _)], Some(upload)) = (files.as_mut_slice(), &upload) {
+                if record.hash.is_none() {
+                    record.hash = upload.checksum();
+                }
+            }
+            // 审计行需要校验和与行数，在成功的文件被移走之前读取
+            let file_rows = match &audit {
+                Some(_) => describe_for_audit(&mut files).await,
+                None => Vec::new(),
+            };
+
+            // 4. 结果处理；合并组的传输字节按成员大小分摊
+            let single = files.len() == 1;
+            for (record, _) in files.iter_mut() {
+                record.query_id = Some(query_id.clone());
+                if let Some(upload) = &upload {
+                    let share = |n: u64| {
+                        (n as u128 * record.bytes as u128 / total_bytes.max(1) as u128) as u64
+                    };
+                    record.raw_bytes = Some(share(upload.raw_bytes()));
+                    record.wire_bytes = Some(share(upload.wire_bytes()));
+                }
+                record.elapsed_secs = start_task.elapsed().as_secs_f64();
+                record.finished_at = report::unix_now();
+            }
+            span.set("ck_loader.query_id", query_id.clone());
+            match result {
+                Ok(summary) => {
+                    // 异步插入的数据由服务端后台刷新写入，插入查询返回的写入行数与字节数不属于本文件
+                    let counted = summary.as_ref().filter(|_| !cfg.async_insert);
+                    println!(
+                        "✅ SUCCESS: {}{} | 耗时: {:.2?}",
+                        unit_name,
+                        counted.map(|s| s.describe()).unwrap_or_default(),
+                        start_task.elapsed()
+                    );
+                    let overflow_values = summary.as_ref().and_then(|s| s.overflow_values);
+                    if let Some(n) = overflow_values.filter(|n| *n > 0) {
+                        eprintln!(
+                            "⚠️ {} 有 {} 个越界值按 {} 处理",
+                            unit_name,
+                            n,
+                            cfg.overflow_policy().unwrap_or_default()
+                        );
+                    }
+                    let written = counted.map(|s| s.written_rows);
+                    if let Some(rows) = written {
+                        span.set("ck_loader.written_rows", rows);
+                    }
+                    // 服务端返回了写入行数时与 ORC 行数对账；源文件处置前读取文件尾
+                    let expected_rows = match (expected_rows, written) {
+                        (None, Some(_)) => files
+                            .iter()
+                            .map(|(r, _)| orc::read_meta(&r.path).ok().map(|m| m.num_rows))
+                            .sum::<Option<u64>>(),
+                        _ => expected_rows,
+                    };
+                    let verified = match (expected_rows, written) {
+                        (Some(expected), Some(written)) => {
+                            Some(written == expected || cfg.allows_errors() && written < expected)
+                        }
+                        _ => None,
+                    };
+                    if let (Some(expected), Some(written)) = (expected_rows, written) {
+                        if cfg.allows_errors() {
+                            let skipped = expected.saturating_sub(written);
+                            if single {
+                                files[0].0.skipped_rows = Some(skipped);
+                            }
+                            if skipped > 0 {
+                                eprintln!("⚠️ {} 跳过了 {} 行错误数据", unit_name, skipped);
+                            }
+                        }
+                        if verified == Some(false) {
+                            eprintln!(
+                                "⚠️ {} 写入 {} 行，与 ORC 文件的 {} 行不一致",
+                                unit_name, written, expected
+                            );
+                        }
+                    }
+                    for (record, before) in files.iter_mut() {
+                        record.status = FileStatus::Success;
+                        record.verified = verified;
+                        if single {
+                            record.written_rows = written;
+                            record.written_bytes = counted.map(|s| s.written_bytes);
+                            record.server_elapsed_secs = counted.and_then(|s| s.elapsed_secs);
+                            record.overflow_values = overflow_values;
+                        }
+                        if cfg.atomic {
+                            deferred
+                                .lock()
+                                .unwrap()
+                                .push((record.path.clone(), *before));
+                        } else {
+                            hooks::run(&cfg, &batch_id, record, None).await;
+                            let moving = span.child("move");
+                            moving.set("ck_loader.file", record.file.clone());
+                            let archived =
+                                dispose(&cfg, record, *before, &d_dir, &processed, &intents).await;
+                            moving.set("ck_loader.archived", archived);
+                            record.archived = Some(archived);
+                        }
+                    }
+                }
+                Err(e) => {
+                    span.fail(e.to_string().trim());
+                    // 相同的错误被限流时不在控制台输出，错误日志照常写入
+                    let shown = errors.admit(&e);
+                    match shown {
+                        Some(0) => {
+                            eprintln!("❌ ERROR: {} | 详情: {}", unit_name, e.to_string().trim())
+                        }
+                        Some(n) => eprintln!(
+                            "❌ ERROR: {} | 详情: {} (此前另有 {} 个文件出现相同错误，未逐条输出)",
+                            unit_name,
+                            e.to_string().trim(),
+                            n
+                        ),
+                        None => {}
+                    }
+                    if e.class() == ErrorClass::Fatal && !cfg.keep_going && !halt.is_cancelled() {
+                        eprintln!(
+                            "⛔ 致命错误，{} 的其余文件不再启动 (--keep-going 可继续)",
+                            table
+                        );
+                        halt.cancel();
+                    }
+                    if budget.record(files.len() as u64) {
+                        eprintln!(
+                            "⛔ 失败文件数达到上限 ({})，所有表都不再启动新文件",
+                            budget.limit().unwrap_or_default()
+                        );
+                    }
+                    for (record, _) in files.iter_mut() {
+                        record.fail(&e);
+                        // 控制台与报告中的错误会被截断，完整输出留在 failed/ 下供事后排查
+                        let mut error_log = None;
+                        if !remote {
+                            match e.write_log(&f_dir, &record.path, &table) {
+                                Ok(log) => {
+                                    if shown.is_some() {
+                                        eprintln!("   完整错误输出: {:?}", log)
+                                    }
+                                    error_log = Some(log);
+                                }
+                                Err(err) => {
+                                    eprintln!("⚠️ 无法写入错误日志: {}, 错误: {}", record.file, err)
+                                }
+                            }
+                        }
+                        hooks::run(&cfg, &batch_id, record, error_log.as_deref()).await;
+                    }
+                }
+            }
+            let records: Vec<FileRecord> = files.into_iter().map(|(r, _)| r).collect();
+            for record in &records {
+                if let Some(ledger) = &ledger {
+                    if let Err(e) = ledger.record(record) {
+                        eprintln!("⚠️ 台账写入失败: {}, 错误: {:#}", record.file, e);
+                    }
+                }
+                metrics.finish(record);
+                events.emit(&batch_id, &table, Event::finished(record));
+                if let Some(hook) = &on_file {
+                    hook(record);
+                }
+            }
+            if let Some(audit) = &audit {
+                let rows: Vec<Option<u64>> = records
+                    .iter()
+                    .zip(file_rows)
+                    .map(|(r, rows)| r.written_rows.or(rows))
+                    .collect();
+                if let Err(e) = audit.record(&cfg, &records, &rows).await {
+                    eprintln!("⚠️ 审计记录写入失败: {}, 错误: {:#}", unit_name, e);
+                }
+            }
+            records
+        })
+    };
+
+    let mut records = Vec::new();
+    if cfg.canary && files.len() > 1 {
+        let canary = files.remove(canary_index(&files));
+        let Some(record) = load_canary(&cfg, &table, canary, files.len(), &pool, |path| {
+            spawn_unit(vec![path], Some(cfg.canary_timeout))
+        })
+        .await?
+        else {
+            if shutdown.is_stopping() {
+                return Ok(Vec::new());
+            }
+            bail!("canary 文件未能启动导入，可能已被移走");
+        };
+        let passed = record.status == FileStatus::Success;
+        records.push(record);
+        if !passed {
+            eprintln!("🐤 canary 导入失败，其余 {} 个文件不再启动", files.len());
+            for path in &files {
+                pool.metrics.exclude(path, SkipReason::CanaryFailed);
+            }
+            if let Some(staging) = &staging {
+                staging.discard(&cfg).await;
+                bail!("--atomic: canary 导入失败，本批次未并入 {}", table);
+            }
+            return Ok(records);
+        }
+    }
+
+    pool.metrics.enqueue(files.len() as u64);
+    let units = if cfg.pack_under_mb.is_some() || cfg.batch_small_files.is_some() {
+        let under = cfg.pack_under_mb.unwrap_or(0) * 1024 * 1024;
+        let batch = cfg.batch_small_files.unwrap_or(0);
+        let max = cfg.pack_max_mb * 1024 * 1024;
+        tokio::task::spawn_blocking(move || pack::plan(files, under, batch, max)).await?
+    } else {
+        files.into_iter().map(|f| vec![f]).collect()
+    };
+    let tasks: Vec<_> = units.into_iter().map(|u| spawn_unit(u, None)).collect();
+
+    // 5. 等待所有 Worker 完成
+    records.extend(
+        join_all(tasks)
+            .await
+            .into_iter()
+            .filter_map(|r| r.ok())
+            .flatten(),
+    );
+
+    if let Some(staging) = &staging {
+        let failed = records
+            .iter()
+            .filter(|r| r.status == FileStatus::Failed)
+            .count();
+        if failed > 0 || shutdown.is_stopping() || halt.is_cancelled() {
+            staging.discard(&cfg).await;
+            bail!(
+                "--atomic: 批次未全部完成 ({} 个文件失败)，未并入 {}，源文件保持原位，可直接重跑",
+                failed,
+                table
+            );
+        }
+        staging.commit(&cfg, &records).await?;
+        let deferred = std::mem::take(&mut *deferred.lock().unwrap());
+        for (path, before) in deferred {
+            if let Some(record) = records.iter_mut().find(|r| r.path == path) {
+                hooks::run(&cfg, &pool.batch_id, record, None).await;
+                let archived =
+                    dispose(&cfg, record, before, &done_dir, &pool.processed, &intents).await;
+                record.archived = Some(archived);
+            }
+        }
+    }
+
+    if let Some(cluster) = &cfg.sync_replicas {
+        if records.iter().any(|r| r.status == FileStatus::Success) {
+            replicas::wait(&cfg, cluster, &table).await?;
+        }
+    }
+
+    if let Some(base) = &baseline {
+        let loaded: Vec<&std::path::Path> = records
+            .iter()
+            .filter(|r| r.status == FileStatus::Success)
+            .map(|r| r.path.as_path())
+            .collect();
+        freshness::check(&cfg, &table, base, &loaded, cfg.freshness_column.as_deref()).await?;
+    }
+    Ok(records)
+}
+
+/// 一个导入单元 (单个文件或 --pack-under-mb / --batch-small-files 合并的一组小文件) 各次尝试共用的参数
+struct Unit<'a> {
+    name: &'a str,
+    /// 实际写入的表，--atomic 时为暂存表
+    insert_table: &'a str,
+    tags: &'a Tags,
+    span: &'a Span,
+    shown: Option<&'a Shown>,
+    total_bytes: u64,
+    timeout: Duration,
+    dedup_token: Option<String>,
+    /// 有去重保护，停滞与暂时性错误后可以重新发送
+    resend_safe: bool,
+}
+
+/// 按传输方式导入一个单元，返回最终结果与最后一次尝试的 query_id。
+/// 中断时直接丢弃 future (子进程随之被 kill)；停滞时中断本次尝试并 KILL QUERY，换新的 query_id 重试，
+/// 整体超时覆盖全部尝试；服务端过载时整个工作池进入冷却，冷却结束后重试，等待冷却的时间不计入超时
+async fn load_unit(
+    cfg: &Args,
+    pool: &Pool,
+    table: &str,
+    shutdown: &Shutdown,
+    unit: &Unit<'_>,
+    files: &mut [(FileRecord, (u64, Option<SystemTime>))],
+    upload: &mut Option<Arc<Upload>>,
+) -> (Result<Option<InsertSummary>, ClickHouseError>, String) {
+    let mut deadline = cli::deadline_after(unit.timeout);
+    let (mut stalls, mut overloads, mut retries) = (0, 0, 0);
+    let paths: Vec<PathBuf> = files.iter().map(|(r, _)| r.path.clone()).collect();
+    loop {
+        let attempt = Arc::new(
+            Attempt::new(
+                &pool.batch_id,
+                &paths,
+                unit.total_bytes,
+                stalls + overloads + retries,
+            )
+            .traceparent(unit.span.traceparent())
+            .dedup_token(unit.dedup_token.clone())
+            .read_limit(pool.read_limit.clone()),
+        );
+        if let Some(shown) = unit.shown {
+            shown.track(&attempt);
+        }
+        let insert = async {
+            if let [(record, _)] = &files[..] {
+                let path = &record.path;
+                insert_file(
+                    cfg,
+                    &pool.http,
+                    pool.session.as_ref(),
+                    &pool.connections,
+                    unit.insert_table,
+                    path,
+                    unit.tags,
+                    upload,
+                    &attempt,
+                )
+                .await
+            } else {
+                insert_pack(
+                    cfg,
+                    &pool.http,
+                    unit.insert_table,
+                    files,
+                    unit.tags,
+                    upload,
+                    &attempt,
+                )
+                .await
+            }
+        };
+        let result = tokio::select! {
+            res = time::timeout_at(deadline, insert) => {
+                res.unwrap_or(Err(ClickHouseError::Timeout(unit.timeout)))
+            }
+            _ = stall::stalled(&attempt, cfg.stall_timeout.unwrap_or_default()),
+                if cfg.stall_timeout.is_some() =>
+            {
+                Err(ClickHouseError::Stalled(cfg.stall_timeout.unwrap_or_default()))
+            }
+            _ = shutdown.aborted() => Err(ClickHouseError::Cancelled),
+            _ = progress::show(&attempt, unit.name), if cfg.server_progress => {
+                unreachable!("进度输出不会结束")
+            }
+            _ = pool.events.progress(&pool.batch_id, table, &attempt, &paths) => {
+                unreachable!("进度事件不会结束")
+            }
+            _ = trace::attempt(unit.span, &attempt, stalls + overloads + retries) => {
+                unreachable!("追踪 span 不会结束")
+            }
+        };
+        match result {
+            Err(ClickHouseError::Stalled(d)) if unit.resend_safe && stalls < cfg.stall_retries => {
+                stalls += 1;
+                eprintln!(
+                    "🐌 {} 超过 {:?} 没有进度，中断后重试 ({}/{})",
+                    unit.name, d, stalls, cfg.stall_retries
+                );
+                stall::kill_query(cfg, &attempt.query_id).await;
+            }
+            Err(ClickHouseError::Stalled(d)) => {
+                stall::kill_query(cfg, &attempt.query_id).await;
+                break (Err(ClickHouseError::Stalled(d)), attempt.query_id.clone());
+            }
+            Err(e) if e.is_overload() && overloads < cfg.overload_retries => {
+                overloads += 1;
+                eprintln!(
+                    "🧊 {} 服务端过载 ({})，冷却 {:?} 后重试 ({}/{})",
+                    unit.name,
+                    e.name().unwrap_or("-"),
+                    cfg.overload_cooldown,
+                    overloads,
+                    cfg.overload_retries
+                );
+                pool.pause.cool_down(cfg.overload_cooldown);
+                let waited = time::Instant::now();
+                tokio::select! {
+                    biased;
+                    _ = shutdown.aborted() => break (Err(ClickHouseError::Cancelled), attempt.query_id.clone()),
+                    _ = pool.pause.wait_resumed() => {}
+                }
+                deadline = deadline.checked_add(waited.elapsed()).unwrap_or(deadline);
+            }
+            Err(e)
+                if e.class() == ErrorClass::Retryable
+                    && !e.is_overload()
+                    && (unit.resend_safe || e.is_connect())
+                    && retries < cfg.retries =>
+            {
+                retries += 1;
+                let backoff = cfg
+                    .retry_backoff
+                    .saturating_mul(2u32.saturating_pow(retries - 1));
+                eprintln!(
+                    "🔁 {} 暂时性错误，{:?} 后重试 ({}/{}): {}",
+                    unit.name,
+                    backoff,
+                    retries,
+                    cfg.retries,
+                    e.to_string().trim()
+                );
+                let waited = time::Instant::now();
+                tokio::select! {
+                    biased;
+                    _ = shutdown.aborted() => break (Err(ClickHouseError::Cancelled), attempt.query_id.clone()),
+                    _ = time::sleep(backoff) => {}
+                }
+                deadline = deadline.checked_add(waited.elapsed()).unwrap_or(deadline);
+            }
+            result => break (result, attempt.query_id.clone()),
+        }
+    }
+}
+
+/// 不再启动新文件的原因：收到停止信号，同一表的文件遇到致命错误，或失败文件数达到上限
+async fn stop_reason(shutdown: &Shutdown, halt: &CancellationToken, budget: &Budget) -> SkipReason {
+    tokio::select! {
+        biased;
+        _ = shutdown.stopping() => SkipReason::Interrupted,
+        _ = halt.cancelled() => SkipReason::FatalError,
+        _ = budget.exhausted() => SkipReason::FailureBudget,
+    }
+}
+
+/// canary 选大小居中的文件：比最小的文件更有代表性，又不会让最大的文件拖住整个批次。
+/// 远端文件不逐个查询大小，按列表顺序取中间一个
+fn canary_index(files: &[PathBuf]) -> usize {
+    let mut sizes: Vec<(u64, usize)> = files
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (std::fs::metadata(p).map(|m| m.len()).unwrap_or(0), i))
+        .collect();
+    sizes.sort_unstable();
+    sizes[sizes.len() / 2].1
+}
+
+/// 单独导入 canary 文件并核对结果：schema 按 strict 检查，写入行数与 ORC 行数比对。
+/// 未执行 (停止信号或文件已消失) 时返回 None；行数不一致时中止批次 (文件已写入，不再回退)
+async fn load_canary<F>(
+    cfg: &Args,
+    table: &str,
+    canary: PathBuf,
+    remaining: usize,
+    pool: &Pool,
+    spawn: F,
+) -> Result<Option<FileRecord>>
+where
+    F: FnOnce(PathBuf) -> tokio::task::JoinHandle<Vec<FileRecord>>,
+{
+    if cfg.schema_check != SchemaCheck::Strict {
+        schema::check(
+            SchemaCheck::Strict,
+            cfg,
+            table,
+            std::slice::from_ref(&canary),
+            1,
+        )
+        .await?;
+    }
+    let expected = orc::read_meta(&canary).ok().map(|m| m.num_rows);
+    println!(
+        "🐤 canary: {:?} (时限 {:?})，通过后再导入其余 {} 个文件",
+        canary, cfg.canary_timeout, remaining
+    );
+    pool.metrics.enqueue(1);
+    let Some(record) = spawn(canary.clone())
+        .await
+        .ok()
+        .and_then(|r| r.into_iter().next())
+    else {
+        return Ok(None);
+    };
+    if record.status != FileStatus::Success {
+        return Ok(Some(record));
+    }
+    match (expected, record.written_rows) {
+        (Some(expected), Some(written)) if written != expected && !cfg.allows_errors() => bail!(
+            "canary 行数不一致: {:?} 写入 {} 行，ORC 文件为 {} 行；其余 {} 个文件不再启动",
+            canary,
+            written,
+            expected,
+            remaining
+        ),
+        (_, None) if cfg.async_insert => {
+            eprintln!("⚠️ 异步插入不返回本文件的写入行数，canary 只核对导入是否成功")
+        }
+        (_, None) => {
+            eprintln!("⚠️ 服务端未返回写入行数 (clickhouse-client 传输)，canary 只核对导入是否成功")
+        }
+        _ => {}
+    }
+    println!("🐤 canary 通过，开始导入其余 {} 个文件", remaining);
+    Ok(Some(record))
+}
+
+/// 日志中导入单元的名称：单个文件为文件名，合并组为首个文件名加成员数
+fn unit_name(members: &[PathBuf]) -> String {
+    let first = members
+        .first()
+        .and_then(|p| p.file_name())
+        .unwrap_or_default()
+        .to_string_lossy();
+    match members.len() {
+        1 => first.to_string(),
+        n => format!("📦 {} 等 {} 个文件", first, n),
+    }
+}
+
+/// 导入前记录的文件大小与修改时间，处置前据此确认文件未被改写
+type Snapshot = (u64, Option<SystemTime>);
+
+/// 成功导入后处置源文件：记入已处理日志，再按 --on-success 移动 / 删除 / 压缩。
+/// 删除 / 归档前确认导入期间文件未被改写，否则退回到移动，保留源文件。返回处置是否完成
+async fn dispose(
+    cfg: &Args,
+    record: &FileRecord,
+    before: Snapshot,
+    done_dir: &Path,
+    processed: &Option<Arc<ProcessedLog>>,
+    intents: &Option<Arc<IntentLog>>,
+) -> bool {
+    let mut policy = cfg.success_policy();
+    let mut d_dir = match &cfg.archive_to {
+        Some(url) => url.clone(),
+        None => done_dir.to_path_buf(),
+    };
+    if policy != OnSuccess::Move && archive::fingerprint(&record.path) != before {
+        eprintln!("⚠️ 导入期间文件发生变化，改为移动到 done: {}", record.file);
+        policy = OnSuccess::Move;
+        d_dir = done_dir.to_path_buf();
+    }
+    let src = record.path.clone();
+    let layout = cfg.done_layout.clone();
+    let processed = processed.clone();
+    let intents = intents.clone();
+    let known_hash = record.hash.clone();
+    let finished = tokio::task::spawn_blocking(move || {
+        // 源文件被移走前记入已处理日志，写入失败不影响后续处置
+        if let Some(log) = &processed {
+            let (checksum, rows) = processed::describe(&src, known_hash.as_deref());
+            if let Err(e) = log.append(&src, &checksum, rows) {
+                eprintln!("⚠️ 已处理日志写入失败: {:?}, 错误: {:#}", src, e);
+            }
+        }
+        let dir = archive::target_dir(&d_dir, layout.as_deref())?;
+        if let Some(log) = &intents {
+            log.begin(&src, policy, &dir)?;
+        }
+        archive::finish(policy, &src, &dir)?;
+        if let Some(log) = &intents {
+            log.commit(&src)?;
+        }
+        anyhow::Ok(())
+    })
+    .await;
+    match finished {
+        Ok(Ok(_)) => return true,
+        Ok(Err(e)) => eprintln!("⚠️ 成功后文件处置失败: {}, 错误: {:#}", record.file, e),
+        Err(e) => eprintln!("⚠️ 成功后文件处置失败: {}, 错误: {}", record.file, e),
+    }
+    false
+}
+
+/// 按 --newer-than / --older-than / --min-size / --max-size 拆分为 (导入, 跳过)；
+/// 读不到元数据的文件留给后续步骤报错
+fn filter_files(cfg: &Args, files: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let now = SystemTime::now();
+    let keep = |path: &Path| {
+        let Ok(meta) = std::fs::metadata(path) else {
+            return true;
+        };
+        let size = meta.len();
+        if cfg.min_size.is_some_and(|min| size < min) || cfg.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        // mtime 在未来 (时钟偏差) 时按刚刚修改处理
+        let age = meta
+            .modified()
+            .map(|m| now.duration_since(m).unwrap_or_default());
+        match age {
+            Ok(age) => {
+                cfg.newer_than.is_none_or(|d| age <= d) && cfg.older_than.is_none_or(|d| age >= d)
+            }
+            Err(_) => true,
+        }
+    };
+    files.into_iter().partition(|p| keep(p))
+}
+
+/// 挑出 0 字节文件 (如 `_SUCCESS` 标记) 与没有数据行的 ORC 文件：导入它们没有意义，
+/// 却会各自占用一次插入。读取失败的文件留给后续步骤处理
+fn skip_empty(files: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<(PathBuf, SkipReason)>) {
+    let mut load = Vec::with_capacity(files.len());
+    let mut empty = Vec::new();
+    for path in files {
+        let reason = match std::fs::metadata(&path) {
+            Ok(m) if m.len() == 0 => Some(SkipReason::Empty),
+            Ok(_) => match orc::read_meta(&path) {
+                Ok(meta) if meta.num_rows == 0 => Some(SkipReason::ZeroRows),
+                _ => None,
+            },
+            Err(_) => None,
+        };
+        match reason {
+            Some(reason) => empty.push((path, reason)),
+            None => load.push(path),
+        }
+    }
+    let count = |r: SkipReason| empty.iter().filter(|(_, reason)| *reason == r).count();
+    let (zero_bytes, zero_rows) = (count(SkipReason::Empty), count(SkipReason::ZeroRows));
+    if !empty.is_empty() {
+        eprintln!(
+            "⚠️ 跳过 {} 个空文件、{} 个没有数据行的 ORC 文件",
+            zero_bytes, zero_rows
+        );
+    }
+    (load, empty)
+}
+
+/// 校验各文件的 ORC 文件尾，按 (完好, 损坏) 拆分。损坏的本地文件连同原因移入 `quarantine_dir`，
+/// 远端文件只跳过不移动
+fn check_footers(files: Vec<PathBuf>, quarantine_dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut intact = Vec::with_capacity(files.len());
+    let mut corrupt = Vec::new();
+    for path in files {
+        // 预压缩文件与行格式文件没有可直接读取的 ORC 文件尾，不校验
+        if !precompressed::is_plain_orc(&path) {
+            intact.push(path);
+            continue;
+        }
+        let Err(e) = orc::read_meta(&path).and_then(|m| m.check_integrity()) else {
+            intact.push(path);
+            continue;
+        };
+        eprintln!("🚧 ORC 文件损坏: {:?} | {:#}", path, e);
+        if !remote::is_remote(&path) {
+            if let Err(err) = quarantine(&path, quarantine_dir, &e) {
+                eprintln!("⚠️ 无法隔离文件: {:?}, 错误: {:#}", path, err);
+            }
+        }
+        corrupt.push(path);
+    }
+    if !corrupt.is_empty() {
+        eprintln!(
+            "🚧 {} 个文件未通过文件尾校验，已移入 {:?}",
+            corrupt.len(),
+            quarantine_dir
+        );
+    }
+    (intact, corrupt)
+}
+
+fn quarantine(path: &Path, dir: &Path, reason: &anyhow::Error) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("无法创建目录: {:?}", dir))?;
+    let file_name = path.file_name().context("无效的文件名")?;
+    let mut log_name = file_name.to_os_string();
+    log_name.push(".error.log");
+    std::fs::write(dir.join(log_name), format!("{:#}\n", reason))?;
+    archive::move_file(path, &dir.join(file_name))
+}
+
+/// 计算尚无摘要的文件的摘要 (随导入结果写入台账与审计表)，按 (待导入, 已导入过) 拆分。
+/// 配置了审计表时以审计表为准，否则查询本地台账
+async fn skip_loaded(
+    cfg: &Args,
+    pool: &Pool,
+    table: &str,
+    files: Vec<PathBuf>,
+    hashes: &mut HashMap<PathBuf, String>,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    fill_hashes(&files, hashes).await?;
+    let loaded = match (&pool.audit, &pool.ledger) {
+        (Some(audit), _) => {
+            let checksums: Vec<&str> = files.iter().map(|p| hashes[p].as_str()).collect();
+            audit.loaded_checksums(cfg, table, &checksums).await?
+        }
+        (None, Some(ledger)) => {
+            let (ledger, table) = (Arc::clone(ledger), table.to_string());
+            tokio::task::spawn_blocking(move || ledger.loaded_hashes(&table)).await??
+        }
+        (None, None) => bail!("--skip-loaded 需要 --audit-table 或 --ledger"),
+    };
+    Ok(files
+        .into_iter()
+        .partition(|p| !loaded.contains(&hashes[p])))
+}
+
+/// 计算尚无摘要的文件的摘要
+async fn fill_hashes(files: &[PathBuf], hashes: &mut HashMap<PathBuf, String>) -> Result<()> {
+    let missing: Vec<PathBuf> = files
+        .iter()
+        .filter(|p| !hashes.contains_key(*p))
+        .cloned()
+        .collect();
+    let computed = tokio::task::spawn_blocking(move || {
+        missing
+            .into_iter()
+            .map(|path| delta::hash_file(&path).map(|hash| (path, hash)))
+            .collect::<Result<Vec<_>>>()
+    })
+    .await??;
+    hashes.extend(computed);
+    Ok(())
+}
+
+/// --reject-duplicates：拆分为 (待导入的, 重复的)。内容与台账中以其他文件名成功导入过的文件相同，
+/// 或与同批中排在前面的文件相同，即视为重复；同名文件的重新导入不在此列 (由 --skip-loaded / --delta 处理)
+async fn reject_duplicates(
+    pool: &Pool,
+    table: &str,
+    files: Vec<PathBuf>,
+    hashes: &mut HashMap<PathBuf, String>,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let Some(ledger) = pool.ledger.clone() else {
+        bail!("--reject-duplicates 需要 --ledger");
+    };
+    fill_hashes(&files, hashes).await?;
+    let table = table.to_string();
+    let mut seen = tokio::task::spawn_blocking(move || ledger.loaded_names(&table)).await??;
+    let (mut load, mut duplicates) = (Vec::new(), Vec::new());
+    for path in files {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let names = seen.entry(hashes[&path].clone()).or_default();
+        match names.iter().find(|n| **n != name) {
+            Some(original) if !names.contains(&name) => {
+                eprintln!(
+                    "🚫 {} 与已导入的 {} 内容相同，拒绝导入 (文件留在原处)",
+                    name, original
+                );
+                duplicates.push(path);
+            }
+            _ => {
+                names.push(name);
+                load.push(path);
+            }
+        }
+    }
+    Ok((load, duplicates))
+}
+
+/// 计算各成员的校验和 (已有摘要时复用) 并从 ORC 文件尾读取行数；校验和记入记录，
+/// 之后的已处理日志与台账直接复用。远端文件不为此整文件下载，校验和为空
+async fn describe_for_audit(
+    files: &mut [(FileRecord, (u64, Option<SystemTime>))],
+) -> Vec<Option<u64>> {
+    let mut rows = Vec::with_capacity(files.len());
+    for (record, _) in files.iter_mut() {
+        let path = record.path.clone();
+        let known = record.hash.clone();
+        let described =
+            tokio::task::spawn_blocking(move || processed::describe(&path, known.as_deref())).await;
+        let (checksum, n) = described.unwrap_or_else(|_| ("-".to_string(), None));
+        if checksum != "-" {
+            record.hash = Some(checksum);
+        }
+        rows.push(n);
+    }
+    rows
+}
+
+/// 文件标识：台账 / --delta 已有的内容摘要，否则由路径、大小与修改时间派生 (见 `pack::meta_id`)
+fn file_id(record: &FileRecord, before: &(u64, Option<SystemTime>)) -> Option<String> {
+    record
+        .hash
+        .clone()
+        .or_else(|| pack::meta_id(&record.path, record.bytes, before.1))
+}
+
+/// 合并组导入：由排序后的成员标识派生组级 insert_deduplication_token，
+/// 同一组文件整组重试时被服务端去重。token 记入每个成员的记录
+async fn insert_pack(
+    cfg: &Args,
+    http_client: &reqwest::Client,
+    table: &str,
+    files: &mut [(FileRecord, (u64, Option<SystemTime>))],
+    tags: &Tags,
+    upload: &mut Option<Arc<Upload>>,
+    attempt: &Attempt,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let mut ids = Vec::with_capacity(files.len());
+    for (record, before) in files.iter() {
+        let id = file_id(record, before).ok_or_else(|| {
+            ClickHouseError::Transport(format!("无法读取 {:?} 的修改时间", record.path))
+        })?;
+        ids.push(id);
+    }
+    let token = pack::token(&ids);
+    let paths: Vec<PathBuf> = files.iter().map(|(r, _)| r.path.clone()).collect();
+    for (record, _) in files.iter_mut() {
+        record.pack = Some(token.clone());
+    }
+    let current = upload.insert(attempt.upload(cfg));
+    if pack::row_format(&paths[0]).is_some() {
+        return http::insert_batch(http_client, cfg, table, &paths, tags, current, &token).await;
+    }
+    http::insert_pack(http_client, cfg, table, &paths, tags, current, &token).await
+}
+
+/// 按传输链依次尝试导入单个文件：连接层失败 (数据尚未写入) 时换下一种传输方式，
+/// 其他错误直接返回。`upload` 留下最后一次 HTTP 尝试的字节统计
+#[allow(clippy::too_many_arguments)]
+async fn insert_file(
+    cfg: &Args,
+    http_client: &reqwest::Client,
+    session_client: Option<&reqwest::Client>,
+    connections: &Connections,
+    table: &str,
+    path: &Path,
+    tags: &Tags,
+    upload: &mut Option<Arc<Upload>>,
+    attempt: &Attempt,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let chain = cfg.transport_chain_for(attempt.bytes());
+    // 小文件从 clickhouse-client 改走 HTTP 会话时使用会话专用的连接池
+    let session = chain[0] != cfg.transport_chain()[0];
+    for (i, transport) in chain.iter().enumerate() {
+        let result = match transport {
+            Transport::Client => {
+                let query = schema::file_insert_sql(cfg, table, path)?;
+                // clickhouse-client 从 stdin 读取原始数据，预压缩文件先在本地解压
+                let scratch = match precompressed::detect(path) {
+                    Some(codec) => Some(precompressed::unpack(path, codec).await?),
+                    None => None,
+                };
+                match std::fs::File::open(scratch.as_ref().map_or(path, |s| s.path())) {
+                    Ok(file) => {
+                        let input = Stdio::from(file);
+                        client::insert(cfg, table, &query, input, tags, Some(attempt))
+                            .await
+                            .map(|_| None)
+                    }
+                    Err(e) => Err(ClickHouseError::Transport(format!("无法打开文件: {}", e))),
+                }
+            }
+            Transport::Http => {
+                let current = upload.insert(attempt.file_upload(cfg, path));
+                let client = match session_client {
+                    Some(session_client) if session => session_client,
+                    _ => http_client,
+                };
+                http::insert(client, cfg, table, path, tags, current).await
+            }
+            Transport::Native => {
+                // 本地解码 ORC，预压缩文件先解压
+                if precompressed::format(path) != "ORC" {
+                    return Err(ClickHouseError::Decode(format!(
+                        "native 传输只支持 ORC 文件: {:?}",
+                        path
+                    )));
+                }
+                let scratch = match precompressed::detect(path) {
+                    Some(codec) => Some(precompressed::unpack(path, codec).await?),
+                    None => None,
+                };
+                let local = scratch.as_ref().map_or(path, |s| s.path());
+                let structure = schema::input_structure(cfg, Some(local))?;
+                let source = path.to_string_lossy();
+                let query = cfg.insert_sql(table, "Native", structure.as_deref(), &source);
+                let current = upload.insert(attempt.upload(cfg));
+                native::insert(
+                    cfg,
+                    connections,
+                    table,
+                    &query,
+                    local,
+                    tags,
+                    &attempt.query_id,
+                    current,
+                )
+                .await
+            }
+        };
+        match (result, chain.get(i + 1)) {
+            (Err(e), Some(next)) if e.is_connect() => {
+                eprintln!(
+                    "⚠️ {} 传输失败，改用 {}: {} | {}",
+                    transport.name(),
+                    next.name(),
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    e
+                );
+            }
+            (result, _) => return result,
+        }
+    }
+    unreachable!("传输链中最后一种方式的结果总是直接返回")
+}
+
+/// 将本进程的 stdin 作为单个流导入 (管道末端用法)，结果同样计入指标、台账与报告。
+/// 流只能读一次，因此不做导入前检查，也没有成功后的文件处置
+pub async fn run_stdin(
+    cfg: Arc<Args>,
+    table: String,
+    format: String,
+    normalize_header: bool,
+    validate_rows: Option<usize>,
+    pool: Pool,
+    shutdown: Shutdown,
+) -> Result<FileRecord> {
+    if cfg.per_file_bandwidth.is_some() && cfg.transport != Transport::Http {
+        bail!("--per-file-bandwidth 仅支持 --transport http");
+    }
+    if cfg.transport == Transport::Native {
+        bail!("stdin 导入不支持 --transport native (需要在本地解码 ORC 文件)");
+    }
+    if cfg.sync_replicas.is_some() && cfg.wait_for_async_insert == Some(false) {
+        bail!(
+            "--sync-replicas 不能与 --wait-for-async-insert off 同用 (数据尚未落盘，无从等待副本)"
+        );
+    }
+    if cfg.overflow_needs_native() {
+        bail!("stdin 导入不支持 --timestamp-overflow null / --decimal-overflow clamp|null");
+    }
+    if normalize_header {
+        if cfg.transport != Transport::Http {
+            bail!("--normalize-header 仅支持 --transport http");
+        }
+        header::check_format(&format)?;
+    }
+    target::check(&cfg, &table).await?;
+    // 目标列数只用于核对不带表头的格式，查询失败时只检查各行列数是否一致
+    let mut expected_columns = None;
+    if validate_rows.is_some() {
+        if cfg.transport != Transport::Http {
+            bail!("--validate-rows 仅支持 --transport http");
+        }
+        sample::check_format(&format)?;
+        expected_columns = match (&cfg.input_structure, cfg.columns()) {
+            (Some(structure), _) if cfg.uses_input() => {
+                Some(schema::structure_columns(structure).len())
+            }
+            (_, Some(cols)) => Some(cols.0.len()),
+            _ => match schema::describe_table(&cfg, &table).await {
+                Ok(cols) => Some(cols.iter().filter(|c| c.insertable()).count()),
+                Err(e) => {
+                    eprintln!("⚠️ 无法读取表结构，抽样校验不核对列数: {:#}", e);
+                    None
+                }
+            },
+        };
+    }
+    let query = cfg.insert_sql(
+        &table,
+        &format,
+        schema::input_structure(&cfg, None)?.as_deref(),
+        "stdin",
+    );
+    let tags: Tags = cfg.tags.iter().cloned().collect();
+    let metrics = Arc::clone(&pool.metrics);
+    let progress = Arc::clone(&metrics);
+    let span = pool.span.child("file");
+    span.set("ck_loader.table", table.clone());
+    span.set("ck_loader.file", STDIN_NAME);
+    let upload = Arc::new(
+        Upload::new(&cfg)
+            .query_id(format!("ckloader-{}-stdin", pool.batch_id))
+            .traceparent(span.traceparent())
+            .on_read(move |n| progress.progress(n)),
+    );
+    metrics.enqueue(1);
+    metrics.start(0);
+    println!("📥 从 stdin 读取 {} 格式数据 → {}", format, table);
+    let stdin_path = Path::new("-");
+    pool.events.emit(
+        &pool.batch_id,
+        &table,
+        Event::Started {
+            path: stdin_path,
+            bytes: 0,
+        },
+    );
+
+    let start_task = Instant::now();
+    let timeout = cfg.timeout_for(None);
+    let insert = async {
+        match cfg.transport {
+            // clickhouse-client 直接继承 stdin，不经过本进程，此时无法统计字节数
+            Transport::Client => {
+                client::insert(&cfg, &table, &query, Stdio::inherit(), &tags, None)
+                    .await
+                    .map(|_| None)
+            }
+            Transport::Http => {
+                let mut stdin: Box<dyn AsyncRead + Send + Unpin> = Box::new(tokio::io::stdin());
+                if normalize_header {
+                    stdin = Box::new(
+                        header::normalize(stdin, &format)
+                            .await
+                            .map_err(|e| ClickHouseError::Transport(format!("{:#}", e)))?,
+                    );
+                }
+                if let Some(rows) = validate_rows {
+                    stdin = Box::new(
+                        sample::validate(stdin, &format, rows, expected_columns)
+                            .await
+                            .map_err(|e| ClickHouseError::Decode(format!("{:#}", e)))?,
+                    );
+                }
+                http::insert_stream(&pool.http, &cfg, &table, &query, stdin, &tags, &upload).await
+            }
+            Transport::Native => unreachable!("stdin 导入在开始前已拒绝 native 传输"),
+        }
+    };
+    let result = tokio::select! {
+        res = tokio::time::timeout(timeout, insert) => {
+            res.unwrap_or(Err(ClickHouseError::Timeout(timeout)))
+        }
+        _ = shutdown.aborted() => Err(ClickHouseError::Cancelled),
+    };
+
+    let mut record = FileRecord {
+        file: STDIN_NAME.to_string(),
+        path: PathBuf::from("-"),
+        table,
+        status: FileStatus::Success,
+        bytes: upload.raw_bytes(),
+        elapsed_secs: start_task.elapsed().as_secs_f64(),
+        finished_at: report::unix_now(),
+        error: None,
+        error_code: None,
+        error_name: None,
+        tags,
+        mtime: None,
+        hash: None,
+        skipped_rows: None,
+        written_rows: None,
+        written_bytes: None,
+        server_elapsed_secs: None,
+        raw_bytes: None,
+        wire_bytes: None,
+        pack: None,
+        query_id: None,
+        overflow_policy: cfg.overflow_policy(),
+        overflow_values: None,
+        verified: None,
+        archived: None,
+    };
+    if cfg.transport == Transport::Http {
+        record.raw_bytes = Some(upload.raw_bytes());
+        record.wire_bytes = Some(upload.wire_bytes());
+    }
+    match result {
+        Ok(summary) => {
+            record.query_id = summary.as_ref().and_then(|s| s.query_id.clone());
+            let counted = summary.as_ref().filter(|_| !cfg.async_insert);
+            record.written_rows = counted.map(|s| s.written_rows);
+            record.written_bytes = counted.map(|s| s.written_bytes);
+            record.server_elapsed_secs = counted.and_then(|s| s.elapsed_secs);
+            println!(
+                "✅ SUCCESS: {} | {:.1} MB{} | 耗时: {:.2?}",
+                STDIN_NAME,
+                record.bytes as f64 / 1024.0 / 1024.0,
+                counted.map(|s| s.describe()).unwrap_or_default(),
+                start_task.elapsed()
+            )
+        }
+        Err(e) => {
+            record.fail(&e);
+            span.fail(e.to_string().trim());
+            eprintln!("❌ ERROR: {} | 详情: {}", STDIN_NAME, e.to_string().trim());
+        }
+    }
+    if let Some(ledger) = &pool.ledger {
+        if let Err(e) = ledger.record(&record) {
+            eprintln!("⚠️ 台账写入失败: {}, 错误: {:#}", STDIN_NAME, e);
+        }
+    }
+    if let Some(audit) = &pool.audit {
+        let rows = [record.written_rows];
+        if let Err(e) = audit
+            .record(&cfg, std::slice::from_ref(&record), &rows)
+            .await
+        {
+            eprintln!("⚠️ 审计记录写入失败: {}, 错误: {:#}", STDIN_NAME, e);
+        }
+    }
+    hooks::run(&cfg, &pool.batch_id, &record, None).await;
+    metrics.finish(&record);
+    pool.events
+        .emit(&pool.batch_id, &record.table, Event::finished(&record));
+    if let Some(cluster) = &cfg.sync_replicas {
+        if record.status == FileStatus::Success {
+            replicas::wait(&cfg, cluster, &record.table).await?;
+        }
+    }
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inflight_permits_round_up_and_cap() {
+        assert_eq!(inflight_permits(0, 100), 1);
+        assert_eq!(inflight_permits(1, 100), 1);
+        assert_eq!(inflight_permits(INFLIGHT_UNIT, 100), 1);
+        assert_eq!(inflight_permits(INFLIGHT_UNIT + 1, 100), 2);
+        // 超过总额度的单元占满额度，不会永远等待
+        assert_eq!(inflight_permits(500 * INFLIGHT_UNIT, 100), 100);
+        assert_eq!(inflight_permits(u64::MAX, u32::MAX), u32::MAX);
+    }
+}
@@ -0,0 +1,138 @@
+//! 落地目录的单实例锁：load / watch / retry 与 serve 的任务在目录下的 `.ck-loader.lock` 上加 flock
+//! (--shard 时每个分片一把；按清单导入时清单涉及的每个目录各一把)，
+//! cron 在上一次运行结束前再次启动时，后一个进程不会与前一个同时拾取同一批文件而重复导入。
+//!
+//! 锁随进程退出 (包括被 kill) 自动释放，残留的锁文件不影响下次运行；文件中记录持有者的 pid 便于排查。
+//! 默认拿不到锁立即退出，`--wait-lock [时长]` 等待前一个进程结束，`--no-lock` 不加锁。
+
+use crate::cli::Args;
+use crate::remote;
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::time;
+
+/// 锁文件名；--shard 的各实例分别处理目录中的一部分文件，各用一把锁 (`.ck-loader.shard-2-of-5.lock`)
+fn lock_file(cfg: &Args) -> String {
+    match cfg.shard {
+        Some(shard) => format!(".ck-loader.shard-{}-of-{}.lock", shard.index, shard.count),
+        None => ".ck-loader.lock".to_string(),
+    }
+}
+
+/// 扫描目录时跳过锁文件
+pub fn is_lock_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with(".ck-loader.") && n.ends_with(".lock"))
+}
+
+/// 等待锁时重试的间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 持有期间其他进程拿不到同一目录的锁，drop 时释放
+pub struct DirLock {
+    _file: Option<File>,
+}
+
+/// 按 --no-lock / --wait-lock 给目录加锁，没有拿到锁时返回 None。
+/// `s3://` / `webhdfs://` 等远端目录无法加 flock，不加锁
+pub async fn acquire(cfg: &Args, dir: &Path) -> Result<Option<DirLock>> {
+    if cfg.no_lock || remote::is_remote(dir) {
+        return Ok(Some(DirLock { _file: None }));
+    }
+    // --wait-lock 不带时长时一直等待
+    let wait = cfg.wait_lock;
+    let (path, mut file) = open(cfg, dir)?;
+    let started = Instant::now();
+    let mut announced = false;
+    loop {
+        if try_lock(&file).with_context(|| format!("无法锁定 {:?}", path))? {
+            return hold(file).map(Some);
+        }
+        let holder = holder(&mut file);
+        match wait {
+            None => {
+                eprintln!(
+                    "🔒 目录 {:?} 正由另一个 ck-loader 进程{}处理，本次不导入 (--wait-lock 可等待其结束)",
+                    dir, holder
+                );
+                return Ok(None);
+            }
+            Some(Some(limit)) if started.elapsed() >= limit => {
+                eprintln!(
+                    "🔒 等待 {:?} 后目录 {:?} 仍由另一个 ck-loader 进程{}处理，本次不导入",
+                    limit, dir, holder
+                );
+                return Ok(None);
+            }
+            Some(_) => {}
+        }
+        if !announced {
+            println!(
+                "⏳ 目录 {:?} 正由另一个 ck-loader 进程{}处理，等待其结束",
+                dir, holder
+            );
+            announced = true;
+        }
+        time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+/// 不等待地给目录加锁 (serve 提交任务时)，已被其他进程或同一进程中的其他任务持有时返回 None
+pub fn try_acquire(cfg: &Args, dir: &Path) -> Result<Option<DirLock>> {
+    if cfg.no_lock || remote::is_remote(dir) {
+        return Ok(Some(DirLock { _file: None }));
+    }
+    let (path, file) = open(cfg, dir)?;
+    if try_lock(&file).with_context(|| format!("无法锁定 {:?}", path))? {
+        return hold(file).map(Some);
+    }
+    Ok(None)
+}
+
+fn open(cfg: &Args, dir: &Path) -> Result<(PathBuf, File)> {
+    let path = dir.join(lock_file(cfg));
+    // 不截断：文件中可能是当前持有者的 pid
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("无法创建锁文件: {:?}", path))?;
+    Ok((path, file))
+}
+
+/// 拿到锁后写入本进程的 pid
+fn hold(mut file: File) -> Result<DirLock> {
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    Ok(DirLock { _file: Some(file) })
+}
+
+/// 非阻塞地尝试加排他锁，已被其他进程持有时返回 false
+fn try_lock(file: &File) -> io::Result<bool> {
+    // SAFETY: fd 由 file 持有且有效
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.kind() == io::ErrorKind::WouldBlock {
+        return Ok(false);
+    }
+    Err(err)
+}
+
+/// 锁文件中记录的持有者 pid，用于提示
+fn holder(file: &mut File) -> String {
+    let mut pid = String::new();
+    if file.rewind().is_ok() && file.read_to_string(&mut pid).is_ok() && !pid.trim().is_empty() {
+        return format!(" (pid {}) ", pid.trim());
+    }
+    String::new()
+}
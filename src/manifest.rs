@@ -0,0 +1,167 @@
+//! 清单驱动导入：按上游作业产出的文件清单依次导入，文件不必位于同一目录
+//!
+//! 支持两种格式 (按行判断，可混用)：
+//! - 纯文本：每行一个路径，可用 Tab 分隔追加目标表 `path<TAB>table`；空行与 `#` 开头的行忽略
+//! - NDJSON：每行一个 `{"path": "...", "table": "..."}`，table 可省略
+//!
+//! 相对路径相对于清单文件所在目录解析，未指定表的条目依次按 `--route`、`-t` 确定目标表。
+
+use crate::loader::Job;
+use crate::remote;
+use crate::route::{self, Route};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Entry {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub table: Option<String>,
+}
+
+pub fn read(manifest: &Path) -> Result<Vec<Entry>> {
+    let content = std::fs::read_to_string(manifest)
+        .with_context(|| format!("无法读取清单: {:?}", manifest))?;
+    let base = manifest.parent().unwrap_or(Path::new(""));
+    let mut entries = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut entry = if line.starts_with('{') {
+            serde_json::from_str::<Entry>(line)
+                .with_context(|| format!("清单第 {} 行不是合法的 JSON 条目", idx + 1))?
+        } else {
+            let (path, table) = match line.split_once('\t') {
+                Some((path, table)) => (path.trim(), Some(table.trim().to_string())),
+                None => (line, None),
+            };
+            Entry {
+                path: PathBuf::from(path),
+                table: table.filter(|t| !t.is_empty()),
+            }
+        };
+        if entry.path.is_relative() && !remote::is_remote(&entry.path) {
+            entry.path = base.join(&entry.path);
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// 按 (所在目录, 目标表) 拆成若干任务，任务与任务内文件都保持清单中首次出现的顺序
+pub fn jobs(
+    entries: Vec<Entry>,
+    routes: &[Route],
+    default_table: Option<&str>,
+) -> Result<Vec<Job>> {
+    let mut jobs: Vec<Job> = Vec::new();
+    for entry in entries {
+        let table = entry
+            .table
+            .as_deref()
+            .or_else(|| route::table_for(routes, &entry.path))
+            .or(default_table);
+        let Some(table) = table else {
+            bail!(
+                "清单条目 {:?} 未指定目标表，且不匹配任何路由或 -t",
+                entry.path
+            );
+        };
+        let dir = entry
+            .path
+            .parent()
+            .with_context(|| format!("无效的文件路径: {:?}", entry.path))?
+            .to_path_buf();
+        match jobs.iter_mut().find(|j| j.dir == dir && j.table == table) {
+            Some(job) => job.files.get_or_insert_with(Vec::new).push(entry.path),
+            None => jobs.push(Job {
+                dir,
+                table: table.to_string(),
+                files: Some(vec![entry.path]),
+            }),
+        }
+    }
+    Ok(jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(name: &str, content: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("ck-manifest-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("files.txt");
+        std::fs::write(&path, content).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn reads_text_and_json_entries() {
+        let (dir, path) = manifest(
+            "read",
+            "# 注释\n\n/data/a.orc\tdb.a\nb.orc\n{\"path\": \"sub/c.orc\", \"table\": \"db.c\"}\n{\"path\": \"/data/d.orc\"}\ns3://bucket/e.orc\t\n",
+        );
+        let entries = read(&path).unwrap();
+        let got: Vec<(PathBuf, Option<&str>)> = entries
+            .iter()
+            .map(|e| (e.path.clone(), e.table.as_deref()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (PathBuf::from("/data/a.orc"), Some("db.a")),
+                (dir.join("b.orc"), None),
+                (dir.join("sub/c.orc"), Some("db.c")),
+                (PathBuf::from("/data/d.orc"), None),
+                (PathBuf::from("s3://bucket/e.orc"), None),
+            ]
+        );
+
+        let (_, path) = manifest("bad", "/data/a.orc\n{\"table\": \"db.a\"}\n");
+        let err = read(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("第 2 行"));
+    }
+
+    #[test]
+    fn groups_entries_by_dir_and_table() {
+        let entry = |path: &str, table: Option<&str>| Entry {
+            path: PathBuf::from(path),
+            table: table.map(str::to_string),
+        };
+        let routes = vec![route::parse_route("ev_*=db.events").unwrap()];
+        let entries = vec![
+            entry("/x/ev_1.orc", None),
+            entry("/y/a.orc", Some("db.a")),
+            entry("/x/ev_2.orc", None),
+            entry("/x/b.orc", None),
+            entry("/y/ev_3.orc", None),
+        ];
+        let planned = jobs(entries.clone(), &routes, Some("db.default")).unwrap();
+        let got: Vec<(&Path, &str, usize)> = planned
+            .iter()
+            .map(|j| {
+                (
+                    j.dir.as_path(),
+                    j.table.as_str(),
+                    j.files.as_ref().unwrap().len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (Path::new("/x"), "db.events", 2),
+                (Path::new("/y"), "db.a", 1),
+                (Path::new("/x"), "db.default", 1),
+                (Path::new("/y"), "db.events", 1),
+            ]
+        );
+
+        // 既无表也不匹配路由且没有 -t 时报错
+        assert!(jobs(entries, &routes, None).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! `--max-memory`：让进程的常驻内存 (RSS) 保持在上限之内，而不是被 OOM killer 直接杀掉。
+//!
+//! 启动时按上限估算每个进行中文件的缓冲区 (读取块 × (预读块数 + 2) + 压缩器状态，拆分导入时乘以并行组数)，
+//! 依次减少 --compress-threads、--read-ahead、--chunk-size-mb、--workers 直到放得下；上限连一个文件都容纳不了时直接报错退出。
+//! 运行中每秒检查一次 RSS：超过上限时暂停分发新文件 (进行中的导入继续)，回落到上限的 90% 以下后恢复。
+
+use crate::cli::{Args, Transport};
+use crate::pause::Pause;
+use crate::wire::Compression;
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+
+const MB: u64 = 1024 * 1024;
+/// 运行时、HTTP 连接池、台账等与文件数无关的开销
+const BASE_RESERVE: u64 = 64 * MB;
+/// 每个请求体的压缩器状态 (zstd 的窗口与输出缓冲、gzip 的字典、lz4 的块缓冲)
+const ZSTD_STATE: u64 = 4 * MB;
+const GZIP_STATE: u64 = MB;
+const LZ4_STATE: u64 = MB;
+/// --compress-threads 的每个 zstd 压缩线程各自缓存一段待压缩的输入与输出
+const ZSTD_THREAD_STATE: u64 = 12 * MB;
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 每个进行中文件的缓冲区估算
+fn per_file(cfg: &Args) -> u64 {
+    let state = match cfg.http_compression {
+        Compression::None => 0,
+        Compression::Gzip => GZIP_STATE,
+        // auto 在此之前已解析为具体方式，未解析时按 zstd 估算
+        Compression::Zstd | Compression::Auto => {
+            ZSTD_STATE + ZSTD_THREAD_STATE * cfg.compress_threads as u64
+        }
+        Compression::Lz4 => LZ4_STATE,
+    };
+    // 正在读取的块、压缩输出与等待发送的块，加上预读的块
+    let body = cfg.chunk_size() * (cfg.read_ahead as u64 + 2) + state;
+    let groups = match cfg.split_stripes {
+        Some(_) if cfg.transport == Transport::Http => cfg.split_parallel.max(1) as u64,
+        _ => 1,
+    };
+    body * groups
+}
+
+/// 按 --max-memory 调整预读块数、读取块大小与并行数
+pub fn fit(cfg: &mut Args) -> Result<()> {
+    let Some(limit) = cfg.max_memory else {
+        return Ok(());
+    };
+    let base = rss().unwrap_or(0) + BASE_RESERVE;
+    if limit <= base {
+        bail!(
+            "--max-memory {} MB 过小：进程本身与基础开销已需约 {} MB",
+            limit / MB,
+            base / MB
+        );
+    }
+    let budget = limit - base;
+    let before = (
+        cfg.compress_threads,
+        cfg.read_ahead,
+        cfg.chunk_size_mb,
+        cfg.workers,
+    );
+    while per_file(cfg) > budget && cfg.compress_threads > 0 {
+        cfg.compress_threads -= 1;
+    }
+    while per_file(cfg) > budget && cfg.read_ahead > 0 {
+        cfg.read_ahead -= 1;
+    }
+    while per_file(cfg) > budget && cfg.chunk_size_mb > 1 {
+        cfg.chunk_size_mb /= 2;
+    }
+    if per_file(cfg) > budget {
+        bail!(
+            "--max-memory {} MB 过小：单个文件的缓冲区至少需要约 {} MB (另需约 {} MB 基础开销)",
+            limit / MB,
+            per_file(cfg).div_ceil(MB),
+            base / MB
+        );
+    }
+    cfg.workers = cfg.workers.min((budget / per_file(cfg)) as usize).max(1);
+    let (threads, read_ahead, chunk_mb, workers) = before;
+    if before
+        != (
+            cfg.compress_threads,
+            cfg.read_ahead,
+            cfg.chunk_size_mb,
+            cfg.workers,
+        )
+    {
+        println!(
+            "🧮 内存上限 {} MB: 并行数 {} → {}, 读取块 {} MB → {} MB, 预读 {} → {} 块, 压缩线程 {} → {}",
+            limit / MB,
+            workers,
+            cfg.workers,
+            chunk_mb,
+            cfg.chunk_size_mb,
+            read_ahead,
+            cfg.read_ahead,
+            threads,
+            cfg.compress_threads
+        );
+    }
+    Ok(())
+}
+
+/// 运行中按 RSS 暂停 / 恢复分发新文件
+pub fn guard(limit: u64, pause: &Arc<Pause>) {
+    let pause = Arc::clone(pause);
+    tokio::spawn(async move {
+        let mut interval = time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(rss) = rss() else {
+                return;
+            };
+            if rss > limit {
+                if pause.hold(true) {
+                    eprintln!(
+                        "🧠 内存占用 {} MB 超过上限 {} MB，暂停启动新文件",
+                        rss / MB,
+                        limit / MB
+                    );
+                }
+            } else if rss < limit / 10 * 9 && pause.hold(false) {
+                println!("▶️ 内存占用回落到 {} MB，恢复分发新文件", rss / MB);
+            }
+        }
+    });
+}
+
+/// 当前进程的常驻内存 (/proc/self/statm 第二列，单位为页)，非 Linux 上为 None
+fn rss() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf 没有前置条件
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page).ok()?)
+}
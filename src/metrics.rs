@@ -0,0 +1,179 @@
+//! 进程级导入指标：所有 worker 通过原子计数器汇报，进度展示、汇总输出等统一从快照读取
+
+use crate::report::{FileRecord, FileStatus, SkipReason, SkippedFile};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// 逐个列出的跳过文件上限
+const SKIPPED_LISTED: usize = 10_000;
+
+pub struct Metrics {
+    started: Instant,
+    /// 已排队、尚未拿到并行许可的文件
+    queued: AtomicU64,
+    in_flight: AtomicU64,
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    /// 发现了但没有导入的文件：按原因计数，并逐个记下 (最多 SKIPPED_LISTED 个，watch / serve 长期运行时不无限增长)
+    skipped: AtomicU64,
+    skip_reasons: Mutex<BTreeMap<SkipReason, u64>>,
+    skipped_files: Mutex<Vec<SkippedFile>>,
+    bytes_in_flight: AtomicU64,
+    bytes_succeeded: AtomicU64,
+    bytes_failed: AtomicU64,
+    /// 可统计网络传输的文件 (HTTP) 读取的原始字节与实际发送的字节
+    bytes_raw: AtomicU64,
+    bytes_wire: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub elapsed_secs: f64,
+    pub queued: u64,
+    pub in_flight: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub skipped: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub skip_reasons: BTreeMap<SkipReason, u64>,
+    pub bytes_in_flight: u64,
+    pub bytes_succeeded: u64,
+    pub bytes_failed: u64,
+    pub bytes_raw: u64,
+    pub bytes_wire: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            queued: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            succeeded: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            skip_reasons: Mutex::new(BTreeMap::new()),
+            skipped_files: Mutex::new(Vec::new()),
+            bytes_in_flight: AtomicU64::new(0),
+            bytes_succeeded: AtomicU64::new(0),
+            bytes_failed: AtomicU64::new(0),
+            bytes_raw: AtomicU64::new(0),
+            bytes_wire: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn enqueue(&self, files: u64) {
+        self.queued.fetch_add(files, Ordering::Relaxed);
+    }
+
+    /// 已排队的文件最终没有启动
+    pub fn skip(&self, path: &Path, reason: SkipReason) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        self.exclude(path, reason);
+    }
+
+    /// 排队前就被排除的文件 (路由、delta 等)
+    pub fn exclude(&self, path: &Path, reason: SkipReason) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+        *self.skip_reasons.lock().unwrap().entry(reason).or_default() += 1;
+        let mut files = self.skipped_files.lock().unwrap();
+        if files.len() < SKIPPED_LISTED {
+            files.push(SkippedFile {
+                path: path.to_path_buf(),
+                reason,
+            });
+        }
+    }
+
+    pub fn skipped_files(&self) -> Vec<SkippedFile> {
+        self.skipped_files.lock().unwrap().clone()
+    }
+
+    pub fn start(&self, bytes: u64) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in_flight.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// 事先不知道大小的输入 (stdin 流) 边读边累加进行中的字节数
+    pub fn progress(&self, bytes: u64) {
+        self.bytes_in_flight.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn finish(&self, record: &FileRecord) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.bytes_in_flight
+            .fetch_sub(record.bytes, Ordering::Relaxed);
+        let (files, bytes) = match record.status {
+            FileStatus::Success => (&self.succeeded, &self.bytes_succeeded),
+            FileStatus::Failed => (&self.failed, &self.bytes_failed),
+        };
+        files.fetch_add(1, Ordering::Relaxed);
+        bytes.fetch_add(record.bytes, Ordering::Relaxed);
+        if let (Some(raw), Some(wire)) = (record.raw_bytes, record.wire_bytes) {
+            self.bytes_raw.fetch_add(raw, Ordering::Relaxed);
+            self.bytes_wire.fetch_add(wire, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            elapsed_secs: self.started.elapsed().as_secs_f64(),
+            queued: self.queued.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            succeeded: self.succeeded.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            skip_reasons: self.skip_reasons.lock().unwrap().clone(),
+            bytes_in_flight: self.bytes_in_flight.load(Ordering::Relaxed),
+            bytes_succeeded: self.bytes_succeeded.load(Ordering::Relaxed),
+            bytes_failed: self.bytes_failed.load(Ordering::Relaxed),
+            bytes_raw: self.bytes_raw.load(Ordering::Relaxed),
+            bytes_wire: self.bytes_wire.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Snapshot {
+    /// 成功导入的平均吞吐 (MB/s)
+    pub fn throughput_mb(&self) -> f64 {
+        if self.elapsed_secs <= 0.0 {
+            return 0.0;
+        }
+        self.bytes_succeeded as f64 / 1024.0 / 1024.0 / self.elapsed_secs
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "📊 成功: {} | 失败: {} | 跳过: {} | 数据量: {:.1} MB | 平均吞吐: {:.1} MB/s",
+            self.succeeded,
+            self.failed,
+            self.skipped,
+            self.bytes_succeeded as f64 / 1024.0 / 1024.0,
+            self.throughput_mb()
+        );
+        if !self.skip_reasons.is_empty() {
+            let reasons: Vec<String> = self
+                .skip_reasons
+                .iter()
+                .map(|(reason, n)| format!("{} × {}", reason.label(), n))
+                .collect();
+            println!("⏭️ 跳过原因: {}", reasons.join(", "));
+        }
+        if self.bytes_wire > 0 {
+            println!(
+                "🗜️ 网络传输: 原始 {:.1} MB → 发送 {:.1} MB | 压缩比: {:.2}x",
+                self.bytes_raw as f64 / 1024.0 / 1024.0,
+                self.bytes_wire as f64 / 1024.0 / 1024.0,
+                self.bytes_raw as f64 / self.bytes_wire as f64
+            );
+        }
+        println!("⏱️ 总耗时: {:.2}s", self.elapsed_secs);
+    }
+}
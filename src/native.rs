@@ -0,0 +1,1495 @@
+//! 原生 TCP 协议导入 (--transport native)：本地用 orc-rust 把 ORC 解码为 Arrow 批次，
+//! 按服务端返回的插入结构编码为 Native 数据块直接发送，不依赖 clickhouse-client，也没有 HTTP 的额外开销。
+//! 服务端异常以结构化的错误码返回，写入行数取自 Progress 包。
+//!
+//! 协议按客户端修订号 54429 实现 (设置以字符串传输)，不使用块压缩；LowCardinality 列通过
+//! low_cardinality_allow_in_native_format=0 以普通列发送，由服务端转换。
+
+use crate::cli::{Args, OverflowPolicy};
+use crate::error::ClickHouseError;
+use crate::http::InsertSummary;
+use crate::report::Tags;
+use crate::throttle::{ReadLimit, Throttled};
+use crate::wire::Upload;
+use anyhow::{bail, Context, Result};
+use arrow::array::{
+    Array, ArrayRef, ArrowNativeTypeOp, AsArray, Decimal128Array, Decimal256Array, Int64Array,
+    OffsetSizeTrait,
+};
+use arrow::compute::{cast_with_options, CastOptions};
+use arrow::datatypes::{
+    i256, ArrowPrimitiveType, DataType, Decimal128Type, Decimal256Type, DecimalType, Float32Type,
+    Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, TimeUnit, ToByteSlice, UInt16Type,
+    UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow::util::display::FormatOptions;
+use orc_rust::projection::ProjectionMask;
+use orc_rust::reader::ChunkReader;
+use orc_rust::ArrowReaderBuilder;
+use std::io::Seek;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::{self, Duration};
+
+/// 客户端协议修订号：54429 起设置以字符串传输，服务端不低于该版本即可
+const REVISION: u64 = 54429;
+const CLIENT_NAME: &str = "ck-loader";
+/// 每个数据块的行数，与服务端默认的 max_block_size 一致
+const BLOCK_ROWS: usize = 65536;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// 发送数据失败后等待服务端异常包的时间
+const EXCEPTION_WAIT: Duration = Duration::from_secs(5);
+
+const CLIENT_HELLO: u64 = 0;
+const CLIENT_QUERY: u64 = 1;
+const CLIENT_DATA: u64 = 2;
+const CLIENT_PING: u64 = 4;
+
+const SERVER_HELLO: u64 = 0;
+const SERVER_DATA: u64 = 1;
+const SERVER_EXCEPTION: u64 = 2;
+const SERVER_PROGRESS: u64 = 3;
+const SERVER_PONG: u64 = 4;
+const SERVER_END_OF_STREAM: u64 = 5;
+const SERVER_PROFILE_INFO: u64 = 6;
+const SERVER_TABLE_COLUMNS: u64 = 11;
+
+/// 类型转换失败时报错而不是静默写入 NULL / 默认值
+const CAST: CastOptions<'static> = CastOptions {
+    safe: false,
+    format_options: FormatOptions::new(),
+};
+
+type Connection = (BufReader<OwnedReadHalf>, BufWriter<OwnedWriteHalf>);
+
+/// --reuse-connections 的空闲连接：插入正常结束 (读到 EndOfStream) 的连接放回，下一个文件取用前先 Ping，
+/// 服务端已关闭的连接丢弃后再取下一个或重新建连。出错的连接协议状态未知，不放回
+#[derive(Clone, Default)]
+pub struct Connections {
+    idle: Arc<Mutex<Vec<Connection>>>,
+}
+
+impl fmt::Debug for Connections {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let idle = self.idle.lock().map_or(0, |idle| idle.len());
+        write!(f, "Connections({} idle)", idle)
+    }
+}
+
+impl Connections {
+    async fn take(&self, cfg: &Args, password: &str) -> Result<Connection, ClickHouseError> {
+        loop {
+            let Some(mut conn) = self.idle.lock().ok().and_then(|mut idle| idle.pop()) else {
+                break;
+            };
+            if ping(&mut conn).await {
+                return Ok(conn);
+            }
+        }
+        connect(cfg, password).await
+    }
+
+    /// 空闲连接最多保留 `limit` 个，多余的直接关闭
+    fn put(&self, conn: Connection, limit: usize) {
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < limit {
+                idle.push(conn);
+            }
+        }
+    }
+
+    /// 认证失败 (密码已轮换) 时关闭所有空闲连接，之后用新密码重新建连
+    fn clear(&self) {
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.clear();
+        }
+    }
+}
+
+/// 空闲连接是否仍然可用：服务端超时断开或重启后 Ping 会失败
+async fn ping((reader, writer): &mut Connection) -> bool {
+    let exchange = async {
+        let mut packet = Vec::new();
+        put_varuint(&mut packet, CLIENT_PING);
+        writer.write_all(&packet).await?;
+        writer.flush().await?;
+        read_varuint(reader).await
+    };
+    matches!(
+        time::timeout(CONNECT_TIMEOUT, exchange).await,
+        Ok(Ok(SERVER_PONG))
+    )
+}
+
+/// 导入单个 ORC 文件；`query` 为 `INSERT INTO ... FORMAT Native`，插入结构由服务端返回
+#[allow(clippy::too_many_arguments)]
+pub async fn insert(
+    cfg: &Args,
+    connections: &Connections,
+    table: &str,
+    query: &str,
+    path: &Path,
+    tags: &Tags,
+    query_id: &str,
+    upload: &Arc<Upload>,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let password = cfg.password.get().await?;
+    let result = run(
+        cfg,
+        connections,
+        table,
+        query,
+        path,
+        tags,
+        query_id,
+        upload,
+        &password,
+    )
+    .await;
+    if let Err(e) = &result {
+        if e.is_auth() {
+            cfg.password.invalidate();
+            connections.clear();
+        }
+    }
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run(
+    cfg: &Args,
+    connections: &Connections,
+    table: &str,
+    query: &str,
+    path: &Path,
+    tags: &Tags,
+    query_id: &str,
+    upload: &Arc<Upload>,
+    password: &str,
+) -> Result<Option<InsertSummary>, ClickHouseError> {
+    let (mut reader, mut writer) = if cfg.reuse_connections {
+        connections.take(cfg, password).await?
+    } else {
+        connect(cfg, password).await?
+    };
+
+    let mut settings = cfg.insert_settings(table);
+    // 标签写入 log_comment，便于在 system.query_log 中按标签归类
+    if !tags.is_empty() {
+        settings.push((
+            "log_comment".to_string(),
+            serde_json::to_string(tags).unwrap_or_default(),
+        ));
+    }
+    if let Some((key, token)) = upload.dedup_param() {
+        settings.push((key.to_string(), token));
+    }
+    settings.push((
+        "low_cardinality_allow_in_native_format".to_string(),
+        "0".to_string(),
+    ));
+    writer
+        .write_all(&query_packet(query_id, &settings, query))
+        .await?;
+    // 空块表示外部表数据结束，之后服务端返回插入结构
+    writer.write_all(&empty_block()).await?;
+    writer.flush().await?;
+
+    let header = loop {
+        match read_packet(&mut reader).await? {
+            Packet::Data(columns) => break columns,
+            Packet::EndOfStream => {
+                return Err(ClickHouseError::Transport(
+                    "服务端未返回插入结构就结束了查询".to_string(),
+                ))
+            }
+            Packet::Progress { .. } | Packet::Other => {}
+        }
+    };
+    let columns = header
+        .into_iter()
+        .map(|(name, type_name)| {
+            let type_name = strip_low_cardinality(&type_name);
+            Ok(Column {
+                ty: parse_type(&type_name)?,
+                name,
+                type_name,
+            })
+        })
+        .collect::<Result<Vec<_>>>()
+        .map_err(|e| ClickHouseError::Decode(format!("{:#}", e)))?;
+
+    // 解码与编码在阻塞线程中进行，最多领先发送两个数据块
+    let (tx, rx) = mpsc::channel(2);
+    let source = path.to_path_buf();
+    let conversion = Conversion::new(cfg);
+    let limit = upload.read_limiter().clone();
+    let adjusted = Arc::new(AtomicU64::new(0));
+    let counter = Arc::clone(&adjusted);
+    tokio::task::spawn_blocking(move || {
+        match encode_file(&source, &columns, conversion, limit, &tx) {
+            Ok(n) => counter.store(n, Ordering::Relaxed),
+            Err(e) => {
+                let _ = tx.blocking_send(Err(e));
+            }
+        }
+    });
+
+    let written = {
+        let send = send_blocks(&mut writer, rx, upload);
+        let end = read_end(&mut reader);
+        tokio::pin!(send, end);
+        tokio::select! {
+        res = &mut end => {
+            return Err(res.err().unwrap_or_else(|| {
+                ClickHouseError::Transport("服务端在数据发送完之前结束了插入".to_string())
+            }));
+        }
+        res = &mut send => match res {
+            Ok(()) => end.await?,
+            // 写入失败通常是服务端已返回异常并关闭了连接，优先报告服务端的异常
+            Err(e @ ClickHouseError::Transport(_)) => {
+                return match time::timeout(EXCEPTION_WAIT, end).await {
+                    Ok(Err(server @ ClickHouseError::Server { .. })) => Err(server),
+                    _ => Err(e),
+                };
+            }
+            Err(e) => return Err(e),
+        },
+        }
+    };
+    if cfg.reuse_connections {
+        connections.put((reader, writer), cfg.workers);
+    }
+    Ok(written.map(|(written_rows, written_bytes)| InsertSummary {
+        written_rows,
+        written_bytes,
+        elapsed_secs: None,
+        query_id: Some(query_id.to_string()),
+        // 全部数据块发出后编码线程已经结束，计数已写入
+        overflow_values: cfg
+            .overflow_policy()
+            .map(|_| adjusted.load(Ordering::Relaxed)),
+    }))
+}
+
+/// 建立连接并完成握手；此阶段的失败都还没有写入数据，可以换传输方式重试
+async fn connect(
+    cfg: &Args,
+    password: &str,
+) -> Result<(BufReader<OwnedReadHalf>, BufWriter<OwnedWriteHalf>), ClickHouseError> {
+    let addr = &cfg.native_addr;
+    let stream = match time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            return Err(ClickHouseError::Connect(format!(
+                "无法连接 {}: {}",
+                addr, e
+            )))
+        }
+        Err(_) => return Err(ClickHouseError::Connect(format!("连接 {} 超时", addr))),
+    };
+    let _ = stream.set_nodelay(true);
+    let (reader, writer) = stream.into_split();
+    let (mut reader, mut writer) = (BufReader::new(reader), BufWriter::new(writer));
+    let handshake_failed = |e: io::Error| ClickHouseError::Connect(format!("握手失败: {}", e));
+
+    let mut hello = Vec::new();
+    put_varuint(&mut hello, CLIENT_HELLO);
+    put_str(&mut hello, CLIENT_NAME);
+    put_client_version(&mut hello);
+    put_str(&mut hello, cfg.database.as_deref().unwrap_or(""));
+    put_str(&mut hello, &cfg.user);
+    put_str(&mut hello, password);
+    writer.write_all(&hello).await.map_err(handshake_failed)?;
+    writer.flush().await.map_err(handshake_failed)?;
+
+    match read_varuint(&mut reader).await.map_err(handshake_failed)? {
+        SERVER_HELLO => {}
+        SERVER_EXCEPTION => return Err(read_exception(&mut reader).await?),
+        other => {
+            return Err(ClickHouseError::Connect(format!(
+                "握手失败: 意外的响应包类型 {}",
+                other
+            )))
+        }
+    }
+    let revision = async {
+        read_string(&mut reader).await?;
+        read_varuint(&mut reader).await?;
+        read_varuint(&mut reader).await?;
+        let revision = read_varuint(&mut reader).await?;
+        // 时区、显示名称、补丁版本
+        read_string(&mut reader).await?;
+        read_string(&mut reader).await?;
+        read_varuint(&mut reader).await?;
+        Ok(revision)
+    }
+    .await
+    .map_err(handshake_failed)?;
+    if revision < REVISION {
+        return Err(ClickHouseError::Connect(format!(
+            "服务端协议版本 {} 过旧，native 传输需要 {} 及以上",
+            revision, REVISION
+        )));
+    }
+    Ok((reader, writer))
+}
+
+fn put_client_version(out: &mut Vec<u8>) {
+    put_varuint(out, env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0));
+    put_varuint(out, env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0));
+    put_varuint(out, REVISION);
+}
+
+fn query_packet(query_id: &str, settings: &[(String, String)], query: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_varuint(&mut out, CLIENT_QUERY);
+    put_str(&mut out, query_id);
+
+    // ClientInfo：首次查询 (非分布式转发)，TCP 接口
+    out.push(1);
+    put_str(&mut out, "");
+    put_str(&mut out, "");
+    put_str(&mut out, "0.0.0.0:0");
+    out.push(1);
+    put_str(&mut out, &std::env::var("USER").unwrap_or_default());
+    put_str(&mut out, &std::env::var("HOSTNAME").unwrap_or_default());
+    put_str(&mut out, CLIENT_NAME);
+    put_client_version(&mut out);
+    put_str(&mut out, "");
+    put_varuint(
+        &mut out,
+        env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+    );
+
+    // 设置以 (名称, 标志, 值) 传输，空名称结束；标志 1 表示服务端不认识时报错而不是忽略，与 HTTP 一致
+    for (key, value) in settings {
+        put_str(&mut out, key);
+        put_varuint(&mut out, 1);
+        put_str(&mut out, value);
+    }
+    put_str(&mut out, "");
+
+    // 执行到 Complete 阶段，不压缩
+    put_varuint(&mut out, 2);
+    put_varuint(&mut out, 0);
+    put_str(&mut out, query);
+    out
+}
+
+/// 数据包头：外部表名 (空) + BlockInfo + 列数与行数
+fn put_block_header(out: &mut Vec<u8>, columns: usize, rows: usize) {
+    put_varuint(out, CLIENT_DATA);
+    put_str(out, "");
+    put_varuint(out, 1);
+    out.push(0);
+    put_varuint(out, 2);
+    out.extend((-1i32).to_le_bytes());
+    put_varuint(out, 0);
+    put_varuint(out, columns as u64);
+    put_varuint(out, rows as u64);
+}
+
+fn empty_block() -> Vec<u8> {
+    let mut out = Vec::new();
+    put_block_header(&mut out, 0, 0);
+    out
+}
+
+async fn send_blocks(
+    writer: &mut BufWriter<OwnedWriteHalf>,
+    mut blocks: mpsc::Receiver<Result<Vec<u8>>>,
+    upload: &Arc<Upload>,
+) -> Result<(), ClickHouseError> {
+    let _sending = upload.sending();
+    while let Some(block) = blocks.recv().await {
+        let block = block.map_err(|e| ClickHouseError::Decode(format!("{:#}", e)))?;
+        writer.write_all(&block).await?;
+        upload.sent(block.len() as u64);
+    }
+    // 空块表示数据结束
+    writer.write_all(&empty_block()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// 读到 EndOfStream 为止，累计 Progress 中的写入行数与字节数；服务端没有返回进度时为 None
+async fn read_end(
+    reader: &mut BufReader<OwnedReadHalf>,
+) -> Result<Option<(u64, u64)>, ClickHouseError> {
+    let mut written: Option<(u64, u64)> = None;
+    loop {
+        match read_packet(reader).await? {
+            Packet::Progress { rows, bytes } => {
+                let (r, b) = written.get_or_insert((0, 0));
+                *r += rows;
+                *b += bytes;
+            }
+            Packet::EndOfStream => return Ok(written),
+            Packet::Data(_) | Packet::Other => {}
+        }
+    }
+}
+
+enum Packet {
+    /// 数据块只解析列名与类型 (插入结构的表头块没有行)
+    Data(Vec<(String, String)>),
+    /// 本次增量写入的行数与字节数
+    Progress {
+        rows: u64,
+        bytes: u64,
+    },
+    EndOfStream,
+    Other,
+}
+
+/// 读取一个服务端包；Exception 包作为错误返回
+async fn read_packet(reader: &mut BufReader<OwnedReadHalf>) -> Result<Packet, ClickHouseError> {
+    let kind = read_varuint(reader).await?;
+    Ok(match kind {
+        SERVER_DATA => {
+            read_string(reader).await?;
+            // BlockInfo：字段号 1 为 u8，2 为 i32，0 结束
+            loop {
+                match read_varuint(reader).await? {
+                    0 => break,
+                    1 => {
+                        reader.read_u8().await?;
+                    }
+                    2 => {
+                        reader.read_i32_le().await?;
+                    }
+                    field => {
+                        return Err(ClickHouseError::Transport(format!(
+                            "无法解析服务端数据块 (BlockInfo 字段 {})",
+                            field
+                        )))
+                    }
+                }
+            }
+            let columns = read_varuint(reader).await?;
+            let rows = read_varuint(reader).await?;
+            if rows > 0 {
+                return Err(ClickHouseError::Transport(
+                    "插入时服务端意外返回了数据行".to_string(),
+                ));
+            }
+            let mut header = Vec::new();
+            for _ in 0..columns {
+                header.push((read_string(reader).await?, read_string(reader).await?));
+            }
+            Packet::Data(header)
+        }
+        SERVER_EXCEPTION => return Err(read_exception(reader).await?),
+        SERVER_PROGRESS => {
+            // 读取行数、读取字节数、预计总行数、写入行数、写入字节数
+            for _ in 0..3 {
+                read_varuint(reader).await?;
+            }
+            Packet::Progress {
+                rows: read_varuint(reader).await?,
+                bytes: read_varuint(reader).await?,
+            }
+        }
+        SERVER_END_OF_STREAM => Packet::EndOfStream,
+        SERVER_PROFILE_INFO => {
+            for _ in 0..3 {
+                read_varuint(reader).await?;
+            }
+            reader.read_u8().await?;
+            read_varuint(reader).await?;
+            reader.read_u8().await?;
+            Packet::Other
+        }
+        SERVER_TABLE_COLUMNS => {
+            read_string(reader).await?;
+            read_string(reader).await?;
+            Packet::Other
+        }
+        other => {
+            return Err(ClickHouseError::Transport(format!(
+                "无法处理的服务端包类型 {}",
+                other
+            )))
+        }
+    })
+}
+
+/// Exception 包：错误码、类名、消息、堆栈，嵌套的异常依次跟在后面
+async fn read_exception<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<ClickHouseError, ClickHouseError> {
+    let mut first: Option<(u32, String)> = None;
+    let mut raw = String::new();
+    loop {
+        let code = reader.read_i32_le().await? as u32;
+        let name = read_string(reader).await?;
+        let message = read_string(reader).await?;
+        let stack = read_string(reader).await?;
+        let nested = reader.read_u8().await? != 0;
+        let message = message
+            .strip_prefix("DB::Exception: ")
+            .unwrap_or(&message)
+            .to_string();
+        raw.push_str(&format!(
+            "Code: {}. {}: {}\n{}\n",
+            code, name, message, stack
+        ));
+        first.get_or_insert((code, message));
+        if !nested {
+            break;
+        }
+    }
+    let (code, message) = first.unwrap_or_default();
+    Ok(ClickHouseError::from_native(code, &message, raw))
+}
+
+fn put_varuint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_bytes(out, s.as_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_varuint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+async fn read_varuint<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = reader.read_u8().await?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "变长整数过长"))
+}
+
+async fn read_string<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<String> {
+    let len = read_varuint(reader).await?;
+    if len > 1 << 30 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "字符串过长"));
+    }
+    let mut buf = vec![0; len as usize];
+    reader.read_exact(&mut buf).await?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// 插入结构中的一列；`type_name` 是去掉 LowCardinality 后实际发送的类型名
+struct Column {
+    name: String,
+    type_name: String,
+    ty: Type,
+}
+
+/// 支持编码的 ClickHouse 类型
+#[derive(Debug)]
+enum Type {
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float32,
+    Float64,
+    Bool,
+    String,
+    FixedString(usize),
+    Uuid,
+    Date,
+    Date32,
+    DateTime,
+    DateTime64(u32),
+    Decimal(u8, i8),
+    /// Enum8 / Enum16：是否为 16 位，名称到值的映射
+    Enum(bool, Vec<(String, i16)>),
+    Nullable(Box<Type>),
+    Array(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+    /// 元素名 (命名元组) 与类型
+    Tuple(Vec<(Option<String>, Type)>),
+}
+
+fn parse_type(s: &str) -> Result<Type> {
+    let s = s.trim();
+    let (head, args) = match s.find('(') {
+        Some(i) if s.ends_with(')') => (&s[..i], split_args(&s[i + 1..s.len() - 1])),
+        _ => (s, Vec::new()),
+    };
+    let arg = |i: usize| -> Result<&str> {
+        args.get(i)
+            .copied()
+            .with_context(|| format!("无法解析类型: {}", s))
+    };
+    Ok(match head {
+        "Int8" => Type::Int8,
+        "Int16" => Type::Int16,
+        "Int32" => Type::Int32,
+        "Int64" => Type::Int64,
+        "UInt8" => Type::UInt8,
+        "UInt16" => Type::UInt16,
+        "UInt32" => Type::UInt32,
+        "UInt64" => Type::UInt64,
+        "Float32" => Type::Float32,
+        "Float64" => Type::Float64,
+        "Bool" | "Boolean" => Type::Bool,
+        "String" => Type::String,
+        "FixedString" => Type::FixedString(arg(0)?.parse()?),
+        "UUID" => Type::Uuid,
+        "Date" => Type::Date,
+        "Date32" => Type::Date32,
+        "DateTime" => Type::DateTime,
+        "DateTime64" => {
+            let precision: u32 = arg(0)?.parse()?;
+            if precision > 9 {
+                bail!("DateTime64 精度超出范围: {}", s);
+            }
+            Type::DateTime64(precision)
+        }
+        "Decimal" => Type::Decimal(arg(0)?.parse()?, arg(1)?.parse()?),
+        "Decimal32" => Type::Decimal(9, arg(0)?.parse()?),
+        "Decimal64" => Type::Decimal(18, arg(0)?.parse()?),
+        "Decimal128" => Type::Decimal(38, arg(0)?.parse()?),
+        "Decimal256" => Type::Decimal(76, arg(0)?.parse()?),
+        "Enum8" | "Enum16" => {
+            let values = args
+                .iter()
+                .map(|a| {
+                    let (name, value) = a
+                        .rsplit_once('=')
+                        .with_context(|| format!("无法解析枚举值: {}", a))?;
+                    let name = name.trim();
+                    let name = name
+                        .strip_prefix('\'')
+                        .and_then(|n| n.strip_suffix('\''))
+                        .unwrap_or(name)
+                        .replace("\\'", "'");
+                    Ok((name, value.trim().parse()?))
+                })
+                .collect::<Result<_>>()?;
+            Type::Enum(head == "Enum16", values)
+        }
+        "Nullable" => Type::Nullable(Box::new(parse_type(arg(0)?)?)),
+        "LowCardinality" => parse_type(arg(0)?)?,
+        "Array" => Type::Array(Box::new(parse_type(arg(0)?)?)),
+        "Map" => Type::Map(
+            Box::new(parse_type(arg(0)?)?),
+            Box::new(parse_type(arg(1)?)?),
+        ),
+        "Tuple" => Type::Tuple(
+            args.iter()
+                .map(|element| {
+                    // 命名元组的元素为 `name Type`，类型名的括号之前不会有空格
+                    let prefix = element.split('(').next().unwrap_or_default();
+                    match prefix.split_once(' ') {
+                        Some((name, _)) => Ok((
+                            Some(name.trim_matches('`').to_string()),
+                            parse_type(&element[name.len()..])?,
+                        )),
+                        None => Ok((None, parse_type(element)?)),
+                    }
+                })
+                .collect::<Result<_>>()?,
+        ),
+        _ => bail!("native 传输暂不支持该类型: {}", s),
+    })
+}
+
+/// 按顶层逗号拆分类型参数，忽略括号与单引号内的逗号
+fn split_args(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    let (mut quoted, mut escaped) = (false, false);
+    for (i, c) in s.char_indices() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '\'' => quoted = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '\'' => quoted = true,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !s[start..].trim().is_empty() {
+        parts.push(s[start..].trim());
+    }
+    parts
+}
+
+/// `LowCardinality(Nullable(String))` → `Nullable(String)`
+fn strip_low_cardinality(type_name: &str) -> String {
+    const PREFIX: &str = "LowCardinality(";
+    let mut out = String::with_capacity(type_name.len());
+    // 每层括号的右括号是否属于 LowCardinality
+    let mut closes = Vec::new();
+    let mut rest = type_name;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix(PREFIX) {
+            closes.push(true);
+            rest = after;
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '(' => closes.push(false),
+            ')' if closes.pop() == Some(true) => continue,
+            _ => {}
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// ORC 列名与插入结构按名称对应，大小写不同时也能匹配
+fn find_column<'a>(names: &[&'a str], name: &str) -> Option<&'a str> {
+    names
+        .iter()
+        .find(|n| **n == name)
+        .or_else(|| names.iter().find(|n| n.eq_ignore_ascii_case(name)))
+        .copied()
+}
+
+/// 越界时间戳 / Decimal 的处理：未指定策略时报错，不静默改写数据
+struct Conversion {
+    timestamp: OverflowPolicy,
+    decimal: OverflowPolicy,
+    /// 按策略截断或置空的值个数
+    adjusted: u64,
+}
+
+impl Conversion {
+    fn new(cfg: &Args) -> Self {
+        Self {
+            timestamp: cfg.timestamp_overflow.unwrap_or(OverflowPolicy::Fail),
+            decimal: cfg.decimal_overflow.unwrap_or(OverflowPolicy::Fail),
+            adjusted: 0,
+        }
+    }
+
+    /// 把 `value` 限制在 `min..=max` 内；返回 None 表示置空
+    fn fit<T: Ord + Copy>(
+        &mut self,
+        policy: OverflowPolicy,
+        value: T,
+        (min, max): (T, T),
+        describe: impl FnOnce() -> String,
+    ) -> Result<Option<T>> {
+        if (min..=max).contains(&value) {
+            return Ok(Some(value));
+        }
+        match policy {
+            OverflowPolicy::Fail => bail!("{}", describe()),
+            OverflowPolicy::Clamp => {
+                self.adjusted += 1;
+                Ok(Some(value.clamp(min, max)))
+            }
+            OverflowPolicy::Null => {
+                self.adjusted += 1;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// 按 --max-read-mbps 限速读取的 ORC 文件
+struct LimitedFile {
+    file: std::fs::File,
+    limit: ReadLimit,
+}
+
+impl ChunkReader for LimitedFile {
+    type T = Throttled<std::io::BufReader<std::fs::File>>;
+
+    fn len(&self) -> u64 {
+        self.file.metadata().map_or(0, |m| m.len())
+    }
+
+    fn get_read(&self, offset_from_start: u64) -> io::Result<Self::T> {
+        let mut file = self.file.try_clone()?;
+        file.seek(io::SeekFrom::Start(offset_from_start))?;
+        Ok(self.limit.reader(std::io::BufReader::new(file)))
+    }
+}
+
+/// 在阻塞线程中解码 ORC 并编码为数据块，按顺序发送给连接；接收端关闭 (导入被中断) 时停止。
+/// 返回按策略处理的越界值个数
+fn encode_file(
+    path: &Path,
+    columns: &[Column],
+    mut conversion: Conversion,
+    limit: ReadLimit,
+    tx: &mpsc::Sender<Result<Vec<u8>>>,
+) -> Result<u64> {
+    let file = std::fs::File::open(path).with_context(|| format!("无法打开文件: {:?}", path))?;
+    let file = LimitedFile { file, limit };
+    let builder = ArrowReaderBuilder::try_new(file).context("无法读取 ORC 元数据")?;
+    let schema = builder.schema();
+    let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    let sources = columns
+        .iter()
+        .map(|c| {
+            find_column(&names, &c.name).with_context(|| {
+                format!(
+                    "ORC 文件中没有列 {}，native 传输无法按表定义补默认值 (可用 --columns 只写入文件中存在的列)",
+                    c.name
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let projection =
+        ProjectionMask::named_roots(builder.file_metadata().root_data_type(), &sources);
+    let reader = builder
+        .with_projection(projection)
+        .with_batch_size(BLOCK_ROWS)
+        .build();
+
+    for batch in reader {
+        let batch = batch.context("ORC 解码失败")?;
+        // 空块表示数据结束，不能发送
+        if batch.num_rows() == 0 {
+            continue;
+        }
+        let mut block = Vec::new();
+        put_block_header(&mut block, columns.len(), batch.num_rows());
+        for (column, source) in columns.iter().zip(&sources) {
+            let array = batch
+                .column_by_name(source)
+                .with_context(|| format!("ORC 批次中缺少列 {}", source))?;
+            put_str(&mut block, &column.name);
+            put_str(&mut block, &column.type_name);
+            encode(&mut block, &column.ty, array, &mut conversion).with_context(|| {
+                format!(
+                    "列 {} ({}) 无法转换为 {}",
+                    column.name,
+                    array.data_type(),
+                    column.type_name
+                )
+            })?;
+        }
+        if tx.blocking_send(Ok(block)).is_err() {
+            break;
+        }
+    }
+    Ok(conversion.adjusted)
+}
+
+fn cast(array: &ArrayRef, to: &DataType) -> Result<ArrayRef> {
+    Ok(cast_with_options(array, to, &CAST)?)
+}
+
+/// 时间戳的可读形式，用于越界报错
+fn show_time(nanos: i128) -> String {
+    let secs = nanos.div_euclid(1_000_000_000) as i64;
+    let nanos = nanos.rem_euclid(1_000_000_000) as u32;
+    chrono::DateTime::from_timestamp(secs, nanos)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_else(|| format!("{} 秒", secs))
+}
+
+/// 时间与 Decimal 列先转换为存储值 (天数 / 秒数 / 刻度值、目标精度的 Decimal) 并按越界策略处理，
+/// 置空的值体现为返回数组中的 NULL；其他类型原样返回
+fn conform(ty: &Type, array: &ArrayRef, conversion: &mut Conversion) -> Result<ArrayRef> {
+    const DAY_NANOS: i128 = 86_400 * 1_000_000_000;
+    let policy = conversion.timestamp;
+    let storage = |array: &ArrayRef, unit: &DataType| -> Result<Vec<Option<i64>>> {
+        let values = cast(array, unit)?;
+        let values = cast(&values, &DataType::Int64)?;
+        let values = values.as_primitive::<Int64Type>();
+        Ok((0..values.len())
+            .map(|i| values.is_valid(i).then(|| values.value(i)))
+            .collect())
+    };
+    let fitted: Vec<Option<i64>> = match ty {
+        Type::Date | Type::Date32 => {
+            // Date 为 1970-01-01 起的 u16 天数，Date32 为 1900-01-01 ~ 2299-12-31
+            let range = match ty {
+                Type::Date => (0, u16::MAX as i64),
+                _ => (-25_567, 120_529),
+            };
+            storage(array, &DataType::Date32)?
+                .into_iter()
+                .map(|day| match day {
+                    Some(day) => conversion.fit(policy, day, range, || {
+                        format!(
+                            "日期 {} 超出 {:?} 的范围",
+                            show_time(day as i128 * DAY_NANOS),
+                            ty
+                        )
+                    }),
+                    None => Ok(None),
+                })
+                .collect::<Result<_>>()?
+        }
+        Type::DateTime => storage(array, &DataType::Timestamp(TimeUnit::Second, None))?
+            .into_iter()
+            .map(|sec| match sec {
+                Some(sec) => conversion.fit(policy, sec, (0, u32::MAX as i64), || {
+                    format!(
+                        "时间 {} 超出 DateTime 的范围",
+                        show_time(sec as i128 * 1_000_000_000)
+                    )
+                }),
+                None => Ok(None),
+            })
+            .collect::<Result<_>>()?,
+        Type::DateTime64(precision) => {
+            // 1900-01-01 00:00:00 ~ 2299-12-31 23:59:59.999999999
+            let ticks = 10i64.pow(*precision);
+            let range = (-2_208_988_800 * ticks, 10_413_792_000 * ticks - 1);
+            let scale = 10i64.pow(9 - precision);
+            storage(array, &DataType::Timestamp(TimeUnit::Nanosecond, None))?
+                .into_iter()
+                .map(|ns| match ns {
+                    Some(ns) => conversion.fit(policy, ns.div_euclid(scale), range, || {
+                        format!("时间 {} 超出 DateTime64 的范围", show_time(ns as i128))
+                    }),
+                    None => Ok(None),
+                })
+                .collect::<Result<_>>()?
+        }
+        Type::Decimal(precision, scale) if *precision <= 38 => {
+            let values = cast_decimal(array, DataType::Decimal128(38, *scale), conversion)?;
+            let values = values.as_primitive::<Decimal128Type>();
+            let max = 10i128.pow(*precision as u32) - 1;
+            let policy = conversion.decimal;
+            let fitted = (0..values.len())
+                .map(|i| match values.is_valid(i).then(|| values.value(i)) {
+                    Some(v) => conversion.fit(policy, v, (-max, max), || {
+                        format!(
+                            "值 {} 超出 Decimal({}, {}) 的范围",
+                            Decimal128Type::format_decimal(v, 38, *scale),
+                            precision,
+                            scale
+                        )
+                    }),
+                    None => Ok(None),
+                })
+                .collect::<Result<Decimal128Array>>()?;
+            return Ok(Arc::new(
+                fitted.with_precision_and_scale(*precision, *scale)?,
+            ));
+        }
+        Type::Decimal(precision, scale) => {
+            let precision = (*precision).min(76);
+            let values = cast_decimal(array, DataType::Decimal256(76, *scale), conversion)?;
+            let values = values.as_primitive::<Decimal256Type>();
+            let max = i256::from_i128(10)
+                .pow_wrapping(precision as u32)
+                .sub_wrapping(i256::ONE);
+            let policy = conversion.decimal;
+            let fitted = (0..values.len())
+                .map(|i| match values.is_valid(i).then(|| values.value(i)) {
+                    Some(v) => conversion.fit(policy, v, (max.neg_wrapping(), max), || {
+                        format!("值超出 Decimal({}, {}) 的范围", precision, scale)
+                    }),
+                    None => Ok(None),
+                })
+                .collect::<Result<Decimal256Array>>()?;
+            return Ok(Arc::new(
+                fitted.with_precision_and_scale(precision, *scale)?,
+            ));
+        }
+        _ => return Ok(Arc::clone(array)),
+    };
+    Ok(Arc::new(Int64Array::from(fitted)))
+}
+
+/// 先转换到最宽的精度再按目标精度检查越界；连最宽精度都放不下时，null 策略置空，其余报错
+fn cast_decimal(array: &ArrayRef, to: DataType, conversion: &mut Conversion) -> Result<ArrayRef> {
+    if conversion.decimal != OverflowPolicy::Null {
+        return cast(array, &to);
+    }
+    let options = CastOptions { safe: true, ..CAST };
+    let values = cast_with_options(array, &to, &options)?;
+    conversion.adjusted += values.null_count().saturating_sub(array.null_count()) as u64;
+    Ok(values)
+}
+
+/// 按列式格式写入整列；非 Nullable 列中的 NULL 写为默认值
+fn encode(
+    out: &mut Vec<u8>,
+    ty: &Type,
+    array: &ArrayRef,
+    conversion: &mut Conversion,
+) -> Result<()> {
+    if let Type::Nullable(inner) = ty {
+        // 越界置空的值也要体现在 NULL 标记中，先转换再写标记
+        let array = conform(inner, array, conversion)?;
+        let nulls = array.logical_nulls();
+        out.extend(
+            (0..array.len()).map(|i| u8::from(nulls.as_ref().is_some_and(|n| n.is_null(i)))),
+        );
+        return encode_values(out, inner, &array, conversion);
+    }
+    let array = conform(ty, array, conversion)?;
+    encode_values(out, ty, &array, conversion)
+}
+
+/// 写入 conform 之后的值
+fn encode_values(
+    out: &mut Vec<u8>,
+    ty: &Type,
+    array: &ArrayRef,
+    conversion: &mut Conversion,
+) -> Result<()> {
+    match ty {
+        Type::Int8 => put_fixed::<Int8Type>(out, array),
+        Type::Int16 => put_fixed::<Int16Type>(out, array),
+        Type::Int32 => put_fixed::<Int32Type>(out, array),
+        Type::Int64 => put_fixed::<Int64Type>(out, array),
+        Type::UInt8 => put_fixed::<UInt8Type>(out, array),
+        Type::UInt16 => put_fixed::<UInt16Type>(out, array),
+        Type::UInt32 => put_fixed::<UInt32Type>(out, array),
+        Type::UInt64 => put_fixed::<UInt64Type>(out, array),
+        Type::Float32 => put_fixed::<Float32Type>(out, array),
+        Type::Float64 => put_fixed::<Float64Type>(out, array),
+        // 时间类型已转换为范围内的存储值
+        Type::Date => put_fixed::<UInt16Type>(out, array),
+        Type::Date32 => put_fixed::<Int32Type>(out, array),
+        Type::DateTime => put_fixed::<UInt32Type>(out, array),
+        Type::DateTime64(_) => put_fixed::<Int64Type>(out, array),
+        Type::Bool => {
+            let values = cast(array, &DataType::Boolean)?;
+            let values = values.as_boolean();
+            out.extend((0..values.len()).map(|i| u8::from(values.is_valid(i) && values.value(i))));
+            Ok(())
+        }
+        Type::String => each_bytes(array, |value| {
+            put_bytes(out, value.unwrap_or_default());
+            Ok(())
+        }),
+        Type::FixedString(n) => each_bytes(array, |value| {
+            let value = value.unwrap_or_default();
+            if value.len() > *n {
+                bail!("值的长度 {} 超过 FixedString({})", value.len(), n);
+            }
+            out.extend_from_slice(value);
+            out.resize(out.len() + n - value.len(), 0);
+            Ok(())
+        }),
+        Type::Uuid => each_bytes(array, |value| {
+            let uuid = match value {
+                Some(value) => {
+                    let text = String::from_utf8_lossy(value).replace('-', "");
+                    if text.len() != 32 {
+                        bail!("无效的 UUID: {}", String::from_utf8_lossy(value));
+                    }
+                    u128::from_str_radix(&text, 16)
+                        .with_context(|| format!("无效的 UUID: {}", text))?
+                }
+                None => 0,
+            };
+            // 高 64 位在前，两半各自小端
+            out.extend(((uuid >> 64) as u64).to_le_bytes());
+            out.extend((uuid as u64).to_le_bytes());
+            Ok(())
+        }),
+        Type::Enum(wide, values) => each_bytes(array, |value| {
+            let value = match value {
+                Some(value) => {
+                    let name = String::from_utf8_lossy(value);
+                    match values.iter().find(|(n, _)| *n == name) {
+                        Some((_, v)) => *v,
+                        None => name
+                            .parse()
+                            .ok()
+                            .filter(|v| values.iter().any(|(_, x)| x == v))
+                            .with_context(|| format!("枚举中没有值 {}", name))?,
+                    }
+                }
+                None => values.first().map(|(_, v)| *v).unwrap_or_default(),
+            };
+            if *wide {
+                out.extend(value.to_le_bytes());
+            } else {
+                out.push(value as i8 as u8);
+            }
+            Ok(())
+        }),
+        Type::Decimal(precision, _) if *precision <= 38 => {
+            let values = array.as_primitive::<Decimal128Type>();
+            for i in 0..values.len() {
+                let v = if values.is_null(i) {
+                    0
+                } else {
+                    values.value(i)
+                };
+                // 存储宽度由精度决定：Decimal32 / Decimal64 / Decimal128
+                match precision {
+                    0..=9 => out.extend((v as i32).to_le_bytes()),
+                    10..=18 => out.extend((v as i64).to_le_bytes()),
+                    _ => out.extend(v.to_le_bytes()),
+                }
+            }
+            Ok(())
+        }
+        Type::Decimal(..) => {
+            let values = array.as_primitive::<Decimal256Type>();
+            for i in 0..values.len() {
+                let v = if values.is_null(i) {
+                    i256::ZERO
+                } else {
+                    values.value(i)
+                };
+                out.extend(v.to_le_bytes());
+            }
+            Ok(())
+        }
+        Type::Nullable(_) => encode(out, ty, array, conversion),
+        Type::Array(inner) => {
+            let (offsets, values) = match array.data_type() {
+                DataType::List(_) => list_parts(
+                    array.as_list::<i32>().value_offsets(),
+                    array.as_list::<i32>().values(),
+                ),
+                DataType::LargeList(_) => list_parts(
+                    array.as_list::<i64>().value_offsets(),
+                    array.as_list::<i64>().values(),
+                ),
+                other => bail!("{} 不是列表", other),
+            };
+            out.extend(offsets.iter().flat_map(|o| o.to_le_bytes()));
+            encode(out, inner, &values, conversion)
+        }
+        Type::Map(key, value) => {
+            let (offsets, entries) = match array.data_type() {
+                DataType::Map(..) => {
+                    let map = array.as_map();
+                    let entries: ArrayRef = Arc::new(map.entries().clone());
+                    list_parts(map.value_offsets(), &entries)
+                }
+                DataType::List(_) => list_parts(
+                    array.as_list::<i32>().value_offsets(),
+                    array.as_list::<i32>().values(),
+                ),
+                other => bail!("{} 不是映射", other),
+            };
+            let entries = entries
+                .as_struct_opt()
+                .filter(|s| s.num_columns() == 2)
+                .context("映射的元素不是键值对")?;
+            out.extend(offsets.iter().flat_map(|o| o.to_le_bytes()));
+            encode(out, key, entries.column(0), conversion)?;
+            encode(out, value, entries.column(1), conversion)
+        }
+        Type::Tuple(elements) => {
+            let fields = array
+                .as_struct_opt()
+                .with_context(|| format!("{} 不是结构体", array.data_type()))?;
+            for (i, (name, ty)) in elements.iter().enumerate() {
+                let field = name
+                    .as_deref()
+                    .and_then(|n| fields.column_by_name(n))
+                    .or_else(|| fields.columns().get(i))
+                    .with_context(|| format!("结构体缺少第 {} 个元素", i + 1))?;
+                encode(out, ty, field, conversion)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 定长数值直接按小端写入值缓冲区
+fn put_fixed<T: ArrowPrimitiveType>(out: &mut Vec<u8>, array: &ArrayRef) -> Result<()> {
+    let values = cast(array, &T::DATA_TYPE)?;
+    let values = values.as_primitive::<T>();
+    if values.null_count() == 0 {
+        out.extend_from_slice(values.values().inner().as_slice());
+    } else {
+        for i in 0..values.len() {
+            let v = if values.is_null(i) {
+                T::Native::default()
+            } else {
+                values.value(i)
+            };
+            out.extend_from_slice(v.to_byte_slice());
+        }
+    }
+    Ok(())
+}
+
+/// 依次取出每个值的字节 (二进制原样，其他类型先转为字符串)，NULL 为 None
+fn each_bytes(array: &ArrayRef, mut f: impl FnMut(Option<&[u8]>) -> Result<()>) -> Result<()> {
+    let array = match array.data_type() {
+        DataType::Binary | DataType::LargeBinary | DataType::Utf8 | DataType::LargeUtf8 => {
+            Arc::clone(array)
+        }
+        _ => cast(array, &DataType::Utf8)?,
+    };
+    let values = cast(&array, &DataType::LargeBinary)?;
+    let values = values.as_binary::<i64>();
+    for i in 0..values.len() {
+        f(values.is_valid(i).then(|| values.value(i)))?;
+    }
+    Ok(())
+}
+
+/// 列表的各行结束位置 (从 0 开始累计) 与对应的元素切片
+fn list_parts<O: OffsetSizeTrait>(offsets: &[O], values: &ArrayRef) -> (Vec<u64>, ArrayRef) {
+    let first = offsets.first().map_or(0, |o| o.as_usize());
+    let last = offsets.last().map_or(0, |o| o.as_usize());
+    let ends = offsets[1.min(offsets.len())..]
+        .iter()
+        .map(|o| (o.as_usize() - first) as u64)
+        .collect();
+    (ends, values.slice(first, last - first))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{
+        BooleanArray, Date32Array, Int32Array, ListArray, StringArray, TimestampSecondArray,
+    };
+
+    fn conversion(policy: OverflowPolicy) -> Conversion {
+        Conversion {
+            timestamp: policy,
+            decimal: policy,
+            adjusted: 0,
+        }
+    }
+
+    /// 按类型名编码一整列
+    fn column(type_name: &str, array: ArrayRef) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let ty = parse_type(type_name)?;
+        encode(&mut out, &ty, &array, &mut conversion(OverflowPolicy::Fail))?;
+        Ok(out)
+    }
+
+    #[tokio::test]
+    async fn varuint() {
+        for (value, bytes) in [
+            (0u64, vec![0x00]),
+            (127, vec![0x7f]),
+            (128, vec![0x80, 0x01]),
+            (300, vec![0xac, 0x02]),
+            (54429, vec![0x9d, 0xa9, 0x03]),
+        ] {
+            let mut out = Vec::new();
+            put_varuint(&mut out, value);
+            assert_eq!(out, bytes, "{}", value);
+            assert_eq!(read_varuint(&mut out.as_slice()).await.unwrap(), value);
+        }
+        let mut out = Vec::new();
+        put_varuint(&mut out, u64::MAX);
+        assert_eq!(out.len(), 10);
+        assert_eq!(read_varuint(&mut out.as_slice()).await.unwrap(), u64::MAX);
+        // 超过 10 字节仍有续位的变长整数报错
+        assert!(read_varuint(&mut [0xff; 11].as_slice()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn strings() {
+        let mut out = Vec::new();
+        put_str(&mut out, "ck");
+        put_str(&mut out, "");
+        put_str(&mut out, "导入");
+        assert_eq!(&out[..4], &[2, b'c', b'k', 0]);
+        assert_eq!(out[4], 6);
+        let mut reader = out.as_slice();
+        assert_eq!(read_string(&mut reader).await.unwrap(), "ck");
+        assert_eq!(read_string(&mut reader).await.unwrap(), "");
+        assert_eq!(read_string(&mut reader).await.unwrap(), "导入");
+        // 长度大于剩余字节时读取失败
+        assert!(read_string(&mut [5, b'a'].as_slice()).await.is_err());
+    }
+
+    #[test]
+    fn block_header() {
+        let mut out = Vec::new();
+        put_block_header(&mut out, 2, 300);
+        assert_eq!(
+            out,
+            [2, 0, 1, 0, 2, 0xff, 0xff, 0xff, 0xff, 0, 2, 0xac, 0x02]
+        );
+        assert_eq!(
+            empty_block(),
+            [2, 0, 1, 0, 2, 0xff, 0xff, 0xff, 0xff, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn fixed_width_columns() {
+        let ints: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None, Some(-2)]));
+        // 非 Nullable 列中的 NULL 写为默认值
+        assert_eq!(
+            column("Int32", Arc::clone(&ints)).unwrap(),
+            [1, 0, 0, 0, 0, 0, 0, 0, 0xfe, 0xff, 0xff, 0xff]
+        );
+        // Nullable 先写 NULL 标记再写值
+        assert_eq!(
+            column("Nullable(Int16)", ints).unwrap(),
+            [0, 1, 0, 1, 0, 0, 0, 0xfe, 0xff]
+        );
+        let flags: ArrayRef = Arc::new(BooleanArray::from(vec![true, false]));
+        assert_eq!(column("Bool", flags).unwrap(), [1, 0]);
+        let decimals: ArrayRef = Arc::new(
+            Decimal128Array::from(vec![12345i128, -1])
+                .with_precision_and_scale(10, 2)
+                .unwrap(),
+        );
+        // Decimal(9, 2) 以 Int32 存储刻度值
+        assert_eq!(
+            column("Decimal(9, 2)", decimals).unwrap(),
+            [0x39, 0x30, 0, 0, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn string_columns() {
+        let names: ArrayRef = Arc::new(StringArray::from(vec![Some("ab"), None, Some("c")]));
+        assert_eq!(
+            column("String", Arc::clone(&names)).unwrap(),
+            [2, b'a', b'b', 0, 1, b'c']
+        );
+        assert_eq!(
+            column("LowCardinality(Nullable(String))", Arc::clone(&names)).unwrap(),
+            [0, 1, 0, 2, b'a', b'b', 0, 1, b'c']
+        );
+        assert_eq!(
+            column("FixedString(3)", names).unwrap(),
+            [b'a', b'b', 0, 0, 0, 0, b'c', 0, 0]
+        );
+        let long: ArrayRef = Arc::new(StringArray::from(vec!["abcd"]));
+        assert!(column("FixedString(3)", long).is_err());
+
+        let uuids: ArrayRef = Arc::new(StringArray::from(vec![
+            "00112233-4455-6677-8899-aabbccddeeff",
+        ]));
+        assert_eq!(
+            column("UUID", uuids).unwrap(),
+            [
+                0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0x00, 0xff, 0xee, 0xdd, 0xcc, 0xbb, 0xaa,
+                0x99, 0x88
+            ]
+        );
+
+        let enums: ArrayRef = Arc::new(StringArray::from(vec!["b", "1", "it's"]));
+        assert_eq!(
+            column("Enum8('a' = 1, 'b' = -2, 'it\\'s' = 3)", Arc::clone(&enums)).unwrap(),
+            [0xfe, 1, 3]
+        );
+        assert_eq!(
+            column("Enum16('a' = 1, 'b' = 258, 'it\\'s' = 3)", enums).unwrap(),
+            [2, 1, 1, 0, 3, 0]
+        );
+        let unknown: ArrayRef = Arc::new(StringArray::from(vec!["x"]));
+        assert!(column("Enum8('a' = 1)", unknown).is_err());
+    }
+
+    #[test]
+    fn time_columns() {
+        let days: ArrayRef = Arc::new(Date32Array::from(vec![1, 19_000]));
+        assert_eq!(
+            column("Date", Arc::clone(&days)).unwrap(),
+            [1, 0, 0x38, 0x4a]
+        );
+        assert_eq!(
+            column("Date32", days).unwrap(),
+            [1, 0, 0, 0, 0x38, 0x4a, 0, 0]
+        );
+
+        let secs: ArrayRef = Arc::new(TimestampSecondArray::from(vec![1, -1]));
+        // 越界时间默认报错，clamp 截断到范围内，null 置空
+        assert!(column("DateTime", Arc::clone(&secs)).is_err());
+        let ty = parse_type("DateTime").unwrap();
+        let mut clamp = conversion(OverflowPolicy::Clamp);
+        let mut out = Vec::new();
+        encode(&mut out, &ty, &secs, &mut clamp).unwrap();
+        assert_eq!(out, [1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(clamp.adjusted, 1);
+        let ty = parse_type("Nullable(DateTime)").unwrap();
+        let mut null = conversion(OverflowPolicy::Null);
+        let mut out = Vec::new();
+        encode(&mut out, &ty, &secs, &mut null).unwrap();
+        assert_eq!(out, [0, 1, 1, 0, 0, 0, 0, 0, 0, 0]);
+
+        let millis: ArrayRef = Arc::new(TimestampSecondArray::from(vec![2]));
+        assert_eq!(
+            column("DateTime64(3)", millis).unwrap(),
+            2000i64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn array_columns() {
+        let lists: ArrayRef = Arc::new(ListArray::from_iter_primitive::<UInt8Type, _, _>(vec![
+            Some(vec![Some(1), Some(2)]),
+            Some(vec![]),
+            Some(vec![Some(3)]),
+        ]));
+        // 先写各行的累计结束位置 (UInt64)，再写元素
+        let mut expected = Vec::new();
+        for end in [2u64, 2, 3] {
+            expected.extend(end.to_le_bytes());
+        }
+        expected.extend([1, 2, 3]);
+        assert_eq!(
+            column("Array(UInt8)", Arc::clone(&lists)).unwrap(),
+            expected
+        );
+        // 切片后的列表从 0 开始重新累计
+        let tail = lists.slice(1, 2);
+        let mut expected = Vec::new();
+        for end in [0u64, 1] {
+            expected.extend(end.to_le_bytes());
+        }
+        expected.push(3);
+        assert_eq!(column("Array(UInt8)", tail).unwrap(), expected);
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!(
+            strip_low_cardinality(
+                "Map(LowCardinality(String), Array(LowCardinality(Nullable(String))))"
+            ),
+            "Map(String, Array(Nullable(String)))"
+        );
+        assert_eq!(
+            split_args("'a,b' = 1, 'c\\'' = 2"),
+            ["'a,b' = 1", "'c\\'' = 2"]
+        );
+        assert!(matches!(
+            parse_type("Tuple(id UInt64, tags Array(String))").unwrap(),
+            Type::Tuple(ref e) if e.len() == 2 && e[0].0.as_deref() == Some("id")
+        ));
+        assert!(matches!(
+            parse_type("Decimal64(4)").unwrap(),
+            Type::Decimal(18, 4)
+        ));
+        assert!(parse_type("DateTime64(10)").is_err());
+        assert!(parse_type("IPv6").is_err());
+    }
+}
@@ -0,0 +1,914 @@
+//! ORC 文件尾部元数据解析 (PostScript + Footer)
+//!
+//! 只读取文件末尾的少量字节，不解码任何数据流，用于导入前的 schema 校验等轻量检查。
+//! protobuf 结构按 orc_proto.proto 手工解码，避免引入完整的 ORC/Arrow 依赖。
+
+use crate::remote;
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const MAGIC: &[u8] = b"ORC";
+/// 一次性读取的文件尾部大小，绝大多数文件的 Footer 都能落在其中
+const TAIL_READ_SIZE: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Zlib,
+    Snappy,
+    Lzo,
+    Lz4,
+    Zstd,
+}
+
+#[derive(Debug, Clone)]
+pub struct StripeInfo {
+    pub offset: u64,
+    pub index_length: u64,
+    pub data_length: u64,
+    pub footer_length: u64,
+    pub num_rows: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Boolean,
+    Byte,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    String,
+    Binary,
+    Timestamp,
+    List,
+    Map,
+    Struct,
+    Union,
+    Decimal,
+    Date,
+    Varchar,
+    Char,
+    TimestampInstant,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrcType {
+    pub kind: TypeKind,
+    pub subtypes: Vec<u32>,
+    pub field_names: Vec<String>,
+    pub precision: u32,
+    pub scale: u32,
+}
+
+/// 列统计中的最小/最大值，按列类型归一为可比较的三类
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum StatValue {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl std::fmt::Display for StatValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatValue::Int(v) => write!(f, "{}", v),
+            StatValue::Float(v) => write!(f, "{}", v),
+            StatValue::Str(v) => write!(f, "{:?}", v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ColumnStats {
+    pub min: Option<StatValue>,
+    pub max: Option<StatValue>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrcMeta {
+    pub file_len: u64,
+    pub compression: Compression,
+    pub num_rows: u64,
+    pub stripes: Vec<StripeInfo>,
+    pub types: Vec<OrcType>,
+    /// 文件级列统计，下标与 types 一致 (0 为根 struct)
+    pub statistics: Vec<ColumnStats>,
+    /// 解压后的原始 Footer 与未压缩的 PostScript，用于按 stripe 拆分时重写文件尾
+    footer_raw: Vec<u8>,
+    postscript_raw: Vec<u8>,
+    compression_block_size: u64,
+    /// Footer 在文件中的起始位置，之前为 stripe 与 Metadata
+    footer_offset: u64,
+}
+
+impl StripeInfo {
+    /// stripe 在文件中占用的总字节数
+    pub fn total_length(&self) -> u64 {
+        self.index_length + self.data_length + self.footer_length
+    }
+}
+
+impl OrcMeta {
+    /// 顶层 struct 的列名及其对应的 ClickHouse 类型
+    pub fn columns(&self) -> Vec<(String, String)> {
+        let Some(root) = self.types.first() else {
+            return Vec::new();
+        };
+        root.field_names
+            .iter()
+            .zip(&root.subtypes)
+            .map(|(name, &id)| (name.clone(), self.clickhouse_type(id)))
+            .collect()
+    }
+
+    /// 顶层列的文件级 (min, max)，文件未写统计或类型不支持时返回 None
+    pub fn column_range(&self, name: &str) -> Option<(StatValue, StatValue)> {
+        let root = self.types.first()?;
+        let idx = root.field_names.iter().position(|n| n == name)?;
+        let id = *root.subtypes.get(idx)? as usize;
+        let stats = self.statistics.get(id)?;
+        Some((stats.min.clone()?, stats.max.clone()?))
+    }
+
+    /// 顶层列的 ORC 类型
+    pub fn column_kind(&self, name: &str) -> Option<TypeKind> {
+        let root = self.types.first()?;
+        let idx = root.field_names.iter().position(|n| n == name)?;
+        let id = *root.subtypes.get(idx)? as usize;
+        Some(self.types.get(id)?.kind)
+    }
+
+    /// 文件尾已能解析 (magic、PostScript、Footer 长度) 的基础上，核对 stripe 列表：
+    /// 有行却没有 stripe、stripe 越过文件尾或相互重叠、各 stripe 行数之和与文件行数不符，
+    /// 都说明文件在写入或传输中被截断或损坏
+    pub fn check_integrity(&self) -> Result<()> {
+        if self.num_rows > 0 && self.stripes.is_empty() {
+            bail!("文件有 {} 行但没有 stripe", self.num_rows);
+        }
+        let mut end = MAGIC.len() as u64;
+        for (i, stripe) in self.stripes.iter().enumerate() {
+            if stripe.offset < end {
+                bail!(
+                    "第 {} 个 stripe 的偏移 {} 与前面的数据重叠",
+                    i + 1,
+                    stripe.offset
+                );
+            }
+            end = stripe.offset + stripe.total_length();
+            if end > self.footer_offset {
+                bail!(
+                    "第 {} 个 stripe 结束于 {}，越过了 Footer 起始位置 {}，文件可能被截断",
+                    i + 1,
+                    end,
+                    self.footer_offset
+                );
+            }
+        }
+        let stripe_rows: u64 = self.stripes.iter().map(|s| s.num_rows).sum();
+        if stripe_rows != self.num_rows {
+            bail!(
+                "各 stripe 行数之和 {} 与文件行数 {} 不一致",
+                stripe_rows,
+                self.num_rows
+            );
+        }
+        Ok(())
+    }
+
+    /// 判断多个文件能否按 stripe 拼接成同一个 ORC 文件的键：压缩方式与块大小、
+    /// 类型定义 (Footer 4)、行索引间隔 (8)、日历 (11) 以及文件格式与写入端版本 (PostScript 4 / 6) 都相同才可拼接
+    pub fn pack_key(&self) -> Result<Vec<u8>> {
+        let mut key = vec![self.compression as u8];
+        key.extend_from_slice(&self.compression_block_size.to_le_bytes());
+        for (buf, fields) in [
+            (&self.footer_raw, &[4, 8, 11][..]),
+            (&self.postscript_raw, &[4, 6][..]),
+        ] {
+            let mut r = ProtoReader::new(buf);
+            loop {
+                let start = r.pos;
+                let Some((field, _)) = r.next_field()? else {
+                    break;
+                };
+                if fields.contains(&field) {
+                    key.extend_from_slice(&buf[start..r.pos]);
+                }
+            }
+        }
+        Ok(key)
+    }
+
+    /// 按 ClickHouse 对 ORC 的推断规则映射类型
+    pub fn clickhouse_type(&self, id: u32) -> String {
+        let Some(t) = self.types.get(id as usize) else {
+            return "Unknown".to_string();
+        };
+        let sub = |i: usize| self.clickhouse_type(t.subtypes.get(i).copied().unwrap_or(u32::MAX));
+        match t.kind {
+            TypeKind::Boolean => "Bool".into(),
+            TypeKind::Byte => "Int8".into(),
+            TypeKind::Short => "Int16".into(),
+            TypeKind::Int => "Int32".into(),
+            TypeKind::Long => "Int64".into(),
+            TypeKind::Float => "Float32".into(),
+            TypeKind::Double => "Float64".into(),
+            TypeKind::String | TypeKind::Binary | TypeKind::Varchar | TypeKind::Char => {
+                "String".into()
+            }
+            TypeKind::Timestamp | TypeKind::TimestampInstant => "DateTime64(9)".into(),
+            TypeKind::Date => "Date32".into(),
+            TypeKind::Decimal => format!("Decimal({}, {})", t.precision, t.scale),
+            TypeKind::List => format!("Array({})", sub(0)),
+            TypeKind::Map => format!("Map({}, {})", sub(0), sub(1)),
+            TypeKind::Struct => {
+                let fields: Vec<String> = t
+                    .field_names
+                    .iter()
+                    .zip(&t.subtypes)
+                    .map(|(n, &id)| format!("{} {}", n, self.clickhouse_type(id)))
+                    .collect();
+                format!("Tuple({})", fields.join(", "))
+            }
+            TypeKind::Union => "Unknown".into(),
+        }
+    }
+}
+
+/// 读取并解析 ORC 文件尾部
+pub fn read_meta(path: &Path) -> Result<OrcMeta> {
+    if remote::is_remote(path) {
+        let file_len = remote::size(path)?;
+        return parse_meta(file_len, |offset, len| {
+            remote::read_range(path, offset, len)
+        });
+    }
+    let mut file = File::open(path).with_context(|| format!("无法打开文件: {:?}", path))?;
+    let file_len = file.metadata()?.len();
+    parse_meta(file_len, |offset, len| {
+        let mut buf = vec![0u8; len as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+/// 通过 `read_at(offset, len)` 读取文件头与尾部，本地文件与远端对象共用
+fn parse_meta(
+    file_len: u64,
+    mut read_at: impl FnMut(u64, u64) -> Result<Vec<u8>>,
+) -> Result<OrcMeta> {
+    if file_len < (MAGIC.len() + 1) as u64 {
+        bail!("文件过小 ({} 字节)，不是有效的 ORC 文件", file_len);
+    }
+
+    if read_at(0, MAGIC.len() as u64)? != MAGIC {
+        bail!("文件头缺少 ORC magic");
+    }
+
+    let tail_len = file_len.min(TAIL_READ_SIZE);
+    let tail = read_at(file_len - tail_len, tail_len)?;
+
+    let ps_len = *tail.last().unwrap() as usize;
+    if ps_len + 1 > tail.len() {
+        bail!("PostScript 长度 {} 超出文件范围", ps_len);
+    }
+    let ps_start = tail.len() - 1 - ps_len;
+    let ps_raw = &tail[ps_start..tail.len() - 1];
+    let ps = parse_postscript(ps_raw)?;
+
+    let footer_len = ps.footer_length as usize;
+    if footer_len as u64 + ps_len as u64 + 1 > file_len {
+        bail!("Footer 长度 {} 超出文件范围，文件可能被截断", footer_len);
+    }
+    let footer_raw = if footer_len <= ps_start {
+        tail[ps_start - footer_len..ps_start].to_vec()
+    } else {
+        // Footer 超出了预读的尾部，单独再读一次
+        read_at(
+            file_len - 1 - ps_len as u64 - footer_len as u64,
+            footer_len as u64,
+        )?
+    };
+    let footer = decompress(ps.compression, ps.compression_block_size, &footer_raw)
+        .context("Footer 解压失败")?;
+
+    let mut meta = parse_footer(&footer)?;
+    meta.file_len = file_len;
+    meta.compression = ps.compression;
+    meta.compression_block_size = ps.compression_block_size;
+    meta.footer_raw = footer;
+    meta.postscript_raw = ps_raw.to_vec();
+    meta.footer_offset = file_len - 1 - ps_len as u64 - footer_len as u64;
+    Ok(meta)
+}
+
+struct PostScript {
+    footer_length: u64,
+    compression: Compression,
+    compression_block_size: u64,
+}
+
+fn parse_postscript(buf: &[u8]) -> Result<PostScript> {
+    let mut footer_length = 0;
+    let mut compression = Compression::None;
+    // proto 中的默认块大小 256KB
+    let mut compression_block_size = 256 * 1024;
+    let mut magic_ok = false;
+    let mut r = ProtoReader::new(buf);
+    while let Some((field, value)) = r.next_field()? {
+        match (field, value) {
+            (1, Value::Varint(v)) => footer_length = v,
+            (2, Value::Varint(v)) => {
+                compression = match v {
+                    0 => Compression::None,
+                    1 => Compression::Zlib,
+                    2 => Compression::Snappy,
+                    3 => Compression::Lzo,
+                    4 => Compression::Lz4,
+                    5 => Compression::Zstd,
+                    other => bail!("未知的压缩类型: {}", other),
+                }
+            }
+            (3, Value::Varint(v)) => compression_block_size = v,
+            (8000, Value::Bytes(b)) => magic_ok = b == MAGIC,
+            _ => {}
+        }
+    }
+    if !magic_ok {
+        bail!("PostScript 缺少 ORC magic，文件可能被截断");
+    }
+    Ok(PostScript {
+        footer_length,
+        compression,
+        compression_block_size,
+    })
+}
+
+fn parse_footer(buf: &[u8]) -> Result<OrcMeta> {
+    let mut meta = OrcMeta {
+        file_len: 0,
+        compression: Compression::None,
+        num_rows: 0,
+        stripes: Vec::new(),
+        types: Vec::new(),
+        statistics: Vec::new(),
+        footer_raw: Vec::new(),
+        postscript_raw: Vec::new(),
+        compression_block_size: 0,
+        footer_offset: 0,
+    };
+    let mut r = ProtoReader::new(buf);
+    while let Some((field, value)) = r.next_field()? {
+        match (field, value) {
+            (3, Value::Bytes(b)) => meta.stripes.push(parse_stripe(b)?),
+            (4, Value::Bytes(b)) => meta.types.push(parse_type(b)?),
+            (6, Value::Varint(v)) => meta.num_rows = v,
+            (7, Value::Bytes(b)) => meta.statistics.push(parse_stats(b)?),
+            _ => {}
+        }
+    }
+    Ok(meta)
+}
+
+fn parse_stats(buf: &[u8]) -> Result<ColumnStats> {
+    let mut stats = ColumnStats::default();
+    let mut r = ProtoReader::new(buf);
+    while let Some((field, value)) = r.next_field()? {
+        let Value::Bytes(b) = value else { continue };
+        // 2: int, 3: double, 4: string, 6: decimal, 7: date, 9: timestamp
+        let (min, max) = match field {
+            2 | 7 => min_max(b, int_stat)?,
+            9 => {
+                // 3/4 为 UTC 毫秒 (新版写入端才有)，旧文件退回到写入端本地时区的 1/2
+                let (min, max) = min_max(b, int_stat)?;
+                let (min_utc, max_utc) = min_max_at(b, 3, 4, int_stat)?;
+                (min_utc.or(min), max_utc.or(max))
+            }
+            3 => min_max(b, |v| match v {
+                Value::Fixed64(v) => Some(StatValue::Float(f64::from_bits(v))),
+                _ => None,
+            })?,
+            4 => min_max(b, |v| match v {
+                Value::Bytes(s) => Some(StatValue::Str(String::from_utf8_lossy(s).into_owned())),
+                _ => None,
+            })?,
+            6 => min_max(b, |v| match v {
+                Value::Bytes(s) => std::str::from_utf8(s)
+                    .ok()?
+                    .parse()
+                    .ok()
+                    .map(StatValue::Float),
+                _ => None,
+            })?,
+            _ => continue,
+        };
+        stats.min = min;
+        stats.max = max;
+    }
+    Ok(stats)
+}
+
+fn int_stat(v: Value) -> Option<StatValue> {
+    match v {
+        Value::Varint(v) => Some(StatValue::Int(zigzag(v))),
+        _ => None,
+    }
+}
+
+/// 各类统计消息的 min / max 均位于字段 1 / 2
+fn min_max(
+    buf: &[u8],
+    conv: impl Fn(Value) -> Option<StatValue>,
+) -> Result<(Option<StatValue>, Option<StatValue>)> {
+    min_max_at(buf, 1, 2, conv)
+}
+
+fn min_max_at(
+    buf: &[u8],
+    min_field: u32,
+    max_field: u32,
+    conv: impl Fn(Value) -> Option<StatValue>,
+) -> Result<(Option<StatValue>, Option<StatValue>)> {
+    let (mut min, mut max) = (None, None);
+    let mut r = ProtoReader::new(buf);
+    while let Some((field, value)) = r.next_field()? {
+        if field == min_field {
+            min = conv(value);
+        } else if field == max_field {
+            max = conv(value);
+        }
+    }
+    Ok((min, max))
+}
+
+fn zigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+fn parse_stripe(buf: &[u8]) -> Result<StripeInfo> {
+    let mut s = StripeInfo {
+        offset: 0,
+        index_length: 0,
+        data_length: 0,
+        footer_length: 0,
+        num_rows: 0,
+    };
+    let mut r = ProtoReader::new(buf);
+    while let Some((field, value)) = r.next_field()? {
+        if let Value::Varint(v) = value {
+            match field {
+                1 => s.offset = v,
+                2 => s.index_length = v,
+                3 => s.data_length = v,
+                4 => s.footer_length = v,
+                5 => s.num_rows = v,
+                _ => {}
+            }
+        }
+    }
+    Ok(s)
+}
+
+fn parse_type(buf: &[u8]) -> Result<OrcType> {
+    let mut kind = None;
+    let mut t = OrcType {
+        kind: TypeKind::Struct,
+        subtypes: Vec::new(),
+        field_names: Vec::new(),
+        precision: 0,
+        scale: 0,
+    };
+    let mut r = ProtoReader::new(buf);
+    while let Some((field, value)) = r.next_field()? {
+        match (field, value) {
+            (1, Value::Varint(v)) => kind = Some(type_kind(v)?),
+            // subtypes 既可能是 packed 也可能逐个出现
+            (2, Value::Varint(v)) => t.subtypes.push(v as u32),
+            (2, Value::Bytes(b)) => {
+                let mut packed = ProtoReader::new(b);
+                while !packed.is_empty() {
+                    t.subtypes.push(packed.varint()? as u32);
+                }
+            }
+            (3, Value::Bytes(b)) => t.field_names.push(String::from_utf8_lossy(b).into_owned()),
+            (5, Value::Varint(v)) => t.precision = v as u32,
+            (6, Value::Varint(v)) => t.scale = v as u32,
+            _ => {}
+        }
+    }
+    t.kind = kind.context("Type 缺少 kind 字段")?;
+    Ok(t)
+}
+
+fn type_kind(v: u64) -> Result<TypeKind> {
+    Ok(match v {
+        0 => TypeKind::Boolean,
+        1 => TypeKind::Byte,
+        2 => TypeKind::Short,
+        3 => TypeKind::Int,
+        4 => TypeKind::Long,
+        5 => TypeKind::Float,
+        6 => TypeKind::Double,
+        7 => TypeKind::String,
+        8 => TypeKind::Binary,
+        9 => TypeKind::Timestamp,
+        10 => TypeKind::List,
+        11 => TypeKind::Map,
+        12 => TypeKind::Struct,
+        13 => TypeKind::Union,
+        14 => TypeKind::Decimal,
+        15 => TypeKind::Date,
+        16 => TypeKind::Varchar,
+        17 => TypeKind::Char,
+        18 => TypeKind::TimestampInstant,
+        other => bail!("未知的 ORC 类型: {}", other),
+    })
+}
+
+/// 为 `stripes` (原文件中连续或不连续的若干 stripe) 构造一个独立 ORC 文件的尾部。
+///
+/// 新文件布局为 `"ORC" + 依次拼接的 stripe 原始字节 + Footer + PostScript + 长度字节`，
+/// stripe 内部偏移均为相对值，字节原样拷贝即可。
+/// 文件级统计与 stripe 统计 (Metadata) 不再成立，直接省略；Footer 以未压缩 chunk 写入。
+pub fn build_split_tail(meta: &OrcMeta, stripes: &[StripeInfo]) -> Result<Vec<u8>> {
+    let mut footer = Vec::new();
+    let header_len = MAGIC.len() as u64;
+    let content_len: u64 = header_len + stripes.iter().map(|s| s.total_length()).sum::<u64>();
+    put_varint_field(&mut footer, 1, header_len);
+    put_varint_field(&mut footer, 2, content_len);
+
+    let mut offset = header_len;
+    for s in stripes {
+        let mut info = Vec::new();
+        put_varint_field(&mut info, 1, offset);
+        put_varint_field(&mut info, 2, s.index_length);
+        put_varint_field(&mut info, 3, s.data_length);
+        put_varint_field(&mut info, 4, s.footer_length);
+        put_varint_field(&mut info, 5, s.num_rows);
+        put_bytes_field(&mut footer, 3, &info);
+        offset += s.total_length();
+    }
+    put_varint_field(&mut footer, 6, stripes.iter().map(|s| s.num_rows).sum());
+
+    // 保留 types / metadata / rowIndexStride / writer 等字段，其余按新内容重写
+    let mut r = ProtoReader::new(&meta.footer_raw);
+    loop {
+        let start = r.pos;
+        let Some((field, _)) = r.next_field()? else {
+            break;
+        };
+        match field {
+            1 | 2 | 3 | 6 | 7 => {}
+            10 => bail!("不支持拆分加密的 ORC 文件"),
+            _ => footer.extend_from_slice(&meta.footer_raw[start..r.pos]),
+        }
+    }
+
+    let mut tail = Vec::with_capacity(footer.len() + 64);
+    let mut footer_len = 0u64;
+    if meta.compression == Compression::None {
+        tail.extend_from_slice(&footer);
+        footer_len = footer.len() as u64;
+    } else {
+        // 压缩文件中的流允许包含 is_original 的未压缩 chunk
+        let block = (meta.compression_block_size as usize).clamp(1, (1 << 23) - 1);
+        for piece in footer.chunks(block) {
+            let header = (piece.len() << 1) | 1;
+            tail.extend_from_slice(&[header as u8, (header >> 8) as u8, (header >> 16) as u8]);
+            tail.extend_from_slice(piece);
+            footer_len += 3 + piece.len() as u64;
+        }
+    }
+
+    let mut ps = Vec::new();
+    put_varint_field(&mut ps, 1, footer_len);
+    put_varint_field(&mut ps, 5, 0);
+    let mut r = ProtoReader::new(&meta.postscript_raw);
+    loop {
+        let start = r.pos;
+        let Some((field, _)) = r.next_field()? else {
+            break;
+        };
+        if !matches!(field, 1 | 5 | 7) {
+            ps.extend_from_slice(&meta.postscript_raw[start..r.pos]);
+        }
+    }
+    if ps.len() > u8::MAX as usize {
+        bail!("PostScript 过长 ({} 字节)", ps.len());
+    }
+    tail.extend_from_slice(&ps);
+    tail.push(ps.len() as u8);
+    Ok(tail)
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u32, v: u64) {
+    put_varint(buf, (field as u64) << 3);
+    put_varint(buf, v);
+}
+
+fn put_bytes_field(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    put_varint(buf, ((field as u64) << 3) | 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// 解压 ORC 压缩流：由若干 chunk 组成，每个 chunk 带 3 字节小端头 (长度 << 1 | is_original)，
+/// 解压后的 chunk 不超过 PostScript 中的 `block_size`
+fn decompress(codec: Compression, block_size: u64, buf: &[u8]) -> Result<Vec<u8>> {
+    if codec == Compression::None {
+        return Ok(buf.to_vec());
+    }
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        if pos + 3 > buf.len() {
+            bail!("压缩块头不完整");
+        }
+        let header =
+            buf[pos] as usize | (buf[pos + 1] as usize) << 8 | (buf[pos + 2] as usize) << 16;
+        pos += 3;
+        let (len, original) = (header >> 1, header & 1 == 1);
+        let chunk = buf.get(pos..pos + len).context("压缩块长度超出范围")?;
+        pos += len;
+        if original {
+            out.extend_from_slice(chunk);
+            continue;
+        }
+        match codec {
+            Compression::Zlib => {
+                flate2::read::DeflateDecoder::new(chunk).read_to_end(&mut out)?;
+            }
+            Compression::Zstd => out.extend(zstd::stream::decode_all(chunk)?),
+            Compression::Snappy => out.extend(snap::raw::Decoder::new().decompress_vec(chunk)?),
+            Compression::Lz4 => {
+                // ORC 的 LZ4 块不带原始长度，先按序列头算出解压后的长度
+                let len = lz4_block_len(chunk, block_size)?;
+                out.extend(lz4_flex::block::decompress(chunk, len)?);
+            }
+            Compression::Lzo => bail!("暂不支持 LZO 压缩的 ORC 文件"),
+            Compression::None => unreachable!(),
+        }
+    }
+    Ok(out)
+}
+
+/// LZ4 块解压后的长度：逐个序列累加字面量与匹配长度，不解压数据。
+/// 空块、截断或超过 `block_size` (ORC 中每个 chunk 解压后的上限，未写时按默认 256K) 时报错
+fn lz4_block_len(chunk: &[u8], block_size: u64) -> Result<usize> {
+    if chunk.is_empty() {
+        bail!("LZ4 压缩块为空");
+    }
+    let limit = if block_size == 0 {
+        256 * 1024
+    } else {
+        block_size as usize
+    };
+    // 长度字段为 15 时后面跟若干扩展字节，直到某个字节不是 255
+    let extended = |pos: &mut usize, mut len: usize| -> Result<usize> {
+        if len == 15 {
+            loop {
+                let b = *chunk.get(*pos).context("LZ4 块被截断")?;
+                *pos += 1;
+                len += b as usize;
+                if b != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(len)
+    };
+    let (mut pos, mut total) = (0usize, 0usize);
+    loop {
+        let token = *chunk.get(pos).context("LZ4 块被截断")?;
+        pos += 1;
+        let literals = extended(&mut pos, (token >> 4) as usize)?;
+        pos = pos.checked_add(literals).context("LZ4 块长度溢出")?;
+        total += literals;
+        if total > limit {
+            bail!("LZ4 块解压后超过 {} 字节", limit);
+        }
+        // 最后一个序列只有字面量
+        if pos >= chunk.len() {
+            if pos > chunk.len() {
+                bail!("LZ4 块被截断");
+            }
+            break;
+        }
+        pos += 2; // 匹配偏移
+        if pos >= chunk.len() {
+            bail!("LZ4 块被截断");
+        }
+        total += extended(&mut pos, (token & 0xf) as usize)? + 4;
+        if total > limit {
+            bail!("LZ4 块解压后超过 {} 字节", limit);
+        }
+    }
+    Ok(total)
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed64(u64),
+    /// fixed32 字段，目前无需解读其内容
+    Fixed32,
+}
+
+/// 极简 protobuf wire format 读取器
+struct ProtoReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut result = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.buf.get(self.pos).context("varint 被截断")?;
+            self.pos += 1;
+            result |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+        bail!("varint 过长")
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).context("字段长度溢出")?;
+        let slice = self.buf.get(self.pos..end).context("字段长度超出范围")?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u32, Value<'a>)>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = (key >> 3) as u32;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => Value::Fixed64(u64::from_le_bytes(self.take(8)?.try_into()?)),
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed32
+            }
+            wt => bail!("不支持的 protobuf wire type: {}", wt),
+        };
+        Ok(Some((field, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use bytes::Bytes;
+    use orc_rust::{ArrowReaderBuilder, ArrowWriterBuilder};
+    use std::sync::Arc;
+
+    /// 写出一个每批一个 stripe 的 ORC 文件，第 i 个 stripe 为 id 从 i * 100 起的 100 行
+    fn sample_orc(stripes: i64) -> Vec<u8> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let mut buf = Vec::new();
+        let mut writer = ArrowWriterBuilder::new(&mut buf, Arc::clone(&schema))
+            .try_build()
+            .unwrap();
+        for i in 0..stripes {
+            let ids: Vec<i64> = (i * 100..(i + 1) * 100).collect();
+            let names: Vec<String> = ids.iter().map(|id| format!("n{}", id)).collect();
+            let batch = RecordBatch::try_new(
+                Arc::clone(&schema),
+                vec![
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .unwrap();
+            writer.write(&batch).unwrap();
+            writer.flush_stripe().unwrap();
+        }
+        writer.close().unwrap();
+        buf
+    }
+
+    fn meta_of(file: &[u8]) -> OrcMeta {
+        parse_meta(file.len() as u64, |offset, len| {
+            Ok(file[offset as usize..(offset + len) as usize].to_vec())
+        })
+        .unwrap()
+    }
+
+    /// 按 `build_split_tail` 的布局拼出只含 `stripes` 的新文件
+    fn split_file(file: &[u8], meta: &OrcMeta, stripes: &[StripeInfo]) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        for s in stripes {
+            out.extend_from_slice(&file[s.offset as usize..(s.offset + s.total_length()) as usize]);
+        }
+        out.extend(build_split_tail(meta, stripes).unwrap());
+        out
+    }
+
+    fn read_ids(file: Vec<u8>) -> Vec<i64> {
+        let reader = ArrowReaderBuilder::try_new(Bytes::from(file))
+            .unwrap()
+            .build();
+        let mut ids = Vec::new();
+        for batch in reader {
+            let batch = batch.unwrap();
+            let col = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap();
+            ids.extend(col.values().iter().copied());
+        }
+        ids
+    }
+
+    #[test]
+    fn split_tail_round_trip() {
+        let file = sample_orc(3);
+        let meta = meta_of(&file);
+        assert_eq!(meta.stripes.len(), 3);
+        assert_eq!(meta.num_rows, 300);
+
+        let picked = vec![meta.stripes[0].clone(), meta.stripes[2].clone()];
+        let split = split_file(&file, &meta, &picked);
+
+        // 重写的文件尾能被本模块解析，stripe 偏移换算为新文件中的位置
+        let rewritten = meta_of(&split);
+        assert_eq!(rewritten.num_rows, 200);
+        assert_eq!(rewritten.stripes.len(), 2);
+        assert_eq!(rewritten.stripes[0].offset, MAGIC.len() as u64);
+        assert_eq!(
+            rewritten.stripes[1].offset,
+            MAGIC.len() as u64 + picked[0].total_length()
+        );
+        assert_eq!(rewritten.columns(), meta.columns());
+        assert_eq!(rewritten.pack_key().unwrap(), meta.pack_key().unwrap());
+
+        // 也能被完整的 ORC 读取器解码，数据与原文件对应的 stripe 一致
+        let expected: Vec<i64> = (0..100).chain(200..300).collect();
+        assert_eq!(read_ids(split), expected);
+    }
+
+    #[test]
+    fn lz4_chunks() {
+        let data = b"ck-loader ".repeat(1000);
+        let packed = lz4_flex::block::compress(&data);
+        let header = packed.len() << 1;
+        let mut buf = vec![header as u8, (header >> 8) as u8, (header >> 16) as u8];
+        buf.extend_from_slice(&packed);
+        assert_eq!(
+            decompress(Compression::Lz4, 256 * 1024, &buf).unwrap(),
+            data
+        );
+        // 未写块大小时按默认 256K 为上限
+        assert_eq!(decompress(Compression::Lz4, 0, &buf).unwrap(), data);
+        // 解压后超过块大小、空块、截断块都报错而不是反复放大缓冲区
+        assert!(decompress(Compression::Lz4, 4096, &buf).is_err());
+        assert!(decompress(Compression::Lz4, 0, &[0, 0, 0]).is_err());
+        let cut = buf.len() - 3;
+        assert!(decompress(Compression::Lz4, 0, &buf[..cut]).is_err());
+    }
+
+    #[test]
+    fn split_tail_single_stripe() {
+        let file = sample_orc(2);
+        let meta = meta_of(&file);
+        let split = split_file(&file, &meta, &meta.stripes[1..]);
+        assert_eq!(meta_of(&split).num_rows, 100);
+        assert_eq!(read_ids(split), (100..200).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,80 @@
+//! 基于 ORC 文件级统计的键范围重叠检测，用于发现上游重复导出
+
+use crate::orc::{self, StatValue};
+use std::path::PathBuf;
+
+/// 最多打印的重叠对数，避免大批量重复时刷屏
+const MAX_REPORTED: usize = 20;
+
+struct KeyRange {
+    file: String,
+    min: StatValue,
+    max: StatValue,
+}
+
+/// 读取每个文件中 `key` 列的 min/max，对范围互相重叠的文件给出警告
+pub fn check(files: &[PathBuf], key: &str) {
+    let mut ranges = Vec::new();
+    let mut missing = 0;
+    for path in files {
+        let file = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        match orc::read_meta(path).ok().and_then(|m| m.column_range(key)) {
+            Some((min, max)) => ranges.push(KeyRange { file, min, max }),
+            None => missing += 1,
+        }
+    }
+    if missing > 0 {
+        println!(
+            "⚠️ 重复检测: {} 个文件缺少列 {} 的统计信息，已跳过",
+            missing, key
+        );
+    }
+
+    // 按 min 排序后扫描：若某文件的 min 不大于此前出现过的最大 max，则两者范围重叠
+    ranges.sort_by(|a, b| {
+        a.min
+            .partial_cmp(&b.min)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut overlaps = Vec::new();
+    let mut widest: Option<&KeyRange> = None;
+    for r in &ranges {
+        if let Some(w) = widest {
+            if r.min <= w.max {
+                overlaps.push((w, r));
+            }
+            if r.max > w.max {
+                widest = Some(r);
+            }
+        } else {
+            widest = Some(r);
+        }
+    }
+
+    if overlaps.is_empty() {
+        println!(
+            "🔑 重复检测: {} 个文件的 {} 范围互不重叠",
+            ranges.len(),
+            key
+        );
+        return;
+    }
+    println!(
+        "⚠️ 重复检测: 发现 {} 处 {} 范围重叠，可能是上游重复导出 (ReplacingMergeTree 将在合并后去重)",
+        overlaps.len(),
+        key
+    );
+    for (a, b) in overlaps.iter().take(MAX_REPORTED) {
+        println!(
+            "   {} [{} .. {}] ∩ {} [{} .. {}]",
+            a.file, a.min, a.max, b.file, b.min, b.max
+        );
+    }
+    if overlaps.len() > MAX_REPORTED {
+        println!("   ... 其余 {} 处省略", overlaps.len() - MAX_REPORTED);
+    }
+}
@@ -0,0 +1,273 @@
+//! 小文件合并导入：小于 `--pack-under-mb` 的文件按 stripe 拼接成一个 ORC 流，一次 INSERT 写入整组，
+//! 避免大量小文件各自一次插入带来的请求开销与 part 数量。
+//!
+//! 只有文件尾参数完全相同的文件才会合并 (见 `OrcMeta::pack_key`)，读取失败的文件照常单独导入。
+//! 小于 `--batch-small-files` 的行格式文件 (CSV / TSV / JSONEachRow) 按格式与表头分组，直接首尾拼接为一个请求体，
+//! 带表头的格式只保留第一个成员的表头 (见 `batch_member`)。
+//! 每组的 insert_deduplication_token 由成员标识 (内容摘要或元数据标识) 排序后派生，与文件顺序无关：整组重试时
+//! (同步插入或 async_insert 均可) 服务端按 token 去重，不会重复写入；成员所属的 token 记入台账。
+
+use crate::orc;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use xxhash_rust::xxh3::Xxh3;
+
+/// 分组时读取表头的长度上限，超过时不参与合并
+const MAX_HEADER: u64 = 64 * 1024;
+
+/// 把文件划分为导入单元：大文件与无法合并的文件各自一个单元，小于 `under_bytes` 的 ORC 文件按 pack_key 分组，
+/// 小于 `batch_bytes` 的行格式文件按格式与表头分组，每组总大小不超过 `max_bytes`。只有一个成员的组退化为普通文件
+pub fn plan(
+    files: Vec<PathBuf>,
+    under_bytes: u64,
+    batch_bytes: u64,
+    max_bytes: u64,
+) -> Vec<Vec<PathBuf>> {
+    let mut units = Vec::new();
+    // (pack_key, 成员, 累计字节)
+    let mut open: Vec<(Vec<u8>, Vec<PathBuf>, u64)> = Vec::new();
+    let mut packed = 0;
+    for path in files {
+        let size = std::fs::metadata(&path)
+            .map(|m| m.len())
+            .unwrap_or(u64::MAX);
+        let key = match row_format(&path) {
+            Some(format) if size < batch_bytes => row_key(&path, format),
+            Some(_) => None,
+            None if size < under_bytes => orc::read_meta(&path).and_then(|m| m.pack_key()).ok(),
+            None => None,
+        };
+        let Some(key) = key else {
+            units.push(vec![path]);
+            continue;
+        };
+        packed += 1;
+        let idx = match open.iter().position(|(k, _, _)| *k == key) {
+            Some(i) if open[i].2 + size > max_bytes => {
+                let (_, members, _) = open.swap_remove(i);
+                units.push(members);
+                open.push((key, Vec::new(), 0));
+                open.len() - 1
+            }
+            Some(i) => i,
+            None => {
+                open.push((key, Vec::new(), 0));
+                open.len() - 1
+            }
+        };
+        open[idx].1.push(path);
+        open[idx].2 += size;
+    }
+    units.extend(open.into_iter().map(|(_, members, _)| members));
+
+    let groups = units.iter().filter(|u| u.len() > 1).count();
+    if groups > 0 {
+        println!("📦 合并小文件: {} 个文件 → {} 组", packed, groups);
+    }
+    units
+}
+
+/// 可以直接首尾拼接的行格式，按扩展名判断。`.json` 常是整个 JSON 数组，拼接后不再合法，不参与合并
+pub fn row_format(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "csv" => Some("CSVWithNames"),
+        "tsv" | "tab" => Some("TabSeparatedWithNames"),
+        "jsonl" | "ndjson" => Some("JSONEachRow"),
+        _ => None,
+    }
+}
+
+/// 行格式文件的分组键：格式名，带表头的格式再加上表头行，表头不同的文件不会进同一组。
+/// 加上前缀，不会与 ORC 的 pack_key 相同；读不到表头时返回 None，照常单独导入
+fn row_key(path: &Path, format: &str) -> Option<Vec<u8>> {
+    let mut key = format!("rows:{}\n", format).into_bytes();
+    if format.ends_with("WithNames") {
+        let mut reader = BufReader::new(File::open(path).ok()?).take(MAX_HEADER);
+        let start = key.len();
+        reader.read_until(b'\n', &mut key).ok()?;
+        if key.last() != Some(&b'\n') && key.len() - start == MAX_HEADER as usize {
+            return None;
+        }
+        while matches!(key.last(), Some(b'\n' | b'\r')) {
+            key.pop();
+        }
+    }
+    Some(key)
+}
+
+/// 合并组中的一个成员：`skip_header` 时去掉首行 (带表头格式的第二个及以后的成员)，
+/// 不以换行结尾时补一个换行，避免与下一个成员的首行连在一起
+pub fn batch_member(mut data: Vec<u8>, skip_header: bool) -> Vec<u8> {
+    if skip_header {
+        let body = data
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |p| p + 1);
+        data.drain(..body);
+    }
+    if data.last().is_some_and(|&b| b != b'\n') {
+        data.push(b'\n');
+    }
+    data
+}
+
+/// 由成员标识派生的组级 insert_deduplication_token
+pub fn token(hashes: &[String]) -> String {
+    let mut sorted: Vec<&str> = hashes.iter().map(String::as_str).collect();
+    sorted.sort_unstable();
+    let mut hasher = Xxh3::new();
+    for hash in sorted {
+        hasher.update(hash.as_bytes());
+        hasher.update(b"\n");
+    }
+    format!("pack-{:032x}", hasher.digest128())
+}
+
+/// 单个文件的 insert_deduplication_token，由文件标识 (内容摘要或 `meta_id`) 派生：同一文件重新发送时服务端去重，
+/// 同名但内容不同的文件不会被当成重复
+pub fn file_token(id: &str) -> String {
+    format!("file-{}", id)
+}
+
+/// 没有内容摘要 (台账 / --delta) 时由路径、大小与修改时间派生的文件标识，不为派生 token 再读一遍文件。
+/// 文件被改写或替换后大小或修改时间随之改变；拿不到修改时间 (如远端文件) 时返回 None
+pub fn meta_id(path: &Path, bytes: u64, mtime: Option<SystemTime>) -> Option<String> {
+    let mtime = mtime?.duration_since(UNIX_EPOCH).ok()?;
+    let mut hasher = Xxh3::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update(&bytes.to_le_bytes());
+    hasher.update(&mtime.as_nanos().to_le_bytes());
+    Some(format!("meta-{:032x}", hasher.digest128()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str, files: &[(&str, &str)]) -> Vec<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("ck-pack-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        files
+            .iter()
+            .map(|(file, content)| {
+                let path = dir.join(file);
+                std::fs::write(&path, content).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    fn names(units: &[Vec<PathBuf>]) -> Vec<Vec<String>> {
+        let mut names: Vec<Vec<String>> = units
+            .iter()
+            .map(|u| {
+                u.iter()
+                    .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn row_formats() {
+        assert_eq!(row_format(Path::new("a.csv")), Some("CSVWithNames"));
+        assert_eq!(
+            row_format(Path::new("a.TSV")),
+            Some("TabSeparatedWithNames")
+        );
+        assert_eq!(row_format(Path::new("a.ndjson")), Some("JSONEachRow"));
+        assert_eq!(row_format(Path::new("a.jsonl")), Some("JSONEachRow"));
+        assert_eq!(row_format(Path::new("a.json")), None);
+        assert_eq!(row_format(Path::new("a.csv.gz")), None);
+        assert_eq!(row_format(Path::new("a.orc")), None);
+    }
+
+    #[test]
+    fn groups_rows_by_format_and_header() {
+        let files = scratch(
+            "header",
+            &[
+                ("a.csv", "id,name\n1,a\n"),
+                ("b.csv", "id,name\r\n2,b"),
+                ("c.csv", "name,id\nc,3\n"),
+                ("d.jsonl", "{\"id\":4}\n"),
+                ("e.jsonl", "{\"id\":5}\n"),
+                ("f.json", "[{\"id\":6}]"),
+            ],
+        );
+        let units = plan(files, 0, 1024, 1024);
+        assert_eq!(
+            names(&units),
+            [
+                vec!["a.csv", "b.csv"],
+                vec!["c.csv"],
+                vec!["d.jsonl", "e.jsonl"],
+                vec!["f.json"],
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_groups_at_max_bytes() {
+        // 每个 10 字节，上限 25 字节：每组最多两个成员
+        let files = scratch(
+            "split",
+            &[
+                ("a.csv", "id\n1234567"),
+                ("b.csv", "id\n2345678"),
+                ("c.csv", "id\n3456789"),
+            ],
+        );
+        let units = plan(files.clone(), 0, 1024, 25);
+        assert_eq!(names(&units), [vec!["a.csv", "b.csv"], vec!["c.csv"]]);
+
+        // 不小于 batch_bytes 的文件单独导入
+        let units = plan(files, 0, 10, 1024);
+        assert_eq!(names(&units), [vec!["a.csv"], vec!["b.csv"], vec!["c.csv"]]);
+    }
+
+    #[test]
+    fn batch_members() {
+        assert_eq!(batch_member(b"id\n1\n".to_vec(), false), b"id\n1\n");
+        assert_eq!(batch_member(b"id\n1\n".to_vec(), true), b"1\n");
+        // 缺少结尾换行时补上
+        assert_eq!(batch_member(b"id\n1".to_vec(), true), b"1\n");
+        assert_eq!(batch_member(b"{\"id\":1}".to_vec(), false), b"{\"id\":1}\n");
+        // 只有表头的成员去掉后为空，不补换行
+        assert_eq!(batch_member(b"id".to_vec(), true), b"");
+        assert_eq!(batch_member(Vec::new(), false), b"");
+    }
+
+    #[test]
+    fn token_ignores_member_order() {
+        let a = token(&["x".to_string(), "y".to_string()]);
+        let b = token(&["y".to_string(), "x".to_string()]);
+        assert_eq!(a, b);
+        assert!(a.starts_with("pack-"));
+        assert_ne!(a, token(&["x".to_string()]));
+    }
+
+    #[test]
+    fn meta_ids() {
+        let path = Path::new("/data/a.orc");
+        let mtime = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let id = meta_id(path, 100, Some(mtime)).unwrap();
+        assert_eq!(Some(id.clone()), meta_id(path, 100, Some(mtime)));
+        assert!(id.starts_with("meta-"));
+        // 路径、大小或修改时间任一变化都得到新的标识
+        assert_ne!(
+            Some(id.clone()),
+            meta_id(Path::new("/data/b.orc"), 100, Some(mtime))
+        );
+        assert_ne!(Some(id.clone()), meta_id(path, 101, Some(mtime)));
+        let later = mtime + std::time::Duration::from_nanos(1);
+        assert_ne!(Some(id), meta_id(path, 100, Some(later)));
+        assert_eq!(meta_id(path, 100, None), None);
+    }
+}
@@ -0,0 +1,104 @@
+//! 按目标分区汇总本批次的写入结果：每个分区写入了多少文件、行、字节，新建了多少 part。
+//! 下游按分区触发的任务据此判断哪些分区已经完整。
+//!
+//! 文件与分区的对应关系由服务端的 system.part_log 给出：按导入时发送的 query_id
+//! (拆分导入的各组为 `<query_id>-<组号>`) 找到每次插入新建的 part。服务端未开启 part_log、
+//! 或插入走 async_insert (part 由服务端的刷新查询创建，query_id 不同) 时无法统计。
+
+use crate::cli::{self, Args};
+use crate::clickhouse;
+use crate::report::{FileRecord, FileStatus};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// 每条查询的 query_id 列表最大长度
+const IDS_PER_QUERY: usize = 1000;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PartitionSummary {
+    pub table: String,
+    pub partition_id: String,
+    pub files: u64,
+    pub rows: u64,
+    pub bytes: u64,
+    pub parts: u64,
+}
+
+/// 汇总成功导入的文件新建的 part，按 (表, 分区) 排序；`since` 为批次开始时间 (unix 秒)
+pub async fn summarize(
+    cfg: &Args,
+    records: &[FileRecord],
+    since: u64,
+) -> Result<Vec<PartitionSummary>> {
+    let ids: Vec<&str> = records
+        .iter()
+        .filter(|r| r.status == FileStatus::Success)
+        .filter_map(|r| r.query_id.as_deref())
+        .collect();
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    // part_log 默认每 7.5 秒落盘一次，先强制刷新；没有 SYSTEM 权限时刚结束的插入可能还查不到
+    if let Err(e) = clickhouse::query(cfg, "SYSTEM FLUSH LOGS").await {
+        eprintln!("⚠️ SYSTEM FLUSH LOGS 失败，分区汇总可能不完整: {:#}", e);
+    }
+
+    // 各批 query_id 互不重叠，文件数可以直接累加
+    let mut merged: BTreeMap<(String, String), PartitionSummary> = BTreeMap::new();
+    for chunk in ids.chunks(IDS_PER_QUERY) {
+        let list: Vec<String> = chunk
+            .iter()
+            .map(|id| format!("'{}'", cli::sql_string(id)))
+            .collect();
+        let sql = format!(
+            "SELECT concat(database, '.', table), partition_id,
+                    uniqExact(replaceRegexpOne(query_id, '-[0-9]+$', '')),
+                    sum(rows), sum(size_in_bytes), count()
+             FROM system.part_log
+             WHERE event_type = 'NewPart'
+               AND event_date >= toDate(toDateTime({since})) AND event_time >= toDateTime({since})
+               AND (query_id IN ({ids}) OR replaceRegexpOne(query_id, '-[0-9]+$', '') IN ({ids}))
+             GROUP BY 1, 2",
+            ids = list.join(", "),
+            since = since
+        );
+        let tsv = clickhouse::query(cfg, &sql).await?;
+        for row in clickhouse::rows(&tsv) {
+            let [table, partition_id, files, rows, bytes, parts] = row[..] else {
+                continue;
+            };
+            let entry = merged
+                .entry((table.to_string(), partition_id.to_string()))
+                .or_insert_with(|| PartitionSummary {
+                    table: table.to_string(),
+                    partition_id: partition_id.to_string(),
+                    ..Default::default()
+                });
+            entry.files += files.parse::<u64>().unwrap_or(0);
+            entry.rows += rows.parse::<u64>().unwrap_or(0);
+            entry.bytes += bytes.parse::<u64>().unwrap_or(0);
+            entry.parts += parts.parse::<u64>().unwrap_or(0);
+        }
+    }
+    Ok(merged.into_values().collect())
+}
+
+pub fn print(partitions: &[PartitionSummary]) {
+    if partitions.is_empty() {
+        println!("🧩 分区汇总: 没有可统计的写入 (没有成功的文件、服务端未开启 part_log 或使用了 async_insert)");
+        return;
+    }
+    println!("🧩 分区汇总 ({} 个分区):", partitions.len());
+    for p in partitions {
+        println!(
+            "   {} | 分区 {} | 文件: {} | 行数: {} | {:.1} MB | 新建 part: {}",
+            p.table,
+            p.partition_id,
+            p.files,
+            p.rows,
+            p.bytes as f64 / 1024.0 / 1024.0,
+            p.parts
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! 暂停 / 恢复分发新文件：SIGUSR1 暂停、SIGUSR2 恢复，serve 模式下也可通过控制接口操作。
+//! 进行中的导入不受影响，暂停期间只是不再启动新文件，用于临时缓解集群压力而不必终止长批次。
+//!
+//! 服务端过载 (TOO_MANY_PARTS 等) 时自动进入冷却：冷却期间同样不启动新文件，到期后自动恢复，
+//! 与手动暂停互不覆盖。--max-memory 的内存占用超过上限时同样暂停，由 memory 模块解除。
+
+use crate::cli;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{self, Instant};
+
+pub struct Pause {
+    paused: watch::Sender<bool>,
+    /// 冷却结束时刻，None 表示不在冷却中
+    cooling: watch::Sender<Option<Instant>>,
+    /// 内存占用超过 --max-memory
+    memory: watch::Sender<bool>,
+}
+
+impl Default for Pause {
+    fn default() -> Self {
+        Self {
+            paused: watch::Sender::new(false),
+            cooling: watch::Sender::new(None),
+            memory: watch::Sender::new(false),
+        }
+    }
+}
+
+impl Pause {
+    /// 返回状态是否发生了变化
+    pub fn pause(&self) -> bool {
+        let changed = !self.paused.send_replace(true);
+        if changed {
+            println!("⏸️ 已暂停：进行中的导入继续，不再启动新文件");
+        }
+        changed
+    }
+
+    pub fn resume(&self) -> bool {
+        let changed = self.paused.send_replace(false);
+        if changed {
+            println!("▶️ 已恢复分发新文件");
+        }
+        changed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow() || self.cooling.borrow().is_some() || *self.memory.borrow()
+    }
+
+    /// 手动暂停与冷却都解除后返回
+    pub async fn wait_resumed(&self) {
+        let mut paused = self.paused.subscribe();
+        let mut cooling = self.cooling.subscribe();
+        let mut memory = self.memory.subscribe();
+        while self.is_paused() {
+            let _ = paused.wait_for(|paused| !*paused).await;
+            let _ = cooling.wait_for(|until| until.is_none()).await;
+            let _ = memory.wait_for(|held| !*held).await;
+        }
+    }
+
+    /// 因内存占用暂停 / 解除，返回状态是否发生了变化
+    pub fn hold(&self, held: bool) -> bool {
+        self.memory.send_replace(held) != held
+    }
+
+    /// 进入冷却 `cooldown`，已在冷却中时延长到较晚的结束时刻
+    pub fn cool_down(self: &Arc<Self>, cooldown: Duration) {
+        let until = cli::deadline_after(cooldown);
+        let mut started = false;
+        let extended = self.cooling.send_if_modified(|current| match current {
+            Some(end) if *end >= until => false,
+            _ => {
+                started = current.is_none();
+                *current = Some(until);
+                true
+            }
+        });
+        if !extended {
+            return;
+        }
+        if started {
+            println!("🧊 服务端过载，{:?} 内不启动新文件", cooldown);
+        }
+        let pause = Arc::clone(self);
+        tokio::spawn(async move {
+            time::sleep_until(until).await;
+            // 期间被再次延长时由后来的任务负责解除
+            let cleared = pause.cooling.send_if_modified(|current| {
+                if *current == Some(until) {
+                    *current = None;
+                    true
+                } else {
+                    false
+                }
+            });
+            if cleared {
+                println!("▶️ 冷却结束，恢复分发新文件");
+            }
+        });
+    }
+
+    /// 监听 SIGUSR1 / SIGUSR2 (仅 unix)
+    pub fn listen_signals(self: &Arc<Self>) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let (Ok(mut usr1), Ok(mut usr2)) = (
+                signal(SignalKind::user_defined1()),
+                signal(SignalKind::user_defined2()),
+            ) else {
+                eprintln!("⚠️ 无法监听 SIGUSR1 / SIGUSR2，暂停 / 恢复信号不可用");
+                return;
+            };
+            let pause = Arc::clone(self);
+            tokio::spawn(async move {
+                loop {
+                    tokio::select! {
+                        _ = usr1.recv() => { pause.pause(); }
+                        _ = usr2.recv() => { pause.resume(); }
+                    }
+                }
+            });
+        }
+    }
+}
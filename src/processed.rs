@@ -0,0 +1,64 @@
+//! 按天滚动的已处理文件日志 `processed-YYYYMMDD.log`
+//!
+//! 每个成功导入的文件追加一行 `路径<TAB>校验和<TAB>行数`，只追加不改写，
+//! 供 tail / grep 等简单的下游脚本消费 (不方便读取 SQLite 台账的场景)。
+
+use crate::{archive, delta, orc, remote};
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub struct ProcessedLog {
+    dir: PathBuf,
+    /// 当前写入的 (日期, 文件)，日期变化时切换到新文件
+    current: Mutex<Option<(String, File)>>,
+}
+
+impl ProcessedLog {
+    pub fn open(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("无法创建目录: {:?}", dir))?;
+        archive::probe_writable(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            current: Mutex::new(None),
+        })
+    }
+
+    pub fn append(&self, path: &Path, checksum: &str, rows: Option<u64>) -> Result<()> {
+        let day = Local::now().format("%Y%m%d").to_string();
+        let mut current = self.current.lock().unwrap();
+        if !matches!(&*current, Some((d, _)) if *d == day) {
+            let log_path = self.dir.join(format!("processed-{}.log", day));
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&log_path)
+                .with_context(|| format!("无法打开 {:?}", log_path))?;
+            *current = Some((day, file));
+        }
+        let (_, file) = current.as_mut().expect("日志文件已打开");
+        let rows = rows.map(|r| r.to_string()).unwrap_or_else(|| "-".into());
+        // 整行一次写入，O_APPEND 下多进程追加也不会交错
+        let line = format!("{}\t{}\t{}\n", path.display(), checksum, rows);
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// 源文件的 (校验和, 行数)，必须在文件被移走或删除之前调用。
+/// 已有校验和 (--delta) 时直接复用；远端文件不为此整文件下载，校验和记为 "-"
+pub fn describe(path: &Path, known: Option<&str>) -> (String, Option<u64>) {
+    let checksum = match known {
+        Some(hash) => hash.to_string(),
+        None if remote::is_remote(path) => "-".to_string(),
+        None => delta::hash_file(path).unwrap_or_else(|e| {
+            eprintln!("⚠️ 无法计算校验和: {:?}, 错误: {:#}", path, e);
+            "-".to_string()
+        }),
+    };
+    let rows = orc::read_meta(path).ok().map(|m| m.num_rows);
+    (checksum, rows)
+}
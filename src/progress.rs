@@ -0,0 +1,211 @@
+//! 服务端实时进度 (--server-progress)：开启 send_progress_in_http_headers 后，ClickHouse 在执行期间
+//! 不断追加 X-ClickHouse-Progress 响应头，查询结束时才写完整个响应头。reqwest 要等响应头全部到达才返回
+//! (且头部数量有上限)，因此 http:// 地址的导入在这里自行收发 HTTP/1.1 报文：边上传请求体边逐行读取响应头，
+//! 把已写入行数记入上传状态，由导入 worker 定期输出。
+
+use crate::error::ClickHouseError;
+use crate::stall::Attempt;
+use crate::wire::Upload;
+use http_body_util::BodyExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Request, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time;
+
+/// 服务端发送进度响应头的间隔 (http_headers_progress_interval_ms)
+pub const HEADER_INTERVAL_MS: u64 = 1000;
+/// 控制台输出各文件进度的间隔
+const SHOW_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// 发送请求并读取完整响应，期间把 X-ClickHouse-Progress 中的已写入行数记入 `upload`；
+/// 进度头不放入返回的 headers
+pub async fn send(mut request: Request, upload: &Arc<Upload>) -> Result<Response, ClickHouseError> {
+    let url = request.url().clone();
+    let host = url.host_str().ok_or("URL 缺少主机名")?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let stream = TcpStream::connect((host, port))
+        .await
+        .map_err(|e| ClickHouseError::Connect(format!("无法连接 {}: {}", url, e)))?;
+    let (reader, mut writer) = stream.into_split();
+
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let mut head = format!("{} {} HTTP/1.1\r\n", request.method(), target).into_bytes();
+    match url.port() {
+        Some(port) => head.extend(format!("Host: {}:{}\r\n", host, port).as_bytes()),
+        None => head.extend(format!("Host: {}\r\n", host).as_bytes()),
+    }
+    for (name, value) in request.headers() {
+        head.extend(name.as_str().as_bytes());
+        head.extend(b": ");
+        head.extend(value.as_bytes());
+        head.extend(b"\r\n");
+    }
+    head.extend(b"Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n");
+
+    let body = request.body_mut().take();
+    let send = async move {
+        writer.write_all(&head).await?;
+        if let Some(mut body) = body {
+            while let Some(frame) = body.frame().await {
+                let frame = frame.map_err(|e| ClickHouseError::Transport(e.to_string()))?;
+                let Ok(data) = frame.into_data() else {
+                    continue;
+                };
+                if data.is_empty() {
+                    continue;
+                }
+                writer
+                    .write_all(format!("{:x}\r\n", data.len()).as_bytes())
+                    .await?;
+                writer.write_all(&data).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+        }
+        writer.write_all(b"0\r\n\r\n").await?;
+        writer.flush().await?;
+        // 写端在读完响应前保持打开
+        Ok::<_, ClickHouseError>(writer)
+    };
+    let receive = read_response(BufReader::new(reader), upload);
+    tokio::pin!(send, receive);
+
+    // 服务端可能在请求体发完之前就返回错误 (如表不存在)，此时以响应为准
+    let mut _writer = None;
+    loop {
+        tokio::select! {
+            sent = &mut send, if _writer.is_none() => match sent {
+                Ok(writer) => _writer = Some(writer),
+                Err(e) => return (&mut receive).await.map_err(|_| e),
+            },
+            response = &mut receive => return response,
+        }
+    }
+}
+
+async fn read_response<R: AsyncRead + Unpin>(
+    mut reader: BufReader<R>,
+    upload: &Upload,
+) -> Result<Response, ClickHouseError> {
+    let status_line = read_line(&mut reader).await?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .ok_or_else(|| ClickHouseError::Transport(format!("无法解析响应: {}", status_line)))?;
+
+    let mut headers = HeaderMap::new();
+    let mut written = 0;
+    loop {
+        let line = read_line(&mut reader).await?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("X-ClickHouse-Progress") {
+            // 每个进度头都是截至当时的累计值，如 {"written_rows":"100",...}
+            let rows = serde_json::from_str::<serde_json::Value>(value)
+                .ok()
+                .and_then(|json| json["written_rows"].as_str()?.parse::<u64>().ok());
+            if let Some(rows) = rows.filter(|rows| *rows > written) {
+                upload.server_written(rows - written);
+                written = rows;
+            }
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.trim().as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+
+    let chunked = headers
+        .get("Transfer-Encoding")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("chunked"));
+    let length = headers
+        .get("Content-Length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let size = read_line(&mut reader).await?;
+            let size = size.split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size.trim(), 16)
+                .map_err(|_| ClickHouseError::Transport(format!("无效的分块长度: {}", size)))?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..]).await?;
+            read_line(&mut reader).await?;
+        }
+    } else if let Some(length) = length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body).await?;
+    } else {
+        reader.read_to_end(&mut body).await?;
+    }
+    Ok(Response {
+        status,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+/// 读取一行 (去掉行尾的 CRLF)；连接在行中途关闭视为传输错误
+async fn read_line<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+) -> Result<String, ClickHouseError> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line).await? == 0 {
+        return Err(ClickHouseError::Transport(
+            "服务端在响应结束前关闭了连接".to_string(),
+        ));
+    }
+    while matches!(line.last(), Some(b'\n' | b'\r')) {
+        line.pop();
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// 定期输出一次导入尝试的上传字节数与服务端已写入行数，没有变化时不输出；不会返回
+pub async fn show(attempt: &Attempt, name: &str) {
+    let mut last = None;
+    loop {
+        time::sleep(SHOW_INTERVAL).await;
+        let Some(upload) = attempt.current_upload() else {
+            continue;
+        };
+        let current = (upload.raw_bytes(), upload.server_rows());
+        if last == Some(current) {
+            continue;
+        }
+        last = Some(current);
+        println!(
+            "⏳ {} | 已上传 {:.1} MB | 服务端已写入 {} 行",
+            name,
+            current.0 as f64 / 1024.0 / 1024.0,
+            current.1
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! 批次结束时的对账矩阵：发现 → 启动 → 服务端确认 → 行数核对 → 处置完成，逐列给出文件数，
+//! 没有走到最后一列的文件连同停下的位置与原因一并列出，一眼回答"是不是全部导入了"。
+
+use crate::report::{FileRecord, FileStatus, SkippedFile};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// 控制台逐个列出的未完成文件上限，完整列表见 --report
+const LISTED: usize = 20;
+
+/// 未完成的文件停在哪一列之后
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Discovered,
+    Attempted,
+    Acknowledged,
+    Verified,
+}
+
+impl Stage {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Discovered => "已发现未启动",
+            Self::Attempted => "服务端未确认",
+            Self::Acknowledged => "行数不一致",
+            Self::Verified => "处置未完成",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Incomplete {
+    pub path: PathBuf,
+    pub stage: Stage,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Reconciliation {
+    pub discovered: u64,
+    /// 按筛选、去重等规则无需导入的文件，不计入之后各列的应到数
+    pub not_needed: u64,
+    pub attempted: u64,
+    pub acknowledged: u64,
+    pub verified: u64,
+    /// 计入 verified 但服务端未返回写入行数等原因无法核对的文件
+    pub unverifiable: u64,
+    pub archived: u64,
+    /// 被跳过的文件超过逐个记录的上限时，部分未完成文件不在 incomplete 中
+    pub unlisted: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub incomplete: Vec<Incomplete>,
+}
+
+impl Reconciliation {
+    /// `skipped` 为跳过的文件总数，`skipped_files` 为其中逐个记下的部分
+    pub fn build(records: &[FileRecord], skipped: u64, skipped_files: &[SkippedFile]) -> Self {
+        let mut r = Self {
+            discovered: records.len() as u64 + skipped,
+            attempted: records.len() as u64,
+            unlisted: skipped.saturating_sub(skipped_files.len() as u64),
+            ..Self::default()
+        };
+        for file in skipped_files {
+            if file.reason.is_expected() {
+                r.not_needed += 1;
+            } else {
+                r.incomplete.push(Incomplete {
+                    path: file.path.clone(),
+                    stage: Stage::Discovered,
+                    reason: file.reason.label().to_string(),
+                });
+            }
+        }
+        for record in records {
+            let (stage, reason) = if record.status == FileStatus::Failed {
+                (Stage::Attempted, record.error.clone().unwrap_or_default())
+            } else if record.verified == Some(false) {
+                r.acknowledged += 1;
+                let reason = match record.written_rows {
+                    Some(n) => format!("服务端写入 {} 行，与 ORC 行数不一致", n),
+                    None => "写入行数与 ORC 行数不一致".to_string(),
+                };
+                (Stage::Acknowledged, reason)
+            } else {
+                r.acknowledged += 1;
+                r.verified += 1;
+                if record.verified.is_none() {
+                    r.unverifiable += 1;
+                }
+                if record.archived == Some(false) {
+                    (Stage::Verified, "源文件处置失败".to_string())
+                } else {
+                    r.archived += 1;
+                    continue;
+                }
+            };
+            r.incomplete.push(Incomplete {
+                path: record.path.clone(),
+                stage,
+                reason,
+            });
+        }
+        r
+    }
+
+    pub fn print(&self) {
+        let unverifiable = if self.unverifiable > 0 {
+            format!(" (其中 {} 个无法核对行数)", self.unverifiable)
+        } else {
+            String::new()
+        };
+        let not_needed = if self.not_needed > 0 {
+            format!(" (其中 {} 个无需导入)", self.not_needed)
+        } else {
+            String::new()
+        };
+        println!(
+            "🧾 对账: 发现 {}{} → 启动 {} → 服务端确认 {} → 行数核对 {}{} → 处置完成 {}",
+            self.discovered,
+            not_needed,
+            self.attempted,
+            self.acknowledged,
+            self.verified,
+            unverifiable,
+            self.archived
+        );
+        let missing = self.incomplete.len() as u64 + self.unlisted;
+        if missing == 0 {
+            println!("   ✅ 所有需要导入的文件均已走完全程");
+            return;
+        }
+        println!("   ⚠️ {} 个文件未走完全程:", missing);
+        for file in self.incomplete.iter().take(LISTED) {
+            println!(
+                "      - {:?}: {} ({})",
+                file.path,
+                file.stage.label(),
+                file.reason.lines().next().unwrap_or_default()
+            );
+        }
+        let rest = missing - (self.incomplete.len().min(LISTED) as u64);
+        if rest > 0 {
+            println!("      … 另有 {} 个未逐个列出，完整列表见 --report", rest);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! 远端输入源的统一入口：按路径前缀分派到 S3 或 WebHDFS
+//!
+//! 远端文件在导入流程中仍以 PathBuf (完整 URL) 表示；只支持 HTTP 传输下的整文件流式导入，
+//! 导入前的 ORC 元数据检查通过区间读取完成。
+
+use crate::archive::OnSuccess;
+use crate::{s3, webhdfs};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+pub fn is_remote(path: &Path) -> bool {
+    s3::is_s3(path) || webhdfs::is_hdfs(path)
+}
+
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    if s3::is_s3(dir) {
+        s3::list(dir)
+    } else {
+        webhdfs::list(dir)
+    }
+}
+
+pub fn size(path: &Path) -> Result<u64> {
+    if s3::is_s3(path) {
+        s3::size(path)
+    } else {
+        webhdfs::size(path)
+    }
+}
+
+pub fn read_range(path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+    if s3::is_s3(path) {
+        s3::read_range(path, offset, len)
+    } else {
+        webhdfs::read_range(path, offset, len)
+    }
+}
+
+/// 启动读取整个文件的子进程，内容从其 stdout 流出
+pub fn stream(path: &Path) -> Result<tokio::process::Child> {
+    if s3::is_s3(path) {
+        s3::stream(path)
+    } else {
+        webhdfs::stream(path)
+    }
+}
+
+pub fn finish(policy: OnSuccess, path: &Path, done_dir: &Path) -> Result<Option<PathBuf>> {
+    if s3::is_s3(path) {
+        s3::finish(policy, path, done_dir)
+    } else {
+        webhdfs::finish(policy, path, done_dir)
+    }
+}
@@ -0,0 +1,189 @@
+//! 可复现的导入清单：发现文件后把文件列表 (大小、摘要)、目标表、格式与插入设置写成 JSON，
+//! 在另一台主机或集群上通过 `--files-from` 原样重放，保证灾备端导入的是完全相同的文件集合。
+//!
+//! 重放前逐个核对文件大小与摘要，任一文件缺失或内容不同则整个批次拒绝执行；
+//! 记录的插入设置与当前命令行不一致时只告警，以当前命令行为准。
+
+use crate::cli::Args;
+use crate::loader::Job;
+use crate::manifest::{self, Entry};
+use crate::{delta, remote};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const VERSION: u32 = 1;
+/// 校验失败时最多列出的文件数
+const SHOW_MISMATCHES: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoadManifest {
+    pub version: u32,
+    pub created_at: String,
+    pub format: String,
+    /// 每张表生效的插入设置
+    pub settings: BTreeMap<String, BTreeMap<String, String>>,
+    pub files: Vec<FileEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub table: String,
+    pub bytes: u64,
+    /// xxh3 摘要；远端文件不为此整文件下载，不记录摘要
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+impl LoadManifest {
+    /// 由已确定文件列表的任务生成清单，本地文件记录绝对路径
+    pub fn build(jobs: &[Job], cfg: &Args, format: &str) -> Result<Self> {
+        let mut settings = BTreeMap::new();
+        let mut files = Vec::new();
+        for job in jobs {
+            settings
+                .entry(job.table.clone())
+                .or_insert_with(|| insert_settings(cfg, &job.table));
+            for path in job.files.iter().flatten() {
+                let (bytes, hash) = describe(path)?;
+                let path = if remote::is_remote(path) {
+                    path.clone()
+                } else {
+                    std::fs::canonicalize(path).unwrap_or_else(|_| path.clone())
+                };
+                files.push(FileEntry {
+                    path,
+                    table: job.table.clone(),
+                    bytes,
+                    hash,
+                });
+            }
+        }
+        Ok(Self {
+            version: VERSION,
+            created_at: chrono::Local::now().to_rfc3339(),
+            format: format.to_string(),
+            settings,
+            files,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("无法写入导入清单: {:?}", path))
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取导入清单: {:?}", path))?;
+        let manifest: Self = serde_json::from_str(&content)
+            .with_context(|| format!("导入清单格式错误: {:?}", path))?;
+        if manifest.version != VERSION {
+            bail!("不支持的导入清单版本: {}", manifest.version);
+        }
+        Ok(manifest)
+    }
+
+    /// 核对每个文件的大小与摘要，全部一致才允许重放
+    pub fn verify(&self) -> Result<()> {
+        let mut mismatches = Vec::new();
+        for entry in &self.files {
+            let problem = match describe(&entry.path) {
+                Err(e) => Some(format!("{:#}", e)),
+                Ok((bytes, _)) if bytes != entry.bytes => {
+                    Some(format!("大小 {} ≠ 清单中的 {}", bytes, entry.bytes))
+                }
+                Ok((_, hash)) if entry.hash.is_some() && hash != entry.hash => {
+                    Some("摘要与清单不一致".to_string())
+                }
+                Ok(_) => None,
+            };
+            if let Some(problem) = problem {
+                mismatches.push((&entry.path, problem));
+            }
+        }
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        for (path, problem) in mismatches.iter().take(SHOW_MISMATCHES) {
+            eprintln!("❌ {:?}: {}", path, problem);
+        }
+        if mismatches.len() > SHOW_MISMATCHES {
+            eprintln!("   ... 另有 {} 个文件", mismatches.len() - SHOW_MISMATCHES);
+        }
+        bail!(
+            "导入清单校验失败: {}/{} 个文件与清单不一致",
+            mismatches.len(),
+            self.files.len()
+        )
+    }
+
+    /// 记录的插入设置与当前命令行不同的表逐项告警
+    pub fn check_settings(&self, cfg: &Args) {
+        for (table, recorded) in &self.settings {
+            let current = insert_settings(cfg, table);
+            if &current != recorded {
+                eprintln!(
+                    "⚠️ 表 {} 的插入设置与清单不同: 清单 {:?}，当前 {:?}",
+                    table, recorded, current
+                );
+            }
+        }
+    }
+
+    /// 按清单中的目标表拆成任务，顺序与清单一致
+    pub fn jobs(self) -> Result<Vec<Job>> {
+        let entries = self
+            .files
+            .into_iter()
+            .map(|f| Entry {
+                path: f.path,
+                table: Some(f.table),
+            })
+            .collect();
+        manifest::jobs(entries, &[], None)
+    }
+}
+
+/// 影响写入结果的插入设置
+fn insert_settings(cfg: &Args, table: &str) -> BTreeMap<String, String> {
+    let mut settings: BTreeMap<String, String> = cfg
+        .allow_errors_settings()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    if let Some(dedup) = cfg.insert_deduplicate_for(table) {
+        settings.insert("insert_deduplicate".into(), u8::from(dedup).to_string());
+    }
+    settings.extend(cfg.settings.iter().cloned());
+    if let Some(cols) = cfg.columns() {
+        settings.insert("columns".into(), cols.0.join(", "));
+    }
+    if let Some(exprs) = &cfg.transform_sql {
+        settings.insert("transform_sql".into(), exprs.clone());
+    }
+    if cfg.with_metadata {
+        let (file_col, time_col) = &cfg.metadata_columns;
+        settings.insert(
+            "metadata_columns".into(),
+            format!("{}, {}", file_col, time_col),
+        );
+    }
+    if let Some(structure) = &cfg.input_structure {
+        settings.insert("input_structure".into(), structure.clone());
+    }
+    settings
+}
+
+/// 文件的 (大小, 摘要)
+fn describe(path: &Path) -> Result<(u64, Option<String>)> {
+    if remote::is_remote(path) {
+        return Ok((remote::size(path)?, None));
+    }
+    let bytes = std::fs::metadata(path)
+        .with_context(|| format!("无法读取 {:?}", path))?
+        .len();
+    Ok((bytes, Some(delta::hash_file(path)?)))
+}
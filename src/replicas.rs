@@ -0,0 +1,102 @@
+//! `--sync-replicas`：导入完成后等待集群内各副本追上目标表的复制日志，再报告成功退出，
+//! 下游从其他副本读取时不会少数据。
+//!
+//! 通过 `clusterAllReplicas` 轮询每个副本：`system.replicas` 的 log_pointer 已越过 log_max_index
+//! (复制日志都已拉进本地队列)，且 `system.replication_queue` 中没有该表待执行的拉取 / 并入分区任务。
+//! 合并任务不影响可见性，不等待。
+
+use crate::cli::{self, Args};
+use crate::clickhouse::{self, locate};
+use anyhow::{bail, Context, Result};
+use tokio::time::{self, Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// 新写入的数据在其他副本上对应的队列任务
+const DATA_ENTRIES: &str = "'GET_PART', 'ATTACH_PART', 'REPLACE_RANGE'";
+
+/// 各副本的同步状态
+struct Lag {
+    replicas: u64,
+    /// 复制日志尚未全部拉进队列的副本数
+    behind: u64,
+    /// 待执行的数据任务数
+    queued: u64,
+}
+
+/// 等待 `cluster` 内 `table` 的全部副本同步完成，超过 --sync-timeout 时返回错误
+pub async fn wait(cfg: &Args, cluster: &str, table: &str) -> Result<()> {
+    let start = Instant::now();
+    let deadline = start + cfg.sync_timeout;
+    println!("⏳ 等待副本同步: {} (集群 {})", table, cluster);
+    loop {
+        let problem = match lag(cfg, cluster, table).await {
+            Ok(Lag { replicas: 0, .. }) => {
+                bail!(
+                    "--sync-replicas: 集群 {} 中没有表 {} 的副本 (不是 Replicated 表，或集群名不对)",
+                    cluster,
+                    table
+                );
+            }
+            Ok(Lag {
+                replicas,
+                behind: 0,
+                queued: 0,
+            }) => {
+                println!(
+                    "🔁 副本同步完成: {} 的 {} 个副本 | 耗时: {:.2?}",
+                    table,
+                    replicas,
+                    start.elapsed()
+                );
+                return Ok(());
+            }
+            Ok(lag) => format!(
+                "{} 个副本中 {} 个尚未拉取复制日志，队列中还有 {} 个数据任务",
+                lag.replicas, lag.behind, lag.queued
+            ),
+            Err(e) => format!("{:#}", e),
+        };
+        if Instant::now() >= deadline {
+            bail!(
+                "副本同步超时 ({:?}): {}: {}",
+                cfg.sync_timeout,
+                table,
+                problem
+            );
+        }
+        time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn lag(cfg: &Args, cluster: &str, table: &str) -> Result<Lag> {
+    let (db, name) = locate(table);
+    let source = |system: &str| {
+        format!(
+            "clusterAllReplicas('{}', system.{}) WHERE database = {} AND table = '{}'",
+            cli::sql_string(cluster),
+            system,
+            db,
+            cli::sql_string(&name)
+        )
+    };
+    let sql = format!(
+        "SELECT count(), countIf(log_pointer <= log_max_index) FROM {}",
+        source("replicas")
+    );
+    let tsv = clickhouse::query(cfg, &sql).await?;
+    let rows = clickhouse::rows(&tsv);
+    let Some([replicas, behind]) = rows.first().and_then(|r| r.get(..2)) else {
+        bail!("无法解析 system.replicas 查询结果: {:?}", tsv);
+    };
+    let sql = format!(
+        "SELECT count() FROM {} AND type IN ({})",
+        source("replication_queue"),
+        DATA_ENTRIES
+    );
+    let queued = clickhouse::query(cfg, &sql).await?;
+    Ok(Lag {
+        replicas: replicas.parse().context("无法解析副本数")?,
+        behind: behind.parse().context("无法解析副本数")?,
+        queued: queued.trim().parse().context("无法解析队列任务数")?,
+    })
+}
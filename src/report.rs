@@ -0,0 +1,223 @@
+//! 批次运行报告：每个文件一条记录，运行结束后写出为 JSON
+
+use crate::effective::EffectiveSetting;
+use crate::error::ClickHouseError;
+use crate::partitions::PartitionSummary;
+use crate::reconcile::Reconciliation;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 用户通过 `--tag key=value` 附加的标签，按 key 排序保证输出稳定
+pub type Tags = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Success,
+    Failed,
+}
+
+/// 发现了但没有导入的文件的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// 不匹配任何路由且未指定 -t
+    Unrouted,
+    /// 不满足 --newer-than / --older-than / --min-size / --max-size
+    Filtered,
+    /// --delta 下内容与上次成功导入时相同
+    Unchanged,
+    /// 0 字节文件
+    Empty,
+    /// ORC 文件没有数据行
+    ZeroRows,
+    /// --skip-loaded 下相同摘要的文件已成功导入过
+    AlreadyLoaded,
+    /// --check-footer 发现 ORC 文件尾损坏，已移入 quarantine/
+    Quarantined,
+    /// --keeper 下已被其他节点认领
+    Claimed,
+    /// --reject-duplicates 下内容与以其他文件名导入过的文件相同
+    Duplicate,
+    /// 启动前已被移走或删除
+    Vanished,
+    /// 收到停止信号时尚未启动
+    Interrupted,
+    /// canary 未通过，其余文件不再启动
+    CanaryFailed,
+    /// 同一表的其他文件遇到致命错误，其余文件不再启动
+    FatalError,
+    /// --depends 声明的依赖表有文件导入失败
+    DependencyFailed,
+    /// --fail-fast / --max-failures 的失败额度用完
+    FailureBudget,
+}
+
+impl SkipReason {
+    /// 按筛选、去重等规则本就无需导入，对账时不算遗漏
+    pub fn is_expected(self) -> bool {
+        matches!(
+            self,
+            Self::Filtered
+                | Self::Unchanged
+                | Self::Empty
+                | Self::ZeroRows
+                | Self::AlreadyLoaded
+                | Self::Claimed
+        )
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Unrouted => "未匹配路由",
+            Self::Filtered => "不满足 mtime / 大小筛选",
+            Self::Unchanged => "内容未变化",
+            Self::Empty => "空文件",
+            Self::ZeroRows => "ORC 无数据行",
+            Self::AlreadyLoaded => "已导入过",
+            Self::Quarantined => "ORC 文件损坏 (已隔离)",
+            Self::Claimed => "已被其他节点认领",
+            Self::Duplicate => "内容与其他文件名的文件重复",
+            Self::Vanished => "文件已消失",
+            Self::Interrupted => "中断时未启动",
+            Self::CanaryFailed => "canary 未通过",
+            Self::FatalError => "遇到致命错误后停止",
+            Self::DependencyFailed => "依赖的表导入失败",
+            Self::FailureBudget => "失败文件数达到上限后停止",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileRecord {
+    pub file: String,
+    pub path: PathBuf,
+    pub table: String,
+    pub status: FileStatus,
+    pub bytes: u64,
+    pub elapsed_secs: f64,
+    pub finished_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// ClickHouse 错误码与名称 (如 60 / UNKNOWN_TABLE)，非服务端错误时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_name: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: Tags,
+    /// 导入开始时文件的修改时间 (unix 秒)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<u64>,
+    /// 文件内容的 xxh3-128 摘要，--delta、合并导入或 --audit-table 时计算
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// 允许错误行时被服务端跳过的行数 (ORC 行数 - 实际写入行数)，仅 HTTP 传输可统计
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_rows: Option<u64>,
+    /// 服务端实际写入的行数与字节数 (HTTP 的 X-ClickHouse-Summary / native 的 Progress 包)，
+    /// clickhouse-client 传输无法统计
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub written_rows: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub written_bytes: Option<u64>,
+    /// 服务端执行耗时 (X-ClickHouse-Summary 的 elapsed_ns)，仅 HTTP 传输可统计
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_elapsed_secs: Option<f64>,
+    /// HTTP 传输读取的原始字节与实际发送的字节 (压缩后)；clickhouse-client 自行处理压缩，无法统计
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wire_bytes: Option<u64>,
+    /// 合并导入时所属组的 insert_deduplication_token，同组文件的值相同
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pack: Option<String>,
+    /// 导入时发送的 query_id (停滞重试时为最后一次尝试)，拆分导入的各组为 `<query_id>-<组号>`；
+    /// stdin 流取服务端返回的 X-ClickHouse-Query-Id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_id: Option<String>,
+    /// 越界时间戳 / Decimal 的处理策略 (如 `timestamp=clamp,decimal=fail`)，均未指定时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overflow_policy: Option<String>,
+    /// native 传输在本地按策略截断或置空的越界值个数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overflow_values: Option<u64>,
+    /// 服务端写入行数与 ORC 文件行数是否一致 (允许错误行时写入行数不多于文件行数即可)；
+    /// 服务端未返回写入行数或读不到 ORC 行数时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verified: Option<bool>,
+    /// 导入成功后的源文件处置 (移动 / 删除 / 压缩 / 上传) 是否完成；stdin 与失败的文件为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived: Option<bool>,
+}
+
+impl FileRecord {
+    pub fn fail(&mut self, err: &ClickHouseError) {
+        self.status = FileStatus::Failed;
+        self.error = Some(err.to_string().trim().to_string());
+        self.error_code = err.code();
+        self.error_name = err.name().map(str::to_string);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    /// 批次 id，也是本批各文件 query_id 的一部分
+    pub batch_id: String,
+    pub started_at: u64,
+    pub elapsed_secs: f64,
+    pub table: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: Tags,
+    pub files: Vec<FileRecord>,
+    /// 发现了但没有导入的文件及原因
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedFile>,
+    /// --partition-summary 的按分区汇总
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub partitions: Vec<PartitionSummary>,
+    /// 从 system.query_log 读回的插入设置及是否按要求生效
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub settings: Vec<EffectiveSetting>,
+    /// 发现 → 启动 → 服务端确认 → 行数核对 → 处置完成 的对账矩阵
+    pub reconciliation: Reconciliation,
+}
+
+impl BatchReport {
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("无法写入报告: {:?}", path))
+    }
+}
+
+/// 新批次的 id：启动时刻精确到毫秒，如 `20240101120000123`
+pub fn new_batch_id() -> String {
+    chrono::Local::now().format("%Y%m%d%H%M%S%3f").to_string()
+}
+
+pub fn unix_now() -> u64 {
+    unix_secs(SystemTime::now())
+}
+
+pub fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 解析 `key=value` 形式的标签
+pub fn parse_tag(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.trim().is_empty() => Ok((k.trim().to_string(), v.trim().to_string())),
+        _ => Err(format!("标签格式应为 key=value: {}", s)),
+    }
+}
@@ -0,0 +1,327 @@
+//! 按文件名路由：同一落地目录中的文件按 `--route 'events_*.orc=db.events'` 分派到不同的目标表
+//!
+//! 模式只匹配文件名，支持 `*` (任意长度) 与 `?` (单个字符)；按命令行顺序取第一个匹配的路由，
+//! 都不匹配时使用 `-t`，未提供 `-t` 的文件跳过。
+//!
+//! 表名后可用逗号附加按表生效的选项：`'stg_*.orc=db.stg,dedup=off'` 对该表关闭 insert_deduplicate。
+//!
+//! `--multi-table` 按目录约定路由：`<dir>/<table>/*.orc`，每个子目录的文件导入到同名表。
+//!
+//! `--depends 'fact_*=dim_*'` 声明表之间的依赖：同一次运行中匹配 `dim_*` 的表全部导入完成后，
+//! 才开始匹配 `fact_*` 的表；依赖的表有文件导入失败时，依赖它的表整体跳过。
+//! 表模式同时匹配完整表名 (`db.fact_x`) 与去掉库名的部分 (`fact_x`)。
+
+use crate::loader::Job;
+use crate::remote;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub pattern: String,
+    pub table: String,
+    /// 该表的 insert_deduplicate，未指定时沿用全局 --insert-deduplicate
+    pub insert_deduplicate: Option<bool>,
+}
+
+/// 表之间的依赖：匹配 `table` 的表在匹配 `after` 的表之后导入
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub table: String,
+    pub after: String,
+}
+
+impl Dependency {
+    /// `table` 是否因为这条依赖需要等待 `other`
+    fn applies(&self, table: &str, other: &str) -> bool {
+        table_match(&self.table, table) && table_match(&self.after, other) && table != other
+    }
+}
+
+/// 解析 `table_pattern=dependency_pattern`
+pub fn parse_dependency(s: &str) -> Result<Dependency, String> {
+    match s.split_once('=') {
+        Some((table, after)) if !table.trim().is_empty() && !after.trim().is_empty() => {
+            Ok(Dependency {
+                table: table.trim().to_string(),
+                after: after.trim().to_string(),
+            })
+        }
+        _ => Err(format!("依赖格式应为 表模式=依赖的表模式: {}", s)),
+    }
+}
+
+/// `table` 是否依赖 `other` (只看直接依赖)
+pub fn depends_on(deps: &[Dependency], table: &str, other: &str) -> bool {
+    deps.iter().any(|d| d.applies(table, other))
+}
+
+/// 按依赖对任务做拓扑排序，没有依赖关系的任务保持原有顺序；存在环时报错
+pub fn order(jobs: Vec<Job>, deps: &[Dependency]) -> Result<Vec<Job>> {
+    if deps.is_empty() {
+        return Ok(jobs);
+    }
+    let mut pending: Vec<Option<Job>> = jobs.into_iter().map(Some).collect();
+    let mut ordered = Vec::with_capacity(pending.len());
+    while ordered.len() < pending.len() {
+        // 每轮取第一个不依赖任何未排定任务的任务
+        let ready = (0..pending.len()).find(|&i| {
+            let Some(job) = &pending[i] else {
+                return false;
+            };
+            pending
+                .iter()
+                .flatten()
+                .all(|other| !depends_on(deps, &job.table, &other.table))
+        });
+        let Some(i) = ready else {
+            let mut tables: Vec<&str> =
+                pending.iter().flatten().map(|j| j.table.as_str()).collect();
+            tables.dedup();
+            bail!("表依赖存在环: {}", tables.join(", "));
+        };
+        ordered.push(pending[i].take().expect("任务尚未排定"));
+    }
+    Ok(ordered)
+}
+
+fn table_match(pattern: &str, table: &str) -> bool {
+    let short = table.rsplit_once('.').map_or(table, |(_, t)| t);
+    glob_match(pattern, table) || glob_match(pattern, short)
+}
+
+/// 解析 `pattern=table[,dedup=on|off]`
+pub fn parse_route(s: &str) -> Result<Route, String> {
+    let (spec, options) = match s.split_once(',') {
+        Some((spec, options)) => (spec, Some(options)),
+        None => (s, None),
+    };
+    let mut route = match spec.split_once('=') {
+        Some((pattern, table)) if !pattern.trim().is_empty() && !table.trim().is_empty() => Route {
+            pattern: pattern.trim().to_string(),
+            table: table.trim().to_string(),
+            insert_deduplicate: None,
+        },
+        _ => return Err(format!("路由格式应为 pattern=table[,dedup=on|off]: {}", s)),
+    };
+    for option in options.into_iter().flat_map(|o| o.split(',')) {
+        match option.trim().split_once('=') {
+            Some(("dedup", value)) => route.insert_deduplicate = Some(parse_switch(value)?),
+            _ => return Err(format!("未知的路由选项: {}", option)),
+        }
+    }
+    Ok(route)
+}
+
+/// on / off 开关，也接受 1/0、true/false
+pub fn parse_switch(s: &str) -> Result<bool, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "on" | "1" | "true" | "yes" => Ok(true),
+        "off" | "0" | "false" | "no" => Ok(false),
+        _ => Err(format!("开关取值应为 on / off: {}", s)),
+    }
+}
+
+/// 文件名匹配到的第一个路由的目标表
+pub fn table_for<'a>(routes: &'a [Route], path: &Path) -> Option<&'a str> {
+    let name = path.file_name()?.to_string_lossy();
+    routes
+        .iter()
+        .find(|r| glob_match(&r.pattern, &name))
+        .map(|r| r.table.as_str())
+}
+
+/// 把一个目录的文件按路由拆成每张表一个任务，任务顺序与路由首次命中的顺序一致；
+/// 同时返回未匹配任何路由 (且未指定 -t) 的文件
+pub fn split(
+    dir: &Path,
+    files: Vec<PathBuf>,
+    routes: &[Route],
+    default: Option<&str>,
+) -> (Vec<Job>, Vec<PathBuf>) {
+    let mut jobs: Vec<Job> = Vec::new();
+    let mut unrouted = Vec::new();
+    for path in files {
+        let Some(table) = table_for(routes, &path).or(default) else {
+            unrouted.push(path);
+            continue;
+        };
+        match jobs.iter_mut().find(|j| j.table == table) {
+            Some(job) => job.files.get_or_insert_with(Vec::new).push(path),
+            None => jobs.push(Job {
+                dir: dir.to_path_buf(),
+                table: table.to_string(),
+                files: Some(vec![path]),
+            }),
+        }
+    }
+    if !unrouted.is_empty() {
+        eprintln!(
+            "⚠️ {} 个文件未匹配任何路由且未指定 -t，已跳过",
+            unrouted.len()
+        );
+    }
+    (jobs, unrouted)
+}
+
+/// `--multi-table` 下的 (子目录, 表名)，按名称排序；跳过隐藏目录与根目录自身的 done / failed / quarantine
+pub fn table_dirs(root: &Path) -> Result<Vec<(PathBuf, String)>> {
+    if remote::is_remote(root) {
+        bail!("--multi-table 只支持本地目录");
+    }
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(root).with_context(|| format!("无法读取目录: {:?}", root))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_dir()
+            || name.starts_with('.')
+            || name == "done"
+            || name == "failed"
+            || name == "quarantine"
+        {
+            continue;
+        }
+        dirs.push((entry.path(), name));
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    // 经典的回溯匹配：记录最近一个 `*` 的位置，失配时让它多吞一个字符
+    let (mut pi, mut ni) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            pi = sp + 1;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(specs: &[&str]) -> Vec<Route> {
+        specs.iter().map(|s| parse_route(s).unwrap()).collect()
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("events_*.orc", "events_20240101.orc"));
+        assert!(glob_match("events_*.orc", "events_.orc"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*_*_x", "a_b_c_x"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("events_*.orc", "events_1.orc.tmp"));
+        assert!(!glob_match("*.orc", "a.csv"));
+    }
+
+    #[test]
+    fn parses_routes() {
+        let route = parse_route(" events_*.orc = db.events ").unwrap();
+        assert_eq!(route.pattern, "events_*.orc");
+        assert_eq!(route.table, "db.events");
+        assert_eq!(route.insert_deduplicate, None);
+
+        let route = parse_route("stg_*.orc=db.stg,dedup=off").unwrap();
+        assert_eq!(route.insert_deduplicate, Some(false));
+        assert_eq!(
+            parse_route("stg_*.orc=db.stg, dedup=ON")
+                .unwrap()
+                .insert_deduplicate,
+            Some(true)
+        );
+
+        assert!(parse_route("events_*.orc").is_err());
+        assert!(parse_route("=db.events").is_err());
+        assert!(parse_route("a=b,unknown=1").is_err());
+        assert!(parse_route("a=b,dedup=maybe").is_err());
+    }
+
+    #[test]
+    fn first_matching_route_wins() {
+        let routes = routes(&["events_eu_*=db.events_eu", "events_*=db.events"]);
+        assert_eq!(
+            table_for(&routes, Path::new("/in/events_eu_1.orc")),
+            Some("db.events_eu")
+        );
+        assert_eq!(
+            table_for(&routes, Path::new("/in/events_us_1.orc")),
+            Some("db.events")
+        );
+        // 只匹配文件名，不匹配目录部分
+        assert_eq!(table_for(&routes, Path::new("/events_x/a.orc")), None);
+    }
+
+    #[test]
+    fn splits_files_into_jobs() {
+        let routes = routes(&["a_*=db.a", "b_*=db.b"]);
+        let files: Vec<PathBuf> = ["b_1.orc", "a_1.orc", "c_1.orc", "b_2.orc"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        let (jobs, unrouted) = split(Path::new("/in"), files.clone(), &routes, None);
+        let tables: Vec<_> = jobs
+            .iter()
+            .map(|j| (j.table.as_str(), j.files.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            tables,
+            [
+                ("db.b", vec![files[0].clone(), files[3].clone()]),
+                ("db.a", vec![files[1].clone()]),
+            ]
+        );
+        assert_eq!(unrouted, [files[2].clone()]);
+
+        // 未匹配的文件进入 -t 指定的默认表
+        let (jobs, unrouted) = split(Path::new("/in"), files, &routes, Some("db.c"));
+        assert_eq!(jobs.len(), 3);
+        assert_eq!(jobs[2].table, "db.c");
+        assert!(unrouted.is_empty());
+    }
+
+    #[test]
+    fn orders_jobs_by_dependency() {
+        let job = |table: &str| Job {
+            dir: PathBuf::from("/in"),
+            table: table.to_string(),
+            files: None,
+        };
+        let deps = vec![parse_dependency("fact_*=dim_*").unwrap()];
+        assert!(depends_on(&deps, "db.fact_sales", "db.dim_shop"));
+        assert!(!depends_on(&deps, "db.dim_shop", "db.fact_sales"));
+
+        let jobs = vec![job("db.fact_sales"), job("db.other"), job("db.dim_shop")];
+        let tables: Vec<String> = order(jobs, &deps)
+            .unwrap()
+            .into_iter()
+            .map(|j| j.table)
+            .collect();
+        assert_eq!(tables, ["db.other", "db.dim_shop", "db.fact_sales"]);
+
+        let cycle = vec![
+            parse_dependency("a=b").unwrap(),
+            parse_dependency("b=a").unwrap(),
+        ];
+        assert!(order(vec![job("a"), job("b")], &cycle).is_err());
+        assert!(parse_dependency("fact_*=").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! S3 输入源：`--dir s3://bucket/prefix/`
+//!
+//! 通过 aws 命令行访问 S3，凭据沿用 aws 的标准链 (环境变量 / profile / 实例角色)，
+//! 自建 S3 兼容存储可通过 AWS_ENDPOINT_URL 指定。对象以 `s3://bucket/key` 形式的路径
+//! 在导入流程中流转，导入时由 `aws s3 cp <url> -` 直接流式写入 HTTP body，不落本地盘。
+
+use crate::archive::OnSuccess;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const SCHEME: &str = "s3://";
+
+pub fn is_s3(path: &Path) -> bool {
+    path.to_string_lossy().starts_with(SCHEME)
+}
+
+/// 拆分为 (bucket, key)
+fn split(path: &Path) -> Result<(String, String)> {
+    let s = path.to_string_lossy();
+    let rest = s
+        .strip_prefix(SCHEME)
+        .with_context(|| format!("不是 S3 路径: {}", s))?;
+    let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        bail!("S3 路径缺少 bucket: {}", s);
+    }
+    Ok((bucket.to_string(), key.to_string()))
+}
+
+fn aws(args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("aws")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context("无法启动 aws 命令行")?;
+    if !output.status.success() {
+        bail!(
+            "aws {} 失败: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// 列出前缀下的对象 (不递归)，跳过目录占位对象
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    let (bucket, mut prefix) = split(dir)?;
+    if !prefix.is_empty() && !prefix.ends_with('/') {
+        prefix.push('/');
+    }
+    let out = aws(&[
+        "s3api",
+        "list-objects-v2",
+        "--bucket",
+        &bucket,
+        "--prefix",
+        &prefix,
+        "--delimiter",
+        "/",
+        "--output",
+        "json",
+    ])?;
+    if out.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(Vec::new());
+    }
+    let json: serde_json::Value = serde_json::from_slice(&out).context("无法解析对象列表")?;
+    let mut files = Vec::new();
+    for obj in json["Contents"].as_array().into_iter().flatten() {
+        if let Some(key) = obj["Key"].as_str() {
+            if !key.ends_with('/') {
+                files.push(PathBuf::from(format!("{}{}/{}", SCHEME, bucket, key)));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// 对象大小
+pub fn size(path: &Path) -> Result<u64> {
+    let (bucket, key) = split(path)?;
+    let out = aws(&[
+        "s3api",
+        "head-object",
+        "--bucket",
+        &bucket,
+        "--key",
+        &key,
+        "--output",
+        "json",
+    ])?;
+    let json: serde_json::Value = serde_json::from_slice(&out)?;
+    json["ContentLength"]
+        .as_u64()
+        .with_context(|| format!("无法获取对象大小: {:?}", path))
+}
+
+/// 读取对象的 [offset, offset + len) 区间，读取 ORC 尾部元数据时使用
+pub fn read_range(path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+    let (bucket, key) = split(path)?;
+    // get-object 会把响应元数据打印到 stdout，内容只能写到文件
+    static SEQ: AtomicU64 = AtomicU64::new(0);
+    let tmp = std::env::temp_dir().join(format!(
+        "ck-loader-{}-{}",
+        std::process::id(),
+        SEQ.fetch_add(1, Ordering::Relaxed)
+    ));
+    let range = format!("bytes={}-{}", offset, offset + len - 1);
+    let result = aws(&[
+        "s3api",
+        "get-object",
+        "--bucket",
+        &bucket,
+        "--key",
+        &key,
+        "--range",
+        &range,
+        &tmp.to_string_lossy(),
+    ])
+    .and_then(|_| Ok(std::fs::read(&tmp)?));
+    let _ = std::fs::remove_file(&tmp);
+    let buf = result?;
+    if buf.len() as u64 != len {
+        bail!("读取 {:?} 区间 {} 不完整", path, range);
+    }
+    Ok(buf)
+}
+
+/// 启动 `aws s3 cp <url> -`，对象内容从子进程 stdout 流出
+pub fn stream(path: &Path) -> Result<tokio::process::Child> {
+    tokio::process::Command::new("aws")
+        .arg("s3")
+        .arg("cp")
+        .arg(path.as_os_str())
+        .arg("-")
+        .arg("--only-show-errors")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("无法启动 aws 命令行")
+}
+
+/// 把本地文件上传为 `target` 对象 (--archive-to)。上传时附带 SHA256 校验和由 S3 在接收端核对，
+/// 摘要 `xxh3` 记入对象元数据；上传后再核对对象的大小与元数据，确认无误才允许删除本地文件
+pub fn upload(src: &Path, target: &Path, xxh3: &str) -> Result<()> {
+    let size = std::fs::metadata(src)?.len();
+    let metadata = format!("xxh3={}", xxh3);
+    aws(&[
+        "s3",
+        "cp",
+        &src.to_string_lossy(),
+        &target.to_string_lossy(),
+        "--checksum-algorithm",
+        "SHA256",
+        "--metadata",
+        &metadata,
+        "--only-show-errors",
+    ])?;
+
+    let (bucket, key) = split(target)?;
+    let out = aws(&[
+        "s3api",
+        "head-object",
+        "--bucket",
+        &bucket,
+        "--key",
+        &key,
+        "--output",
+        "json",
+    ])?;
+    let json: serde_json::Value = serde_json::from_slice(&out)?;
+    if json["ContentLength"].as_u64() != Some(size) {
+        bail!(
+            "归档对象大小不一致: {:?} (本地 {} 字节，对象 {})",
+            target,
+            size,
+            json["ContentLength"]
+        );
+    }
+    if json["Metadata"]["xxh3"].as_str() != Some(xxh3) {
+        bail!("归档对象摘要不一致: {:?}", target);
+    }
+    Ok(())
+}
+
+/// 导入成功后在 S3 上移动或删除对象
+pub fn finish(policy: OnSuccess, path: &Path, done_dir: &Path) -> Result<Option<PathBuf>> {
+    let url = path.to_string_lossy();
+    match policy {
+        OnSuccess::Move => {
+            let target = done_dir.join(path.file_name().context("无效的对象名")?);
+            aws(&[
+                "s3",
+                "mv",
+                &url,
+                &target.to_string_lossy(),
+                "--only-show-errors",
+            ])?;
+            Ok(Some(target))
+        }
+        OnSuccess::Delete => {
+            aws(&["s3", "rm", &url, "--only-show-errors"])?;
+            Ok(None)
+        }
+        OnSuccess::Compress => bail!("S3 源不支持 --on-success compress"),
+        OnSuccess::Archive => bail!("S3 源不支持 --archive-to"),
+    }
+}
@@ -0,0 +1,155 @@
+//! 文本格式的抽样校验 (--validate-rows)：stdin 流开始上传前先在本地读出前 N 行，检查编码、分隔符与列数。
+//! 分隔符或编码不对时服务端往往要读完大量数据才报解析错误，这里在发送任何数据之前就拒绝；
+//! 校验通过后读出的部分原样拼回流的开头，服务端看到的数据不变。
+
+use crate::header;
+use anyhow::{bail, Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+/// 抽样读取的字节上限，超过仍读不满 N 行 (如整个流没有换行符) 时按已读到的行校验
+const SAMPLE_LIMIT: u64 = 64 * 1024 * 1024;
+/// 分隔符不对时检查的其他常见分隔符
+const CANDIDATES: [u8; 4] = [b',', b'\t', b';', b'|'];
+
+/// 格式的列分隔符、是否支持双引号包裹字段、表头行数
+struct Layout {
+    delimiter: u8,
+    quoted: bool,
+    header_rows: usize,
+}
+
+fn layout(format: &str) -> Option<Layout> {
+    let format = format.to_ascii_lowercase();
+    let (base, header_rows) = if let Some(base) = format.strip_suffix("withnamesandtypes") {
+        (base, 2)
+    } else if let Some(base) = format.strip_suffix("withnames") {
+        (base, 1)
+    } else {
+        (format.as_str(), 0)
+    };
+    let (delimiter, quoted) = match base {
+        "csv" => (b',', true),
+        "tsv" | "tsvraw" | "tabseparated" | "tabseparatedraw" => (b'\t', false),
+        _ => return None,
+    };
+    Some(Layout {
+        delimiter,
+        quoted,
+        header_rows,
+    })
+}
+
+/// 检查格式是否支持抽样校验，在开始读取 stdin 之前调用
+pub fn check_format(format: &str) -> Result<()> {
+    if layout(format).is_none() {
+        bail!("--validate-rows 仅支持 CSV / TSV 系列格式: {}", format);
+    }
+    Ok(())
+}
+
+/// 读出表头与前 `rows` 行数据并校验，返回拼回原样数据的完整流。
+/// `expected` 为目标需要的列数 (表 / --columns / --input-structure)，不带表头的格式按它核对列数
+pub async fn validate<R>(
+    reader: R,
+    format: &str,
+    rows: usize,
+    expected: Option<usize>,
+) -> Result<impl AsyncRead + Send + Unpin>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let Some(layout) = layout(format) else {
+        bail!("{} 格式不支持抽样校验", format);
+    };
+    let mut reader = BufReader::new(reader);
+    let mut sampled = Vec::new();
+    // (起始行号, 内容)；CSV 带引号的字段可以跨行，引号闭合前的行拼为一条记录
+    let mut records: Vec<(usize, String)> = Vec::new();
+    let mut line_no = 0;
+    let mut pending: Option<(usize, String)> = None;
+    while records.len() < layout.header_rows + rows && (sampled.len() as u64) < SAMPLE_LIMIT {
+        let mut raw = Vec::new();
+        let limit = SAMPLE_LIMIT - sampled.len() as u64;
+        let n = (&mut reader)
+            .take(limit)
+            .read_until(b'\n', &mut raw)
+            .await
+            .context("无法读取输入流")?;
+        if n == 0 {
+            break;
+        }
+        sampled.extend_from_slice(&raw);
+        line_no += 1;
+        let line = match String::from_utf8(raw) {
+            Ok(line) => line,
+            Err(e) => bail!(
+                "第 {} 行不是有效的 UTF-8 (行内第 {} 字节)，请检查文件编码 (如 GBK 需先转码)",
+                line_no,
+                e.utf8_error().valid_up_to() + 1
+            ),
+        };
+        let (start, mut record) = pending.take().unwrap_or((line_no, String::new()));
+        record.push_str(&line);
+        if layout.quoted && record.matches('"').count() % 2 == 1 {
+            pending = Some((start, record));
+            continue;
+        }
+        let record = record.trim_end_matches(['\r', '\n']).to_string();
+        if !record.is_empty() {
+            records.push((start, record));
+        }
+    }
+    if let Some((start, _)) = pending {
+        bail!("第 {} 行起的双引号没有闭合", start);
+    }
+
+    let counts: Vec<(usize, usize)> = records
+        .iter()
+        .map(|(no, record)| (*no, count_fields(record, &layout, layout.delimiter)))
+        .collect();
+    let data = &counts[layout.header_rows.min(counts.len())..];
+    if let Some(&(first_no, width)) = counts.first() {
+        if let Some(&(no, n)) = counts.iter().find(|(_, n)| *n != width) {
+            bail!(
+                "第 {} 行有 {} 列，与第 {} 行的 {} 列不一致，请检查分隔符与引号",
+                no,
+                n,
+                first_no,
+                width
+            );
+        }
+        if let Some(expected) = expected.filter(|e| layout.header_rows == 0 && *e != width) {
+            let mut message = format!(
+                "每行 {} 列，目标需要 {} 列 (按 {:?} 分隔)",
+                width, expected, layout.delimiter as char
+            );
+            if let Some(other) = guess_delimiter(&records, &layout, expected) {
+                message.push_str(&format!("；数据看起来是按 {:?} 分隔的", other as char));
+            }
+            bail!(message);
+        }
+        println!("🔍 抽样校验通过: {} 行数据，每行 {} 列", data.len(), width);
+    }
+
+    Ok(std::io::Cursor::new(sampled).chain(reader))
+}
+
+fn count_fields(record: &str, layout: &Layout, delimiter: u8) -> usize {
+    if layout.quoted {
+        header::split_fields(record, delimiter).len()
+    } else {
+        record.split(delimiter as char).count()
+    }
+}
+
+/// 按其他常见分隔符拆分后各行列数一致且等于目标列数时，认为数据实际使用的是该分隔符
+fn guess_delimiter(records: &[(usize, String)], layout: &Layout, expected: usize) -> Option<u8> {
+    CANDIDATES
+        .into_iter()
+        .filter(|d| *d != layout.delimiter)
+        .find(|d| {
+            records
+                .iter()
+                .all(|(_, record)| count_fields(record, layout, *d) == expected)
+        })
+}
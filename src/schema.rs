@@ -0,0 +1,376 @@
+//! 导入前的 schema 兼容性检查：对比 ORC 文件列与目标表 DESCRIBE 结果
+
+use crate::cli::Args;
+use crate::{clickhouse, orc, precompressed, remote};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SchemaCheck {
+    /// 不检查
+    Off,
+    /// 只打印差异
+    Warn,
+    /// 存在类型不兼容时中止整个批次
+    Strict,
+}
+
+/// 固定的 INSERT 列清单，生成 `INSERT INTO t (a, b, ...)`，不随文件中的列顺序变化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnList(pub Vec<String>);
+
+impl ColumnList {
+    /// 每行一个列名，`#` 之后为注释，空行忽略；列名可带反引号
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut cols: Vec<String> = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let name = line.trim().trim_end_matches(',').trim();
+            if name.is_empty() {
+                continue;
+            }
+            let name = name.trim_matches('`').to_string();
+            if cols.contains(&name) {
+                return Err(format!("列清单中重复的列: {}", name));
+            }
+            cols.push(name);
+        }
+        if cols.is_empty() {
+            return Err("列清单为空".to_string());
+        }
+        Ok(Self(cols))
+    }
+
+    /// 供 clap 使用：逗号分隔的列清单，如 `"id, ts, payload"`
+    pub fn inline(text: &str) -> Result<Self, String> {
+        Self::parse(&text.replace(',', "\n"))
+    }
+
+    /// 供 clap 使用：读取并解析列清单文件
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("无法读取列清单 {}: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    /// 形如 (`a`, `b`)，直接拼接在表名之后
+    pub fn sql(&self) -> String {
+        let quoted: Vec<String> = self
+            .0
+            .iter()
+            .map(|c| format!("`{}`", c.replace('`', "\\`")))
+            .collect();
+        format!("({})", quoted.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableColumn {
+    pub name: String,
+    pub type_name: String,
+    /// DEFAULT / MATERIALIZED / ALIAS / EPHEMERAL，普通列为空
+    pub default_kind: String,
+}
+
+impl TableColumn {
+    /// MATERIALIZED / ALIAS 列不能出现在 INSERT 中
+    pub fn insertable(&self) -> bool {
+        !matches!(self.default_kind.as_str(), "MATERIALIZED" | "ALIAS")
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SchemaDiff {
+    /// 表中存在但文件中缺失的列 (将使用默认值)
+    pub missing_in_file: Vec<(String, String)>,
+    /// 文件中存在但表中没有的列 (导入时被忽略)
+    pub extra_in_file: Vec<(String, String)>,
+    /// 同名但类型不兼容的列: (列名, ORC 类型, 表类型)
+    pub type_mismatch: Vec<(String, String, String)>,
+}
+
+impl SchemaDiff {
+    pub fn is_clean(&self) -> bool {
+        self.missing_in_file.is_empty()
+            && self.extra_in_file.is_empty()
+            && self.type_mismatch.is_empty()
+    }
+
+    pub fn print(&self) {
+        for (name, ty) in &self.missing_in_file {
+            println!("   - {:<32} {} (文件中缺失，将写入默认值)", name, ty);
+        }
+        for (name, ty) in &self.extra_in_file {
+            println!("   + {:<32} {} (表中不存在，将被忽略)", name, ty);
+        }
+        for (name, orc_ty, table_ty) in &self.type_mismatch {
+            println!("   ! {:<32} 文件: {} ≠ 表: {}", name, orc_ty, table_ty);
+        }
+    }
+}
+
+pub async fn describe_table(cfg: &Args, table: &str) -> Result<Vec<TableColumn>> {
+    let out = clickhouse::query(cfg, &format!("DESCRIBE TABLE {}", table)).await?;
+    Ok(clickhouse::rows(&out)
+        .into_iter()
+        .map(|r| TableColumn {
+            name: r.first().unwrap_or(&"").to_string(),
+            type_name: r.get(1).unwrap_or(&"").to_string(),
+            default_kind: r.get(2).unwrap_or(&"").to_string(),
+        })
+        .collect())
+}
+
+pub fn diff(file_cols: &[(String, String)], table_cols: &[TableColumn]) -> SchemaDiff {
+    let mut d = SchemaDiff::default();
+    for col in table_cols.iter().filter(|c| c.insertable()) {
+        match file_cols.iter().find(|(n, _)| *n == col.name) {
+            None => d
+                .missing_in_file
+                .push((col.name.clone(), col.type_name.clone())),
+            Some((_, orc_ty)) if !compatible(orc_ty, &col.type_name) => {
+                d.type_mismatch
+                    .push((col.name.clone(), orc_ty.clone(), col.type_name.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, ty) in file_cols {
+        if !table_cols.iter().any(|c| c.insertable() && c.name == *name) {
+            d.extra_in_file.push((name.clone(), ty.clone()));
+        }
+    }
+    d
+}
+
+#[derive(PartialEq)]
+enum Family {
+    Numeric,
+    Text,
+    Date,
+    Array,
+    Map,
+    Tuple,
+    Other,
+}
+
+/// 去掉 Nullable / LowCardinality 包装
+fn strip_wrappers(mut ty: &str) -> &str {
+    loop {
+        let inner = ["Nullable(", "LowCardinality("]
+            .iter()
+            .find_map(|p| ty.strip_prefix(p).and_then(|s| s.strip_suffix(')')));
+        match inner {
+            Some(i) => ty = i,
+            None => return ty,
+        }
+    }
+}
+
+fn family(ty: &str) -> Family {
+    let ty = strip_wrappers(ty);
+    let base = ty.split('(').next().unwrap_or(ty);
+    match base {
+        "Bool" | "Float32" | "Float64" | "Decimal" | "Decimal32" | "Decimal64" | "Decimal128"
+        | "Decimal256" => Family::Numeric,
+        b if b.starts_with("Int") || b.starts_with("UInt") => Family::Numeric,
+        "String" | "FixedString" | "Enum8" | "Enum16" | "UUID" | "IPv4" | "IPv6" => Family::Text,
+        "Date" | "Date32" | "DateTime" | "DateTime64" => Family::Date,
+        "Array" => Family::Array,
+        "Map" => Family::Map,
+        "Tuple" => Family::Tuple,
+        _ => Family::Other,
+    }
+}
+
+/// 宽松的兼容判断：同一大类内的转换交给服务端完成
+pub fn compatible(orc_ty: &str, table_ty: &str) -> bool {
+    let (a, b) = (family(orc_ty), family(table_ty));
+    a == b && a != Family::Other
+}
+
+/// 根据 ORC 元数据生成建表语句。
+/// 列不包装为 Nullable，以便 ORDER BY 模板可以直接引用；文件中的 NULL 由服务端按默认值写入
+pub fn create_table_sql(table: &str, meta: &orc::OrcMeta, engine: &str) -> String {
+    let cols: Vec<String> = meta
+        .columns()
+        .iter()
+        .map(|(name, ty)| format!("    `{}` {}", name.replace('`', "\\`"), ty))
+        .collect();
+    format!(
+        "CREATE TABLE IF NOT EXISTS {}\n(\n{}\n)\nENGINE = {}",
+        table,
+        cols.join(",\n"),
+        engine
+    )
+}
+
+/// 经 input() 写入时的输入结构：优先用 --input-structure，否则按 ORC 文件尾部推断。
+/// 不经 input() 写入时返回 None
+pub fn input_structure(cfg: &Args, path: Option<&Path>) -> Result<Option<String>> {
+    if !cfg.uses_input() {
+        return Ok(None);
+    }
+    if let Some(structure) = &cfg.input_structure {
+        return Ok(Some(structure.clone()));
+    }
+    let Some(path) = path else {
+        bail!("stdin 导入使用 --transform-sql / --with-metadata 时需要 --input-structure");
+    };
+    if precompressed::format(path) != "ORC" {
+        bail!(
+            "非 ORC 文件 {:?} 使用 --transform-sql / --with-metadata 时需要 --input-structure",
+            path
+        );
+    }
+    let scratch = match precompressed::detect(path) {
+        Some(_) if remote::is_remote(path) => {
+            bail!(
+                "远端的预压缩文件 {:?} 使用 --transform-sql / --with-metadata 时需要 --input-structure",
+                path
+            );
+        }
+        Some(codec) => Some(precompressed::decompress(path, codec)?),
+        None => None,
+    };
+    let local = scratch.as_ref().map_or(path, |s| s.path());
+    let meta = orc::read_meta(local).with_context(|| format!("无法从 {:?} 推断输入结构", path))?;
+    let cols: Vec<String> = meta
+        .columns()
+        .iter()
+        .map(|(name, ty)| format!("`{}` {}", name.replace('`', "\\`"), ty))
+        .collect();
+    if cols.is_empty() {
+        bail!("{:?} 中没有可用的列定义", path);
+    }
+    Ok(Some(cols.join(", ")))
+}
+
+/// 单个文件的 INSERT 语句，经 input() 写入时按该文件推断输入结构；
+/// 格式按 (去掉压缩后缀后的) 扩展名推断，未知扩展名按 ORC
+pub fn file_insert_sql(cfg: &Args, table: &str, path: &Path) -> Result<String> {
+    let format = precompressed::format(path);
+    let structure = input_structure(cfg, Some(path))?;
+    Ok(cfg.insert_sql(table, format, structure.as_deref(), &path.to_string_lossy()))
+}
+
+/// 结构定义 (如 `a UInt64, b Tuple(x String, y Int8)`) 中的顶层列名
+pub fn structure_columns(structure: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, c) in structure.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&structure[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&structure[start..]);
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.strip_prefix('`') {
+            Some(rest) => rest.split('`').next().unwrap_or_default().to_string(),
+            None => p.split_whitespace().next().unwrap_or_default().to_string(),
+        })
+        .collect()
+}
+
+/// 以第一个文件的 schema 创建目标表 (已存在则不做任何事)
+pub async fn create_table(cfg: &Args, table: &str, sample: &Path, engine: &str) -> Result<()> {
+    let meta = orc::read_meta(sample).with_context(|| format!("无法从 {:?} 推断表结构", sample))?;
+    if meta.columns().is_empty() {
+        bail!("{:?} 中没有可用的列定义", sample);
+    }
+    let sql = create_table_sql(table, &meta, engine);
+    println!("🛠️ 建表 (IF NOT EXISTS):\n{}", sql);
+    clickhouse::query(cfg, &sql).await?;
+    Ok(())
+}
+
+/// 读取前 `sample` 个文件的 schema 与目标表比较，strict 模式下不兼容即报错
+pub async fn check(
+    mode: SchemaCheck,
+    cfg: &Args,
+    table: &str,
+    files: &[PathBuf],
+    sample: usize,
+) -> Result<()> {
+    if mode == SchemaCheck::Off {
+        return Ok(());
+    }
+    // 经过 SELECT 转换后文件列与表列不再一一对应，列名与类型由服务端在执行 SELECT 时校验
+    if cfg.transform_sql.is_some() {
+        println!("🧬 使用 --transform-sql，跳过 ORC 与表结构的直接比对");
+        return Ok(());
+    }
+    let mut table_cols = match describe_table(cfg, table).await {
+        Ok(cols) => cols,
+        Err(e) if mode == SchemaCheck::Warn => {
+            println!("⚠️ schema 检查: 无法读取表 {} 的结构，跳过: {:#}", table, e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let mut incompatible = 0;
+
+    // 指定了列清单时只核对清单中的列，清单里表中不存在的列直接判为不兼容
+    if let Some(list) = cfg.columns() {
+        for name in &list.0 {
+            if !table_cols.iter().any(|c| c.insertable() && c.name == *name) {
+                println!("🧬 列清单中的 {} 不是表 {} 的可写入列", name, table);
+                incompatible += 1;
+            }
+        }
+        table_cols.retain(|c| list.0.contains(&c.name));
+    }
+    // --with-metadata 的来源与时间列由本工具填充，不要求出现在文件中
+    if cfg.with_metadata {
+        let (file_col, time_col) = &cfg.metadata_columns;
+        table_cols.retain(|c| c.name != *file_col && c.name != *time_col);
+    }
+
+    for path in files.iter().take(sample.max(1)) {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !precompressed::is_plain_orc(path) {
+            println!("⏭️ schema 检查: {} 不是未压缩的 ORC 文件，跳过", file_name);
+            continue;
+        }
+        let meta = match orc::read_meta(path) {
+            Ok(m) => m,
+            Err(e) => {
+                println!(
+                    "⚠️ schema 检查: 无法读取 {} 的 ORC 元数据: {:#}",
+                    file_name, e
+                );
+                incompatible += 1;
+                continue;
+            }
+        };
+        let d = diff(&meta.columns(), &table_cols);
+        if d.is_clean() {
+            println!("🧬 schema 检查通过: {}", file_name);
+            continue;
+        }
+        println!("🧬 schema 差异: {} ↔ {}", file_name, table);
+        d.print();
+        if !d.type_mismatch.is_empty() || d.missing_in_file.len() == table_cols.len() {
+            incompatible += 1;
+        }
+    }
+
+    if incompatible > 0 && mode == SchemaCheck::Strict {
+        bail!(
+            "{} 个文件与表 {} 的 schema 不兼容，已中止 (可用 --schema-check warn 跳过)",
+            incompatible,
+            table
+        );
+    }
+    Ok(())
+}
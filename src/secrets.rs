@@ -0,0 +1,258 @@
+//! 凭据解析：`--password` 除明文外还支持从外部密钥服务读取
+//!
+//! - `env:VAR`                  环境变量
+//! - `file:/path`               文件内容 (去掉首尾空白)，适用于挂载的 k8s secret
+//! - `vault:secret/ch#password` HashiCorp Vault KV (v1/v2)，地址与 token 取自 VAULT_ADDR / VAULT_TOKEN
+//! - `aws-sm:<id>[#field]`      AWS Secrets Manager，通过 aws CLI 读取，指定 field 时按 JSON 取字段
+//!
+//! 外部来源的值会缓存一段时间 (Vault 以 lease_duration 为准)，到期后重新读取；
+//! 服务端返回认证失败时调用 `invalidate` 立即作废缓存，下一次使用时拿到轮换后的新值。
+
+use crate::cli;
+use crate::http;
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::process::Command;
+use tokio::time::{Duration, Instant};
+
+/// 外部来源未给出有效期时的默认缓存时长
+const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+#[derive(Debug)]
+enum Source {
+    Literal(String),
+    Env(String),
+    File(PathBuf),
+    Vault { path: String, field: String },
+    AwsSm { id: String, field: Option<String> },
+}
+
+#[derive(Clone)]
+pub struct Secret {
+    source: Arc<Source>,
+    cache: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.source {
+            Source::Literal(_) => f.write_str("Secret(***)"),
+            other => write!(f, "Secret({:?})", other),
+        }
+    }
+}
+
+impl Secret {
+    /// clap 参数解析入口，不含已知前缀的值按明文处理
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let source = if let Some(var) = s.strip_prefix("env:") {
+            Source::Env(var.to_string())
+        } else if let Some(path) = s.strip_prefix("file:") {
+            Source::File(PathBuf::from(path))
+        } else if let Some(rest) = s.strip_prefix("vault:") {
+            let (path, field) = rest
+                .split_once('#')
+                .ok_or_else(|| format!("Vault 引用格式应为 vault:<path>#<field>: {}", s))?;
+            Source::Vault {
+                path: path.trim_matches('/').to_string(),
+                field: field.to_string(),
+            }
+        } else if let Some(rest) = s.strip_prefix("aws-sm:") {
+            let (id, field) = match rest.split_once('#') {
+                Some((id, field)) => (id, Some(field.to_string())),
+                None => (rest, None),
+            };
+            Source::AwsSm {
+                id: id.to_string(),
+                field,
+            }
+        } else {
+            Source::Literal(s.to_string())
+        };
+        Ok(Self {
+            source: Arc::new(source),
+            cache: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// 明文值，不识别 env: / file: 等前缀 (如连接串中的密码)
+    pub fn literal(s: &str) -> Self {
+        Self {
+            source: Arc::new(Source::Literal(s.to_string())),
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 取当前值，缓存未过期时不访问外部服务
+    pub async fn get(&self) -> Result<String> {
+        if let Source::Literal(v) = &*self.source {
+            return Ok(v.clone());
+        }
+        if let Some((value, expires)) = &*self.cache.lock().unwrap() {
+            if Instant::now() < *expires {
+                return Ok(value.clone());
+            }
+        }
+        let (value, ttl) = self.fetch().await?;
+        *self.cache.lock().unwrap() = Some((value.clone(), cli::deadline_after(ttl)));
+        Ok(value)
+    }
+
+    /// 作废缓存 (凭据可能已被轮换)
+    pub fn invalidate(&self) {
+        if self.cache.lock().unwrap().take().is_some() {
+            println!("🔑 凭据缓存已作废，下次使用时重新读取");
+        }
+    }
+
+    async fn fetch(&self) -> Result<(String, Duration)> {
+        match &*self.source {
+            Source::Literal(v) => Ok((v.clone(), DEFAULT_TTL)),
+            Source::Env(var) => {
+                let v = std::env::var(var).with_context(|| format!("环境变量未设置: {}", var))?;
+                Ok((v, DEFAULT_TTL))
+            }
+            Source::File(path) => {
+                let v = std::fs::read_to_string(path)
+                    .with_context(|| format!("无法读取密码文件: {:?}", path))?;
+                Ok((v.trim().to_string(), DEFAULT_TTL))
+            }
+            Source::Vault { path, field } => vault(path, field).await,
+            Source::AwsSm { id, field } => aws_sm(id, field.as_deref()).await,
+        }
+    }
+}
+
+async fn vault(path: &str, field: &str) -> Result<(String, Duration)> {
+    let addr = std::env::var("VAULT_ADDR").context("使用 vault: 凭据需要设置 VAULT_ADDR")?;
+    let token = match std::env::var("VAULT_TOKEN") {
+        Ok(t) => t,
+        Err(_) => {
+            let home = std::env::var("HOME").unwrap_or_default();
+            std::fs::read_to_string(PathBuf::from(home).join(".vault-token"))
+                .context("使用 vault: 凭据需要设置 VAULT_TOKEN 或 ~/.vault-token")?
+                .trim()
+                .to_string()
+        }
+    };
+    let url = format!("{}/v1/{}", addr.trim_end_matches('/'), path);
+    let mut req = http::build_client()?
+        .get(&url)
+        .header("X-Vault-Token", token);
+    if let Ok(ns) = std::env::var("VAULT_NAMESPACE") {
+        req = req.header("X-Vault-Namespace", ns);
+    }
+    let resp = req
+        .send()
+        .await
+        .with_context(|| format!("无法连接 Vault: {}", addr))?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("读取 Vault 密钥失败: {} | HTTP {}", path, status);
+    }
+    let body: serde_json::Value =
+        serde_json::from_str(&resp.text().await?).context("Vault 响应不是合法 JSON")?;
+
+    // KV v2 的值在 data.data 下，KV v1 直接在 data 下
+    let data = &body["data"];
+    let value = data["data"]
+        .get(field)
+        .or_else(|| data.get(field))
+        .and_then(|v| v.as_str())
+        .with_context(|| format!("Vault 密钥 {} 中没有字段 {}", path, field))?;
+    let ttl = match body["lease_duration"].as_u64() {
+        Some(secs) if secs > 0 => Duration::from_secs(secs),
+        _ => DEFAULT_TTL,
+    };
+    Ok((value.to_string(), ttl))
+}
+
+async fn aws_sm(id: &str, field: Option<&str>) -> Result<(String, Duration)> {
+    let output = Command::new("aws")
+        .args(["secretsmanager", "get-secret-value", "--secret-id", id])
+        .args(["--query", "SecretString", "--output", "text"])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("无法启动 aws 命令行")?;
+    if !output.status.success() {
+        bail!(
+            "读取 AWS Secrets Manager 密钥失败: {} | {}",
+            id,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let value = match field {
+        None => text,
+        Some(field) => {
+            let json: serde_json::Value =
+                serde_json::from_str(&text).with_context(|| format!("密钥 {} 不是 JSON", id))?;
+            json.get(field)
+                .and_then(|v| v.as_str())
+                .with_context(|| format!("密钥 {} 中没有字段 {}", id, field))?
+                .to_string()
+        }
+    };
+    Ok((value, DEFAULT_TTL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_specs() {
+        let source = |s: &str| Secret::parse(s).unwrap().source;
+        assert!(matches!(&*source("hunter2"), Source::Literal(v) if v == "hunter2"));
+        assert!(matches!(&*source("env:CH_PASS"), Source::Env(v) if v == "CH_PASS"));
+        assert!(
+            matches!(&*source("file:/run/secrets/ch"), Source::File(p) if p == std::path::Path::new("/run/secrets/ch"))
+        );
+        assert!(matches!(
+            &*source("vault:/secret/data/ch/#password"),
+            Source::Vault { path, field } if path == "secret/data/ch" && field == "password"
+        ));
+        assert!(matches!(
+            &*source("aws-sm:prod/ch#password"),
+            Source::AwsSm { id, field: Some(f) } if id == "prod/ch" && f == "password"
+        ));
+        assert!(matches!(
+            &*source("aws-sm:prod/ch"),
+            Source::AwsSm { id, field: None } if id == "prod/ch"
+        ));
+        // Vault 引用必须指定字段
+        assert!(Secret::parse("vault:secret/ch").is_err());
+        // 明文不出现在 Debug 输出中
+        assert_eq!(
+            format!("{:?}", Secret::parse("hunter2").unwrap()),
+            "Secret(***)"
+        );
+        assert_eq!(format!("{:?}", Secret::literal("env:X")), "Secret(***)");
+    }
+
+    #[tokio::test]
+    async fn caches_until_expired_or_invalidated() {
+        let path = std::env::temp_dir().join(format!("ck-secret-{}", std::process::id()));
+        std::fs::write(&path, " first\n").unwrap();
+        let secret = Secret::parse(&format!("file:{}", path.display())).unwrap();
+        assert_eq!(secret.get().await.unwrap(), "first");
+
+        // 缓存有效期内不重新读取
+        std::fs::write(&path, "second").unwrap();
+        assert_eq!(secret.get().await.unwrap(), "first");
+
+        // 作废后立即读取新值
+        secret.invalidate();
+        assert_eq!(secret.get().await.unwrap(), "second");
+
+        // 缓存过期后重新读取
+        std::fs::write(&path, "third").unwrap();
+        secret.cache.lock().unwrap().as_mut().unwrap().1 = Instant::now();
+        assert_eq!(secret.get().await.unwrap(), "third");
+        let _ = std::fs::remove_file(&path);
+    }
+}
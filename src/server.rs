@@ -0,0 +1,416 @@
+//! serve 子命令：常驻进程 + HTTP 控制接口
+//!
+//! - `POST   /jobs`       提交任务 `{"dir": "...", "table": "..."}`；目录已被其他任务或进程锁定时返回 409
+//! - `GET    /jobs`       列出全部任务
+//! - `GET    /jobs/{id}`  查询单个任务 (含最近的失败文件)
+//! - `DELETE /jobs/{id}`  取消任务：未开始的文件不再启动，进行中的导入被中止
+//! - `POST   /pause`      暂停分发新文件 (所有任务)，进行中的导入继续；等同 SIGUSR1
+//! - `POST   /resume`     恢复分发；等同 SIGUSR2
+//! - `GET    /api/files`  只读查询台账 (需 --ledger)，参数 status / table / since / limit，
+//!   since 可以是 unix 时间戳，也可以是相对时长如 `2h`
+//!
+//! 任务只保留成功/失败计数与最近 `RECENT_FAILURES` 个失败文件；已结束的任务超过 --job-ttl
+//! 或超出 --max-finished-jobs 时从列表移除，常驻进程的内存不随处理过的文件数增长。
+//!
+//! 工作池 (并行许可) 与 HTTP 连接池在所有任务间共享，--workers 是整个进程的并行上限。
+//! 收到 SIGINT / SIGTERM 后不再接受新任务，各任务不再启动新文件；进行中的导入在 --shutdown-grace
+//! 宽限期内可以完成，之后 (或再次收到信号) 被中断，所有任务结束后进程退出。
+
+use crate::cli::{self, Args, ServeArgs};
+use crate::events::EventStream;
+use crate::ledger::{LedgerEntry, LedgerQuery};
+use crate::loader::{self, FileHook, Job, Pool};
+use crate::lock;
+use crate::report::{self, FileRecord, FileStatus};
+use crate::shutdown::Shutdown;
+use anyhow::{Context, Result};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// 每个任务保留的最近失败文件数
+const RECENT_FAILURES: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JobState {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct JobView {
+    id: u64,
+    dir: PathBuf,
+    table: String,
+    state: JobState,
+    submitted_at: u64,
+    succeeded: usize,
+    failed: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<u64>,
+    /// 最近的失败文件，最多 RECENT_FAILURES 个
+    #[serde(skip_serializing_if = "VecDeque::is_empty")]
+    failures: VecDeque<FileRecord>,
+}
+
+struct JobEntry {
+    view: JobView,
+    cancel: Shutdown,
+    /// 任务结束 (含取消) 时触发，退出时据此等待所有任务
+    done: CancellationToken,
+    /// 任务结束时刻，用于按 --job-ttl 淘汰
+    finished: Option<Instant>,
+}
+
+struct AppState {
+    cfg: Arc<Args>,
+    pool: Pool,
+    jobs: Mutex<BTreeMap<u64, JobEntry>>,
+    next_id: AtomicU64,
+    /// 进程退出信号
+    signals: Shutdown,
+    job_ttl: Duration,
+    max_finished_jobs: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubmitRequest {
+    dir: PathBuf,
+    table: String,
+}
+
+pub async fn serve(args: ServeArgs, events: EventStream) -> Result<()> {
+    let cfg = Arc::new(args.opts);
+    let state = Arc::new(AppState {
+        pool: Pool::new(&cfg, events).await?,
+        signals: Shutdown::on_signals(cfg.shutdown_grace),
+        cfg,
+        jobs: Mutex::new(BTreeMap::new()),
+        next_id: AtomicU64::new(1),
+        job_ttl: args.job_ttl,
+        max_finished_jobs: args.max_finished_jobs,
+    });
+
+    let app = Router::new()
+        .route("/jobs", get(list_jobs).post(submit_job))
+        .route("/jobs/{id}", get(get_job).delete(cancel_job))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/api/files", get(list_files))
+        .with_state(Arc::clone(&state));
+
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .with_context(|| format!("无法监听 {}", args.listen))?;
+    println!("🛰️ 控制接口已启动: http://{}", args.listen);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(drain(state))
+        .await?;
+    Ok(())
+}
+
+/// 收到退出信号后让各任务不再启动新文件，宽限期结束 (或再次收到信号) 时中断进行中的导入，
+/// 所有任务结束后返回；排空期间控制接口照常可以查询进度
+async fn drain(state: Arc<AppState>) {
+    state.signals.stopping().await;
+    let done: Vec<CancellationToken> = {
+        let jobs = state.jobs.lock().unwrap();
+        for entry in jobs.values() {
+            entry.cancel.stop();
+        }
+        jobs.values().map(|e| e.done.clone()).collect()
+    };
+    let finished = join_all(done.iter().map(|d| d.cancelled()));
+    tokio::pin!(finished);
+    tokio::select! {
+        _ = &mut finished => return,
+        _ = state.signals.aborted() => {}
+    }
+    for entry in state.jobs.lock().unwrap().values() {
+        entry.cancel.cancel();
+    }
+    finished.await;
+}
+
+async fn submit_job(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SubmitRequest>,
+) -> Result<(StatusCode, Json<JobView>), (StatusCode, String)> {
+    // 与 load / watch 共用目录锁，同一目录同时只有一个任务或进程在导入；任务结束时释放
+    let dir_lock = match lock::try_acquire(&state.cfg, &req.dir) {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("目录 {:?} 正由另一个任务或 ck-loader 进程处理", req.dir),
+            ))
+        }
+        Err(e) => return Err((StatusCode::BAD_REQUEST, format!("{:#}", e))),
+    };
+    let id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    let cancel = Shutdown::default();
+    let done = CancellationToken::new();
+    let view = JobView {
+        id,
+        dir: req.dir.clone(),
+        table: req.table.clone(),
+        state: JobState::Running,
+        submitted_at: report::unix_now(),
+        succeeded: 0,
+        failed: 0,
+        error: None,
+        finished_at: None,
+        failures: VecDeque::new(),
+    };
+    {
+        // 持锁检查退出信号，drain 取得任务列表之后不会再有新任务加入
+        let mut jobs = state.jobs.lock().unwrap();
+        evict(&mut jobs, state.job_ttl, state.max_finished_jobs);
+        if state.signals.is_stopping() {
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "进程正在退出，不再接受新任务".to_string(),
+            ));
+        }
+        jobs.insert(
+            id,
+            JobEntry {
+                view: view.clone(),
+                cancel: cancel.clone(),
+                done: done.clone(),
+                finished: None,
+            },
+        );
+    }
+    println!("📥 收到任务 #{}: {:?} → {}", id, req.dir, req.table);
+
+    // 逐文件回调：实时累计成功/失败数，只保留最近的失败文件
+    let hook_state = Arc::clone(&state);
+    let on_file: FileHook = Arc::new(move |record: &FileRecord| {
+        if let Some(entry) = hook_state.jobs.lock().unwrap().get_mut(&id) {
+            match record.status {
+                FileStatus::Success => entry.view.succeeded += 1,
+                FileStatus::Failed => {
+                    entry.view.failed += 1;
+                    if entry.view.failures.len() == RECENT_FAILURES {
+                        entry.view.failures.pop_front();
+                    }
+                    entry.view.failures.push_back(record.clone());
+                }
+            }
+        }
+    });
+
+    let run_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let _done = done.drop_guard();
+        let _lock = dir_lock;
+        let job = Job {
+            dir: req.dir,
+            table: req.table,
+            files: None,
+        };
+        let result = loader::run(
+            Arc::clone(&run_state.cfg),
+            job,
+            run_state.pool.next_batch(),
+            cancel.clone(),
+            Some(on_file),
+        )
+        .await;
+
+        let mut jobs = run_state.jobs.lock().unwrap();
+        let Some(entry) = jobs.get_mut(&id) else {
+            return;
+        };
+        entry.view.state = match &result {
+            _ if cancel.is_stopping() => JobState::Cancelled,
+            Ok(_) if entry.view.failed == 0 => JobState::Succeeded,
+            Ok(_) => JobState::Failed,
+            Err(_) => JobState::Failed,
+        };
+        if let Err(e) = result {
+            entry.view.error = Some(format!("{:#}", e));
+        }
+        entry.view.finished_at = Some(report::unix_now());
+        entry.finished = Some(Instant::now());
+        println!("📤 任务 #{} 结束: {:?}", id, entry.view.state);
+        evict(&mut jobs, run_state.job_ttl, run_state.max_finished_jobs);
+    });
+
+    Ok((StatusCode::CREATED, Json(view)))
+}
+
+/// 移除超过 --job-ttl 的已结束任务，再按结束先后移除超出 --max-finished-jobs 的部分；运行中的任务不受影响
+fn evict(jobs: &mut BTreeMap<u64, JobEntry>, ttl: Duration, max_finished: usize) {
+    jobs.retain(|_, e| e.finished.is_none_or(|t| t.elapsed() < ttl));
+    let mut finished: Vec<(Instant, u64)> = jobs
+        .iter()
+        .filter_map(|(id, e)| e.finished.map(|t| (t, *id)))
+        .collect();
+    if finished.len() > max_finished {
+        finished.sort_unstable();
+        for (_, id) in &finished[..finished.len() - max_finished] {
+            jobs.remove(id);
+        }
+    }
+}
+
+async fn list_jobs(State(state): State<Arc<AppState>>) -> Json<Vec<JobView>> {
+    let mut jobs = state.jobs.lock().unwrap();
+    evict(&mut jobs, state.job_ttl, state.max_finished_jobs);
+    // 列表只返回汇总，失败文件通过单个任务接口查看
+    Json(
+        jobs.values()
+            .map(|e| JobView {
+                failures: VecDeque::new(),
+                ..e.view.clone()
+            })
+            .collect(),
+    )
+}
+
+async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Result<Json<JobView>, StatusCode> {
+    let mut jobs = state.jobs.lock().unwrap();
+    evict(&mut jobs, state.job_ttl, state.max_finished_jobs);
+    jobs.get(&id)
+        .map(|e| Json(e.view.clone()))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn cancel_job(State(state): State<Arc<AppState>>, Path(id): Path<u64>) -> StatusCode {
+    let jobs = state.jobs.lock().unwrap();
+    match jobs.get(&id) {
+        Some(entry) if entry.view.state == JobState::Running => {
+            entry.cancel.cancel();
+            println!("🛑 取消任务 #{}", id);
+            StatusCode::ACCEPTED
+        }
+        Some(_) => StatusCode::CONFLICT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PauseView {
+    paused: bool,
+    changed: bool,
+}
+
+async fn pause(State(state): State<Arc<AppState>>) -> Json<PauseView> {
+    let changed = state.pool.pause.pause();
+    Json(PauseView {
+        paused: true,
+        changed,
+    })
+}
+
+async fn resume(State(state): State<Arc<AppState>>) -> Json<PauseView> {
+    let changed = state.pool.pause.resume();
+    Json(PauseView {
+        paused: false,
+        changed,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct FilesParams {
+    status: Option<String>,
+    table: Option<String>,
+    since: Option<String>,
+    limit: Option<usize>,
+}
+
+/// 大于该值的纯数字 since 视为 unix 时间戳，否则视为相对时长
+const TIMESTAMP_THRESHOLD: u64 = 1_000_000_000;
+
+async fn list_files(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<FilesParams>,
+) -> Result<Json<Vec<LedgerEntry>>, (StatusCode, String)> {
+    let Some(ledger) = state.pool.ledger.clone() else {
+        return Err((StatusCode::NOT_FOUND, "未启用台账 (--ledger)".to_string()));
+    };
+    let since = match params.since.as_deref() {
+        None => None,
+        Some(s) => match s.parse::<u64>() {
+            Ok(ts) if ts >= TIMESTAMP_THRESHOLD => Some(ts),
+            _ => {
+                let ago = cli::parse_duration(s).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+                Some(report::unix_now().saturating_sub(ago.as_secs()))
+            }
+        },
+    };
+    let query = LedgerQuery {
+        status: params.status,
+        table: params.table,
+        since,
+        limit: params.limit.unwrap_or(1000),
+    };
+    tokio::task::spawn_blocking(move || ledger.query(&query))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: u64, finished: Option<Instant>) -> JobEntry {
+        JobEntry {
+            view: JobView {
+                id,
+                dir: PathBuf::from("/data"),
+                table: "t".to_string(),
+                state: JobState::Running,
+                submitted_at: 0,
+                succeeded: 0,
+                failed: 0,
+                error: None,
+                finished_at: None,
+                failures: VecDeque::new(),
+            },
+            cancel: Shutdown::default(),
+            done: CancellationToken::new(),
+            finished,
+        }
+    }
+
+    #[test]
+    fn evicts_finished_jobs() {
+        let now = Instant::now();
+        let mut jobs = BTreeMap::new();
+        jobs.insert(1, entry(1, Some(now - Duration::from_secs(7200))));
+        jobs.insert(2, entry(2, Some(now - Duration::from_secs(30))));
+        jobs.insert(3, entry(3, Some(now - Duration::from_secs(20))));
+        jobs.insert(4, entry(4, None));
+        jobs.insert(5, entry(5, Some(now - Duration::from_secs(10))));
+
+        // 超过 TTL 的任务移除，运行中的任务保留
+        evict(&mut jobs, Duration::from_secs(3600), 10);
+        assert_eq!(jobs.keys().copied().collect::<Vec<_>>(), [2, 3, 4, 5]);
+
+        // 超出数量上限时先移除最早结束的
+        evict(&mut jobs, Duration::from_secs(3600), 1);
+        assert_eq!(jobs.keys().copied().collect::<Vec<_>>(), [4, 5]);
+    }
+}
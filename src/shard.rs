@@ -0,0 +1,78 @@
+//! `--shard K/N`：多台机器上的 N 个实例各自只导入文件名哈希落在第 K 份的文件，
+//! 不需要协调者即可分摊同一个超大目录。
+//!
+//! 按文件名 (不含目录) 的 xxh3 取模，与文件的挂载路径、列出顺序无关，各实例的结果互不重叠且合起来覆盖全部文件。
+//! 分片在扫描目录之后、其他筛选之前进行，其他实例负责的文件不计入本实例的对账与报告。
+
+use std::path::Path;
+use xxhash_rust::xxh3::xxh3_64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    /// 从 1 开始
+    pub index: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    pub fn contains(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        xxh3_64(name.as_encoded_bytes()) % self.count as u64 == (self.index - 1) as u64
+    }
+}
+
+impl std::fmt::Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// 解析 `K/N`，1 <= K <= N
+pub fn parse_shard(s: &str) -> Result<Shard, String> {
+    let invalid = || format!("无效的分片 (应为 K/N，如 2/5): {}", s);
+    let (index, count) = s.trim().split_once('/').ok_or_else(invalid)?;
+    let index: u32 = index.trim().parse().map_err(|_| invalid())?;
+    let count: u32 = count.trim().parse().map_err(|_| invalid())?;
+    if count == 0 || index == 0 || index > count {
+        return Err(invalid());
+    }
+    Ok(Shard { index, count })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn parses_shards() {
+        assert_eq!(parse_shard("2/5"), Ok(Shard { index: 2, count: 5 }));
+        assert_eq!(parse_shard(" 1 / 1 "), Ok(Shard { index: 1, count: 1 }));
+        assert_eq!(parse_shard("3/4").unwrap().to_string(), "3/4");
+        for bad in ["0/5", "6/5", "1/0", "2", "a/5", "-1/5"] {
+            assert!(parse_shard(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn shards_partition_files_by_name() {
+        let files: Vec<PathBuf> = (0..200)
+            .map(|i| PathBuf::from(format!("/in/f{}.orc", i)))
+            .collect();
+        let shards: Vec<Shard> = (1..=3).map(|index| Shard { index, count: 3 }).collect();
+        let mut sizes = [0; 3];
+        for file in &files {
+            // 每个文件恰好属于一个分片
+            let owners: Vec<usize> = (0..3).filter(|&i| shards[i].contains(file)).collect();
+            assert_eq!(owners.len(), 1, "{:?}", file);
+            sizes[owners[0]] += 1;
+            // 只按文件名划分，与所在目录无关
+            let moved = Path::new("/mnt/other").join(file.file_name().unwrap());
+            assert!(shards[owners[0]].contains(&moved));
+        }
+        assert!(sizes.iter().all(|&n| n > 40), "{:?}", sizes);
+        assert!(files
+            .iter()
+            .all(|f| Shard { index: 1, count: 1 }.contains(f)));
+    }
+}
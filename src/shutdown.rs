@@ -0,0 +1,82 @@
+//! 优雅退出：收到 SIGINT / SIGTERM 后不再启动新文件，进行中的导入有一段宽限期可以完成，
+//! 宽限期过后 (或再次收到信号) 中断它们并记为失败，批次汇总与报告照常输出
+
+use tokio::time::{self, Duration};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+
+#[derive(Clone)]
+pub struct Shutdown {
+    /// 不再启动新文件
+    stop: CancellationToken,
+    /// 中断进行中的导入，同时意味着 stop
+    abort: CancellationToken,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let abort = CancellationToken::new();
+        Self {
+            stop: abort.child_token(),
+            abort,
+        }
+    }
+}
+
+impl Shutdown {
+    /// 监听进程信号：第一次进入停止阶段，宽限期结束或第二次信号时中断进行中的导入
+    pub fn on_signals(grace: Duration) -> Self {
+        let shutdown = Self::default();
+        let handle = shutdown.clone();
+        tokio::spawn(async move {
+            wait_signal().await;
+            eprintln!(
+                "🛑 收到退出信号：不再启动新文件，等待进行中的导入完成 (最长 {:?})，再次发送信号立即中断",
+                grace
+            );
+            handle.stop.cancel();
+            tokio::select! {
+                _ = time::sleep(grace) => eprintln!("⏰ 宽限期已到，中断进行中的导入"),
+                _ = wait_signal() => eprintln!("🛑 再次收到退出信号，立即中断进行中的导入"),
+            }
+            handle.abort.cancel();
+        });
+        shutdown
+    }
+
+    /// 不再启动新文件，进行中的导入继续 (serve 模式收到退出信号时转给各任务)
+    pub fn stop(&self) {
+        self.stop.cancel();
+    }
+
+    /// 立即中断 (serve 模式取消任务)
+    pub fn cancel(&self) {
+        self.abort.cancel();
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stop.is_cancelled()
+    }
+
+    pub fn stopping(&self) -> WaitForCancellationFuture<'_> {
+        self.stop.cancelled()
+    }
+
+    pub fn aborted(&self) -> WaitForCancellationFuture<'_> {
+        self.abort.cancelled()
+    }
+}
+
+async fn wait_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,174 @@
+//! 停滞检测：数据仍在发送却长时间没有进度的导入 (服务端卡住、网络半开等) 提前中断，
+//! 不必等到按文件大小计算的整体超时。
+//!
+//! 进度来源：HTTP 传输看上传管道已读取的字节；clickhouse-client 的 stdin 直接是文件句柄，
+//! 看子进程 stdin 的读取位置 (`/proc/<pid>/fdinfo/0`，仅 Linux)。数据全部发出后只是在等待服务端写入，
+//! 不算停滞。判定停滞后丢弃导入 future (连接断开、子进程被 kill)，再按 query_id 执行 KILL QUERY。
+
+use crate::cli::{self, Args};
+use crate::throttle::ReadLimit;
+use crate::wire::Upload;
+use crate::{clickhouse, precompressed};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// 进度采样间隔
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 一次导入尝试：独立的 query_id 与进度来源，停滞重试时换一个新的
+pub struct Attempt {
+    pub query_id: String,
+    /// 待发送的字节数，client 子进程读到该位置即视为发送完毕
+    bytes: u64,
+    upload: Mutex<Option<Arc<Upload>>>,
+    child: Mutex<Option<u32>>,
+    /// 开启 OpenTelemetry 追踪时随插入请求发送的 W3C traceparent
+    pub traceparent: Option<String>,
+    /// 由文件标识 (内容摘要或元数据) 派生的 insert_deduplication_token，各次尝试相同，重新发送时服务端据此去重
+    pub dedup_token: Option<String>,
+    /// --max-read-mbps 的读取限速器，传给本次尝试的上传
+    read_limit: ReadLimit,
+}
+
+impl Attempt {
+    /// query_id 为 `ckloader-<批次 id>-<路径摘要>`，由批次与文件确定，可据此在 system.query_log 中
+    /// 找到文件对应的查询；同一 query_id 不能同时运行两次，重试时追加 `-r<次数>`
+    pub fn new(batch_id: &str, paths: &[PathBuf], bytes: u64, attempt: u32) -> Self {
+        let joined: Vec<String> = paths.iter().map(|p| p.to_string_lossy().into()).collect();
+        let mut query_id = format!(
+            "ckloader-{}-{:016x}",
+            batch_id,
+            xxh3_64(joined.join("\n").as_bytes())
+        );
+        if attempt > 0 {
+            query_id.push_str(&format!("-r{}", attempt));
+        }
+        Self {
+            query_id,
+            bytes,
+            upload: Mutex::new(None),
+            child: Mutex::new(None),
+            traceparent: None,
+            dedup_token: None,
+            read_limit: ReadLimit::default(),
+        }
+    }
+
+    pub fn traceparent(mut self, traceparent: Option<String>) -> Self {
+        self.traceparent = traceparent;
+        self
+    }
+
+    pub fn dedup_token(mut self, token: Option<String>) -> Self {
+        self.dedup_token = token;
+        self
+    }
+
+    pub fn read_limit(mut self, limit: ReadLimit) -> Self {
+        self.read_limit = limit;
+        self
+    }
+
+    /// 本次尝试待发送的字节数
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// 本次尝试的 HTTP 上传状态，带上 query_id 并登记为进度来源
+    pub fn upload(&self, cfg: &Args) -> Arc<Upload> {
+        self.register(Upload::new(cfg))
+    }
+
+    /// 上传单个文件：文件本身已压缩时原样发送
+    pub fn file_upload(&self, cfg: &Args, path: &Path) -> Arc<Upload> {
+        self.register(Upload::new(cfg).precompressed(precompressed::detect(path)))
+    }
+
+    fn register(&self, upload: Upload) -> Arc<Upload> {
+        let upload = Arc::new(
+            upload
+                .query_id(self.query_id.clone())
+                .traceparent(self.traceparent.clone())
+                .dedup_token(self.dedup_token.clone())
+                .read_limit(self.read_limit.clone()),
+        );
+        *self.upload.lock().unwrap() = Some(Arc::clone(&upload));
+        *self.child.lock().unwrap() = None;
+        upload
+    }
+
+    /// 当前的 HTTP 上传状态 (clickhouse-client 传输时为 None)
+    pub fn current_upload(&self) -> Option<Arc<Upload>> {
+        self.upload.lock().unwrap().clone()
+    }
+
+    /// 登记 clickhouse-client 子进程为进度来源
+    pub fn track_child(&self, pid: u32) {
+        *self.child.lock().unwrap() = Some(pid);
+        *self.upload.lock().unwrap() = None;
+    }
+
+    /// 已发送的字节数 (HTTP / native 为上传管道读出的字节，client 为子进程 stdin 的读取位置)；
+    /// 尚未开始时为 None
+    pub fn sent(&self) -> Option<u64> {
+        if let Some(upload) = self.upload.lock().unwrap().as_ref() {
+            return Some(upload.raw_bytes());
+        }
+        let pid = (*self.child.lock().unwrap())?;
+        child_stdin_pos(pid)
+    }
+
+    /// 数据已开始发送且已全部发出 (此后只是在等待服务端写入)
+    pub fn sent_all(&self) -> bool {
+        self.sent().is_some_and(|n| n > 0) && self.sample().is_none()
+    }
+
+    /// 数据仍在发送时返回已发送的字节数；尚未开始或已全部发出时返回 None
+    fn sample(&self) -> Option<u64> {
+        if let Some(upload) = self.upload.lock().unwrap().as_ref() {
+            return upload.is_sending().then(|| upload.raw_bytes());
+        }
+        let pid = (*self.child.lock().unwrap())?;
+        let pos = child_stdin_pos(pid)?;
+        (pos < self.bytes).then_some(pos)
+    }
+}
+
+/// 子进程 stdin 的当前读取位置
+fn child_stdin_pos(pid: u32) -> Option<u64> {
+    let info = std::fs::read_to_string(format!("/proc/{}/fdinfo/0", pid)).ok()?;
+    info.lines()
+        .find_map(|l| l.strip_prefix("pos:"))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// 数据仍在发送但连续 `timeout` 没有进度时返回，否则一直等待
+pub async fn stalled(attempt: &Attempt, timeout: Duration) {
+    let mut last = None;
+    let mut since = Instant::now();
+    loop {
+        time::sleep(SAMPLE_INTERVAL).await;
+        let current = attempt.sample();
+        if current.is_none() || current != last {
+            last = current;
+            since = Instant::now();
+        } else if since.elapsed() >= timeout {
+            return;
+        }
+    }
+}
+
+/// 中断停滞的查询；拆分导入各组的 query_id 为 `<id>-<组号>`
+pub async fn kill_query(cfg: &Args, query_id: &str) {
+    let id = cli::sql_string(query_id);
+    let sql = format!(
+        "KILL QUERY WHERE query_id = '{}' OR startsWith(query_id, '{}-') ASYNC",
+        id, id
+    );
+    if let Err(e) = clickhouse::query(cfg, &sql).await {
+        eprintln!("⚠️ KILL QUERY 失败: {}, 错误: {:#}", query_id, e);
+    }
+}
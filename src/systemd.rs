@@ -0,0 +1,85 @@
+//! systemd 集成：watch 作为 `Type=notify` 服务运行时，通过 $NOTIFY_SOCKET 报告启动完成 (READY=1)、
+//! 各轮扫描的状态 (STATUS=)、看门狗心跳 (WATCHDOG=1，按 WatchdogSec= 的一半间隔) 与进入停止阶段 (STOPPING=1)。
+//! 停止时按 --shutdown-grace 延长 systemd 的停止超时，进行中的导入可以在宽限期内完成，不会被提前 SIGKILL。
+//! 不在 systemd 下运行 (没有 $NOTIFY_SOCKET) 时什么也不做。
+//!
+//! ```ini
+//! [Service]
+//! Type=notify
+//! ExecStart=/usr/local/bin/ck-loader watch -d /data/landing -t db.events
+//! WatchdogSec=60
+//! KillMode=mixed
+//! ```
+
+use crate::shutdown::Shutdown;
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+use tokio::time;
+
+/// 向 $NOTIFY_SOCKET 发送一条状态 (如 `READY=1`)，多个字段以换行分隔；失败时忽略
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+    let path = path.to_string_lossy();
+    // `@` 开头为抽象命名空间的套接字
+    match path.strip_prefix('@') {
+        Some(name) => send_abstract(&socket, name, state),
+        None => {
+            let _ = socket.send_to(state.as_bytes(), path.as_ref());
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(socket: &UnixDatagram, name: &str, state: &str) {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+    if let Ok(addr) = SocketAddr::from_abstract_name(name) {
+        let _ = socket.send_to_addr(state.as_bytes(), &addr);
+    }
+}
+
+/// 抽象命名空间只在 Linux 上存在
+#[cfg(not(target_os = "linux"))]
+fn send_abstract(_: &UnixDatagram, _: &str, _: &str) {}
+
+/// 启动完成：开始看门狗心跳，进入停止阶段时通知 systemd 并按宽限期延长停止超时
+pub fn ready(status: &str, shutdown: &Shutdown, grace: Duration) {
+    if std::env::var_os("NOTIFY_SOCKET").is_none() {
+        return;
+    }
+    notify(&format!("READY=1\nSTATUS={}", status));
+    if let Some(interval) = watchdog_interval() {
+        tokio::spawn(async move {
+            loop {
+                time::sleep(interval).await;
+                notify("WATCHDOG=1");
+            }
+        });
+    }
+    let shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown.stopping().await;
+        // 宽限期之外留出输出汇总的时间
+        let extend = grace + Duration::from_secs(30);
+        notify(&format!(
+            "STOPPING=1\nSTATUS=正在停止，等待进行中的导入完成\nEXTEND_TIMEOUT_USEC={}",
+            extend.as_micros()
+        ));
+    });
+}
+
+/// 看门狗心跳间隔：WatchdogSec= 的一半；$WATCHDOG_PID 指向其他进程时不发送
+fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
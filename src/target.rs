@@ -0,0 +1,91 @@
+//! 启动 worker 之前的目标表检查：确认服务端可以连接、凭据有效、目标表存在且引擎可以写入。
+//! 表名写错时在这里给出一条带候选表名的错误，而不是每个文件各失败一次。
+
+use crate::cli::{self, Args};
+use crate::clickhouse::{self, locate};
+use crate::error;
+use anyhow::{bail, Result};
+
+/// 不接受 INSERT 的表引擎
+const READ_ONLY_ENGINES: [&str; 5] = ["View", "Dictionary", "Merge", "LiveView", "WindowView"];
+/// 候选表名的最大编辑距离
+const SUGGEST_DISTANCE: usize = 3;
+
+pub async fn check(cfg: &Args, table: &str) -> Result<()> {
+    let (db, name) = locate(table);
+    let sql = format!(
+        "SELECT engine FROM system.tables WHERE database = {} AND name = '{}'",
+        db,
+        cli::sql_string(&name)
+    );
+    let engine = match clickhouse::query(cfg, &sql).await {
+        Ok(out) => out.trim().to_string(),
+        Err(e) if error::is_auth_error(&format!("{:#}", e)) => {
+            bail!(
+                "ClickHouse 认证失败 (用户 {})，请检查 --user / --password: {:#}",
+                cfg.user,
+                e
+            )
+        }
+        Err(e) => bail!(
+            "无法查询目标表信息 (服务端不可达或没有 system.tables 的权限)，请检查 --url 与服务端状态: {:#}",
+            e
+        ),
+    };
+    if engine.is_empty() {
+        bail!(missing(cfg, table, &db, &name).await);
+    }
+    if READ_ONLY_ENGINES.contains(&engine.as_str()) || engine.starts_with("System") {
+        bail!("目标表 {} 的引擎为 {}，不能写入", table, engine);
+    }
+    println!("🎯 目标表: {} ({})", table, engine);
+    Ok(())
+}
+
+/// 目标表不存在时的说明：库不存在，或列出同库中名字相近的表
+async fn missing(cfg: &Args, table: &str, db: &str, name: &str) -> String {
+    let exists = format!("SELECT count() FROM system.databases WHERE name = {}", db);
+    if clickhouse::query(cfg, &exists)
+        .await
+        .ok()
+        .as_deref()
+        .map(str::trim)
+        == Some("0")
+    {
+        return format!(
+            "目标表 {} 不存在: 库 {} 不存在",
+            table,
+            db.trim_matches('\'')
+        );
+    }
+    let mut message = format!("目标表 {} 不存在，请检查 -t/--table 或 --route", table);
+    let sql = format!("SELECT name FROM system.tables WHERE database = {}", db);
+    if let Ok(out) = clickhouse::query(cfg, &sql).await {
+        let mut candidates: Vec<(usize, &str)> = out
+            .lines()
+            .map(|n| (distance(&n.to_lowercase(), &name.to_lowercase()), n))
+            .filter(|(d, _)| *d <= SUGGEST_DISTANCE)
+            .collect();
+        candidates.sort_unstable();
+        if !candidates.is_empty() {
+            let names: Vec<&str> = candidates.iter().take(3).map(|(_, n)| *n).collect();
+            message.push_str(&format!("；相近的表: {}", names.join(", ")));
+        }
+    }
+    message
+}
+
+/// 两个名字之间的编辑距离
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            row.push((prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,196 @@
+//! 限速：每发送 (或读取) 一块数据就记入字节数，超出速率时等待到对应的时间点再继续。
+//! 单文件上传限速 (--per-file-bandwidth) 供低优先级的回灌任务与同机的实时导入共享带宽；
+//! 源文件读取限速 (--max-read-mbps) 由所有 worker 共用，避免导入占满同时服务其他使用者的 NFS 等共享存储
+
+use futures::stream::{BoxStream, Stream, StreamExt};
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::time::Duration;
+
+/// 空闲期间最多累积的额度：暂停一段时间后不会以远超上限的速率补齐
+const MAX_BURST: Duration = Duration::from_secs(1);
+
+pub struct RateLimit {
+    bytes_per_sec: f64,
+    /// (开始时间, 已发送字节数)
+    state: Mutex<(Instant, u64)>,
+}
+
+impl RateLimit {
+    pub fn new(mb_per_sec: f64) -> Self {
+        Self {
+            bytes_per_sec: mb_per_sec * 1024.0 * 1024.0,
+            state: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// 记入即将发送的 `bytes`，平均速率超过上限时等待
+    pub async fn consume(&self, bytes: u64) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// consume 的阻塞版本，供在阻塞线程中读取的调用方使用
+    pub fn consume_blocking(&self, bytes: u64) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// 记入 `bytes`，返回需要等待的时长
+    fn reserve(&self, bytes: u64) -> Duration {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let due = state.0 + Duration::from_secs_f64(state.1 as f64 / self.bytes_per_sec);
+        if now.saturating_duration_since(due) > MAX_BURST {
+            *state = (now - MAX_BURST, 0);
+        }
+        state.1 += bytes;
+        let due = state.0 + Duration::from_secs_f64(state.1 as f64 / self.bytes_per_sec);
+        due.saturating_duration_since(now)
+    }
+}
+
+/// --max-read-mbps 的源文件读取限速，各 worker 与各批次共用；未设置时不限速
+#[derive(Clone, Default)]
+pub struct ReadLimit(Option<Arc<RateLimit>>);
+
+impl fmt::Debug for ReadLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(limit) => write!(
+                f,
+                "ReadLimit({:.1} MB/s)",
+                limit.bytes_per_sec / 1024.0 / 1024.0
+            ),
+            None => write!(f, "ReadLimit(none)"),
+        }
+    }
+}
+
+impl ReadLimit {
+    pub fn new(mb_per_sec: Option<f64>) -> Self {
+        Self(mb_per_sec.map(|rate| Arc::new(RateLimit::new(rate))))
+    }
+
+    /// 每读出一块就记入字节数，超过速率时推迟下一块的读取
+    pub fn stream<S, B>(&self, chunks: S) -> BoxStream<'static, io::Result<B>>
+    where
+        S: Stream<Item = io::Result<B>> + Send + 'static,
+        B: AsRef<[u8]> + Send + 'static,
+    {
+        let Some(limit) = self.0.clone() else {
+            return chunks.boxed();
+        };
+        chunks
+            .then(move |chunk| {
+                let limit = Arc::clone(&limit);
+                async move {
+                    if let Ok(chunk) = &chunk {
+                        limit.consume(chunk.as_ref().len() as u64).await;
+                    }
+                    chunk
+                }
+            })
+            .boxed()
+    }
+
+    /// 阻塞读取的限速包装
+    pub fn reader<R: Read>(&self, inner: R) -> Throttled<R> {
+        Throttled {
+            inner,
+            limit: self.0.clone(),
+        }
+    }
+}
+
+pub struct Throttled<R> {
+    inner: R,
+    limit: Option<Arc<RateLimit>>,
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(limit) = &self.limit {
+            limit.consume_blocking(n as u64);
+        }
+        Ok(n)
+    }
+}
+
+/// 解析 MB/s 速率，必须为正数
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let num = s
+        .strip_suffix("MB/s")
+        .or_else(|| s.strip_suffix("MB"))
+        .unwrap_or(s)
+        .trim();
+    match num.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(format!("无效的速率 (MB/s): {}", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn parses_rates() {
+        assert_eq!(parse_rate("50"), Ok(50.0));
+        assert_eq!(parse_rate(" 12.5MB/s "), Ok(12.5));
+        assert_eq!(parse_rate("8 MB"), Ok(8.0));
+        for bad in ["0", "-1", "fast", "inf", "NaN"] {
+            assert!(parse_rate(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn reserves_time_for_bytes() {
+        let limit = RateLimit::new(1.0);
+        // 1 MB/s 下记入 2 MB 需要约 2 秒
+        let wait = limit.reserve(2 * MB);
+        assert!(wait > Duration::from_millis(1900) && wait <= Duration::from_secs(2));
+        // 额度是累计的，再记入 1 MB 排在前面的 2 MB 之后
+        let wait = limit.reserve(MB);
+        assert!(wait > Duration::from_millis(2900) && wait <= Duration::from_secs(3));
+    }
+
+    #[test]
+    fn idle_credit_is_capped() {
+        let limit = RateLimit::new(1.0);
+        // 模拟空闲了 10 秒：最多只累积 MAX_BURST 的额度
+        limit.state.lock().unwrap().0 = Instant::now() - Duration::from_secs(10);
+        assert!(limit.reserve(MB / 2).is_zero());
+        assert!(limit.reserve(MB / 2).is_zero());
+        let wait = limit.reserve(MB);
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn read_limit_passes_data_through() {
+        let chunks = || futures::stream::iter(vec![Ok(vec![1u8, 2]), Ok(vec![3])]);
+        for limit in [ReadLimit::default(), ReadLimit::new(Some(100.0))] {
+            let out: Vec<Vec<u8>> = limit.stream(chunks()).map(|c| c.unwrap()).collect().await;
+            assert_eq!(out, [vec![1, 2], vec![3]]);
+
+            let mut buf = Vec::new();
+            limit.reader(&b"abc"[..]).read_to_end(&mut buf).unwrap();
+            assert_eq!(buf, b"abc");
+        }
+        assert_eq!(
+            format!("{:?}", ReadLimit::new(Some(2.5))),
+            "ReadLimit(2.5 MB/s)"
+        );
+        assert_eq!(format!("{:?}", ReadLimit::new(None)), "ReadLimit(none)");
+    }
+}
@@ -0,0 +1,572 @@
+//! OpenTelemetry 追踪：设置了 OTEL_EXPORTER_OTLP_ENDPOINT (或 OTEL_EXPORTER_OTLP_TRACES_ENDPOINT) 时，
+//! 以 OTLP/HTTP JSON 上报批次与各文件的 span，导入与它所解锁的下游查询出现在同一个 Tempo / Jaeger trace 中。
+//!
+//! span 层级：batch → discovery (每个目录 + 表的扫描与筛选) / file (每个导入单元) →
+//! wait_for_permit / upload / server_processing / move。插入请求带 W3C traceparent
+//! (HTTP 头、clickhouse-client 的 --opentelemetry-traceparent；native 传输不传递)，
+//! 服务端开启 opentelemetry_span_log 时查询的 span 挂在对应的 file 之下。
+//!
+//! 支持的环境变量：OTEL_SDK_DISABLED、OTEL_TRACES_EXPORTER (none 关闭)、OTEL_EXPORTER_OTLP_[TRACES_]ENDPOINT、
+//! OTEL_EXPORTER_OTLP_[TRACES_]HEADERS、OTEL_SERVICE_NAME、OTEL_RESOURCE_ATTRIBUTES，
+//! 以及调度系统传入的 TRACEPARENT (批次挂在其下)。只支持 http/json 协议。
+
+use crate::stall::Attempt;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// 后台上报间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// 采集端不可用时最多积压的 span 数，超出的丢弃
+const MAX_PENDING: usize = 20_000;
+/// 检查数据是否已全部发出的间隔
+const UPLOAD_POLL: Duration = Duration::from_millis(200);
+
+const KIND_INTERNAL: u8 = 1;
+const KIND_CLIENT: u8 = 3;
+
+/// span 的上报端，未配置 OTLP 端点时所有 span 直接丢弃
+#[derive(Debug, Clone, Default)]
+pub struct Tracer {
+    exporter: Option<Arc<Exporter>>,
+}
+
+#[derive(Debug)]
+struct Exporter {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    resource: Value,
+    client: reqwest::Client,
+    pending: Mutex<Vec<Value>>,
+    /// 上报失败只告警一次
+    warned: AtomicBool,
+    /// 批次的上级 span (TRACEPARENT)
+    parent: Option<Context>,
+}
+
+/// W3C trace context 中的 trace id 与 span id
+#[derive(Debug, Clone, Copy)]
+struct Context {
+    trace_id: u128,
+    span_id: u64,
+}
+
+pub struct Span {
+    exporter: Option<Arc<Exporter>>,
+    context: Context,
+    parent: Option<u64>,
+    name: &'static str,
+    kind: u8,
+    start: u64,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    attributes: Vec<(&'static str, Value)>,
+    error: Option<String>,
+    ended: bool,
+}
+
+impl Tracer {
+    /// 按 OTEL_* 环境变量配置；未设置端点或显式关闭时返回不上报的 Tracer
+    pub fn from_env() -> Self {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let disabled = var("OTEL_SDK_DISABLED").is_some_and(|v| v.eq_ignore_ascii_case("true"))
+            || var("OTEL_TRACES_EXPORTER").is_some_and(|v| v.eq_ignore_ascii_case("none"));
+        let endpoint = match (
+            var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"),
+            var("OTEL_EXPORTER_OTLP_ENDPOINT"),
+        ) {
+            (Some(traces), _) => traces,
+            (None, Some(base)) => format!("{}/v1/traces", base.trim_end_matches('/')),
+            (None, None) => return Self::default(),
+        };
+        if disabled {
+            return Self::default();
+        }
+        let protocol = var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")
+            .or_else(|| var("OTEL_EXPORTER_OTLP_PROTOCOL"));
+        if let Some(protocol) = protocol.filter(|p| p != "http/json") {
+            eprintln!(
+                "⚠️ OpenTelemetry 只支持 http/json 协议，忽略 OTEL_EXPORTER_OTLP_PROTOCOL={}",
+                protocol
+            );
+        }
+        let mut headers = pairs(var("OTEL_EXPORTER_OTLP_HEADERS").as_deref());
+        headers.extend(pairs(var("OTEL_EXPORTER_OTLP_TRACES_HEADERS").as_deref()));
+
+        let mut resource = vec![
+            (
+                "service.name".to_string(),
+                var("OTEL_SERVICE_NAME").unwrap_or_else(|| "ck-loader".to_string()),
+            ),
+            (
+                "service.version".to_string(),
+                env!("CARGO_PKG_VERSION").to_string(),
+            ),
+        ];
+        if let Some(host) = var("HOSTNAME") {
+            resource.push(("host.name".to_string(), host));
+        }
+        for (key, value) in pairs(var("OTEL_RESOURCE_ATTRIBUTES").as_deref()) {
+            // OTEL_SERVICE_NAME 优先于资源属性中的 service.name
+            if key == "service.name" && var("OTEL_SERVICE_NAME").is_some() {
+                continue;
+            }
+            resource.retain(|(k, _)| *k != key);
+            resource.push((key, value));
+        }
+        let attributes: Vec<Value> = resource
+            .into_iter()
+            .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+            .collect();
+
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("⚠️ 无法创建 OpenTelemetry 上报客户端，不上报 span: {}", e);
+                return Self::default();
+            }
+        };
+        let exporter = Arc::new(Exporter {
+            endpoint,
+            headers,
+            resource: json!({"attributes": attributes}),
+            client,
+            pending: Mutex::new(Vec::new()),
+            warned: AtomicBool::new(false),
+            parent: var("TRACEPARENT").as_deref().and_then(parse_traceparent),
+        });
+        println!("🔭 OpenTelemetry span 上报到 {}", exporter.endpoint);
+        let background = Arc::clone(&exporter);
+        tokio::spawn(async move {
+            loop {
+                time::sleep(FLUSH_INTERVAL).await;
+                background.flush().await;
+            }
+        });
+        Self {
+            exporter: Some(exporter),
+        }
+    }
+
+    /// 一个批次的根 span，设置了 TRACEPARENT 时挂在其下
+    pub fn batch(&self, batch_id: &str) -> Span {
+        let parent = self.exporter.as_ref().and_then(|e| e.parent);
+        let trace_id = parent.map_or_else(
+            || random_id(0) as u128 | (random_id(1) as u128) << 64,
+            |p| p.trace_id,
+        );
+        let span = Span::new(
+            self.exporter.clone(),
+            trace_id,
+            parent.map(|p| p.span_id),
+            "batch",
+            KIND_INTERNAL,
+        );
+        span.set("ck_loader.batch_id", batch_id);
+        span
+    }
+
+    /// 立即上报积压的 span (批次结束、进程退出前)
+    pub async fn flush(&self) {
+        if let Some(exporter) = &self.exporter {
+            exporter.flush().await;
+        }
+    }
+}
+
+impl Exporter {
+    async fn flush(&self) {
+        let spans = std::mem::take(&mut *self.pending.lock().unwrap());
+        if spans.is_empty() {
+            return;
+        }
+        let body = json!({
+            "resourceSpans": [{
+                "resource": self.resource,
+                "scopeSpans": [{
+                    "scope": {"name": "ck-loader", "version": env!("CARGO_PKG_VERSION")},
+                    "spans": spans,
+                }],
+            }],
+        });
+        let mut req = self
+            .client
+            .post(&self.endpoint)
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+        for (key, value) in &self.headers {
+            req = req.header(key, value);
+        }
+        let result = match req.send().await {
+            Ok(resp) if resp.status().is_success() => Ok(()),
+            Ok(resp) => Err(format!("HTTP {}", resp.status())),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            if !self.warned.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "⚠️ OpenTelemetry span 上报失败 (后续失败不再提示): {}, 错误: {}",
+                    self.endpoint, e
+                );
+            }
+        }
+    }
+
+    fn push(&self, span: Value) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() < MAX_PENDING {
+            pending.push(span);
+        }
+    }
+}
+
+impl Span {
+    fn new(
+        exporter: Option<Arc<Exporter>>,
+        trace_id: u128,
+        parent: Option<u64>,
+        name: &'static str,
+        kind: u8,
+    ) -> Self {
+        Self {
+            context: Context {
+                trace_id,
+                span_id: if exporter.is_some() { random_id(2) } else { 0 },
+            },
+            exporter,
+            parent,
+            name,
+            kind,
+            start: unix_nanos(),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn child(&self, name: &'static str) -> Span {
+        self.child_kind(name, KIND_INTERNAL)
+    }
+
+    fn child_kind(&self, name: &'static str, kind: u8) -> Span {
+        Span::new(
+            self.exporter.clone(),
+            self.context.trace_id,
+            Some(self.context.span_id),
+            name,
+            kind,
+        )
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.exporter.is_some()
+    }
+
+    pub fn set(&self, key: &'static str, value: impl Into<Value>) {
+        if self.is_recording() {
+            self.state
+                .lock()
+                .unwrap()
+                .attributes
+                .push((key, value.into()));
+        }
+    }
+
+    /// 标记为失败 (span status = ERROR)
+    pub fn fail(&self, message: &str) {
+        if self.is_recording() {
+            self.state.lock().unwrap().error = Some(message.to_string());
+        }
+    }
+
+    /// 传给服务端的 W3C traceparent，服务端的查询 span 挂在本 span 之下
+    pub fn traceparent(&self) -> Option<String> {
+        self.is_recording().then(|| {
+            format!(
+                "00-{:032x}-{:016x}-01",
+                self.context.trace_id, self.context.span_id
+            )
+        })
+    }
+
+    /// 结束并排队上报；重复调用与之后的 drop 不再上报
+    pub fn end(&self) {
+        let Some(exporter) = &self.exporter else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        if std::mem::replace(&mut state.ended, true) {
+            return;
+        }
+        let attributes: Vec<Value> = state
+            .attributes
+            .iter()
+            .map(|(key, value)| json!({"key": key, "value": any_value(value)}))
+            .collect();
+        let mut span = json!({
+            "traceId": format!("{:032x}", self.context.trace_id),
+            "spanId": format!("{:016x}", self.context.span_id),
+            "name": self.name,
+            "kind": self.kind,
+            "startTimeUnixNano": self.start.to_string(),
+            "endTimeUnixNano": unix_nanos().to_string(),
+            "attributes": attributes,
+        });
+        if let Some(parent) = self.parent {
+            span["parentSpanId"] = json!(format!("{:016x}", parent));
+        }
+        if let Some(error) = &state.error {
+            span["status"] = json!({"code": 2, "message": error});
+        }
+        exporter.push(span);
+    }
+}
+
+/// 提前返回 (跳过、出错) 的 span 在离开作用域时结束
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
+
+/// 一次导入尝试的 upload 与 server_processing span：数据全部发出时 upload 结束、server_processing 开始，
+/// 两者随本 future 被丢弃 (尝试结束) 而结束；不会返回 (未开启追踪时一直等待)
+pub async fn attempt(file: &Span, attempt: &Attempt, number: u32) {
+    if !file.is_recording() {
+        return std::future::pending().await;
+    }
+    let upload = file.child_kind("upload", KIND_CLIENT);
+    upload.set("ck_loader.query_id", attempt.query_id.clone());
+    upload.set("ck_loader.attempt", number);
+    while !attempt.sent_all() {
+        time::sleep(UPLOAD_POLL).await;
+    }
+    upload.set("ck_loader.sent_bytes", attempt.sent().unwrap_or_default());
+    upload.end();
+    let _server = file.child_kind("server_processing", KIND_CLIENT);
+    std::future::pending().await
+}
+
+/// OTLP JSON 的 AnyValue；整数按规范编码为字符串
+fn any_value(value: &Value) -> Value {
+    match value {
+        Value::Bool(b) => json!({"boolValue": b}),
+        Value::Number(n) if n.is_f64() => json!({"doubleValue": n}),
+        Value::Number(n) => json!({"intValue": n.to_string()}),
+        Value::String(s) => json!({"stringValue": s}),
+        other => json!({"stringValue": other.to_string()}),
+    }
+}
+
+/// `k1=v1,k2=v2` 形式的列表 (OTEL_EXPORTER_OTLP_HEADERS、OTEL_RESOURCE_ATTRIBUTES)
+fn pairs(s: Option<&str>) -> Vec<(String, String)> {
+    s.unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .filter(|(k, _)| !k.is_empty())
+        .collect()
+}
+
+/// `00-<trace id>-<span id>-<flags>`
+fn parse_traceparent(s: &str) -> Option<Context> {
+    let mut parts = s.trim().split('-');
+    let (_, trace_id, span_id) = (parts.next()?, parts.next()?, parts.next()?);
+    let trace_id = u128::from_str_radix(trace_id, 16)
+        .ok()
+        .filter(|_| trace_id.len() == 32)?;
+    let span_id = u64::from_str_radix(span_id, 16)
+        .ok()
+        .filter(|_| span_id.len() == 16)?;
+    (trace_id != 0 && span_id != 0).then_some(Context { trace_id, span_id })
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// 不依赖随机数库的 id：时间、进程号与计数器的摘要，同一进程内不重复
+fn random_id(salt: u64) -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut seed = Vec::with_capacity(32);
+    seed.extend(unix_nanos().to_le_bytes());
+    seed.extend(u64::from(std::process::id()).to_le_bytes());
+    seed.extend(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    seed.extend(salt.to_le_bytes());
+    xxh3_64(&seed).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    const PARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    fn exporter(endpoint: String) -> Arc<Exporter> {
+        Arc::new(Exporter {
+            endpoint,
+            headers: vec![("x-tenant".to_string(), "etl".to_string())],
+            resource: json!({"attributes": [{"key": "service.name", "value": {"stringValue": "ck-loader"}}]}),
+            client: reqwest::Client::new(),
+            pending: Mutex::new(Vec::new()),
+            warned: AtomicBool::new(false),
+            parent: parse_traceparent(PARENT),
+        })
+    }
+
+    #[test]
+    fn any_values() {
+        assert_eq!(any_value(&json!(true)), json!({"boolValue": true}));
+        assert_eq!(any_value(&json!(1.5)), json!({"doubleValue": 1.5}));
+        // 整数按 OTLP JSON 规范编码为字符串
+        assert_eq!(
+            any_value(&json!(u64::MAX)),
+            json!({"intValue": "18446744073709551615"})
+        );
+        assert_eq!(any_value(&json!("a")), json!({"stringValue": "a"}));
+        assert_eq!(any_value(&json!([1])), json!({"stringValue": "[1]"}));
+    }
+
+    #[test]
+    fn env_lists() {
+        assert_eq!(
+            pairs(Some(" a = 1,b=x=y,,=z,c")),
+            [
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "x=y".to_string())
+            ]
+        );
+        assert!(pairs(None).is_empty());
+    }
+
+    #[test]
+    fn traceparents() {
+        let context = parse_traceparent(PARENT).unwrap();
+        assert_eq!(context.trace_id, 0x0af7651916cd43dd8448eb211c80319c);
+        assert_eq!(context.span_id, 0xb7ad6b7169203331);
+        assert!(parse_traceparent("00-0af7651916cd43dd8448eb211c80319c-b7ad6b71-01").is_none());
+        assert!(
+            parse_traceparent("00-00000000000000000000000000000000-b7ad6b7169203331-01").is_none()
+        );
+        assert!(parse_traceparent("garbage").is_none());
+        // 未开启追踪时不生成 traceparent，也不排队
+        let span = Tracer::default().batch("b");
+        assert!(span.traceparent().is_none());
+        assert!(!span.is_recording());
+    }
+
+    #[test]
+    fn span_encoding() {
+        let exporter = exporter(String::new());
+        let tracer = Tracer {
+            exporter: Some(Arc::clone(&exporter)),
+        };
+        let batch = tracer.batch("20240101120000123");
+        let file = batch.child("file");
+        file.set("ck_loader.bytes", 1024u64);
+        file.set("ck_loader.retried", false);
+        file.fail("code 27");
+        let traceparent = file.traceparent().unwrap();
+        assert!(traceparent.starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+        file.end();
+        // 重复结束与 drop 不再上报
+        file.end();
+        drop(file);
+        drop(batch);
+
+        let spans = std::mem::take(&mut *exporter.pending.lock().unwrap());
+        assert_eq!(spans.len(), 2);
+        let (file, batch) = (&spans[0], &spans[1]);
+        assert_eq!(file["traceId"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(file["spanId"], traceparent[36..52]);
+        assert_eq!(file["parentSpanId"], batch["spanId"]);
+        assert_eq!(batch["parentSpanId"], "b7ad6b7169203331");
+        assert_eq!(file["name"], "file");
+        assert_eq!(file["kind"], KIND_INTERNAL);
+        assert_eq!(
+            file["attributes"],
+            json!([
+                {"key": "ck_loader.bytes", "value": {"intValue": "1024"}},
+                {"key": "ck_loader.retried", "value": {"boolValue": false}},
+            ])
+        );
+        assert_eq!(file["status"], json!({"code": 2, "message": "code 27"}));
+        assert!(batch.get("status").is_none());
+        // 时间戳为字符串形式的纳秒数
+        let start: u64 = file["startTimeUnixNano"].as_str().unwrap().parse().unwrap();
+        let end: u64 = file["endTimeUnixNano"].as_str().unwrap().parse().unwrap();
+        assert!(start > 0 && start <= end);
+    }
+
+    #[tokio::test]
+    async fn export_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line.trim_end().to_ascii_lowercase());
+            }
+            let len: usize = head
+                .iter()
+                .find_map(|h| h.strip_prefix("content-length: "))
+                .unwrap()
+                .parse()
+                .unwrap();
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).await.unwrap();
+            reader
+                .into_inner()
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            (head, body)
+        });
+        let exporter = exporter(endpoint);
+        Tracer {
+            exporter: Some(Arc::clone(&exporter)),
+        }
+        .batch("b")
+        .end();
+        exporter.flush().await;
+        assert!(exporter.pending.lock().unwrap().is_empty());
+        assert!(!exporter.warned.load(Ordering::Relaxed));
+
+        let (head, body) = server.await.unwrap();
+        assert_eq!(head[0], "post /v1/traces http/1.1");
+        assert!(head.contains(&"content-type: application/json".to_string()));
+        assert!(head.contains(&"x-tenant: etl".to_string()));
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let resource_spans = &body["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0]["key"],
+            "service.name"
+        );
+        let scope = &resource_spans["scopeSpans"][0];
+        assert_eq!(scope["scope"]["name"], "ck-loader");
+        assert_eq!(scope["spans"][0]["name"], "batch");
+        assert_eq!(
+            scope["spans"][0]["attributes"][0],
+            json!({"key": "ck_loader.batch_id", "value": {"stringValue": "b"}})
+        );
+    }
+}
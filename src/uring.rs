@@ -0,0 +1,195 @@
+//! `--io-uring`：经 io_uring 读取本地源文件 (Linux，需以 `--features io-uring` 编译)。
+//!
+//! 进程内共用一个 ring，由专门的线程提交读请求并收取完成事件：并行导入几十个大文件时，
+//! 各文件的读请求合并在同一次 io_uring_enter 中提交，不再每块一次 read 系统调用、也不占用 tokio 的阻塞线程池。
+//! 内核不支持 (或被 seccomp 禁止) io_uring、或编译时未启用该特性时提示一次，改用普通读取。
+//! 只用于整个文件的顺序读取 (含 --align-stripes)；按 stripe 拆分、合并组等按区间拼接的请求体仍用普通读取。
+
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use std::io;
+use std::path::Path;
+
+/// 按 plan 中的 (offset, len) 依次读取文件，每个区间读满后作为一块输出；
+/// io_uring 不可用时返回 None，由调用方改用普通读取
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub fn read_ranges(
+    path: &Path,
+    plan: Vec<(u64, u64)>,
+) -> io::Result<Option<BoxStream<'static, io::Result<Bytes>>>> {
+    use futures::stream::{self, StreamExt};
+    use std::sync::Arc;
+
+    let Some(ring) = driver::ring() else {
+        return Ok(None);
+    };
+    let file = Arc::new(std::fs::File::open(path)?);
+    let chunks = stream::try_unfold(
+        (file, plan.into_iter()),
+        move |(file, mut plan)| async move {
+            let Some((offset, len)) = plan.next() else {
+                return Ok(None);
+            };
+            // 短读时从读到的位置继续，直到读满整个区间
+            let mut buf = Vec::with_capacity(len as usize);
+            while (buf.len() as u64) < len {
+                let pos = offset + buf.len() as u64;
+                let want = (len - buf.len() as u64) as usize;
+                let data = ring.read(Arc::clone(&file), pos, want).await?;
+                if data.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("文件在偏移 {} 处提前结束", pos),
+                    ));
+                }
+                buf.extend_from_slice(&data);
+            }
+            Ok(Some((Bytes::from(buf), (file, plan))))
+        },
+    );
+    Ok(Some(chunks.boxed()))
+}
+
+#[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+pub fn read_ranges(
+    _path: &Path,
+    _plan: Vec<(u64, u64)>,
+) -> io::Result<Option<BoxStream<'static, io::Result<Bytes>>>> {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        eprintln!(
+            "⚠️ 本程序编译时未启用 io-uring 特性 (仅支持 Linux)，--io-uring 不生效，改用普通读取"
+        );
+    });
+    Ok(None)
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod driver {
+    use io_uring::{opcode, types, IoUring};
+    use std::collections::{HashMap, VecDeque};
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+    use std::sync::mpsc;
+    use std::sync::{Arc, OnceLock};
+    use tokio::sync::oneshot;
+
+    /// 提交队列深度，同时进行的读请求不超过该数
+    const ENTRIES: u32 = 256;
+
+    struct Request {
+        /// 读取完成前保持文件打开
+        file: Arc<File>,
+        offset: u64,
+        /// 内核写入的缓冲区，读取完成前由驱动线程持有
+        buf: Vec<u8>,
+        done: oneshot::Sender<io::Result<Vec<u8>>>,
+    }
+
+    pub struct Ring {
+        tx: mpsc::Sender<Request>,
+    }
+
+    impl Ring {
+        /// 从 offset 处读取至多 len 字节，读到文件末尾时返回空
+        pub async fn read(&self, file: Arc<File>, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+            let (done, rx) = oneshot::channel();
+            let req = Request {
+                file,
+                offset,
+                buf: vec![0u8; len],
+                done,
+            };
+            self.tx
+                .send(req)
+                .map_err(|_| io::Error::other("io_uring 驱动线程已退出"))?;
+            rx.await
+                .map_err(|_| io::Error::other("io_uring 驱动线程已退出"))?
+        }
+    }
+
+    /// 首次使用时创建 ring 与驱动线程；失败时提示一次，之后一直返回 None
+    pub fn ring() -> Option<&'static Ring> {
+        static RING: OnceLock<Option<Ring>> = OnceLock::new();
+        RING.get_or_init(|| match IoUring::new(ENTRIES) {
+            Ok(ring) => {
+                let (tx, rx) = mpsc::channel();
+                std::thread::Builder::new()
+                    .name("io-uring".into())
+                    .spawn(move || drive(ring, rx))
+                    .ok()?;
+                Some(Ring { tx })
+            }
+            Err(e) => {
+                eprintln!("⚠️ 无法初始化 io_uring ({})，改用普通读取", e);
+                None
+            }
+        })
+        .as_ref()
+    }
+
+    /// 驱动循环：收下新的读请求并提交，等待至少一个完成事件，把结果交还给等待的读取方。
+    /// 没有进行中的读取时阻塞等待新请求；有进行中的读取时，新请求在下一个完成事件后提交
+    fn drive(mut ring: IoUring, rx: mpsc::Receiver<Request>) {
+        let mut inflight: HashMap<u64, Request> = HashMap::new();
+        let mut pending: VecDeque<Request> = VecDeque::new();
+        let mut next_id = 0u64;
+        loop {
+            if inflight.is_empty() && pending.is_empty() {
+                match rx.recv() {
+                    Ok(req) => pending.push_back(req),
+                    Err(_) => return,
+                }
+            }
+            pending.extend(rx.try_iter());
+
+            while inflight.len() < ENTRIES as usize {
+                let Some(mut req) = pending.pop_front() else {
+                    break;
+                };
+                let entry = opcode::Read::new(
+                    types::Fd(req.file.as_raw_fd()),
+                    req.buf.as_mut_ptr(),
+                    req.buf.len() as u32,
+                )
+                .offset(req.offset)
+                .build()
+                .user_data(next_id);
+                // SAFETY: 缓冲区与文件由 inflight 中的请求持有，直到收到该请求的完成事件
+                if unsafe { ring.submission().push(&entry) }.is_err() {
+                    pending.push_front(req);
+                    break;
+                }
+                inflight.insert(next_id, req);
+                next_id += 1;
+            }
+
+            match ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    // 已提交的请求可能仍在内核中进行，不能释放其缓冲区，只通知读取方失败
+                    for req in pending.drain(..) {
+                        let _ = req.done.send(Err(io::Error::new(e.kind(), e.to_string())));
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    continue;
+                }
+            }
+            for cqe in ring.completion() {
+                let Some(mut req) = inflight.remove(&cqe.user_data()) else {
+                    continue;
+                };
+                let result = match cqe.result() {
+                    n if n >= 0 => {
+                        req.buf.truncate(n as usize);
+                        Ok(req.buf)
+                    }
+                    errno => Err(io::Error::from_raw_os_error(-errno)),
+                };
+                let _ = req.done.send(result);
+            }
+        }
+    }
+}
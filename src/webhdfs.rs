@@ -0,0 +1,176 @@
+//! HDFS 输入源，通过 WebHDFS REST 接口访问
+//!
+//! - `webhdfs://namenode:9870/path` / `swebhdfs://namenode:9871/path` (https)
+//! - `hdfs://namenode[:rpc端口]/path` 按同一 NameNode 的默认 HTTP 端口 9870 访问
+//!
+//! 请求由 curl 发出 (跟随到 DataNode 的重定向)，简单认证的用户名取自 HADOOP_USER_NAME，
+//! 启用安全认证的集群可通过 WEBHDFS_DELEGATION_TOKEN 传入委托令牌。
+
+use crate::archive::OnSuccess;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const SCHEMES: [&str; 3] = ["webhdfs://", "swebhdfs://", "hdfs://"];
+const DEFAULT_HTTP_PORT: u16 = 9870;
+
+pub fn is_hdfs(path: &Path) -> bool {
+    let s = path.to_string_lossy();
+    SCHEMES.iter().any(|scheme| s.starts_with(scheme))
+}
+
+/// 拆分为 (REST 接口前缀, HDFS 绝对路径)
+fn split(path: &Path) -> Result<(String, String)> {
+    let s = path.to_string_lossy();
+    let (scheme, rest) = s
+        .split_once("://")
+        .with_context(|| format!("不是 HDFS 路径: {}", s))?;
+    let (authority, hdfs_path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (proto, authority) = match scheme {
+        "webhdfs" => ("http", authority.to_string()),
+        "swebhdfs" => ("https", authority.to_string()),
+        "hdfs" => {
+            let host = authority.split(':').next().unwrap_or_default();
+            ("http", format!("{}:{}", host, DEFAULT_HTTP_PORT))
+        }
+        _ => bail!("不支持的 HDFS 协议: {}", s),
+    };
+    if authority.is_empty() || authority.starts_with(':') {
+        bail!("HDFS 路径缺少 NameNode 地址: {}", s);
+    }
+    Ok((
+        format!("{}://{}/webhdfs/v1", proto, authority),
+        hdfs_path.trim_end_matches('/').to_string(),
+    ))
+}
+
+/// 百分号编码，保留 `/` 与 RFC 3986 的非保留字符
+fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn url(path: &Path, op: &str, extra: &[(&str, String)]) -> Result<String> {
+    let (base, hdfs_path) = split(path)?;
+    let mut url = format!("{}{}?op={}", base, encode(&hdfs_path), op);
+    if let Ok(user) = std::env::var("HADOOP_USER_NAME") {
+        url.push_str(&format!("&user.name={}", user));
+    }
+    if let Ok(token) = std::env::var("WEBHDFS_DELEGATION_TOKEN") {
+        url.push_str(&format!("&delegation={}", token));
+    }
+    for (k, v) in extra {
+        url.push_str(&format!("&{}={}", k, encode(v)));
+    }
+    Ok(url)
+}
+
+fn curl(method: &str, url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["-sS", "-f", "-L", "-X", method, url])
+        .stdin(Stdio::null())
+        .output()
+        .context("无法启动 curl")?;
+    if !output.status.success() {
+        bail!(
+            "WebHDFS 请求失败: {} {} | {}",
+            method,
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+fn json(method: &str, url: &str) -> Result<serde_json::Value> {
+    serde_json::from_slice(&curl(method, url)?).context("无法解析 WebHDFS 响应")
+}
+
+/// 列出目录下的文件 (不递归)
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    let resp = json("GET", &url(dir, "LISTSTATUS", &[])?)?;
+    let mut files = Vec::new();
+    let statuses = resp["FileStatuses"]["FileStatus"].as_array();
+    for st in statuses.into_iter().flatten() {
+        if st["type"] == "FILE" {
+            if let Some(name) = st["pathSuffix"].as_str() {
+                files.push(dir.join(name));
+            }
+        }
+    }
+    Ok(files)
+}
+
+pub fn size(path: &Path) -> Result<u64> {
+    let resp = json("GET", &url(path, "GETFILESTATUS", &[])?)?;
+    resp["FileStatus"]["length"]
+        .as_u64()
+        .with_context(|| format!("无法获取文件大小: {:?}", path))
+}
+
+pub fn read_range(path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+    let buf = curl(
+        "GET",
+        &url(
+            path,
+            "OPEN",
+            &[("offset", offset.to_string()), ("length", len.to_string())],
+        )?,
+    )?;
+    if buf.len() as u64 != len {
+        bail!("读取 {:?} 区间 {}+{} 不完整", path, offset, len);
+    }
+    Ok(buf)
+}
+
+/// 启动 curl 读取整个文件，内容从子进程 stdout 流出
+pub fn stream(path: &Path) -> Result<tokio::process::Child> {
+    tokio::process::Command::new("curl")
+        .args(["-sS", "-f", "-L"])
+        .arg(url(path, "OPEN", &[])?)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("无法启动 curl")
+}
+
+/// 导入成功后在 HDFS 上移动或删除文件
+pub fn finish(policy: OnSuccess, path: &Path, done_dir: &Path) -> Result<Option<PathBuf>> {
+    match policy {
+        OnSuccess::Move => {
+            json("PUT", &url(done_dir, "MKDIRS", &[])?)?;
+            let target = done_dir.join(path.file_name().context("无效的文件名")?);
+            let (_, destination) = split(&target)?;
+            let resp = json(
+                "PUT",
+                &url(path, "RENAME", &[("destination", destination)])?,
+            )?;
+            if resp["boolean"] != true {
+                bail!("HDFS 重命名失败: {:?} -> {:?}", path, target);
+            }
+            Ok(Some(target))
+        }
+        OnSuccess::Delete => {
+            let resp = json("DELETE", &url(path, "DELETE", &[])?)?;
+            if resp["boolean"] != true {
+                bail!("HDFS 删除失败: {:?}", path);
+            }
+            Ok(None)
+        }
+        OnSuccess::Compress => bail!("HDFS 源不支持 --on-success compress"),
+        OnSuccess::Archive => bail!("HDFS 源不支持 --archive-to"),
+    }
+}
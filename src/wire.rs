@@ -0,0 +1,361 @@
+//! HTTP 上传管道：读取块 → 预读 (可选) → 压缩 (可选) → 限速 (可选) → 请求体。
+//! 同时统计读取的原始字节与实际发送的字节，用于评估各链路开启压缩是否值得额外的 CPU
+
+use crate::cli::Args;
+use crate::throttle::{RateLimit, ReadLimit};
+use bytes::Bytes;
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use reqwest::Body;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use xxhash_rust::xxh3::Xxh3;
+
+/// 请求体压缩方式，通过 Content-Encoding 告知服务端解压
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Lz4,
+    /// 启动时按首个文件探测磁盘、CPU 与网络的吞吐后选择 none / lz4 / zstd 及其级别 (见 autocompress)
+    Auto,
+}
+
+impl Compression {
+    pub fn content_encoding(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gzip"),
+            Self::Zstd => Some("zstd"),
+            Self::Lz4 => Some("lz4"),
+            // 启动时已解析为具体方式
+            Self::Auto => None,
+        }
+    }
+}
+
+/// 单个文件的上传状态，拆分导入的各组共用同一个限速器与计数器
+pub struct Upload {
+    compression: Compression,
+    /// 源文件本身已压缩时的压缩方式：原样发送，不再压缩
+    precompressed: Option<Compression>,
+    zstd_level: i32,
+    /// --compress-threads 的 zstd 压缩线程数
+    compress_threads: u32,
+    limit: Option<RateLimit>,
+    raw: AtomicU64,
+    wire: AtomicU64,
+    /// 尚未发送完的请求体数量
+    active: AtomicUsize,
+    /// 服务端进度 (X-ClickHouse-Progress) 报告的已写入行数，拆分导入的各组累加
+    server_rows: AtomicU64,
+    /// 随请求发送的 query_id，拆分导入的各组追加 `-<组号>`
+    query_id: Option<String>,
+    /// 随请求发送的 W3C traceparent 头
+    traceparent: Option<String>,
+    /// 随请求发送的 insert_deduplication_token，拆分导入的各组在此基础上追加 `#<组号>/<组数>`
+    dedup_token: Option<String>,
+    /// 每读到一块原始数据的回调 (stdin 流用它实时更新进行中的字节数)
+    on_read: Option<Box<dyn Fn(u64) + Send + Sync>>,
+    /// 整个文件顺序读完后得到的 xxh3-128 摘要
+    checksum: Mutex<Option<String>>,
+    /// --read-ahead 的预读块数
+    read_ahead: usize,
+    /// --max-read-mbps 的读取限速器，各 worker 共用
+    read_limit: ReadLimit,
+}
+
+impl Upload {
+    pub fn new(cfg: &Args) -> Self {
+        Self {
+            compression: cfg.http_compression,
+            precompressed: None,
+            zstd_level: cfg.zstd_level,
+            compress_threads: cfg.compress_threads,
+            limit: cfg.per_file_bandwidth.map(RateLimit::new),
+            raw: AtomicU64::new(0),
+            wire: AtomicU64::new(0),
+            active: AtomicUsize::new(0),
+            server_rows: AtomicU64::new(0),
+            query_id: None,
+            traceparent: None,
+            dedup_token: None,
+            on_read: None,
+            checksum: Mutex::new(None),
+            read_ahead: cfg.read_ahead,
+            read_limit: ReadLimit::default(),
+        }
+    }
+
+    pub fn on_read(mut self, hook: impl Fn(u64) + Send + Sync + 'static) -> Self {
+        self.on_read = Some(Box::new(hook));
+        self
+    }
+
+    pub fn precompressed(mut self, codec: Option<Compression>) -> Self {
+        self.precompressed = codec;
+        self
+    }
+
+    /// 请求的 Content-Encoding：预压缩文件为其自身的压缩方式，否则为 --http-compression
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        self.precompressed
+            .unwrap_or(self.compression)
+            .content_encoding()
+    }
+
+    pub fn query_id(mut self, query_id: String) -> Self {
+        self.query_id = Some(query_id);
+        self
+    }
+
+    pub fn traceparent(mut self, traceparent: Option<String>) -> Self {
+        self.traceparent = traceparent;
+        self
+    }
+
+    pub fn traceparent_header(&self) -> Option<&str> {
+        self.traceparent.as_deref()
+    }
+
+    pub fn dedup_token(mut self, token: Option<String>) -> Self {
+        self.dedup_token = token;
+        self
+    }
+
+    pub fn read_limit(mut self, limit: ReadLimit) -> Self {
+        self.read_limit = limit;
+        self
+    }
+
+    /// 读取源数据时使用的 --max-read-mbps 限速器
+    pub fn read_limiter(&self) -> &ReadLimit {
+        &self.read_limit
+    }
+
+    /// 请求的 insert_deduplication_token 参数
+    pub fn dedup_param(&self) -> Option<(&'static str, String)> {
+        let token = self.dedup_token.clone()?;
+        Some(("insert_deduplication_token", token))
+    }
+
+    /// 请求的 query_id 参数；`group` 为拆分导入的组号
+    pub fn query_id_param(&self, group: Option<usize>) -> Option<(&'static str, String)> {
+        let id = self.query_id.as_ref()?;
+        Some(match group {
+            Some(group) => ("query_id", format!("{}-{}", id, group)),
+            None => ("query_id", id.clone()),
+        })
+    }
+
+    /// 是否有请求体仍在发送 (数据全部发出后只是在等待服务端响应)
+    pub fn is_sending(&self) -> bool {
+        self.active.load(Ordering::Relaxed) > 0
+    }
+
+    /// 从数据源读取的原始字节数
+    pub fn raw_bytes(&self) -> u64 {
+        self.raw.load(Ordering::Relaxed)
+    }
+
+    /// 实际发送的字节数 (压缩后)
+    pub fn wire_bytes(&self) -> u64 {
+        self.wire.load(Ordering::Relaxed)
+    }
+
+    /// 登记服务端进度中已写入行数的增量
+    pub fn server_written(&self, rows: u64) {
+        self.server_rows.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    /// 服务端报告的已写入行数
+    pub fn server_rows(&self) -> u64 {
+        self.server_rows.load(Ordering::Relaxed)
+    }
+
+    /// 不经过 body() 发送的数据 (native 协议的数据块) 由调用方登记：守卫存活期间视为仍在发送
+    pub fn sending(self: &Arc<Self>) -> Sending {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        Sending(Arc::clone(self))
+    }
+
+    /// 登记已发送的未压缩字节数
+    pub fn sent(&self, len: u64) {
+        self.raw.fetch_add(len, Ordering::Relaxed);
+        self.wire.fetch_add(len, Ordering::Relaxed);
+        if let Some(hook) = &self.on_read {
+            hook(len);
+        }
+    }
+
+    /// 文件内容的 xxh3-128 摘要 (与 delta::hash_file 一致)，只有整个文件经 file_body() 读完时才有
+    pub fn checksum(&self) -> Option<String> {
+        self.checksum.lock().unwrap().clone()
+    }
+
+    /// 整个文件作为一个请求体顺序上传：在 body() 的基础上顺带计算原始内容的摘要，读到末尾后由 checksum() 取得
+    pub fn file_body<S, B>(self: &Arc<Self>, chunks: S) -> io::Result<Body>
+    where
+        S: Stream<Item = io::Result<B>> + Send + 'static,
+        B: AsRef<[u8]> + Into<Bytes> + Send + 'static,
+    {
+        let hasher = Arc::new(Mutex::new(Xxh3::new()));
+        let (feed, upload) = (Arc::clone(&hasher), Arc::clone(self));
+        let finish = stream::once(async move {
+            let digest = hasher.lock().unwrap().digest128();
+            *upload.checksum.lock().unwrap() = Some(format!("{:032x}", digest));
+        })
+        .filter_map(|()| async { None });
+        let chunks = chunks
+            .inspect(move |chunk| {
+                if let Ok(chunk) = chunk {
+                    feed.lock().unwrap().update(chunk.as_ref());
+                }
+            })
+            .chain(finish);
+        self.body(chunks)
+    }
+
+    /// 把读取块包装为请求体；每个请求体是一个独立的压缩流
+    pub fn body<S, B>(self: &Arc<Self>, chunks: S) -> io::Result<Body>
+    where
+        S: Stream<Item = io::Result<B>> + Send + 'static,
+        B: AsRef<[u8]> + Into<Bytes> + Send + 'static,
+    {
+        let compression = match self.precompressed {
+            Some(_) => Compression::None,
+            None => self.compression,
+        };
+        let encoder = Encoder::new(compression, self.zstd_level, self.compress_threads)?;
+        self.active.fetch_add(1, Ordering::Relaxed);
+        let chunks = read_ahead(chunks, self.read_ahead);
+        let state = (chunks.fuse(), encoder, Arc::clone(self));
+        let body = stream::try_unfold(state, |(mut chunks, mut encoder, upload)| async move {
+            loop {
+                let out = match chunks.next().await {
+                    Some(chunk) => {
+                        let chunk = chunk?;
+                        let len = chunk.as_ref().len() as u64;
+                        upload.raw.fetch_add(len, Ordering::Relaxed);
+                        if let Some(hook) = &upload.on_read {
+                            hook(len);
+                        }
+                        match encoder.as_mut() {
+                            Some(e) => e.encode(chunk.as_ref())?,
+                            None => chunk.into(),
+                        }
+                    }
+                    None => match encoder.take() {
+                        Some(e) => e.finish()?,
+                        None => {
+                            upload.active.fetch_sub(1, Ordering::Relaxed);
+                            return Ok(None);
+                        }
+                    },
+                };
+                // 压缩器可能暂存数据而没有输出，空块不发送
+                if out.is_empty() {
+                    continue;
+                }
+                upload.wire.fetch_add(out.len() as u64, Ordering::Relaxed);
+                if let Some(limit) = &upload.limit {
+                    limit.consume(out.len() as u64).await;
+                }
+                return Ok::<_, io::Error>(Some((out, (chunks, encoder, upload))));
+            }
+        });
+        Ok(Body::wrap_stream(body))
+    }
+}
+
+/// 后台任务提前读取至多 `depth` 块放入通道，下一块的磁盘 (或源端) 读取与当前块的压缩、发送重叠；
+/// 请求体被丢弃 (请求中断) 时通道关闭，后台任务随之结束。`depth` 为 0 时不预读
+fn read_ahead<S, B>(chunks: S, depth: usize) -> BoxStream<'static, io::Result<B>>
+where
+    S: Stream<Item = io::Result<B>> + Send + 'static,
+    B: Send + 'static,
+{
+    if depth == 0 {
+        return chunks.boxed();
+    }
+    let (tx, rx) = tokio::sync::mpsc::channel(depth);
+    tokio::spawn(async move {
+        let mut chunks = Box::pin(chunks);
+        while let Some(chunk) = chunks.next().await {
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+    stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    })
+    .boxed()
+}
+
+pub struct Sending(Arc<Upload>);
+
+impl Drop for Sending {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 请求体的压缩器，bench 也用它单独测量压缩吞吐
+pub enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    Lz4(lz4_flex::frame::FrameEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    /// `threads` 大于 0 时 zstd 在后台的 `threads` 个线程中分段压缩，压缩与读取、发送并行；gzip、lz4 始终单线程
+    pub fn new(compression: Compression, level: i32, threads: u32) -> io::Result<Option<Self>> {
+        Ok(match compression {
+            Compression::None | Compression::Auto => None,
+            Compression::Gzip => Some(Self::Gzip(GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::default(),
+            ))),
+            Compression::Zstd => {
+                let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), level)?;
+                if threads > 0 {
+                    encoder.multithread(threads)?;
+                }
+                Some(Self::Zstd(encoder))
+            }
+            Compression::Lz4 => Some(Self::Lz4(lz4_flex::frame::FrameEncoder::new(Vec::new()))),
+        })
+    }
+
+    /// 写入一块数据并取出目前已产生的压缩输出
+    pub fn encode(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        let out = match self {
+            Self::Gzip(e) => {
+                e.write_all(data)?;
+                e.get_mut()
+            }
+            Self::Zstd(e) => {
+                e.write_all(data)?;
+                e.get_mut()
+            }
+            Self::Lz4(e) => {
+                e.write_all(data)?;
+                e.get_mut()
+            }
+        };
+        Ok(Bytes::from(std::mem::take(out)))
+    }
+
+    pub fn finish(self) -> io::Result<Bytes> {
+        let out = match self {
+            Self::Gzip(e) => e.finish()?,
+            Self::Zstd(e) => e.finish()?,
+            Self::Lz4(e) => e.finish().map_err(io::Error::other)?,
+        };
+        Ok(Bytes::from(out))
+    }
+}